@@ -0,0 +1,173 @@
+use crate::{
+    LINE_ENDING, format_as_escaped, push_namespace_close, push_namespace_open,
+    region::{self, RegionStyle},
+    toc::TocEntry,
+};
+
+/// The generated source of one input, waiting to be amalgamated
+#[derive(Clone)]
+pub struct Part {
+    pub src: String,
+    /// Name under which the input is listed in the index
+    pub name: String,
+    /// Expression evaluating to something with `data()` and `size()` over the embedded bytes, if
+    /// the input is embedded as bytes (rather than decoded into structs, say)
+    pub view_expr: Option<String>,
+    /// Symbol through which the data is accessed, listed in the metadata block
+    pub access_symbol: String,
+    /// Namespace the symbol is declared in, if any
+    pub ns_name: Option<String>,
+    pub toc_entry: TocEntry,
+    pub data: Vec<u8>,
+}
+
+/// Concatenate the sources of every part into one header, dropping `#include` lines which have
+/// already appeared outside of any conditional, and append an index of every part embedded as
+/// bytes with a `<prefix>_find(name)` lookup and, if given, a `<prefix>_version` constant
+pub fn generate_src(
+    parts: &[Part],
+    prefix: &str,
+    ns_name: Option<&str>,
+    version: Option<&str>,
+    regions: Option<RegionStyle>,
+    internal_linkage: bool,
+) -> String {
+    let total_len: usize = parts.iter().map(|part| part.src.len()).sum();
+    let mut out_string = String::with_capacity(total_len + 0x100 * parts.len() + 0x400);
+    for header in ["array", "cstddef", "string_view"] {
+        out_string.push_str(format!("#include <{}>", header).as_str());
+        out_string.push_str(LINE_ENDING);
+    }
+
+    let mut seen_includes: Vec<String> = ["array", "cstddef", "string_view"]
+        .iter()
+        .map(|header| format!("#include <{}>", header))
+        .collect();
+    for part in parts {
+        let mut depth: usize = 0;
+        for line in part.src.lines() {
+            let directive = line.trim_start();
+            if directive.starts_with("#if") {
+                depth += 1;
+            } else if directive.starts_with("#endif") {
+                depth = depth.saturating_sub(1);
+            } else if depth == 0 && directive.starts_with("#include") {
+                if seen_includes.iter().any(|seen| seen == line) {
+                    continue;
+                }
+                seen_includes.push(line.to_string());
+            }
+            out_string.push_str(line);
+            out_string.push_str(LINE_ENDING);
+        }
+    }
+
+    // Index
+    let mut index = String::with_capacity(0x40 * parts.len() + 0x200);
+    let entries: Vec<String> = parts
+        .iter()
+        .filter_map(|part| {
+            let view_expr = part.view_expr.as_ref()?;
+            return Some(format!(
+                "{{\"{}\",{1}.data(),{1}.size()}}",
+                format_as_escaped(part.name.as_bytes()),
+                view_expr
+            ));
+        })
+        .collect();
+
+    push_namespace_open(&mut index, ns_name, internal_linkage);
+    index.push_str(
+        format!(
+            "struct {0}_entry{{std::string_view name;const void* data;std::size_t size;}};inline const std::array<{0}_entry,{1}> {0}_index{{{{{2}}}}};",
+            prefix,
+            entries.len(),
+            entries.join(",")
+        )
+        .as_str(),
+    );
+    index.push_str(LINE_ENDING);
+    if let Some(version) = version {
+        index.push_str(
+            format!(
+                "constexpr std::string_view {}_version=\"{}\";",
+                prefix,
+                format_as_escaped(version.as_bytes())
+            )
+            .as_str(),
+        );
+        index.push_str(LINE_ENDING);
+    }
+    index.push_str(
+        format!(
+            "inline const {0}_entry* {0}_find(std::string_view name){{for(const auto& entry:{0}_index){{if(entry.name==name)return &entry;}}return nullptr;}}",
+            prefix
+        )
+        .as_str(),
+    );
+    push_namespace_close(&mut index, ns_name, internal_linkage);
+    index.push_str(LINE_ENDING);
+    match regions {
+        Some(style) => {
+            out_string.push_str(&region::wrap(&index, &format!("{}_index", prefix), style))
+        }
+        None => out_string.push_str(&index),
+    }
+
+    return out_string;
+}
+
+/// Pack parts of the given sizes into as few bins as possible, each holding at most `budget`
+/// bytes unless a single part is larger, returning the indices of the parts in each bin in their
+/// original order (first-fit decreasing, so bins are filled from the largest part down)
+pub fn pack(sizes: &[usize], budget: usize) -> Vec<Vec<usize>> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by(|&a, &b| sizes[b].cmp(&sizes[a]).then(a.cmp(&b)));
+
+    let mut bins: Vec<(usize, Vec<usize>)> = Vec::new();
+    for index in order {
+        match bins
+            .iter_mut()
+            .find(|(used, _)| used + sizes[index] <= budget)
+        {
+            Some((used, bin)) => {
+                *used += sizes[index];
+                bin.push(index);
+            }
+            None => bins.push((sizes[index], vec![index])),
+        }
+    }
+
+    return bins
+        .into_iter()
+        .map(|(_, mut bin)| {
+            bin.sort_unstable();
+            return bin;
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parts_within_the_budget_share_a_bin() {
+        assert_eq!(pack(&[10, 20, 30], 100), vec![vec![0, 1, 2]]);
+        assert_eq!(pack(&[], 100), Vec::<Vec<usize>>::new());
+    }
+
+    #[test]
+    fn bins_are_filled_largest_first() {
+        // First-fit in input order would need four bins, the 3s filling one and each 7 another
+        assert_eq!(
+            pack(&[3, 3, 3, 7, 7, 7], 10),
+            vec![vec![0, 3], vec![1, 4], vec![2, 5]]
+        );
+    }
+
+    #[test]
+    fn oversized_parts_get_a_bin_of_their_own() {
+        assert_eq!(pack(&[2, 50, 3], 10), vec![vec![1], vec![0, 2]]);
+    }
+}
@@ -0,0 +1,166 @@
+use crate::{
+    LINE_ENDING, Language, SourceOptions,
+    object::{self, ObjectFormat},
+    push_doc_comment, push_namespace_close, push_namespace_open,
+};
+
+/// Alignment of the data (unless more is asked for), as for `.incbin` in headers
+const ASM_ALIGNMENT: usize = 16;
+
+/// Generate a GNU assembler file pulling the data in with `.incbin` as `<symbol>_start` up to
+/// `<symbol>_end`, followed by a pointer-sized `<symbol>_size`, for the object format given
+///
+/// Nothing of the data is written out, so the file is tiny and assembling it costs no more than
+/// reading the input; the size is worked out by the assembler, so it stays right if the input
+/// changes without the file being regenerated. A section asked for only applies to ELF.
+pub fn generate_src(
+    path: &str,
+    array_len: usize,
+    symbol_name: &str,
+    target: ObjectFormat,
+    options: &SourceOptions,
+) -> String {
+    let object_symbol = object::object_symbol(symbol_name, options.ns_name.as_deref());
+    // Mach-O prefixes C symbols with an underscore
+    let symbol = |suffix: &str| {
+        return match target {
+            ObjectFormat::MachO => format!("_{}_{}", object_symbol, suffix),
+            ObjectFormat::Elf | ObjectFormat::Coff => format!("{}_{}", object_symbol, suffix),
+        };
+    };
+    let (start_symbol, end_symbol, size_symbol) = (symbol("start"), symbol("end"), symbol("size"));
+    let asm_path = path.replace('\\', "\\\\").replace('"', "\\\"");
+    let alignment = options
+        .align
+        .unwrap_or(0)
+        .max(ASM_ALIGNMENT)
+        .next_power_of_two();
+    // Only the part of the file which was embedded is included if it was not all of it
+    let incbin_directive = match options.input_offset {
+        Some(offset) => format!(".incbin \"{}\",{},{}", asm_path, offset, array_len),
+        None => format!(".incbin \"{}\"", asm_path),
+    };
+
+    let mut lines = match target {
+        ObjectFormat::Elf => vec![
+            format!(
+                ".section {},\"a\",@progbits",
+                options
+                    .section
+                    .clone()
+                    .unwrap_or_else(|| format!(".rodata.{}", object_symbol))
+            ),
+            format!(".balign {}", alignment),
+        ],
+        ObjectFormat::Coff => vec![
+            ".section .rdata,\"dr\"".to_string(),
+            format!(".balign {}", alignment),
+        ],
+        ObjectFormat::MachO => vec![
+            ".section __TEXT,__const".to_string(),
+            format!(".p2align {}", alignment.trailing_zeros()),
+        ],
+    };
+    lines.push(format!(".globl {}", start_symbol));
+    lines.push(format!("{}:", start_symbol));
+    lines.push(incbin_directive);
+    lines.push(format!(".globl {}", end_symbol));
+    lines.push(format!("{}:", end_symbol));
+    if options.null_terminate {
+        lines.push(".byte 0".to_string());
+    }
+    lines.push(match target {
+        ObjectFormat::MachO => ".p2align 3".to_string(),
+        ObjectFormat::Elf | ObjectFormat::Coff => ".balign 8".to_string(),
+    });
+    lines.push(format!(".globl {}", size_symbol));
+    lines.push(format!("{}:", size_symbol));
+    lines.push(format!(".quad {} - {}", end_symbol, start_symbol));
+    if target == ObjectFormat::Elf {
+        lines.push(format!(".type {},@object", start_symbol));
+        lines.push(format!(
+            ".size {},{} - {}",
+            start_symbol, end_symbol, start_symbol
+        ));
+        lines.push(format!(".type {},@object", size_symbol));
+        lines.push(format!(".size {},8", size_symbol));
+        // Linking this in must not make the stack executable
+        lines.push(".section .note.GNU-stack,\"\",@progbits".to_string());
+    }
+
+    let mut out_string = String::with_capacity(0x40 * lines.len());
+    for line in lines {
+        match line.ends_with(':') {
+            true => out_string.push_str(&line),
+            false => {
+                out_string.push('\t');
+                out_string.push_str(&line);
+            }
+        }
+        out_string.push_str(LINE_ENDING);
+    }
+
+    return out_string;
+}
+
+/// Generate a header declaring the symbols defined by the assembler file, and (in C++) referring
+/// to them as `<symbol>_start`, `<symbol>_end` and `<symbol>_size` in the namespace if one was
+/// given
+pub fn generate_header_src(symbol_name: &str, options: &SourceOptions) -> String {
+    let object_symbol = object::object_symbol(symbol_name, options.ns_name.as_deref());
+
+    let mut out_string = String::with_capacity(0x200);
+    match options.language {
+        Language::Cpp => {
+            out_string.push_str("#include <cstddef>");
+            out_string.push_str(LINE_ENDING);
+            out_string.push_str("#include <cstdint>");
+            out_string.push_str(LINE_ENDING);
+            if options.ns_name.is_none() {
+                push_doc_comment(&mut out_string, options.comment.as_deref());
+            }
+            out_string.push_str(
+                format!(
+                    "extern \"C\" const {0} {1}_start[];extern \"C\" const {0} {1}_end[];extern \"C\" const std::size_t {1}_size;",
+                    options.element_type.name(),
+                    object_symbol
+                )
+                .as_str(),
+            );
+            out_string.push_str(LINE_ENDING);
+            if options.ns_name.is_some() {
+                push_namespace_open(&mut out_string, options.ns_name.as_deref(), false);
+                push_doc_comment(&mut out_string, options.comment.as_deref());
+                for suffix in ["start", "end", "size"] {
+                    out_string.push_str(
+                        format!(
+                            "constexpr const auto& {0}_{2}=::{1}_{2};",
+                            symbol_name, object_symbol, suffix
+                        )
+                        .as_str(),
+                    );
+                }
+                push_namespace_close(&mut out_string, options.ns_name.as_deref(), false);
+                out_string.push_str(LINE_ENDING);
+            }
+        }
+        Language::C => {
+            out_string.push_str("#include <stddef.h>");
+            out_string.push_str(LINE_ENDING);
+            push_doc_comment(&mut out_string, options.comment.as_deref());
+            out_string.push_str(
+                format!(
+                    "extern const unsigned char {0}_start[];extern const unsigned char {0}_end[];extern const size_t {0}_size;",
+                    object_symbol
+                )
+                .as_str(),
+            );
+            out_string.push_str(LINE_ENDING);
+        }
+        Language::Rust | Language::CSharp | Language::Python => {
+            unreachable!("--format asm generates C and C++ headers only")
+        }
+    }
+
+    return out_string;
+}
@@ -0,0 +1,206 @@
+use crate::{Representation, SourceOptions};
+
+/// How the data is generated into the source of one target language, in each representation the
+/// language has
+///
+/// Every `Language` has one, returned by `Language::backend`, through which `generate_src` and
+/// `write_src` generate everything; a new language only takes an implementation and a variant
+/// returning it. Files generated alongside a header (split definitions, object files, assembler
+/// files and resource scripts) are not written by backends, as only C and C++ have them.
+pub trait Backend: Sync {
+    /// Extension of the files generated
+    fn extension(&self) -> &'static str;
+
+    /// What starts a comment running to the end of the line
+    fn line_comment(&self) -> &'static str {
+        return "//";
+    }
+
+    /// Whether the files generated are included textually, and so have to guard against being
+    /// included twice
+    fn guarded(&self) -> bool {
+        return true;
+    }
+
+    /// Keywords of the language, which a symbol cannot be named
+    fn keywords(&self) -> &'static [&'static str];
+
+    /// Whether the language reserves identifiers beginning with an underscore or containing two
+    /// in a row for the implementation, as C and C++ do
+    fn reserves_underscores(&self) -> bool {
+        return true;
+    }
+
+    /// Symbol derived from a filename (sanitised into an identifier), in the case the language
+    /// names constants in
+    fn derived_symbol(&self, symbol_name: String) -> String {
+        return symbol_name;
+    }
+
+    /// Generate the source for the data in the given representation
+    fn generate_src(
+        &self,
+        data: &[u8],
+        representation: Representation,
+        symbol_name: &str,
+        options: &SourceOptions,
+    ) -> String;
+
+    /// Template of the source for `array_len` bytes in the given representation (counting a NUL
+    /// terminator if there is one), with a `DATA_MARKER` wherever the data is to be formatted in,
+    /// if the representation can be written out a chunk of data at a time
+    ///
+    /// Data written into a template is laid out as a byte array is (obfuscated and terminated),
+    /// as an initialiser list or, for escaped string literals, as the contents of C string
+    /// literals. Backends with neither return `None` and are only ever asked to generate the
+    /// source as a whole.
+    fn template(
+        &self,
+        _array_len: usize,
+        _representation: Representation,
+        _symbol_name: &str,
+        _options: &SourceOptions,
+    ) -> Option<String> {
+        return None;
+    }
+
+    /// Why the options asked for cannot be generated in the language, as the end of a sentence
+    /// starting with `--language <name>`, or nothing if they can
+    fn unsupported(&self, _features: &Features) -> Option<&'static str> {
+        return None;
+    }
+}
+
+/// Options asked for which not every language has, as given to `Backend::unsupported`
+#[derive(Debug, Clone, Default)]
+pub struct Features {
+    pub namespace: bool,
+    /// An include guard of any style but none, or a guard name
+    pub guard: bool,
+    pub guard_macro: bool,
+    pub hybrid_loader: bool,
+    pub verify_fn: bool,
+    pub stream_accessor: bool,
+    pub span_accessor: bool,
+    /// A character type other than `char`
+    pub char_type: bool,
+    pub raw_string: bool,
+    pub octal: bool,
+    pub regions: bool,
+    pub pragma_regions: bool,
+    pub toc: bool,
+    /// An encoding other than bytes
+    pub encoding: bool,
+    /// An encoding including the input file by its path (`incbin` or `embed`)
+    pub included_encoding: bool,
+    /// Only part of the input (`--offset` or `--length`)
+    pub partial_input: bool,
+    pub delta_base: bool,
+    /// Decoding the input into structs (`--decode-csv` or `--decode-struct`)
+    pub decoding: bool,
+    pub locales: bool,
+    pub manifest: bool,
+    pub emit_test: bool,
+    pub split: bool,
+    /// `--assert-size` or `--assert-size-max`
+    pub size_assertion: bool,
+    /// An element type other than `uint8`
+    pub element_type: bool,
+    pub align: bool,
+    pub section: bool,
+    pub obfuscate_xor: bool,
+    pub compress: bool,
+    pub dedupe: bool,
+    pub self_check: bool,
+    pub object: bool,
+    /// A `--format` other than a header
+    pub format: bool,
+    pub internal_linkage: bool,
+    /// Several inputs generated into one header
+    pub amalgamation: bool,
+}
+
+/// Why the options asked for cannot be generated in a language other than C and C++, which only
+/// get the data (and its size and checksums) with none of the machinery around it
+pub(crate) fn unsupported_outside_c(features: &Features) -> Option<&'static str> {
+    let unsupported = features.guard
+        || features.guard_macro
+        || features.hybrid_loader
+        || features.verify_fn
+        || features.stream_accessor
+        || features.char_type
+        || features.raw_string
+        || features.octal
+        || features.pragma_regions
+        || features.delta_base
+        || features.decoding
+        || features.locales
+        || features.manifest
+        || features.emit_test
+        || features.split
+        || features.size_assertion
+        || features.element_type
+        || features.align
+        || features.section
+        || features.obfuscate_xor
+        || features.compress
+        || features.dedupe
+        || features.self_check
+        || features.object
+        || features.format
+        || features.amalgamation;
+
+    return unsupported.then_some(
+        "only supports byte arrays and string literals, without --guard, --guard-name, --guard-macro, --hybrid-loader, --verify-fn, --stream-accessor, --char-type, --raw-string, --radix oct, --regions pragma, --delta-base, --decode-csv, --decode-struct, --locales, --manifest, --emit-test, --split, --assert-size, --assert-size-max, --element-type, --align, --section, --obfuscate-xor, --compress, --dedupe, --self-check, --object, --format or several inputs in one header",
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Language;
+
+    const LANGUAGES: [Language; 5] = [
+        Language::Cpp,
+        Language::C,
+        Language::Rust,
+        Language::CSharp,
+        Language::Python,
+    ];
+
+    #[test]
+    fn every_language_has_plain_data() {
+        for language in LANGUAGES {
+            assert_eq!(language.backend().unsupported(&Features::default()), None);
+        }
+    }
+
+    #[test]
+    fn only_cpp_has_everything() {
+        let features = Features {
+            namespace: true,
+            split: true,
+            ..Features::default()
+        };
+        for language in LANGUAGES {
+            let unsupported = language.backend().unsupported(&features);
+            assert_eq!(unsupported.is_none(), language == Language::Cpp);
+        }
+    }
+
+    #[test]
+    fn rust_includes_whole_files_only() {
+        let included = Features {
+            encoding: true,
+            included_encoding: true,
+            ..Features::default()
+        };
+        assert_eq!(Language::Rust.backend().unsupported(&included), None);
+        let partial = Features {
+            partial_input: true,
+            ..included
+        };
+        assert!(Language::Rust.backend().unsupported(&partial).is_some());
+        assert!(Language::C.backend().unsupported(&partial).is_some());
+    }
+}
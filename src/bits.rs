@@ -0,0 +1,123 @@
+use crate::{
+    LINE_ENDING, SourceOptions, push_checksum_constants, push_declaration, push_doc_comment,
+    push_namespace_close, push_namespace_open, push_size_constant,
+};
+
+/// Pack bytes into 64-bit words, bit `i` of the input (counting from the most significant bit of
+/// the first byte, the usual order for bitmaps) becoming bit `i % 64` of word `i / 64`
+///
+/// This matches the indexing of `std::bitset`, so bit `i` of the input is `bitset[i]`.
+fn pack_words(data: &[u8]) -> Vec<u64> {
+    let mut words = vec![0u64; data.len().div_ceil(8)];
+    for (byte_index, &b) in data.iter().enumerate() {
+        for bit in 0..8 {
+            if b & (0x80 >> bit) != 0 {
+                let index = byte_index * 8 + bit;
+                words[index / 64] |= 1 << (index % 64);
+            }
+        }
+    }
+
+    return words;
+}
+
+fn words_declaration(words: &[u64], bit_count: usize, symbol_name: &str) -> String {
+    let contents: Vec<String> = words.iter().map(|w| format!("{:#x}u", w)).collect();
+    return format!(
+        "constexpr std::size_t {}_bit_count={};constexpr std::array<std::uint64_t,{}> {}{{{}}};",
+        symbol_name,
+        bit_count,
+        words.len(),
+        symbol_name,
+        contents.join(",")
+    );
+}
+
+/// Generate the input as an array of packed 64-bit words along with a bit count constant, a
+/// `<symbol>_test(i)` accessor and a `<symbol>_bitset()` conversion to `std::bitset`
+pub fn generate_src(data: &[u8], symbol_name: &str, options: &SourceOptions) -> String {
+    let words = pack_words(data);
+    let bit_count = data.len() * 8;
+
+    // Includes
+    let mut out_string = String::with_capacity(words.len() * 20 + 0x200);
+    for header in ["array", "bitset", "cstddef", "cstdint"] {
+        out_string.push_str(format!("#include <{}>", header).as_str());
+        out_string.push_str(LINE_ENDING);
+    }
+
+    // Namespace
+    push_namespace_open(
+        &mut out_string,
+        options.ns_name.as_deref(),
+        options.internal_linkage,
+    );
+
+    // Words and bit count
+    push_doc_comment(&mut out_string, options.comment.as_deref());
+    push_declaration(
+        &mut out_string,
+        &words_declaration(&words, bit_count, symbol_name),
+        &words_declaration(&[], 0, symbol_name),
+        options.guard_macro.as_deref(),
+    );
+
+    // Size of the input in bytes
+    push_size_constant(&mut out_string, symbol_name, data.len(), 0, options);
+
+    // Checksums
+    push_checksum_constants(&mut out_string, symbol_name, &[], options);
+
+    // Accessors
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str(
+        format!(
+            "constexpr bool {0}_test(std::size_t i){{return ({0}[i/64]>>(i%64))&1u;}}",
+            symbol_name
+        )
+        .as_str(),
+    );
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str(
+        format!(
+            "inline std::bitset<{0}_bit_count> {0}_bitset(){{std::bitset<{0}_bit_count> bits;for(std::size_t i=0;i<{0}_bit_count;++i)bits[i]={0}_test(i);return bits;}}",
+            symbol_name
+        )
+        .as_str(),
+    );
+    out_string.push_str(LINE_ENDING);
+
+    // Close namespace (if need be)
+    if options.ns_name.is_some() || options.internal_linkage {
+        push_namespace_close(
+            &mut out_string,
+            options.ns_name.as_deref(),
+            options.internal_linkage,
+        );
+        out_string.push_str(LINE_ENDING);
+    }
+
+    return out_string;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bits_are_numbered_from_the_most_significant_bit_of_the_first_byte() {
+        assert_eq!(pack_words(&[0x80]), vec![1]);
+        assert_eq!(pack_words(&[0x01]), vec![1 << 7]);
+        assert_eq!(pack_words(&[0x00, 0x80]), vec![1 << 8]);
+        assert_eq!(pack_words(&[0xff; 8]), vec![u64::MAX]);
+    }
+
+    #[test]
+    fn words_past_the_first_hold_later_bytes() {
+        let mut data = [0u8; 9];
+        data[0] = 0x80;
+        data[8] = 0xc0;
+        assert_eq!(pack_words(&data), vec![1, 0b11]);
+        assert_eq!(pack_words(&[]), Vec::<u64>::new());
+    }
+}
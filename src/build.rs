@@ -0,0 +1,261 @@
+use clap::ValueEnum;
+
+use crate::{LINE_ENDING, sanitise_identifier};
+
+/// Build system to print a snippet for (`--build-snippet`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BuildSystem {
+    /// A `generator()` for headers generated one per input, or a `custom_target()` for an
+    /// amalgamation
+    Meson,
+    /// A `bin2hpp` rule to save as `tools/bin2hpp.bzl`, and the BUILD targets using it
+    Bazel,
+}
+
+/// Starlark rule running the tool once per input (or once for an amalgamation), with every input
+/// and output declared so that the actions are hermetic and cacheable
+const BAZEL_RULE: &str = r#""""Embeds files into C and C++ headers with bin2hpp"""
+
+def _bin2hpp_impl(ctx):
+    headers = []
+    sources = []
+    if ctx.attr.out:
+        out = ctx.actions.declare_file(ctx.attr.out)
+        args = ctx.actions.args()
+        for src in ctx.files.srcs:
+            args.add("-i", src)
+        args.add("--amalgamate", out)
+        args.add_all(ctx.attr.options)
+        ctx.actions.run(
+            executable = ctx.executable.tool,
+            arguments = [args],
+            inputs = ctx.files.srcs,
+            outputs = [out],
+            mnemonic = "Bin2hpp",
+            progress_message = "Embedding %d files into %s" % (len(ctx.files.srcs), out.short_path),
+        )
+        headers.append(out)
+    else:
+        for src in ctx.files.srcs:
+            stem = src.basename.rsplit(".", 1)[0]
+            outs = [ctx.actions.declare_file(stem + "." + extension) for extension in ctx.attr.extensions]
+            args = ctx.actions.args()
+            args.add("-i", src)
+            args.add("-o", outs[0])
+            args.add_all(ctx.attr.options)
+            ctx.actions.run(
+                executable = ctx.executable.tool,
+                arguments = [args],
+                inputs = [src],
+                outputs = outs,
+                mnemonic = "Bin2hpp",
+                progress_message = "Embedding %s" % src.short_path,
+            )
+            headers.append(outs[0])
+            sources.extend(outs[1:])
+    return [
+        DefaultInfo(files = depset(headers + sources)),
+        OutputGroupInfo(headers = depset(headers), sources = depset(sources)),
+    ]
+
+bin2hpp = rule(
+    implementation = _bin2hpp_impl,
+    doc = "Generates a header for each file in srcs, or one header embedding all of them if out is given.",
+    attrs = {
+        "srcs": attr.label_list(allow_files = True, mandatory = True, doc = "Files to embed"),
+        "out": attr.string(doc = "Header to generate every file into, rather than one header each"),
+        "extensions": attr.string_list(default = ["hpp"], doc = "Extensions of the files generated for each input, the header's first"),
+        "options": attr.string_list(doc = "Other bin2hpp options"),
+        "tool": attr.label(executable = True, cfg = "exec", mandatory = True, doc = "The bin2hpp binary"),
+    },
+)
+"#;
+
+/// What a build snippet generates
+pub struct SnippetJob<'a> {
+    /// Input files, as given
+    pub inputs: &'a [String],
+    /// Arguments to pass on to every run, other than the inputs and outputs
+    pub arguments: &'a [String],
+    /// Path of the header every input is amalgamated into, if they are
+    pub amalgamation: Option<&'a str>,
+    /// Extensions of the files generated one per input, the header's first (a `--split`
+    /// definition is written next to it)
+    pub extensions: &'a [&'a str],
+}
+
+/// Generate a snippet which runs the tool from a build system as it was run now
+pub fn generate_snippet(build_system: BuildSystem, job: &SnippetJob) -> String {
+    return match build_system {
+        BuildSystem::Meson => meson_snippet(job),
+        BuildSystem::Bazel => bazel_snippet(job),
+    };
+}
+
+/// Quote a string as a Meson string literal
+fn meson_string(s: &str) -> String {
+    return format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"));
+}
+
+/// Meson snippet for the job: headers generated one per input are named after it with its
+/// extension replaced, exactly as Meson's `@BASENAME@` names them, so a `generator()` can declare
+/// them up front
+fn meson_snippet(job: &SnippetJob) -> String {
+    let arguments: Vec<String> = job.arguments.iter().map(|a| meson_string(a)).collect();
+    let inputs: Vec<String> = job.inputs.iter().map(|i| meson_string(i)).collect();
+
+    let mut out_string = String::with_capacity(0x200 + 0x40 * inputs.len());
+    out_string.push_str("bin2hpp = find_program('bin2hpp')");
+    out_string.push_str(LINE_ENDING);
+    match job.amalgamation {
+        Some(amalgamation) => {
+            let mut command = vec!["bin2hpp".to_string()];
+            for index in 0..inputs.len() {
+                command.push("'-i'".to_string());
+                command.push(format!("'@INPUT{}@'", index));
+            }
+            command.push("'--amalgamate'".to_string());
+            command.push("'@OUTPUT@'".to_string());
+            command.extend(arguments);
+            let file_name = amalgamation
+                .rsplit(['/', '\\'])
+                .next()
+                .unwrap_or(amalgamation);
+            let target_name = sanitise_identifier(file_name);
+            out_string.push_str(
+                format!(
+                    "{} = custom_target({},",
+                    target_name,
+                    meson_string(&target_name)
+                )
+                .as_str(),
+            );
+            out_string.push_str(LINE_ENDING);
+            out_string.push_str(format!("  input: [{}],", inputs.join(", ")).as_str());
+            out_string.push_str(LINE_ENDING);
+            out_string.push_str(format!("  output: {},", meson_string(file_name)).as_str());
+            out_string.push_str(LINE_ENDING);
+            out_string.push_str(format!("  command: [{}],", command.join(", ")).as_str());
+            out_string.push_str(LINE_ENDING);
+            out_string.push(')');
+            out_string.push_str(LINE_ENDING);
+        }
+        None => {
+            let mut command = vec![
+                "'-i'".to_string(),
+                "'@INPUT@'".to_string(),
+                "'-o'".to_string(),
+                "'@OUTPUT0@'".to_string(),
+            ];
+            let outputs: Vec<String> = job
+                .extensions
+                .iter()
+                .map(|extension| meson_string(&format!("@BASENAME@.{}", extension)))
+                .collect();
+            command.extend(arguments);
+            out_string.push_str("bin2hpp_gen = generator(bin2hpp,");
+            out_string.push_str(LINE_ENDING);
+            out_string.push_str(format!("  output: [{}],", outputs.join(", ")).as_str());
+            out_string.push_str(LINE_ENDING);
+            out_string.push_str(format!("  arguments: [{}],", command.join(", ")).as_str());
+            out_string.push_str(LINE_ENDING);
+            out_string.push(')');
+            out_string.push_str(LINE_ENDING);
+            out_string.push_str(
+                format!(
+                    "embedded_headers = bin2hpp_gen.process({})",
+                    inputs.join(", ")
+                )
+                .as_str(),
+            );
+            out_string.push_str(LINE_ENDING);
+        }
+    }
+
+    return out_string;
+}
+
+/// Quote a string as a Starlark string literal
+fn starlark_string(s: &str) -> String {
+    return format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+}
+
+fn starlark_list(items: &[String]) -> String {
+    let quoted: Vec<String> = items.iter().map(|item| starlark_string(item)).collect();
+    return format!("[{}]", quoted.join(", "));
+}
+
+/// Bazel snippet for the job: the rule, followed by the BUILD targets generating the inputs (which
+/// must be in the package) and a `cc_library` exposing the headers, the rule naming each header as
+/// the tool does, after its input with the extension replaced
+fn bazel_snippet(job: &SnippetJob) -> String {
+    let mut out_string = String::with_capacity(BAZEL_RULE.len() + 0x400 + 0x40 * job.inputs.len());
+    out_string.push_str("# tools/bin2hpp.bzl");
+    out_string.push_str(LINE_ENDING);
+    for line in BAZEL_RULE.lines() {
+        out_string.push_str(line);
+        out_string.push_str(LINE_ENDING);
+    }
+    out_string.push_str(LINE_ENDING);
+
+    let file_name = job.amalgamation.map(|amalgamation| {
+        amalgamation
+            .rsplit(['/', '\\'])
+            .next()
+            .unwrap_or(amalgamation)
+    });
+    let name = match file_name {
+        Some(file_name) => sanitise_identifier(file_name),
+        None => "embedded".to_string(),
+    };
+    let split = job.amalgamation.is_none() && job.extensions.len() > 1;
+
+    let mut lines = vec![
+        "# BUILD".to_string(),
+        "load(\"//tools:bin2hpp.bzl\", \"bin2hpp\")".to_string(),
+        String::new(),
+        "bin2hpp(".to_string(),
+        format!("    name = {},", starlark_string(&name)),
+        format!("    srcs = {},", starlark_list(job.inputs)),
+    ];
+    match file_name {
+        Some(file_name) => lines.push(format!("    out = {},", starlark_string(file_name))),
+        None => {
+            let extensions: Vec<String> = job.extensions.iter().map(|e| e.to_string()).collect();
+            lines.push(format!("    extensions = {},", starlark_list(&extensions)));
+        }
+    }
+    if !job.arguments.is_empty() {
+        lines.push(format!("    options = {},", starlark_list(job.arguments)));
+    }
+    lines.push("    tool = \"//tools:bin2hpp\",".to_string());
+    lines.push(")".to_string());
+    lines.push(String::new());
+    // The definitions of a split header are compiled, the headers only included
+    if split {
+        for group in ["headers", "sources"] {
+            lines.push("filegroup(".to_string());
+            lines.push(format!("    name = \"{}_{}\",", name, group));
+            lines.push(format!("    srcs = [\":{}\"],", name));
+            lines.push(format!("    output_group = \"{}\",", group));
+            lines.push(")".to_string());
+            lines.push(String::new());
+        }
+    }
+    lines.push("cc_library(".to_string());
+    lines.push(format!("    name = \"{}_lib\",", name));
+    match split {
+        true => {
+            lines.push(format!("    srcs = [\":{}_sources\"],", name));
+            lines.push(format!("    hdrs = [\":{}_headers\"],", name));
+        }
+        false => lines.push(format!("    hdrs = [\":{}\"],", name)),
+    }
+    lines.push(")".to_string());
+    for line in lines {
+        out_string.push_str(&line);
+        out_string.push_str(LINE_ENDING);
+    }
+
+    return out_string;
+}
@@ -0,0 +1,370 @@
+use crate::{
+    CppBackend, DATA_MARKER, LINE_ENDING, Representation, SourceOptions, array_data,
+    backend::{Backend, Features},
+    checksum,
+    checksum::ChecksumAlgorithm,
+    format_as_elements, format_as_escaped_split, push_declaration, push_doc_comment, section,
+    stub_data,
+};
+
+/// Backend generating C99 headers, in which only byte arrays and string literals can be
+/// generated (other representations are still generated as C++)
+pub struct CBackend;
+
+/// Keywords of C23, along with the underscored spellings of earlier standards
+const KEYWORDS: [&str; 59] = [
+    "alignas",
+    "alignof",
+    "auto",
+    "bool",
+    "break",
+    "case",
+    "char",
+    "const",
+    "constexpr",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extern",
+    "false",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "nullptr",
+    "register",
+    "restrict",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "static_assert",
+    "struct",
+    "switch",
+    "thread_local",
+    "true",
+    "typedef",
+    "typeof",
+    "typeof_unqual",
+    "union",
+    "unsigned",
+    "void",
+    "volatile",
+    "while",
+    "_Alignas",
+    "_Alignof",
+    "_Atomic",
+    "_BitInt",
+    "_Bool",
+    "_Complex",
+    "_Decimal128",
+    "_Decimal32",
+    "_Decimal64",
+    "_Generic",
+    "_Imaginary",
+    "_Noreturn",
+    "_Static_assert",
+    "_Thread_local",
+];
+
+impl Backend for CBackend {
+    fn extension(&self) -> &'static str {
+        return "h";
+    }
+
+    fn keywords(&self) -> &'static [&'static str] {
+        return &KEYWORDS;
+    }
+
+    fn unsupported(&self, features: &Features) -> Option<&'static str> {
+        let unsupported = features.namespace
+            || features.hybrid_loader
+            || features.verify_fn
+            || features.stream_accessor
+            || features.span_accessor
+            || features.char_type
+            || features.raw_string
+            || features.encoding
+            || features.delta_base
+            || features.decoding
+            || features.locales
+            || features.manifest
+            || features.emit_test
+            || features.split
+            || features.size_assertion
+            || features.element_type
+            || features.obfuscate_xor
+            || features.compress
+            || features.dedupe
+            || features.amalgamation;
+
+        return unsupported.then_some(
+            "only supports byte arrays and string literals, without --namespace, --hybrid-loader, --verify-fn, --stream-accessor, --span-accessor, --char-type, --raw-string, --encode, --delta-base, --decode-csv, --decode-struct, --locales, --manifest, --emit-test, --split, --assert-size, --assert-size-max, --element-type, --obfuscate-xor, --compress, --dedupe or several inputs in one header",
+        );
+    }
+
+    fn generate_src(
+        &self,
+        data: &[u8],
+        representation: Representation,
+        symbol_name: &str,
+        options: &SourceOptions,
+    ) -> String {
+        return match representation {
+            Representation::BraceInitialiser => {
+                let data = array_data(data, options);
+                generate_src_for_array(
+                    &format_as_elements(&data, &options.byte_format, options.element_type),
+                    data.len(),
+                    symbol_name,
+                    options,
+                )
+            }
+            Representation::EscapedStringLiteral | Representation::StringLiteral => {
+                generate_src_for_string(
+                    &format_as_escaped_split(data, options.max_literal_len),
+                    data.len(),
+                    representation == Representation::EscapedStringLiteral,
+                    symbol_name,
+                    options,
+                )
+            }
+            _ => CppBackend.generate_src(data, representation, symbol_name, options),
+        };
+    }
+
+    fn template(
+        &self,
+        array_len: usize,
+        representation: Representation,
+        symbol_name: &str,
+        options: &SourceOptions,
+    ) -> Option<String> {
+        return match representation {
+            Representation::BraceInitialiser => Some(generate_src_for_array(
+                DATA_MARKER,
+                array_len,
+                symbol_name,
+                options,
+            )),
+            Representation::EscapedStringLiteral => Some(generate_src_for_string(
+                DATA_MARKER,
+                array_len,
+                true,
+                symbol_name,
+                options,
+            )),
+            _ => None,
+        };
+    }
+}
+
+/// Generate a C header declaring the data as a `static const unsigned char` array along with a
+/// `<symbol>_len` constant
+///
+/// C (before C23) has no empty initialiser lists, so empty data is declared as a single zero and
+/// only `<symbol>_len` says there is nothing there. Neither does it count a NUL terminator.
+fn generate_src_for_array(
+    array_contents: &str,
+    array_len: usize,
+    symbol_name: &str,
+    options: &SourceOptions,
+) -> String {
+    let array_contents = match array_len {
+        0 => "0",
+        _ => array_contents,
+    };
+    let data_len = match options.null_terminate {
+        true => array_len.saturating_sub(1),
+        false => array_len,
+    };
+    let stub = stub_data(true);
+    let stub_declaration = array_declaration(
+        &crate::format_as_binary(&stub),
+        stub.len(),
+        symbol_name,
+        &declaration_specifiers(options),
+    ) + &size_declaration(symbol_name, stub.len(), options)
+        + &checksum_declarations(symbol_name, Some(&stub), options);
+
+    return generate_src(
+        &(array_declaration(
+            array_contents,
+            data_len,
+            symbol_name,
+            &declaration_specifiers(options),
+        ) + &size_declaration(symbol_name, data_len, options)
+            + &checksum_declarations(symbol_name, None, options)),
+        &stub_declaration,
+        options,
+    );
+}
+
+/// Generate a C header declaring the data as a string literal along with a `<symbol>_len`
+/// constant: a `static const char` array for text, or a `static const unsigned char` array
+/// exactly as long as the data (leaving out the terminating NUL, as C allows) for binary data
+fn generate_src_for_string(
+    string_contents: &str,
+    string_len: usize,
+    binary: bool,
+    symbol_name: &str,
+    options: &SourceOptions,
+) -> String {
+    return generate_src(
+        &(string_declaration(
+            string_contents,
+            string_len,
+            binary,
+            symbol_name,
+            &declaration_specifiers(options),
+        ) + &size_declaration(symbol_name, string_len, options)
+            + &checksum_declarations(symbol_name, None, options)),
+        &(string_declaration("", 0, binary, symbol_name, &declaration_specifiers(options))
+            + &size_declaration(symbol_name, 0, options)
+            + &checksum_declarations(symbol_name, Some(&[]), options)),
+        options,
+    );
+}
+
+fn generate_src(declaration: &str, stub_declaration: &str, options: &SourceOptions) -> String {
+    // Includes
+    let mut out_string = String::with_capacity(declaration.len() + 0x100);
+    out_string.push_str("#include <stddef.h>");
+    out_string.push_str(LINE_ENDING);
+    if options.section.is_some() {
+        section::push_helper(&mut out_string);
+    }
+
+    // Declarations
+    push_doc_comment(&mut out_string, options.comment.as_deref());
+    push_declaration(
+        &mut out_string,
+        declaration,
+        stub_declaration,
+        options.guard_macro.as_deref(),
+    );
+    if !out_string.ends_with(LINE_ENDING) {
+        out_string.push_str(LINE_ENDING);
+    }
+
+    return out_string;
+}
+
+fn array_declaration(
+    array_contents: &str,
+    data_len: usize,
+    symbol_name: &str,
+    specifiers: &str,
+) -> String {
+    return format!(
+        "{3}static const unsigned char {0}[] = {{{1}}};static const size_t {0}_len = {2};",
+        symbol_name, array_contents, data_len, specifiers
+    );
+}
+
+fn string_declaration(
+    string_contents: &str,
+    string_len: usize,
+    binary: bool,
+    symbol_name: &str,
+    specifiers: &str,
+) -> String {
+    return match binary {
+        true => format!(
+            "{4}static const unsigned char {0}[{1}] = \"{2}\";static const size_t {0}_len = {3};",
+            symbol_name,
+            string_len.max(1),
+            string_contents,
+            string_len,
+            specifiers
+        ),
+        false => format!(
+            "{3}static const char {0}[] = \"{1}\";static const size_t {0}_len = {2};",
+            symbol_name, string_contents, string_len, specifiers
+        ),
+    };
+}
+
+/// `<symbol>_size` constant following a declaration, the same as `<symbol>_len` but named as in
+/// C++ headers, if one is asked for
+fn size_declaration(symbol_name: &str, size: usize, options: &SourceOptions) -> String {
+    return match options.size_constant {
+        true => format!("static const size_t {}_size = {};", symbol_name, size),
+        false => String::new(),
+    };
+}
+
+/// `<symbol>_<algorithm>` constants (and `_hex` strings if asked for) following a declaration,
+/// holding the checksums of the data or, given a stub, of that
+fn checksum_declarations(
+    symbol_name: &str,
+    stub: Option<&[u8]>,
+    options: &SourceOptions,
+) -> String {
+    let mut declarations = String::new();
+    for checksum in &options.checksums {
+        let name = checksum.algorithm.name();
+        let digest = match stub {
+            Some(stub) => checksum.algorithm.digest(stub),
+            None => checksum.digest.clone(),
+        };
+        match checksum.algorithm {
+            ChecksumAlgorithm::Crc32 => {
+                let mut crc = [0; 4];
+                crc.copy_from_slice(&digest);
+                declarations.push_str(
+                    format!(
+                        "static const unsigned long {}_{} = {:#010x}UL;",
+                        symbol_name,
+                        name,
+                        u32::from_be_bytes(crc)
+                    )
+                    .as_str(),
+                );
+            }
+            ChecksumAlgorithm::Sha256 => declarations.push_str(
+                format!(
+                    "static const unsigned char {}_{}[32] = {};",
+                    symbol_name,
+                    name,
+                    checksum::to_initialiser(&digest)
+                )
+                .as_str(),
+            ),
+        }
+        if options.checksum_hex {
+            declarations.push_str(
+                format!(
+                    "static const char {}_{}_hex[] = \"{}\";",
+                    symbol_name,
+                    name,
+                    checksum::to_hex(&digest)
+                )
+                .as_str(),
+            );
+        }
+    }
+
+    return declarations;
+}
+
+/// Specifiers starting a declaration: its alignment and section, if it has them
+fn declaration_specifiers(options: &SourceOptions) -> String {
+    let alignment = match options.align {
+        Some(align) => format!("_Alignas({}) ", align),
+        None => String::new(),
+    };
+
+    return format!(
+        "{}{}",
+        alignment,
+        section::specifier(options.section.as_deref())
+    );
+}
@@ -0,0 +1,321 @@
+use clap::ValueEnum;
+
+/// Checksum algorithms which can be computed over embedded data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ChecksumAlgorithm {
+    Crc32,
+    Sha256,
+}
+
+impl ChecksumAlgorithm {
+    /// Compute the digest of some data, big-endian in the case of CRC32
+    pub fn digest(self, data: &[u8]) -> Vec<u8> {
+        return match self {
+            ChecksumAlgorithm::Crc32 => crc32(data).to_be_bytes().to_vec(),
+            ChecksumAlgorithm::Sha256 => sha256(data).to_vec(),
+        };
+    }
+
+    /// Name of the algorithm as it appears in generated symbols
+    pub fn name(self) -> &'static str {
+        return match self {
+            ChecksumAlgorithm::Crc32 => "crc32",
+            ChecksumAlgorithm::Sha256 => "sha256",
+        };
+    }
+}
+
+/// Reversed IEEE 802.3 CRC32 polynomial, as used by zlib, PNG, Ethernet, etc.
+const CRC32_POLYNOMIAL: u32 = 0xedb88320;
+
+/// Compute the (IEEE) CRC32 of some data
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffffffffu32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (CRC32_POLYNOMIAL & (crc & 1).wrapping_neg());
+        }
+    }
+
+    return !crc;
+}
+
+/// SHA-256 round constants (FIPS 180-4, section 4.2.2)
+pub const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// SHA-256 initial hash value (FIPS 180-4, section 5.3.3)
+pub const SHA256_H: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// Compute the SHA-256 digest of some data
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut state = SHA256_H;
+
+    // Pad with a single set bit, zeroes and the message length in bits so the total length is a
+    // multiple of 64 bytes
+    let mut tail = Vec::with_capacity(128);
+    let full_blocks = data.len() / 64 * 64;
+    tail.extend_from_slice(&data[full_blocks..]);
+    tail.push(0x80);
+    while tail.len() % 64 != 56 {
+        tail.push(0);
+    }
+    tail.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_be_bytes());
+
+    for block in data[..full_blocks]
+        .chunks_exact(64)
+        .chain(tail.chunks_exact(64))
+    {
+        sha256_compress(&mut state, block);
+    }
+
+    let mut digest = [0; 32];
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+
+    return digest;
+}
+
+fn sha256_compress(state: &mut [u32; 8], block: &[u8]) {
+    let mut w = [0u32; 64];
+    for (word, bytes) in w.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for i in 0..64 {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(SHA256_K[i])
+            .wrapping_add(w[i]);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (word, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *word = word.wrapping_add(value);
+    }
+}
+
+/// Generate the source of a C++ function with the signature
+/// `std::array<std::uint8_t,32> name(const unsigned char* data,std::size_t size)` computing the
+/// SHA-256 digest of some data, for generated code which needs to check embedded data at runtime
+///
+/// Requires `<array>`, `<cstddef>` and `<cstdint>`.
+pub fn cpp_sha256_function(name: &str) -> String {
+    let join = |words: &[u32]| {
+        words
+            .iter()
+            .map(|w| format!("{:#010x}", w))
+            .collect::<Vec<String>>()
+            .join(",")
+    };
+
+    let lines = [
+        format!(
+            "inline std::array<std::uint8_t,32> {}(const unsigned char* data,std::size_t size){{",
+            name
+        ),
+        format!("static constexpr std::uint32_t k[64]={{{}}};", join(&SHA256_K)),
+        format!("std::uint32_t h[8]={{{}}};", join(&SHA256_H)),
+        "auto rotr=[](std::uint32_t x,int n)->std::uint32_t{return (x>>n)|(x<<(32-n));};".to_string(),
+        // Padding: a single set bit, zeroes and the message length in bits
+        "const std::size_t padded=((size+8)/64+1)*64;const std::uint64_t bits=static_cast<std::uint64_t>(size)*8;".to_string(),
+        "auto byte_at=[&](std::size_t i)->std::uint32_t{if(i<size)return data[i];if(i==size)return 0x80;if(i>=padded-8)return static_cast<std::uint32_t>(bits>>(8*(padded-1-i)))&0xff;return 0;};".to_string(),
+        "for(std::size_t off=0;off<padded;off+=64){".to_string(),
+        "std::uint32_t w[64];".to_string(),
+        "for(int i=0;i<16;++i)w[i]=byte_at(off+4*i)<<24|byte_at(off+4*i+1)<<16|byte_at(off+4*i+2)<<8|byte_at(off+4*i+3);".to_string(),
+        "for(int i=16;i<64;++i){std::uint32_t s0=rotr(w[i-15],7)^rotr(w[i-15],18)^(w[i-15]>>3);std::uint32_t s1=rotr(w[i-2],17)^rotr(w[i-2],19)^(w[i-2]>>10);w[i]=w[i-16]+s0+w[i-7]+s1;}".to_string(),
+        "std::uint32_t a=h[0],b=h[1],c=h[2],d=h[3],e=h[4],f=h[5],g=h[6],hh=h[7];".to_string(),
+        "for(int i=0;i<64;++i){std::uint32_t t1=hh+(rotr(e,6)^rotr(e,11)^rotr(e,25))+((e&f)^(~e&g))+k[i]+w[i];std::uint32_t t2=(rotr(a,2)^rotr(a,13)^rotr(a,22))+((a&b)^(a&c)^(b&c));hh=g;g=f;f=e;e=d+t1;d=c;c=b;b=a;a=t1+t2;}".to_string(),
+        "h[0]+=a;h[1]+=b;h[2]+=c;h[3]+=d;h[4]+=e;h[5]+=f;h[6]+=g;h[7]+=hh;".to_string(),
+        "}".to_string(),
+        "std::array<std::uint8_t,32> digest{};".to_string(),
+        "for(int i=0;i<32;++i)digest[i]=static_cast<std::uint8_t>(h[i/4]>>(24-8*(i%4)));".to_string(),
+        "return digest;".to_string(),
+        "}".to_string(),
+    ];
+
+    let mut out_string = String::with_capacity(0x800);
+    for line in lines {
+        out_string.push_str(&line);
+        out_string.push_str(crate::LINE_ENDING);
+    }
+
+    return out_string;
+}
+
+/// Format a digest as lowercase hexadecimal
+pub fn to_hex(digest: &[u8]) -> String {
+    return digest.iter().map(|b| format!("{:02x}", b)).collect();
+}
+
+/// Format a digest as a C++ brace initialiser list
+pub fn to_initialiser(digest: &[u8]) -> String {
+    let bytes: Vec<String> = digest.iter().map(|b| format!("{:#04x}", b)).collect();
+    return format!("{{{}}}", bytes.join(","));
+}
+
+/// Generate the source of a C++ function with the signature
+/// `std::uint32_t name(const unsigned char* data,std::size_t size)` computing the CRC32 of some
+/// data
+///
+/// Requires `<cstddef>` and `<cstdint>`.
+pub fn cpp_crc32_function(name: &str) -> String {
+    let mut out_string = format!(
+        "inline std::uint32_t {}(const unsigned char* data,std::size_t size){{",
+        name
+    );
+    out_string.push_str(crate::LINE_ENDING);
+    out_string.push_str(
+        format!(
+            "std::uint32_t crc=0xffffffffu;for(std::size_t i=0;i<size;++i){{crc^=data[i];for(int b=0;b<8;++b)crc=(crc>>1)^({:#010x}u&(0u-(crc&1u)));}}return ~crc;",
+            CRC32_POLYNOMIAL
+        )
+        .as_str(),
+    );
+    out_string.push_str(crate::LINE_ENDING);
+    out_string.push('}');
+    out_string.push_str(crate::LINE_ENDING);
+
+    return out_string;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(digest: &[u8]) -> String {
+        return digest.iter().map(|b| format!("{:02x}", b)).collect();
+    }
+
+    #[test]
+    fn crc32_matches_the_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog"),
+            0x414fa339
+        );
+        assert_eq!(
+            ChecksumAlgorithm::Crc32.digest(b"123456789"),
+            vec![0xcb, 0xf4, 0x39, 0x26]
+        );
+    }
+
+    #[test]
+    fn sha256_matches_the_nist_vectors() {
+        // FIPS 180-2, appendix B
+        assert_eq!(
+            hex(&sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hex(&sha256(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        assert_eq!(
+            hex(&sha256(&[b'a'; 1_000_000])),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+        assert_eq!(
+            hex(&sha256(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn sha256_pads_messages_around_block_boundaries() {
+        // The length no longer fits in the block holding the last bytes from 56 bytes on
+        for (length, expected) in [
+            (
+                55,
+                "9f4390f8d30c2dd92ec9f095b65e2b9ae9b0a925a5258e241c9f1e910f734318",
+            ),
+            (
+                56,
+                "b35439a4ac6f0948b6d6f9e3c6af0f5f590ce20f1bde7090ef7970686ec6738a",
+            ),
+            (
+                63,
+                "7d3e74a05d7db15bce4ad9ec0658ea98e3f06eeecf16b4c6fff2da457ddc2f34",
+            ),
+            (
+                64,
+                "ffe054fe7ae0cb6dc65c3af9b61d5209f439851db43d0ba5997337df154668eb",
+            ),
+            (
+                65,
+                "635361c48bb9eab14198e76ea8ab7f1a41685d6ad62aa9146d301d4f17eb0ae0",
+            ),
+        ] {
+            assert_eq!(hex(&sha256(&vec![b'a'; length])), expected);
+        }
+    }
+
+    #[test]
+    fn generated_functions_compute_the_same_digests() {
+        let dir = std::env::temp_dir().join(format!("bin2hpp-checksum-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let src = format!(
+            "#include <array>\n#include <cstddef>\n#include <cstdint>\n#include <cstdio>\n{}{}\
+             int main(){{static const unsigned char data[]=\"123456789\";\
+             std::printf(\"%08x\\n\",check_crc32(data,9));\
+             for(std::uint8_t b:check_sha256(data,9))std::printf(\"%02x\",b);return 0;}}\n",
+            cpp_crc32_function("check_crc32"),
+            cpp_sha256_function("check_sha256")
+        );
+        std::fs::write(dir.join("check.cpp"), src).unwrap();
+
+        // Skipped where there is no C++ compiler
+        let Ok(status) = std::process::Command::new("c++")
+            .args(["-std=c++17", "check.cpp", "-o", "check"])
+            .current_dir(&dir)
+            .status()
+        else {
+            return;
+        };
+        assert!(status.success());
+        let output = std::process::Command::new(dir.join("check"))
+            .output()
+            .unwrap();
+        let expected = format!("cbf43926\n{}", hex(&sha256(b"123456789")));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,966 @@
+use clap::ValueEnum;
+
+use crate::{
+    LINE_ENDING, SourceOptions, array_specifiers, checksum, format_as_binary_with,
+    push_checksum_constants, push_declaration, push_doc_comment, push_namespace_close,
+    push_namespace_open, push_size_constant, section,
+};
+
+/// Compression algorithms data can be embedded with (`--compress`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CompressionAlgorithm {
+    /// A Zstandard frame, decompressed with libzstd (link with -lzstd)
+    Zstd,
+    /// A gzip member, decompressed with zlib (link with -lz)
+    Gzip,
+    /// An LZ4 block, decompressed by a small decoder emitted into the header
+    Lz4,
+}
+
+impl CompressionAlgorithm {
+    pub fn compress(self, data: &[u8]) -> Vec<u8> {
+        return match self {
+            CompressionAlgorithm::Zstd => zstd(data),
+            CompressionAlgorithm::Gzip => gzip(data),
+            CompressionAlgorithm::Lz4 => lz4_block(data),
+        };
+    }
+
+    /// Library the generated header needs to decompress the data, as its header and the linker
+    /// flag pulling it in
+    fn library(self) -> Option<(&'static str, &'static str)> {
+        return match self {
+            CompressionAlgorithm::Zstd => Some(("zstd.h", "-lzstd")),
+            CompressionAlgorithm::Gzip => Some(("zlib.h", "-lz")),
+            CompressionAlgorithm::Lz4 => None,
+        };
+    }
+}
+
+/// Positions hashed into the match finder's table, by their first four bytes
+const HASH_BITS: u32 = 16;
+
+/// Candidates tried for each match before settling for the longest found so far
+const MAX_CHAIN_LEN: usize = 32;
+
+/// No position, in the match finder's tables
+const NO_POSITION: u32 = u32::MAX;
+
+/// Hash chains over the positions of the data seen so far, for finding earlier occurrences of
+/// the bytes at a position
+struct MatchFinder<'a> {
+    data: &'a [u8],
+    head: Vec<u32>,
+    prev: Vec<u32>,
+}
+
+impl<'a> MatchFinder<'a> {
+    fn new(data: &'a [u8]) -> MatchFinder<'a> {
+        return MatchFinder {
+            data,
+            head: vec![NO_POSITION; 1 << HASH_BITS],
+            prev: vec![NO_POSITION; data.len()],
+        };
+    }
+
+    fn hash(&self, pos: usize) -> usize {
+        let bytes = [
+            self.data[pos],
+            self.data[pos + 1],
+            self.data[pos + 2],
+            self.data[pos + 3],
+        ];
+        return (u32::from_le_bytes(bytes).wrapping_mul(0x9e3779b1) >> (32 - HASH_BITS)) as usize;
+    }
+
+    fn insert(&mut self, pos: usize) {
+        if pos + 4 > self.data.len() {
+            return;
+        }
+        let hash = self.hash(pos);
+        self.prev[pos] = self.head[hash];
+        self.head[hash] = pos as u32;
+    }
+
+    /// Longest earlier occurrence of the bytes at `pos` within `max_dist` bytes of it, as its
+    /// length (at most `max_len`, and not reaching `end`) and distance back
+    fn find(&self, pos: usize, end: usize, max_dist: usize, max_len: usize) -> (usize, usize) {
+        if pos + 4 > end {
+            return (0, 0);
+        }
+        let max_len = max_len.min(end - pos);
+
+        let (mut best_len, mut best_dist) = (0, 0);
+        let mut candidate = self.head[self.hash(pos)];
+        for _ in 0..MAX_CHAIN_LEN {
+            if candidate == NO_POSITION || pos - candidate as usize > max_dist {
+                break;
+            }
+            let candidate_pos = candidate as usize;
+            let len = self.data[candidate_pos..]
+                .iter()
+                .zip(&self.data[pos..pos + max_len])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if len > best_len {
+                (best_len, best_dist) = (len, pos - candidate_pos);
+                if len == max_len {
+                    break;
+                }
+            }
+            candidate = self.prev[candidate_pos];
+        }
+
+        return (best_len, best_dist);
+    }
+}
+
+/// Run of literal bytes followed by a copy of earlier data
+struct Sequence {
+    literal_len: usize,
+    match_len: usize,
+    distance: usize,
+}
+
+/// What a format allows of the matches it encodes
+struct MatchLimits {
+    min_len: usize,
+    max_len: usize,
+    max_dist: usize,
+    /// Bytes at the end of the data which no match may start in
+    start_margin: usize,
+    /// Bytes at the end of the data which no match may reach into
+    end_margin: usize,
+}
+
+/// Split `data[start..end]` into literals each followed by a match, greedily taking the longest
+/// match at each position, returning them along with where the literals left at the end start
+fn find_sequences(
+    finder: &mut MatchFinder,
+    start: usize,
+    end: usize,
+    limits: &MatchLimits,
+) -> (Vec<Sequence>, usize) {
+    let last_start = end.saturating_sub(limits.start_margin);
+    let match_end = end.saturating_sub(limits.end_margin);
+
+    let mut sequences = Vec::new();
+    let mut literal_start = start;
+    let mut pos = start;
+    while pos < last_start {
+        let (len, distance) = finder.find(pos, match_end, limits.max_dist, limits.max_len);
+        if len >= limits.min_len {
+            sequences.push(Sequence {
+                literal_len: pos - literal_start,
+                match_len: len,
+                distance,
+            });
+            for p in pos..pos + len {
+                finder.insert(p);
+            }
+            pos += len;
+            literal_start = pos;
+        } else {
+            finder.insert(pos);
+            pos += 1;
+        }
+    }
+    // Later blocks can still match what is left
+    for p in pos..end {
+        finder.insert(p);
+    }
+
+    return (sequences, literal_start);
+}
+
+/// Writer of a stream of bits, filling each byte from its least significant bit
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u64,
+    bit_count: u32,
+}
+
+impl BitWriter {
+    fn new() -> BitWriter {
+        return BitWriter {
+            out: Vec::new(),
+            bits: 0,
+            bit_count: 0,
+        };
+    }
+
+    fn write(&mut self, value: u64, bit_count: u32) {
+        let mask = match bit_count {
+            64 => u64::MAX,
+            _ => (1 << bit_count) - 1,
+        };
+        self.bits |= (value & mask) << self.bit_count;
+        self.bit_count += bit_count;
+        while self.bit_count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    /// Pad the last byte with zeros, returning the bytes written
+    fn finish(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.out.push(self.bits as u8);
+        }
+        return self.out;
+    }
+}
+
+/// Match lengths at which each DEFLATE length code starts (RFC 1951, section 3.2.5)
+const DEFLATE_LENGTH_BASE: [usize; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const DEFLATE_LENGTH_EXTRA_BITS: [u32; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// Distances at which each DEFLATE distance code starts
+const DEFLATE_DISTANCE_BASE: [usize; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DEFLATE_DISTANCE_EXTRA_BITS: [u32; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Code with the largest base at most `value`, and how far past that base the value is
+fn base_code(bases: &[usize], value: usize) -> (usize, usize) {
+    let code = bases.iter().rposition(|&base| base <= value).unwrap_or(0);
+    return (code, value - bases[code]);
+}
+
+/// Write a literal/length symbol with the fixed Huffman code (RFC 1951, section 3.2.6), whose
+/// bits go most significant first
+fn write_fixed_symbol(writer: &mut BitWriter, symbol: usize) {
+    let (code, bit_count) = match symbol {
+        0..=143 => (0x30 + symbol, 8),
+        144..=255 => (0x190 + symbol - 144, 9),
+        256..=279 => (symbol - 256, 7),
+        _ => (0xc0 + symbol - 280, 8),
+    };
+    writer.write(
+        u64::from((code as u32).reverse_bits() >> (32 - bit_count)),
+        bit_count,
+    );
+}
+
+/// Compress data into a single DEFLATE block with the fixed Huffman codes, which saves
+/// describing any codes and does well enough on the repetitive assets worth compressing
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut finder = MatchFinder::new(data);
+    let (sequences, trailing_literals) = find_sequences(
+        &mut finder,
+        0,
+        data.len(),
+        &MatchLimits {
+            min_len: 3,
+            max_len: 258,
+            max_dist: 32768,
+            start_margin: 0,
+            end_margin: 0,
+        },
+    );
+
+    let mut writer = BitWriter::new();
+    // Final block, fixed Huffman codes
+    writer.write(1, 1);
+    writer.write(1, 2);
+    let mut pos = 0;
+    for sequence in &sequences {
+        for &b in &data[pos..pos + sequence.literal_len] {
+            write_fixed_symbol(&mut writer, usize::from(b));
+        }
+        let (length_code, length_extra) = base_code(&DEFLATE_LENGTH_BASE, sequence.match_len);
+        write_fixed_symbol(&mut writer, 257 + length_code);
+        writer.write(length_extra as u64, DEFLATE_LENGTH_EXTRA_BITS[length_code]);
+        let (distance_code, distance_extra) = base_code(&DEFLATE_DISTANCE_BASE, sequence.distance);
+        writer.write(u64::from((distance_code as u32).reverse_bits() >> 27), 5);
+        writer.write(
+            distance_extra as u64,
+            DEFLATE_DISTANCE_EXTRA_BITS[distance_code],
+        );
+        pos += sequence.literal_len + sequence.match_len;
+    }
+    for &b in &data[trailing_literals..] {
+        write_fixed_symbol(&mut writer, usize::from(b));
+    }
+    write_fixed_symbol(&mut writer, 256);
+
+    return writer.finish();
+}
+
+/// Compress data into a gzip member (RFC 1952) with no name or timestamp, so that the same input
+/// always gives the same bytes
+fn gzip(data: &[u8]) -> Vec<u8> {
+    // Magic, deflate, no flags, no modification time, no extra flags, unknown OS
+    let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+    out.extend(deflate(data));
+    out.extend(checksum::crc32(data).to_le_bytes());
+    out.extend((data.len() as u32).to_le_bytes());
+
+    return out;
+}
+
+/// Write the part of an LZ4 length which does not fit in its token's nibble
+fn push_lz4_length(out: &mut Vec<u8>, len: usize) {
+    if len < 15 {
+        return;
+    }
+    let mut rest = len - 15;
+    while rest >= 255 {
+        out.push(255);
+        rest -= 255;
+    }
+    out.push(rest as u8);
+}
+
+/// Compress data into an LZ4 block (without the frame around it, as the header records the
+/// sizes itself)
+fn lz4_block(data: &[u8]) -> Vec<u8> {
+    let mut finder = MatchFinder::new(data);
+    // The last match has to start 12 bytes before the end and the last 5 bytes are literals
+    let (sequences, trailing_literals) = find_sequences(
+        &mut finder,
+        0,
+        data.len(),
+        &MatchLimits {
+            min_len: 4,
+            max_len: usize::MAX,
+            max_dist: 0xffff,
+            start_margin: 12,
+            end_margin: 5,
+        },
+    );
+
+    let mut out = Vec::with_capacity(data.len() / 2 + 16);
+    let mut pos = 0;
+    for sequence in &sequences {
+        let match_len = sequence.match_len - 4;
+        out.push(((sequence.literal_len.min(15) as u8) << 4) | match_len.min(15) as u8);
+        push_lz4_length(&mut out, sequence.literal_len);
+        out.extend_from_slice(&data[pos..pos + sequence.literal_len]);
+        out.extend((sequence.distance as u16).to_le_bytes());
+        push_lz4_length(&mut out, match_len);
+        pos += sequence.literal_len + sequence.match_len;
+    }
+    let literal_len = data.len() - trailing_literals;
+    out.push((literal_len.min(15) as u8) << 4);
+    push_lz4_length(&mut out, literal_len);
+    out.extend_from_slice(&data[trailing_literals..]);
+
+    return out;
+}
+
+/// Bytes of input in each Zstandard block, the most a block may decompress to
+const ZSTD_BLOCK_LEN: usize = 0x2_0000;
+
+/// Literal lengths at which each Zstandard literal length code starts (RFC 8878, section
+/// 3.1.1.3.2.1.1), and the extra bits following it
+const ZSTD_LITERAL_LENGTH_BASE: [usize; 36] = [
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 18, 20, 22, 24, 28, 32, 40, 48, 64,
+    128, 256, 512, 1024, 2048, 4096, 8192, 16384, 32768, 65536,
+];
+const ZSTD_LITERAL_LENGTH_BITS: [u32; 36] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 3, 3, 4, 6, 7, 8, 9, 10, 11,
+    12, 13, 14, 15, 16,
+];
+
+/// Match lengths at which each Zstandard match length code starts, and the extra bits following
+/// it
+const ZSTD_MATCH_LENGTH_BASE: [usize; 53] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27,
+    28, 29, 30, 31, 32, 33, 34, 35, 37, 39, 41, 43, 47, 51, 59, 67, 83, 99, 131, 259, 515, 1027,
+    2051, 4099, 8195, 16387, 32771, 65539,
+];
+const ZSTD_MATCH_LENGTH_BITS: [u32; 53] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    1, 1, 1, 1, 2, 2, 3, 3, 4, 4, 5, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16,
+];
+
+/// Predefined distributions of the literal length, match length and offset codes (RFC 8878,
+/// section 3.1.1.3.2.2), which save describing any in the blocks
+const ZSTD_LITERAL_LENGTH_DISTRIBUTION: [i16; 36] = [
+    4, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 3, 2, 1, 1, 1, 1, 1,
+    -1, -1, -1, -1,
+];
+const ZSTD_MATCH_LENGTH_DISTRIBUTION: [i16; 53] = [
+    1, 4, 3, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1, -1, -1,
+];
+const ZSTD_OFFSET_DISTRIBUTION: [i16; 29] = [
+    1, 1, 1, 1, 1, 1, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1,
+];
+
+/// Table for encoding symbols of a distribution with finite state entropy (tANS), built as
+/// Zstandard builds it so that the decoder's table (which the RFC specifies) matches
+struct FseTable {
+    table_log: u32,
+    state_table: Vec<u32>,
+    /// For each symbol, the offset of its states in `state_table` and the (offset) number of
+    /// bits its encoding outputs
+    transforms: Vec<(i64, u32)>,
+}
+
+impl FseTable {
+    fn new(distribution: &[i16], table_log: u32) -> FseTable {
+        let table_size = 1usize << table_log;
+        let mask = table_size - 1;
+        let step = (table_size >> 1) + (table_size >> 3) + 3;
+
+        // Symbols with "less than one" probability take the last cells
+        let mut cumulative = vec![0usize; distribution.len() + 1];
+        let mut table_symbols = vec![0usize; table_size];
+        let mut high_threshold = table_size - 1;
+        for (symbol, &count) in distribution.iter().enumerate() {
+            cumulative[symbol + 1] = match count {
+                -1 => {
+                    table_symbols[high_threshold] = symbol;
+                    high_threshold -= 1;
+                    cumulative[symbol] + 1
+                }
+                _ => cumulative[symbol] + count as usize,
+            };
+        }
+
+        let mut position = 0;
+        for (symbol, &count) in distribution.iter().enumerate() {
+            for _ in 0..count.max(0) {
+                table_symbols[position] = symbol;
+                position = (position + step) & mask;
+                while position > high_threshold {
+                    position = (position + step) & mask;
+                }
+            }
+        }
+
+        let mut state_table = vec![0u32; table_size];
+        for (u, &symbol) in table_symbols.iter().enumerate() {
+            state_table[cumulative[symbol]] = (table_size + u) as u32;
+            cumulative[symbol] += 1;
+        }
+
+        let mut total: i64 = 0;
+        let transforms = distribution
+            .iter()
+            .map(|&count| match count {
+                0 => (0, ((table_log + 1) << 16).wrapping_sub(1 << table_log)),
+                -1 | 1 => {
+                    total += 1;
+                    (total - 2, (table_log << 16).wrapping_sub(1 << table_log))
+                }
+                _ => {
+                    let count = count as u32;
+                    let max_bits_out = table_log - (31 - (count - 1).leading_zeros());
+                    let offset = total - i64::from(count);
+                    total += i64::from(count);
+                    (
+                        offset,
+                        (max_bits_out << 16).wrapping_sub(count << max_bits_out),
+                    )
+                }
+            })
+            .collect::<Vec<_>>();
+
+        return FseTable {
+            table_log,
+            state_table,
+            transforms,
+        };
+    }
+
+    fn next_state(&self, state: u32, bit_count: u32, symbol: usize) -> u32 {
+        let index = i64::from(state >> bit_count) + self.transforms[symbol].0;
+        return self.state_table[index as usize];
+    }
+
+    /// State encoding the first symbol, which outputs no bits
+    fn initial_state(&self, symbol: usize) -> u32 {
+        let delta_bits = self.transforms[symbol].1;
+        let bit_count = delta_bits.wrapping_add(1 << 15) >> 16;
+        let state = (bit_count << 16).wrapping_sub(delta_bits);
+        return self.next_state(state, bit_count, symbol);
+    }
+
+    /// Encode a symbol, writing the low bits of the state out and moving to the next state
+    fn encode(&self, writer: &mut BitWriter, state: u32, symbol: usize) -> u32 {
+        let bit_count = state.wrapping_add(self.transforms[symbol].1) >> 16;
+        writer.write(u64::from(state), bit_count);
+        return self.next_state(state, bit_count, symbol);
+    }
+}
+
+/// Codes of a sequence's literal length, match length and offset, with their extra bits
+struct ZstdCodes {
+    literal_length: (usize, u64, u32),
+    match_length: (usize, u64, u32),
+    offset: (usize, u64, u32),
+}
+
+impl ZstdCodes {
+    fn new(sequence: &Sequence) -> ZstdCodes {
+        let (ll_code, ll_extra) = base_code(&ZSTD_LITERAL_LENGTH_BASE, sequence.literal_len);
+        let (ml_code, ml_extra) = base_code(&ZSTD_MATCH_LENGTH_BASE, sequence.match_len);
+        // Offset values up to 3 are repeat offsets, which are never used
+        let offset_value = sequence.distance as u64 + 3;
+        let of_code = 63 - offset_value.leading_zeros();
+        return ZstdCodes {
+            literal_length: (ll_code, ll_extra as u64, ZSTD_LITERAL_LENGTH_BITS[ll_code]),
+            match_length: (ml_code, ml_extra as u64, ZSTD_MATCH_LENGTH_BITS[ml_code]),
+            offset: (of_code as usize, offset_value - (1 << of_code), of_code),
+        };
+    }
+
+    fn write_extra_bits(&self, writer: &mut BitWriter) {
+        writer.write(self.literal_length.1, self.literal_length.2);
+        writer.write(self.match_length.1, self.match_length.2);
+        writer.write(self.offset.1, self.offset.2);
+    }
+}
+
+/// Encode the sequences of a Zstandard block with the predefined distributions, the last one
+/// first as the decoder reads the bitstream backwards
+fn zstd_sequences(sequences: &[Sequence], tables: &[FseTable; 3]) -> Vec<u8> {
+    let [ll_table, ml_table, of_table] = tables;
+    let mut out = Vec::new();
+    match sequences.len() {
+        count @ 0..0x80 => out.push(count as u8),
+        count @ 0x80..0x7f00 => out.extend([(count >> 8) as u8 + 0x80, count as u8]),
+        count => {
+            out.push(0xff);
+            out.extend(((count - 0x7f00) as u16).to_le_bytes());
+        }
+    }
+    let Some(last) = sequences.last() else {
+        return out;
+    };
+    // Predefined distributions for all three codes
+    out.push(0);
+
+    let mut writer = BitWriter::new();
+    let codes = ZstdCodes::new(last);
+    let mut ml_state = ml_table.initial_state(codes.match_length.0);
+    let mut of_state = of_table.initial_state(codes.offset.0);
+    let mut ll_state = ll_table.initial_state(codes.literal_length.0);
+    codes.write_extra_bits(&mut writer);
+    for sequence in sequences.iter().rev().skip(1) {
+        let codes = ZstdCodes::new(sequence);
+        of_state = of_table.encode(&mut writer, of_state, codes.offset.0);
+        ml_state = ml_table.encode(&mut writer, ml_state, codes.match_length.0);
+        ll_state = ll_table.encode(&mut writer, ll_state, codes.literal_length.0);
+        codes.write_extra_bits(&mut writer);
+    }
+    writer.write(u64::from(ml_state), ml_table.table_log);
+    writer.write(u64::from(of_state), of_table.table_log);
+    writer.write(u64::from(ll_state), ll_table.table_log);
+    // End marker, which the decoder finds the start of the stream by
+    writer.write(1, 1);
+    out.extend(writer.finish());
+
+    return out;
+}
+
+/// Compress data into a Zstandard frame (RFC 8878) of blocks whose literals are stored as they
+/// are and whose sequences use the predefined distributions, which keeps the encoder small
+fn zstd(data: &[u8]) -> Vec<u8> {
+    let tables = [
+        FseTable::new(&ZSTD_LITERAL_LENGTH_DISTRIBUTION, 6),
+        FseTable::new(&ZSTD_MATCH_LENGTH_DISTRIBUTION, 6),
+        FseTable::new(&ZSTD_OFFSET_DISTRIBUTION, 5),
+    ];
+
+    // Magic, then a single segment (so no window descriptor) with the content size and no
+    // checksum or dictionary
+    let mut out = vec![0x28, 0xb5, 0x2f, 0xfd];
+    match data.len() {
+        len @ 0..0x100 => out.extend([0x20, len as u8]),
+        len @ 0x100..0x1_0100 => {
+            out.push(0x60);
+            out.extend(((len - 0x100) as u16).to_le_bytes());
+        }
+        len if len <= u32::MAX as usize => {
+            out.push(0xa0);
+            out.extend((len as u32).to_le_bytes());
+        }
+        len => {
+            out.push(0xe0);
+            out.extend((len as u64).to_le_bytes());
+        }
+    }
+
+    let mut finder = MatchFinder::new(data);
+    let limits = MatchLimits {
+        min_len: 3,
+        max_len: ZSTD_BLOCK_LEN,
+        max_dist: (1 << 28) - 4,
+        start_margin: 0,
+        end_margin: 0,
+    };
+    let mut start = 0;
+    loop {
+        let end = (start + ZSTD_BLOCK_LEN).min(data.len());
+        let last_block = end == data.len();
+        let (sequences, trailing_literals) = find_sequences(&mut finder, start, end, &limits);
+
+        // Raw literals: every byte not covered by a match
+        let mut literals = Vec::with_capacity(end - start);
+        let mut pos = start;
+        for sequence in &sequences {
+            literals.extend_from_slice(&data[pos..pos + sequence.literal_len]);
+            pos += sequence.literal_len + sequence.match_len;
+        }
+        literals.extend_from_slice(&data[trailing_literals..end]);
+        let mut block = match literals.len() {
+            len @ 0..32 => vec![(len << 3) as u8],
+            len @ 32..0x1000 => ((len << 4) as u16 | 0b0100).to_le_bytes().to_vec(),
+            len => ((len << 4) as u32 | 0b1100).to_le_bytes()[..3].to_vec(),
+        };
+        block.extend(literals);
+        block.extend(zstd_sequences(&sequences, &tables));
+
+        // Blocks which do not shrink are stored as they are
+        let (block_type, contents) = match block.len() < end - start {
+            true => (2, &block[..]),
+            false => (0, &data[start..end]),
+        };
+        let header = (contents.len() << 3) as u32 | (block_type << 1) | u32::from(last_block);
+        out.extend(&header.to_le_bytes()[..3]);
+        out.extend_from_slice(contents);
+
+        if last_block {
+            break;
+        }
+        start = end;
+    }
+
+    return out;
+}
+
+/// Minimal LZ4 block decoder, which refuses malformed input rather than overrunning either buffer
+const LZ4_DECODER: &str = r#"inline bool lz4_decompress(const unsigned char* src,std::size_t src_size,unsigned char* dst,std::size_t dst_size) noexcept{
+std::size_t i=0,o=0;
+auto length=[&](std::size_t len,bool& ok){if(len!=15)return len;unsigned char b;do{if(i>=src_size){ok=false;return len;}b=src[i++];len+=b;}while(b==255);return len;};
+while(i<src_size){
+const unsigned token=src[i++];bool ok=true;
+const std::size_t literals=length(token>>4,ok);
+if(!ok||literals>src_size-i||literals>dst_size-o)return false;
+for(std::size_t k=0;k<literals;++k)dst[o++]=src[i++];
+if(i==src_size)break;
+if(src_size-i<2)return false;
+const std::size_t offset=src[i]|(std::size_t(src[i+1])<<8);i+=2;
+const std::size_t match=length(token&15u,ok)+4;
+if(!ok||offset==0||offset>o||match>dst_size-o)return false;
+for(std::size_t k=0;k<match;++k,++o)dst[o]=dst[o-offset];
+}
+return o==dst_size;
+}"#;
+
+fn data_declaration(
+    compressed: &[u8],
+    uncompressed_size: usize,
+    symbol_name: &str,
+    options: &SourceOptions,
+) -> String {
+    return format!(
+        "{4}constexpr std::array<std::uint8_t,{1}> {0}{{{2}}};constexpr std::size_t {0}_uncompressed_size={3};",
+        symbol_name,
+        compressed.len(),
+        format_as_binary_with(compressed, &options.byte_format),
+        uncompressed_size,
+        array_specifiers(options)
+    );
+}
+
+/// Generate the input compressed with the given algorithm, along with its size before
+/// compression and functions decompressing it into a buffer (`<symbol>_decompress_into()`) or a
+/// `std::vector` (`<symbol>_decompress()`)
+///
+/// Zstandard and gzip data is decompressed with their reference libraries, which the header
+/// includes, while LZ4 data is decompressed by a decoder emitted into the header.
+pub fn generate_src(
+    data: &[u8],
+    algorithm: CompressionAlgorithm,
+    symbol_name: &str,
+    options: &SourceOptions,
+) -> String {
+    let compressed = algorithm.compress(data);
+
+    // Includes
+    let mut out_string = String::with_capacity(compressed.len() * 5 + 0x400);
+    for header in ["array", "cstddef", "cstdint", "vector"] {
+        out_string.push_str(format!("#include <{}>", header).as_str());
+        out_string.push_str(LINE_ENDING);
+    }
+    match algorithm.library() {
+        Some((header, linker_flag)) => {
+            out_string.push_str(format!("// Decompressing needs {}", linker_flag).as_str());
+            out_string.push_str(LINE_ENDING);
+            out_string.push_str(format!("#include <{}>", header).as_str());
+            out_string.push_str(LINE_ENDING);
+        }
+        None => {
+            out_string.push_str("#ifndef BIN2HPP_DETAIL_LZ4");
+            out_string.push_str(LINE_ENDING);
+            out_string.push_str("#define BIN2HPP_DETAIL_LZ4");
+            out_string.push_str(LINE_ENDING);
+            out_string.push_str("namespace bin2hpp_detail{");
+            out_string.push_str(LINE_ENDING);
+            for line in LZ4_DECODER.lines() {
+                out_string.push_str(line);
+                out_string.push_str(LINE_ENDING);
+            }
+            out_string.push('}');
+            out_string.push_str(LINE_ENDING);
+            out_string.push_str("#endif");
+            out_string.push_str(LINE_ENDING);
+        }
+    }
+    if options.section.is_some() {
+        section::push_helper(&mut out_string);
+    }
+
+    // Namespace
+    push_namespace_open(
+        &mut out_string,
+        options.ns_name.as_deref(),
+        options.internal_linkage,
+    );
+
+    // Compressed data and its size before compression
+    push_doc_comment(&mut out_string, options.comment.as_deref());
+    push_declaration(
+        &mut out_string,
+        &data_declaration(&compressed, data.len(), symbol_name, options),
+        &data_declaration(&algorithm.compress(&[]), 0, symbol_name, options),
+        options.guard_macro.as_deref(),
+    );
+    if !out_string.ends_with(LINE_ENDING) {
+        out_string.push_str(LINE_ENDING);
+    }
+
+    // Size and checksums of the data before compression
+    push_size_constant(&mut out_string, symbol_name, data.len(), 0, options);
+    push_checksum_constants(&mut out_string, symbol_name, &[], options);
+
+    // Decompression
+    let decompress_into = match algorithm {
+        CompressionAlgorithm::Zstd => format!(
+            "return ZSTD_decompress(out,{0}_uncompressed_size,{0}.data(),{0}.size())=={0}_uncompressed_size;",
+            symbol_name
+        ),
+        CompressionAlgorithm::Gzip => format!(
+            "z_stream stream{{}};Bytef none=0;stream.next_in=const_cast<Bytef*>(reinterpret_cast<const Bytef*>({0}.data()));stream.avail_in=static_cast<uInt>({0}.size());stream.next_out=out!=nullptr?out:&none;stream.avail_out=static_cast<uInt>({0}_uncompressed_size);if(inflateInit2(&stream,16+MAX_WBITS)!=Z_OK)return false;const int result=inflate(&stream,Z_FINISH);inflateEnd(&stream);return result==Z_STREAM_END&&stream.total_out=={0}_uncompressed_size;",
+            symbol_name
+        ),
+        CompressionAlgorithm::Lz4 => format!(
+            "return ::bin2hpp_detail::lz4_decompress({0}.data(),{0}.size(),out,{0}_uncompressed_size);",
+            symbol_name
+        ),
+    };
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str(
+        format!(
+            "inline bool {}_decompress_into(std::uint8_t* out){{{}}}",
+            symbol_name, decompress_into
+        )
+        .as_str(),
+    );
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str(
+        format!(
+            "inline std::vector<std::uint8_t> {0}_decompress(){{std::vector<std::uint8_t> out({0}_uncompressed_size);if(!{0}_decompress_into(out.data()))out.clear();return out;}}",
+            symbol_name
+        )
+        .as_str(),
+    );
+    out_string.push_str(LINE_ENDING);
+
+    // Close namespace (if need be)
+    push_namespace_close(
+        &mut out_string,
+        options.ns_name.as_deref(),
+        options.internal_linkage,
+    );
+
+    // Trailing newline
+    if !out_string.ends_with(LINE_ENDING) {
+        out_string.push_str(LINE_ENDING);
+    }
+
+    return out_string;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Write,
+        process::{Command, Stdio},
+    };
+
+    use super::*;
+
+    /// Inputs covering the empty case, literals only, long matches, incompressible data and
+    /// several Zstandard blocks
+    fn samples() -> Vec<Vec<u8>> {
+        let mut state: u32 = 1;
+        let noise: Vec<u8> = (0..0x1_1000)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                return (state >> 16) as u8;
+            })
+            .collect();
+        let text = b"the quick brown fox jumps over the lazy dog ".repeat(0x2000);
+        let mixed: Vec<u8> = noise[..0x4000]
+            .iter()
+            .chain(&text[..0x3_0000])
+            .chain(&vec![0; 0x1_0000])
+            .copied()
+            .collect();
+
+        return vec![
+            Vec::new(),
+            b"a".to_vec(),
+            b"abcdefghijklmnop".to_vec(),
+            vec![7; 1000],
+            noise,
+            text,
+            mixed,
+        ];
+    }
+
+    fn lz4_decompress(src: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut i = 0;
+        let length = |nibble: usize, i: &mut usize| {
+            let mut len = nibble;
+            if nibble == 15 {
+                loop {
+                    let b = src[*i];
+                    *i += 1;
+                    len += b as usize;
+                    if b != 255 {
+                        break;
+                    }
+                }
+            }
+            return len;
+        };
+        loop {
+            let token = src[i] as usize;
+            i += 1;
+            let literals = length(token >> 4, &mut i);
+            out.extend_from_slice(&src[i..i + literals]);
+            i += literals;
+            if i == src.len() {
+                return out;
+            }
+            let offset = src[i] as usize | (src[i + 1] as usize) << 8;
+            i += 2;
+            let match_len = length(token & 15, &mut i) + 4;
+            assert!(offset > 0 && offset <= out.len(), "offset out of range");
+            for _ in 0..match_len {
+                out.push(out[out.len() - offset]);
+            }
+        }
+    }
+
+    /// Decompress with a command line tool, or nothing if it is not installed
+    fn decompress_with(program: &str, compressed: &[u8]) -> Option<Vec<u8>> {
+        let mut child = Command::new(program)
+            .args(["-d", "-c"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .ok()?;
+        child.stdin.take()?.write_all(compressed).ok()?;
+        let output = child.wait_with_output().ok()?;
+        assert!(
+            output.status.success(),
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        return Some(output.stdout);
+    }
+
+    #[test]
+    fn lz4_round_trips() {
+        for data in samples() {
+            let compressed = CompressionAlgorithm::Lz4.compress(&data);
+            assert_eq!(lz4_decompress(&compressed), data);
+        }
+        let text = b"the quick brown fox jumps over the lazy dog ".repeat(0x2000);
+        assert!(CompressionAlgorithm::Lz4.compress(&text).len() < text.len() / 20);
+    }
+
+    #[test]
+    fn lz4_round_trips_through_lz4() {
+        for data in samples() {
+            // A legacy frame is just the magic number and the size of each block
+            let block = CompressionAlgorithm::Lz4.compress(&data);
+            let mut frame = vec![0x02, 0x21, 0x4c, 0x18];
+            frame.extend_from_slice(&(block.len() as u32).to_le_bytes());
+            frame.extend_from_slice(&block);
+            if let Some(decompressed) = decompress_with("lz4", &frame) {
+                assert_eq!(decompressed, data);
+            }
+        }
+    }
+
+    #[test]
+    fn lz4_blocks_end_in_literals() {
+        // The last five bytes must be literals, and no match may start in the last twelve
+        let compressed = CompressionAlgorithm::Lz4.compress(&[0; 64]);
+        assert_eq!(&compressed[compressed.len() - 5..], &[0; 5]);
+        assert_eq!(lz4_decompress(&compressed), vec![0; 64]);
+    }
+
+    #[test]
+    fn gzip_members_are_reproducible() {
+        let data = b"hello hello hello hello";
+        let compressed = CompressionAlgorithm::Gzip.compress(data);
+        // No modification time, so the same input always gives the same bytes
+        assert_eq!(&compressed[..10], &[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff]);
+        let trailer = &compressed[compressed.len() - 8..];
+        assert_eq!(&trailer[..4], &checksum::crc32(data).to_le_bytes());
+        assert_eq!(&trailer[4..], &(data.len() as u32).to_le_bytes());
+        assert_eq!(CompressionAlgorithm::Gzip.compress(data), compressed);
+    }
+
+    #[test]
+    fn gzip_round_trips_through_gzip() {
+        for data in samples() {
+            let compressed = CompressionAlgorithm::Gzip.compress(&data);
+            if let Some(decompressed) = decompress_with("gzip", &compressed) {
+                assert_eq!(decompressed, data);
+            }
+        }
+    }
+
+    #[test]
+    fn zstd_frames_record_the_content_size() {
+        for (len, descriptor) in [(0, 0x20), (0xff, 0x20), (0x100, 0x60), (0x1_0100, 0xa0)] {
+            let compressed = CompressionAlgorithm::Zstd.compress(&vec![1; len]);
+            assert_eq!(&compressed[..5], &[0x28, 0xb5, 0x2f, 0xfd, descriptor]);
+        }
+    }
+
+    #[test]
+    fn zstd_round_trips_through_zstd() {
+        for data in samples() {
+            let compressed = CompressionAlgorithm::Zstd.compress(&data);
+            if let Some(decompressed) = decompress_with("zstd", &compressed) {
+                assert_eq!(decompressed, data);
+            }
+        }
+    }
+}
@@ -0,0 +1,365 @@
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+
+use bin2hpp::{
+    ElementType,
+    checksum::ChecksumAlgorithm,
+    compress::CompressionAlgorithm,
+    structured::{ParseError, Value},
+    toml,
+};
+
+use crate::{CliArgs, Encoding};
+
+/// Options which the `[defaults]` table and each `[[embed]]` entry of a config file may set
+#[derive(Debug, Clone, Default)]
+struct Overrides {
+    output_path: Option<PathBuf>,
+    output_dir: Option<PathBuf>,
+    symbol_name: Option<String>,
+    namespace: Option<String>,
+    binary: Option<bool>,
+    encode: Option<Encoding>,
+    comment: Option<String>,
+    guard_macro: Option<String>,
+    section: Option<String>,
+    element_type: Option<ElementType>,
+    compress: Option<CompressionAlgorithm>,
+    checksum: Option<Vec<ChecksumAlgorithm>>,
+    size_constant: Option<bool>,
+    null_terminate: Option<bool>,
+    span_accessor: Option<bool>,
+    internal_linkage: Option<bool>,
+    align: Option<usize>,
+    offset: Option<usize>,
+    length: Option<usize>,
+    bytes_per_line: Option<usize>,
+}
+
+impl Overrides {
+    /// Options set here take precedence over those set in `fallback`
+    fn or(self, fallback: &Overrides) -> Overrides {
+        return Overrides {
+            output_path: self.output_path.or_else(|| fallback.output_path.clone()),
+            output_dir: self.output_dir.or_else(|| fallback.output_dir.clone()),
+            symbol_name: self.symbol_name.or_else(|| fallback.symbol_name.clone()),
+            namespace: self.namespace.or_else(|| fallback.namespace.clone()),
+            binary: self.binary.or(fallback.binary),
+            encode: self.encode.or(fallback.encode),
+            comment: self.comment.or_else(|| fallback.comment.clone()),
+            guard_macro: self.guard_macro.or_else(|| fallback.guard_macro.clone()),
+            section: self.section.or_else(|| fallback.section.clone()),
+            element_type: self.element_type.or(fallback.element_type),
+            compress: self.compress.or(fallback.compress),
+            checksum: self.checksum.or_else(|| fallback.checksum.clone()),
+            size_constant: self.size_constant.or(fallback.size_constant),
+            null_terminate: self.null_terminate.or(fallback.null_terminate),
+            span_accessor: self.span_accessor.or(fallback.span_accessor),
+            internal_linkage: self.internal_linkage.or(fallback.internal_linkage),
+            align: self.align.or(fallback.align),
+            offset: self.offset.or(fallback.offset),
+            length: self.length.or(fallback.length),
+            bytes_per_line: self.bytes_per_line.or(fallback.bytes_per_line),
+        };
+    }
+}
+
+/// One `[[embed]]` entry of a config file, with the defaults already folded in
+#[derive(Debug)]
+pub struct EmbedEntry {
+    input_path: PathBuf,
+    overrides: Overrides,
+}
+
+impl EmbedEntry {
+    pub fn input_path(&self) -> &Path {
+        return &self.input_path;
+    }
+
+    /// Arguments for generating this entry: those given on the command line, overridden by the
+    /// config file's defaults and then by the entry itself
+    pub fn apply(&self, cli_args: &CliArgs) -> CliArgs {
+        let mut args = cli_args.clone();
+        let overrides = self.overrides.clone();
+        args.input_path = vec![self.input_path.clone()];
+        args.config = None;
+        if overrides.output_path.is_some() {
+            args.output_path = overrides.output_path;
+            args.output_dir = None;
+        } else if overrides.output_dir.is_some() {
+            args.output_path = None;
+            args.output_dir = overrides.output_dir;
+        }
+        if overrides.symbol_name.is_some() {
+            args.symbol_name = overrides.symbol_name;
+        }
+        if overrides.namespace.is_some() {
+            args.namespace = overrides.namespace;
+        }
+        if overrides.binary.is_some() {
+            args.binary = overrides.binary;
+        }
+        if let Some(encode) = overrides.encode {
+            args.encode = encode;
+        }
+        if overrides.comment.is_some() {
+            args.comment = overrides.comment;
+        }
+        if overrides.guard_macro.is_some() {
+            args.guard_macro = overrides.guard_macro;
+        }
+        if overrides.section.is_some() {
+            args.section = overrides.section;
+        }
+        if let Some(element_type) = overrides.element_type {
+            args.element_type = element_type;
+        }
+        if overrides.compress.is_some() {
+            args.compress = overrides.compress;
+        }
+        if let Some(checksum) = overrides.checksum {
+            args.checksum = checksum;
+        }
+        if let Some(size_constant) = overrides.size_constant {
+            args.size_constant = size_constant;
+        }
+        if let Some(null_terminate) = overrides.null_terminate {
+            args.null_terminate = null_terminate;
+        }
+        if let Some(span_accessor) = overrides.span_accessor {
+            args.span_accessor = span_accessor;
+        }
+        if let Some(internal_linkage) = overrides.internal_linkage {
+            args.internal_linkage = internal_linkage;
+        }
+        if overrides.align.is_some() {
+            args.align = overrides.align;
+        }
+        if overrides.offset.is_some() {
+            args.offset = overrides.offset;
+        }
+        if overrides.length.is_some() {
+            args.length = overrides.length;
+        }
+        if let Some(bytes_per_line) = overrides.bytes_per_line {
+            args.bytes_per_line = bytes_per_line;
+        }
+
+        return args;
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(ParseError),
+    /// The document is valid TOML but not a valid config
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(error) => write!(f, "failed to read config file: {}", error),
+            ConfigError::Parse(error) => write!(f, "failed to parse config file, {}", error),
+            ConfigError::Invalid(message) => write!(f, "invalid config file: {}", message),
+        }
+    }
+}
+
+/// Load the embedding jobs described by a config file
+///
+/// Relative paths in the config file are relative to the directory containing it, so that the
+/// same config works no matter where the tool is run from.
+pub fn load(path: &Path) -> Result<Vec<EmbedEntry>, ConfigError> {
+    let text = std::fs::read_to_string(path).map_err(ConfigError::Io)?;
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+
+    let Value::Table(root) = toml::parse(&text).map_err(ConfigError::Parse)? else {
+        return Err(ConfigError::Invalid("document is not a table".to_string()));
+    };
+
+    let mut defaults = Overrides::default();
+    let mut entries = Vec::new();
+    for (key, value) in root {
+        match (key.as_str(), value) {
+            ("defaults", Value::Table(table)) => {
+                let (input_path, overrides) = parse_table(table, base_dir, "[defaults]")?;
+                if input_path.is_some()
+                    || overrides.output_path.is_some()
+                    || overrides.symbol_name.is_some()
+                    || overrides.comment.is_some()
+                {
+                    return Err(ConfigError::Invalid(
+                        "[defaults] cannot set input, output, symbol or comment, which are per entry"
+                            .to_string(),
+                    ));
+                }
+                defaults = overrides;
+            }
+            ("embed", Value::Array(elements)) => {
+                for (index, element) in elements.into_iter().enumerate() {
+                    let context = format!("[[embed]] entry {}", index + 1);
+                    let Value::Table(table) = element else {
+                        return Err(ConfigError::Invalid(format!("{} is not a table", context)));
+                    };
+                    let (input_path, overrides) = parse_table(table, base_dir, &context)?;
+                    let Some(input_path) = input_path else {
+                        return Err(ConfigError::Invalid(format!("{} has no input", context)));
+                    };
+                    entries.push((input_path, overrides));
+                }
+            }
+            ("defaults", _) => {
+                return Err(ConfigError::Invalid("defaults is not a table".to_string()));
+            }
+            ("embed", _) => {
+                return Err(ConfigError::Invalid(
+                    "embed is not an array of tables".to_string(),
+                ));
+            }
+            (key, _) => {
+                return Err(ConfigError::Invalid(format!(
+                    "unknown top-level key \"{}\"",
+                    key
+                )));
+            }
+        }
+    }
+
+    if entries.is_empty() {
+        return Err(ConfigError::Invalid("no [[embed]] entries".to_string()));
+    }
+
+    return Ok(entries
+        .into_iter()
+        .map(|(input_path, overrides)| EmbedEntry {
+            input_path,
+            overrides: overrides.or(&defaults),
+        })
+        .collect());
+}
+
+/// Parse the options of a `[defaults]` table or `[[embed]]` entry, returning its input path (if
+/// any) separately
+fn parse_table(
+    table: Vec<(String, Value)>,
+    base_dir: &Path,
+    context: &str,
+) -> Result<(Option<PathBuf>, Overrides), ConfigError> {
+    let mut input_path = None;
+    let mut overrides = Overrides::default();
+    for (key, value) in table {
+        let config_key = ConfigKey { key: &key, context };
+        match key.as_str() {
+            "input" => input_path = Some(base_dir.join(config_key.string(value)?)),
+            "output" => overrides.output_path = Some(base_dir.join(config_key.string(value)?)),
+            "output_dir" => overrides.output_dir = Some(base_dir.join(config_key.string(value)?)),
+            "symbol" => overrides.symbol_name = Some(config_key.string(value)?),
+            "namespace" => overrides.namespace = Some(config_key.string(value)?),
+            "comment" => overrides.comment = Some(config_key.string(value)?),
+            "guard_macro" => overrides.guard_macro = Some(config_key.string(value)?),
+            "section" => overrides.section = Some(config_key.string(value)?),
+            "mode" => {
+                let string = config_key.string(value)?;
+                overrides.binary = match string.as_str() {
+                    "binary" => Some(true),
+                    "text" => Some(false),
+                    _ => {
+                        return Err(ConfigError::Invalid(format!(
+                            "mode \"{}\" in {} is neither \"binary\" nor \"text\"",
+                            string, context
+                        )));
+                    }
+                }
+            }
+            "encode" => overrides.encode = Some(config_key.value_enum(value, "encoding")?),
+            "element_type" => {
+                overrides.element_type = Some(config_key.value_enum(value, "element type")?)
+            }
+            "compress" => {
+                overrides.compress = Some(config_key.value_enum(value, "compression algorithm")?)
+            }
+            // A single algorithm or an array of them
+            "checksum" => {
+                let values = match value {
+                    Value::Array(values) => values,
+                    value => vec![value],
+                };
+                overrides.checksum = Some(
+                    values
+                        .into_iter()
+                        .map(|value| config_key.value_enum(value, "checksum algorithm"))
+                        .collect::<Result<_, _>>()?,
+                );
+            }
+            "size_constant" => overrides.size_constant = Some(config_key.bool(value)?),
+            "null_terminate" => overrides.null_terminate = Some(config_key.bool(value)?),
+            "span_accessor" => overrides.span_accessor = Some(config_key.bool(value)?),
+            "internal_linkage" => overrides.internal_linkage = Some(config_key.bool(value)?),
+            "align" => overrides.align = Some(config_key.size(value)?),
+            "offset" => overrides.offset = Some(config_key.size(value)?),
+            "length" => overrides.length = Some(config_key.size(value)?),
+            "bytes_per_line" => overrides.bytes_per_line = Some(config_key.size(value)?),
+            _ => {
+                return Err(ConfigError::Invalid(format!(
+                    "unknown key \"{}\" in {} (expected input, output, output_dir, symbol, namespace, mode, encode, comment, guard_macro, section, element_type, compress, checksum, size_constant, null_terminate, span_accessor, internal_linkage, align, offset, length or bytes_per_line)",
+                    key, context
+                )));
+            }
+        }
+    }
+
+    return Ok((input_path, overrides));
+}
+
+/// A key of a `[defaults]` table or `[[embed]]` entry, for checking the type of its value
+struct ConfigKey<'a> {
+    key: &'a str,
+    context: &'a str,
+}
+
+impl ConfigKey<'_> {
+    fn invalid(&self, expected: &str) -> ConfigError {
+        return ConfigError::Invalid(format!(
+            "\"{}\" in {} must be {}",
+            self.key, self.context, expected
+        ));
+    }
+
+    fn string(&self, value: Value) -> Result<String, ConfigError> {
+        return match value {
+            Value::String(string) => Ok(string),
+            _ => Err(self.invalid("a string")),
+        };
+    }
+
+    fn bool(&self, value: Value) -> Result<bool, ConfigError> {
+        return match value {
+            Value::Bool(b) => Ok(b),
+            _ => Err(self.invalid("true or false")),
+        };
+    }
+
+    fn size(&self, value: Value) -> Result<usize, ConfigError> {
+        return match value {
+            Value::Integer(n) if n >= 0 => Ok(n as usize),
+            _ => Err(self.invalid("a non-negative integer")),
+        };
+    }
+
+    /// Parse a string naming one of the values of a command line option
+    fn value_enum<T: ValueEnum>(&self, value: Value, what: &str) -> Result<T, ConfigError> {
+        let string = self.string(value)?;
+        return T::from_str(&string, false).map_err(|_| {
+            ConfigError::Invalid(format!(
+                "unknown {} \"{}\" in {}",
+                what, string, self.context
+            ))
+        });
+    }
+}
@@ -0,0 +1,94 @@
+use std::fmt;
+
+use crate::Representation;
+
+/// Rough compiler memory used per element of a brace initialiser list, in bytes
+///
+/// Every element becomes its own node in the compiler's AST, which GCC, Clang and MSVC all store
+/// in somewhere between 50 and 200 bytes.
+const BRACE_INITIALISER_BYTES_PER_ELEMENT: u64 = 160;
+/// Rough compiler memory used per byte of a string literal, in bytes
+const STRING_LITERAL_BYTES_PER_ELEMENT: u64 = 4;
+
+/// Coarse classification of how expensive a generated header is to compile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CostClass {
+    /// Compiles in well under a second
+    Trivial,
+    /// Noticeable, but unlikely to cause problems
+    Moderate,
+    /// Likely to take tens of seconds and gigabytes of memory
+    Heavy,
+    /// Likely to exhaust the compiler's memory (MSVC especially) or take minutes
+    Extreme,
+}
+
+impl fmt::Display for CostClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CostClass::Trivial => "trivial",
+            CostClass::Moderate => "moderate",
+            CostClass::Heavy => "heavy",
+            CostClass::Extreme => "extreme",
+        };
+        return f.write_str(name);
+    }
+}
+
+/// Estimated cost of compiling a generated header
+#[derive(Debug, Clone, Copy)]
+pub struct CompileCost {
+    pub element_count: u64,
+    pub memory_bytes: u64,
+    pub class: CostClass,
+}
+
+impl fmt::Display for CompileCost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (~{} MiB of compiler memory for {} elements)",
+            self.class,
+            self.memory_bytes.div_ceil(0x10_0000),
+            self.element_count
+        )
+    }
+}
+
+/// Estimate the cost of compiling `data_len` bytes of data in the given representation
+pub fn estimate(representation: Representation, data_len: u64) -> CompileCost {
+    // The expanded fallback of incbin is skipped by the preprocessor on toolchains which matter,
+    // unlike that of #embed which few toolchains support yet
+    let element_count = match representation {
+        Representation::PackedBits => data_len.div_ceil(8),
+        Representation::Words(layout) => data_len.div_ceil(layout.size.bytes() as u64),
+        Representation::Incbin => 0,
+        _ => data_len,
+    };
+    let bytes_per_element = match representation {
+        Representation::BraceInitialiser
+        | Representation::PackedBits
+        | Representation::Words(_)
+        | Representation::Compressed(_)
+        | Representation::Incbin
+        | Representation::Embed
+        | Representation::Delta => BRACE_INITIALISER_BYTES_PER_ELEMENT,
+        Representation::EscapedStringLiteral | Representation::StringLiteral => {
+            STRING_LITERAL_BYTES_PER_ELEMENT
+        }
+    };
+    let memory_bytes = element_count.saturating_mul(bytes_per_element);
+
+    let class = match memory_bytes {
+        0..0x400_0000 => CostClass::Trivial,            // < 64 MiB
+        0x400_0000..0x2000_0000 => CostClass::Moderate, // < 512 MiB
+        0x2000_0000..0x8000_0000 => CostClass::Heavy,   // < 2 GiB
+        _ => CostClass::Extreme,
+    };
+
+    return CompileCost {
+        element_count,
+        memory_bytes,
+        class,
+    };
+}
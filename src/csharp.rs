@@ -0,0 +1,335 @@
+use crate::{
+    DATA_MARKER, ElementType, LINE_ENDING, Representation, SourceOptions, array_data,
+    backend::{self, Backend, Features},
+    checksum::{self, ChecksumAlgorithm},
+    format_as_elements, push_doc_comment,
+};
+
+/// Keywords of C#, leaving out the contextual ones which can still name a class
+const KEYWORDS: [&str; 77] = [
+    "abstract",
+    "as",
+    "base",
+    "bool",
+    "break",
+    "byte",
+    "case",
+    "catch",
+    "char",
+    "checked",
+    "class",
+    "const",
+    "continue",
+    "decimal",
+    "default",
+    "delegate",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "event",
+    "explicit",
+    "extern",
+    "false",
+    "finally",
+    "fixed",
+    "float",
+    "for",
+    "foreach",
+    "goto",
+    "if",
+    "implicit",
+    "in",
+    "int",
+    "interface",
+    "internal",
+    "is",
+    "lock",
+    "long",
+    "namespace",
+    "new",
+    "null",
+    "object",
+    "operator",
+    "out",
+    "override",
+    "params",
+    "private",
+    "protected",
+    "public",
+    "readonly",
+    "ref",
+    "return",
+    "sbyte",
+    "sealed",
+    "short",
+    "sizeof",
+    "stackalloc",
+    "static",
+    "string",
+    "struct",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "uint",
+    "ulong",
+    "unchecked",
+    "unsafe",
+    "ushort",
+    "using",
+    "virtual",
+    "void",
+    "volatile",
+    "while",
+];
+
+/// Backend generating C# files, in which only byte arrays and strings can be generated (binary
+/// data picked to be a string literal is generated as an array, as C# has no byte strings)
+pub struct CSharpBackend;
+
+impl Backend for CSharpBackend {
+    fn extension(&self) -> &'static str {
+        return "cs";
+    }
+
+    fn keywords(&self) -> &'static [&'static str] {
+        return &KEYWORDS;
+    }
+
+    fn reserves_underscores(&self) -> bool {
+        return false;
+    }
+
+    fn guarded(&self) -> bool {
+        return false;
+    }
+
+    fn derived_symbol(&self, symbol_name: String) -> String {
+        return class_name(&symbol_name);
+    }
+
+    fn unsupported(&self, features: &Features) -> Option<&'static str> {
+        if let Some(reason) = backend::unsupported_outside_c(features) {
+            return Some(reason);
+        }
+
+        return features
+            .encoding
+            .then_some("cannot be combined with --encode");
+    }
+
+    fn generate_src(
+        &self,
+        data: &[u8],
+        representation: Representation,
+        symbol_name: &str,
+        options: &SourceOptions,
+    ) -> String {
+        return match representation {
+            Representation::BraceInitialiser | Representation::EscapedStringLiteral => {
+                let data = array_data(data, options);
+                generate_src_for_array(
+                    &format_as_elements(&data, &options.byte_format, options.element_type),
+                    data.len(),
+                    symbol_name,
+                    options,
+                )
+            }
+            Representation::StringLiteral => generate_src_for_string(data, symbol_name, options),
+            _ => unreachable!("representation without a C# equivalent"),
+        };
+    }
+
+    fn template(
+        &self,
+        array_len: usize,
+        representation: Representation,
+        symbol_name: &str,
+        options: &SourceOptions,
+    ) -> Option<String> {
+        return match representation {
+            Representation::BraceInitialiser => Some(generate_src_for_array(
+                DATA_MARKER,
+                array_len,
+                symbol_name,
+                options,
+            )),
+            _ => None,
+        };
+    }
+}
+
+/// Name of a C# class for a symbol derived from a filename: its parts between underscores
+/// capitalised and joined, as in `SBin` for `s_bin`
+pub fn class_name(symbol_name: &str) -> String {
+    let mut name = String::with_capacity(symbol_name.len());
+    for part in symbol_name.split('_').filter(|part| !part.is_empty()) {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            name.push(first.to_ascii_uppercase());
+            name.extend(chars);
+        }
+    }
+    // A filename made of underscores alone still needs a name
+    if name.is_empty() {
+        name.push('_');
+    }
+
+    return name;
+}
+
+/// Generate a C# file declaring the data in a static class named after the symbol, as a `Data`
+/// array, or (with `span_accessor`) a `ReadOnlySpan<byte>` property over the array initialiser,
+/// which the compiler turns into a reference to the bytes stored in the assembly rather than an
+/// array allocated on first use
+///
+/// Unlike a C array the C# array knows its length, so `Size` (if one is asked for) is only there
+/// for symmetry with the C and C++ headers; it does not count a NUL terminator.
+fn generate_src_for_array(
+    array_contents: &str,
+    array_len: usize,
+    symbol_name: &str,
+    options: &SourceOptions,
+) -> String {
+    let data_len = match options.null_terminate {
+        true => array_len.saturating_sub(1),
+        false => array_len,
+    };
+
+    return generate_src(
+        &byte_array_member("Data", array_contents, options),
+        data_len,
+        symbol_name,
+        options,
+    );
+}
+
+/// Generate a C# file declaring text in a static class named after the symbol, as a `Data`
+/// string constant, or as an array if it is not valid UTF-8 (which C# strings cannot hold)
+fn generate_src_for_string(data: &[u8], symbol_name: &str, options: &SourceOptions) -> String {
+    let declaration = match std::str::from_utf8(data) {
+        Ok(text) => format!("public const string Data = \"{}\";", escape_str(text)),
+        Err(_) => byte_array_member(
+            "Data",
+            &format_as_elements(data, &options.byte_format, ElementType::Uint8),
+            options,
+        ),
+    };
+
+    return generate_src(&declaration, data.len(), symbol_name, options);
+}
+
+fn generate_src(
+    declaration: &str,
+    data_len: usize,
+    symbol_name: &str,
+    options: &SourceOptions,
+) -> String {
+    let visibility = match options.internal_linkage {
+        true => "internal",
+        false => "public",
+    };
+
+    let mut out_string = String::with_capacity(declaration.len() + 0x200);
+    if options.span_accessor {
+        out_string.push_str("using System;");
+        out_string.push_str(LINE_ENDING);
+    }
+    if let Some(ref namespace) = options.ns_name {
+        out_string.push_str(format!("namespace {}{{", namespace.replace("::", ".")).as_str());
+    }
+    push_doc_comment(&mut out_string, options.comment.as_deref());
+    out_string.push_str(format!("{} static class {}{{", visibility, symbol_name).as_str());
+    out_string.push_str(declaration);
+    if options.size_constant {
+        out_string.push_str(format!("public const int Size = {};", data_len).as_str());
+    }
+    out_string.push_str(&checksum_declarations(options));
+    out_string.push('}');
+    if options.ns_name.is_some() {
+        out_string.push('}');
+    }
+    out_string.push_str(LINE_ENDING);
+
+    return out_string;
+}
+
+/// Byte array member of the class, a `ReadOnlySpan<byte>` property with `span_accessor`
+fn byte_array_member(name: &str, array_contents: &str, options: &SourceOptions) -> String {
+    return match options.span_accessor {
+        true => format!(
+            "public static ReadOnlySpan<byte> {} => new byte[]{{{}}};",
+            name, array_contents
+        ),
+        false => format!(
+            "public static readonly byte[] {} = {{{}}};",
+            name, array_contents
+        ),
+    };
+}
+
+/// Members holding the checksums asked for, named after their algorithms: a `uint` for CRC32 and
+/// a byte array for SHA-256, with a `<Algorithm>Hex` string of each if asked for
+fn checksum_declarations(options: &SourceOptions) -> String {
+    let mut declarations = String::new();
+    for checksum in &options.checksums {
+        let name = class_name(checksum.algorithm.name());
+        match checksum.algorithm {
+            ChecksumAlgorithm::Crc32 => {
+                let mut crc = [0; 4];
+                crc.copy_from_slice(&checksum.digest);
+                declarations.push_str(
+                    format!(
+                        "public const uint {} = {:#010x};",
+                        name,
+                        u32::from_be_bytes(crc)
+                    )
+                    .as_str(),
+                );
+            }
+            ChecksumAlgorithm::Sha256 => {
+                let elements: Vec<String> = checksum
+                    .digest
+                    .iter()
+                    .map(|byte| format!("{:#04x}", byte))
+                    .collect();
+                declarations.push_str(&byte_array_member(&name, &elements.join(","), options));
+            }
+        }
+        if options.checksum_hex {
+            declarations.push_str(
+                format!(
+                    "public const string {}Hex = \"{}\";",
+                    name,
+                    checksum::to_hex(&checksum.digest)
+                )
+                .as_str(),
+            );
+        }
+    }
+
+    return declarations;
+}
+
+/// Escape text for a C# string literal, leaving everything but quotes, backslashes and control
+/// characters as it is
+fn escape_str(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + text.len() / 8);
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\0' => escaped.push_str("\\0"),
+            c if c.is_control() => escaped.push_str(format!("\\u{:04x}", c as u32).as_str()),
+            c => escaped.push(c),
+        }
+    }
+
+    return escaped;
+}
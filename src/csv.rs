@@ -0,0 +1,353 @@
+use std::fmt;
+
+use crate::{
+    LINE_ENDING, format_as_escaped, push_doc_comment, push_namespace_close, push_namespace_open,
+    sanitise_identifier,
+};
+
+/// Type of a CSV column, as given to `--decode-csv`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+    Bool,
+    String,
+}
+
+impl ColumnType {
+    fn parse(name: &str) -> Option<ColumnType> {
+        return match name.trim() {
+            "i8" => Some(ColumnType::I8),
+            "i16" => Some(ColumnType::I16),
+            "i32" => Some(ColumnType::I32),
+            "i64" => Some(ColumnType::I64),
+            "u8" => Some(ColumnType::U8),
+            "u16" => Some(ColumnType::U16),
+            "u32" => Some(ColumnType::U32),
+            "u64" => Some(ColumnType::U64),
+            "f32" => Some(ColumnType::F32),
+            "f64" => Some(ColumnType::F64),
+            "bool" => Some(ColumnType::Bool),
+            "string" => Some(ColumnType::String),
+            _ => None,
+        };
+    }
+
+    fn cpp_type(self) -> &'static str {
+        return match self {
+            ColumnType::I8 => "std::int8_t",
+            ColumnType::I16 => "std::int16_t",
+            ColumnType::I32 => "std::int32_t",
+            ColumnType::I64 => "std::int64_t",
+            ColumnType::U8 => "std::uint8_t",
+            ColumnType::U16 => "std::uint16_t",
+            ColumnType::U32 => "std::uint32_t",
+            ColumnType::U64 => "std::uint64_t",
+            ColumnType::F32 => "float",
+            ColumnType::F64 => "double",
+            ColumnType::Bool => "bool",
+            ColumnType::String => "std::string_view",
+        };
+    }
+
+    /// Convert a field into a C++ literal of this type
+    fn literal(self, field: &str) -> Option<String> {
+        let trimmed = field.trim();
+        return match self {
+            ColumnType::I8 => trimmed.parse::<i8>().ok().map(|v| v.to_string()),
+            ColumnType::I16 => trimmed.parse::<i16>().ok().map(|v| v.to_string()),
+            ColumnType::I32 => trimmed.parse::<i32>().ok().map(|v| v.to_string()),
+            // INT64_MIN cannot be written as a negated literal without overflowing first
+            ColumnType::I64 => trimmed.parse::<i64>().ok().map(|v| match v {
+                i64::MIN => "(-9223372036854775807LL-1)".to_string(),
+                _ => format!("{}LL", v),
+            }),
+            ColumnType::U8 => trimmed.parse::<u8>().ok().map(|v| v.to_string()),
+            ColumnType::U16 => trimmed.parse::<u16>().ok().map(|v| v.to_string()),
+            ColumnType::U32 => trimmed.parse::<u32>().ok().map(|v| format!("{}U", v)),
+            ColumnType::U64 => trimmed.parse::<u64>().ok().map(|v| format!("{}ULL", v)),
+            ColumnType::F32 => trimmed
+                .parse::<f32>()
+                .ok()
+                .filter(|v| v.is_finite())
+                .map(|v| format!("{:?}f", v)),
+            ColumnType::F64 => trimmed
+                .parse::<f64>()
+                .ok()
+                .filter(|v| v.is_finite())
+                .map(|v| format!("{:?}", v)),
+            ColumnType::Bool => match trimmed {
+                "true" | "1" => Some("true".to_string()),
+                "false" | "0" => Some("false".to_string()),
+                _ => None,
+            },
+            ColumnType::String => Some(format!("\"{}\"", format_as_escaped(field.as_bytes()))),
+        };
+    }
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.write_str(self.cpp_type());
+    }
+}
+
+#[derive(Debug)]
+pub enum CsvError {
+    /// A column type given to `--decode-csv` is not known
+    UnknownType(String),
+    /// The input is not valid UTF-8
+    NotUtf8,
+    /// A quoted field is never closed
+    UnterminatedQuote { line: usize },
+    /// A row has a different number of fields to the number of column types
+    FieldCount {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// A field cannot be converted to its column's type
+    InvalidField {
+        line: usize,
+        column: usize,
+        column_type: ColumnType,
+        field: String,
+    },
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CsvError::UnknownType(name) => write!(
+                f,
+                "unknown CSV column type \"{}\" (expected one of i8, i16, i32, i64, u8, u16, u32, u64, f32, f64, bool, string)",
+                name
+            ),
+            CsvError::NotUtf8 => write!(f, "CSV input is not valid UTF-8"),
+            CsvError::UnterminatedQuote { line } => {
+                write!(f, "CSV line {}: quoted field is never closed", line)
+            }
+            CsvError::FieldCount {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "CSV line {}: expected {} fields but found {}",
+                line, expected, found
+            ),
+            CsvError::InvalidField {
+                line,
+                column,
+                column_type,
+                field,
+            } => write!(
+                f,
+                "CSV line {}, column {}: \"{}\" is not a valid {}",
+                line, column, field, column_type
+            ),
+        }
+    }
+}
+
+/// Parse a comma-separated list of column types, e.g. `i32,f32,string`
+pub fn parse_column_types(spec: &str) -> Result<Vec<ColumnType>, CsvError> {
+    return spec
+        .split(',')
+        .map(|name| ColumnType::parse(name).ok_or_else(|| CsvError::UnknownType(name.to_string())))
+        .collect();
+}
+
+/// A parsed CSV record along with the line it started on
+struct Record {
+    line: usize,
+    fields: Vec<String>,
+}
+
+/// Split CSV text into records (RFC 4180: fields may be quoted, with `""` escaping a quote, and
+/// quoted fields may span lines), skipping blank lines
+fn parse_records(text: &str) -> Result<Vec<Record>, CsvError> {
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                '\n' => {
+                    line += 1;
+                    field.push(c);
+                }
+                _ => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_quotes = true,
+            ',' => fields.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => (),
+            '\n' => {
+                fields.push(std::mem::take(&mut field));
+                if fields.len() == 1 && fields[0].is_empty() {
+                    fields.clear();
+                } else {
+                    records.push(Record {
+                        line: record_line,
+                        fields: std::mem::take(&mut fields),
+                    });
+                }
+                line += 1;
+                record_line = line;
+            }
+            _ => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err(CsvError::UnterminatedQuote { line: record_line });
+    }
+
+    fields.push(field);
+    if !(fields.len() == 1 && fields[0].is_empty()) {
+        records.push(Record {
+            line: record_line,
+            fields,
+        });
+    }
+
+    return Ok(records);
+}
+
+/// Generate a struct describing a row and a `constexpr std::array` of every row in the CSV input
+///
+/// If `has_header` is set, the first record names the struct's fields, otherwise they are named
+/// `field0`, `field1` and so on.
+pub fn generate_src(
+    data: &[u8],
+    column_types: &[ColumnType],
+    has_header: bool,
+    symbol_name: &str,
+    ns_name: Option<&str>,
+    comment: Option<&str>,
+    internal_linkage: bool,
+) -> Result<String, CsvError> {
+    let text = match std::str::from_utf8(data) {
+        Ok(text) => text.strip_prefix('\u{feff}').unwrap_or(text), // byte order mark
+        Err(_) => return Err(CsvError::NotUtf8),
+    };
+
+    let mut records = parse_records(text)?;
+    for record in &records {
+        if record.fields.len() != column_types.len() {
+            return Err(CsvError::FieldCount {
+                line: record.line,
+                expected: column_types.len(),
+                found: record.fields.len(),
+            });
+        }
+    }
+
+    let field_names: Vec<String> = if has_header && !records.is_empty() {
+        records
+            .remove(0)
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(index, name)| match name.trim() {
+                "" => format!("field{}", index),
+                name => sanitise_identifier(name),
+            })
+            .collect()
+    } else {
+        (0..column_types.len())
+            .map(|index| format!("field{}", index))
+            .collect()
+    };
+
+    let struct_name = format!("{}_row", symbol_name);
+
+    // Includes
+    let mut out_string = String::with_capacity(data.len() * 2 + 0x100);
+    out_string.push_str("#include <array>");
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str("#include <cstdint>");
+    out_string.push_str(LINE_ENDING);
+    if column_types.contains(&ColumnType::String) {
+        out_string.push_str("#include <string_view>");
+        out_string.push_str(LINE_ENDING);
+    }
+
+    // Namespace
+    push_namespace_open(&mut out_string, ns_name, internal_linkage);
+
+    // Row struct
+    out_string.push_str(format!("struct {}{{", struct_name).as_str());
+    for (column_type, name) in column_types.iter().zip(&field_names) {
+        out_string.push_str(format!("{} {};", column_type.cpp_type(), name).as_str());
+    }
+    out_string.push_str("};");
+    out_string.push_str(LINE_ENDING);
+
+    // Rows
+    push_doc_comment(&mut out_string, comment);
+    out_string.push_str(
+        format!(
+            "constexpr std::array<{},{}> {}{{{{",
+            struct_name,
+            records.len(),
+            symbol_name
+        )
+        .as_str(),
+    );
+    for (row_index, record) in records.iter().enumerate() {
+        if row_index > 0 {
+            out_string.push(',');
+        }
+        out_string.push('{');
+        for (column, (column_type, field)) in column_types.iter().zip(&record.fields).enumerate() {
+            let literal = match column_type.literal(field) {
+                Some(literal) => literal,
+                None => {
+                    return Err(CsvError::InvalidField {
+                        line: record.line,
+                        column: column + 1,
+                        column_type: *column_type,
+                        field: field.clone(),
+                    });
+                }
+            };
+            if column > 0 {
+                out_string.push(',');
+            }
+            out_string.push_str(&literal);
+        }
+        out_string.push('}');
+    }
+    out_string.push_str("}};");
+
+    // Close namespace (if need be)
+    push_namespace_close(&mut out_string, ns_name, internal_linkage);
+
+    // Trailing newline
+    out_string.push_str(LINE_ENDING);
+
+    return Ok(out_string);
+}
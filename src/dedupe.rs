@@ -0,0 +1,110 @@
+use std::path::PathBuf;
+
+use crate::{
+    CharType, ElementType, LINE_ENDING, Representation, SourceOptions, checksum,
+    push_checksum_constants, push_doc_comment, push_namespace_close, push_namespace_open,
+    push_size_constant,
+};
+
+/// What two inputs must have in common for one to alias the data generated for the other: their
+/// content and everything deciding the type of the symbol it is embedded as
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentKey {
+    sha256: [u8; 32],
+    len: usize,
+    representation: Representation,
+    element_type: ElementType,
+    null_terminate: bool,
+    char_type: CharType,
+    text_view: bool,
+}
+
+impl ContentKey {
+    pub fn new(data: &[u8], representation: Representation, options: &SourceOptions) -> ContentKey {
+        return ContentKey {
+            sha256: checksum::sha256(data),
+            len: data.len(),
+            representation,
+            element_type: options.element_type,
+            null_terminate: options.null_terminate,
+            char_type: options.char_type,
+            text_view: options.text_view,
+        };
+    }
+}
+
+/// Data generated for an earlier input, which later inputs with the same content alias
+#[derive(Debug, Clone)]
+pub struct Original {
+    pub key: ContentKey,
+    /// Input the data was generated from, for diagnostics
+    pub name: String,
+    /// Fully qualified symbol the data was embedded as
+    pub symbol: String,
+    /// Header the symbol is declared in, unless it is the header being generated
+    pub header: Option<PathBuf>,
+}
+
+/// Generate a reference to the data of an earlier input with the same content rather than
+/// embedding it again, along with the input's own `<symbol>_size` and checksum constants
+///
+/// If the original is declared in another header, `include` is the path by which to include it.
+/// The reference is `constexpr` unless the original only refers to assembler data.
+pub fn generate_src(
+    original: &Original,
+    include: Option<&str>,
+    data: &[u8],
+    symbol_name: &str,
+    options: &SourceOptions,
+) -> String {
+    let mut out_string = String::with_capacity(0x200);
+    out_string.push_str("#include <cstddef>");
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str("#include <cstdint>");
+    out_string.push_str(LINE_ENDING);
+    if let Some(include) = include {
+        out_string.push_str(format!("#include \"{}\"", include).as_str());
+        out_string.push_str(LINE_ENDING);
+    }
+
+    // Namespace
+    push_namespace_open(
+        &mut out_string,
+        options.ns_name.as_deref(),
+        options.internal_linkage,
+    );
+
+    // Reference to the original
+    push_doc_comment(&mut out_string, options.comment.as_deref());
+    out_string.push_str(
+        format!(
+            "{} const auto& {}={};",
+            match original.key.representation {
+                Representation::Incbin => "inline",
+                _ => "constexpr",
+            },
+            symbol_name,
+            original.symbol
+        )
+        .as_str(),
+    );
+    out_string.push_str(LINE_ENDING);
+
+    // Size and checksums
+    push_size_constant(&mut out_string, symbol_name, data.len(), 0, options);
+    push_checksum_constants(&mut out_string, symbol_name, &[], options);
+
+    // Close namespace (if need be)
+    push_namespace_close(
+        &mut out_string,
+        options.ns_name.as_deref(),
+        options.internal_linkage,
+    );
+
+    // Trailing newline
+    if !out_string.ends_with(LINE_ENDING) {
+        out_string.push_str(LINE_ENDING);
+    }
+
+    return out_string;
+}
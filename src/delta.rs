@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+
+use crate::{
+    ByteFormat, CharType, DEFAULT_MAX_LITERAL_LEN, ElementType, LINE_ENDING, Language,
+    SourceOptions,
+    checksum::{self, ChecksumAlgorithm},
+    format_as_binary_with, push_doc_comment, push_namespace_close, push_namespace_open,
+    push_verifier_helper,
+};
+
+/// Shortest run of bytes worth encoding as a copy from the base rather than as literals
+const MIN_MATCH: usize = 8;
+
+/// Base the embedded data is stored as a delta against (`--delta-base`)
+pub struct DeltaBase {
+    pub len: usize,
+    pub crc32: u32,
+    /// Encoded delta turning the base into the input
+    pub ops: Vec<u8>,
+}
+
+fn push_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn push_literal(out: &mut Vec<u8>, literal: &[u8]) {
+    if literal.is_empty() {
+        return;
+    }
+    push_varint(out, literal.len() << 1);
+    out.extend_from_slice(literal);
+}
+
+fn window(data: &[u8], at: usize) -> u64 {
+    let mut bytes = [0; MIN_MATCH];
+    bytes.copy_from_slice(&data[at..at + MIN_MATCH]);
+    return u64::from_le_bytes(bytes);
+}
+
+fn match_len(base: &[u8], base_at: usize, target: &[u8], target_at: usize) -> usize {
+    return base[base_at..]
+        .iter()
+        .zip(&target[target_at..])
+        .take_while(|(a, b)| a == b)
+        .count();
+}
+
+/// Encode `target` as a sequence of operations on `base`
+///
+/// Every operation starts with a varint `len << 1 | kind`: kind 0 is followed by `len` literal
+/// bytes, kind 1 by a varint offset into the base from which `len` bytes are copied. Matches are
+/// found greedily, trying the position following the previous copy first since related files
+/// (A/B images, per-locale variants) tend to differ in place.
+pub fn encode(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut index: HashMap<u64, usize> = HashMap::new();
+    if base.len() >= MIN_MATCH {
+        for at in 0..=base.len() - MIN_MATCH {
+            index.entry(window(base, at)).or_insert(at);
+        }
+    }
+
+    let mut out = Vec::new();
+    let mut literal_start = 0;
+    let mut next_in_base = 0;
+    let mut at = 0;
+    while at + MIN_MATCH <= target.len() {
+        let in_place = match next_in_base < base.len() {
+            true => match_len(base, next_in_base, target, at),
+            false => 0,
+        };
+        let (offset, len) = match index.get(&window(target, at)) {
+            Some(&candidate) if in_place < MIN_MATCH => {
+                (candidate, match_len(base, candidate, target, at))
+            }
+            _ => (next_in_base, in_place),
+        };
+
+        if len < MIN_MATCH {
+            at += 1;
+            continue;
+        }
+
+        push_literal(&mut out, &target[literal_start..at]);
+        push_varint(&mut out, (len << 1) | 1);
+        push_varint(&mut out, offset);
+        at += len;
+        literal_start = at;
+        next_in_base = offset + len;
+    }
+    push_literal(&mut out, &target[literal_start..]);
+
+    return out;
+}
+
+/// Generate the delta as an array along with a `<symbol>_apply(base, base_size)` function which
+/// reconstructs the data into a `std::vector`, or returns an empty one if given the wrong base
+pub fn generate_src(
+    delta: &DeltaBase,
+    data_len: usize,
+    symbol_name: &str,
+    options: &SourceOptions,
+) -> String {
+    // Includes
+    let mut out_string = String::with_capacity(delta.ops.len() * 5 + 0x400);
+    for header in ["array", "cstddef", "cstdint", "vector"] {
+        out_string.push_str(format!("#include <{}>", header).as_str());
+        out_string.push_str(LINE_ENDING);
+    }
+    push_verifier_helper(&mut out_string, ChecksumAlgorithm::Crc32);
+
+    // Namespace
+    push_namespace_open(
+        &mut out_string,
+        options.ns_name.as_deref(),
+        options.internal_linkage,
+    );
+
+    // Delta and sizes
+    out_string.push_str(
+        format!(
+            "constexpr std::size_t {0}_size={1};constexpr std::array<std::uint8_t,{2}> {0}_delta{{{3}}};",
+            symbol_name,
+            data_len,
+            delta.ops.len(),
+            format_as_binary_with(&delta.ops, &options.byte_format)
+        )
+        .as_str(),
+    );
+    out_string.push_str(LINE_ENDING);
+
+    // Apply function
+    push_doc_comment(&mut out_string, options.comment.as_deref());
+    out_string.push_str(
+        format!(
+            "inline std::vector<std::uint8_t> {}_apply(const unsigned char* base,std::size_t base_size){{",
+            symbol_name
+        )
+        .as_str(),
+    );
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str(
+        format!(
+            "if(base_size!={}u||::bin2hpp_detail::crc32(base,base_size)!={:#010x}u)return {{}};",
+            delta.len, delta.crc32
+        )
+        .as_str(),
+    );
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str(
+        format!(
+            "const auto& ops={0}_delta;std::vector<std::uint8_t> out;out.reserve({0}_size);std::size_t i=0;",
+            symbol_name
+        )
+        .as_str(),
+    );
+    out_string.push_str(
+        "auto next=[&]{std::size_t v=0;for(unsigned shift=0;;shift+=7){const std::uint8_t b=ops[i++];v|=std::size_t(b&0x7fu)<<shift;if(!(b&0x80u))return v;}};",
+    );
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str(
+        "while(i<ops.size()){const std::size_t op=next();const std::size_t len=op>>1;if(op&1u){const std::size_t offset=next();out.insert(out.end(),base+offset,base+offset+len);}else{out.insert(out.end(),ops.begin()+i,ops.begin()+i+len);i+=len;}}",
+    );
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str("return out;");
+    out_string.push_str(LINE_ENDING);
+    out_string.push('}');
+
+    // Close namespace (if need be)
+    push_namespace_close(
+        &mut out_string,
+        options.ns_name.as_deref(),
+        options.internal_linkage,
+    );
+    out_string.push_str(LINE_ENDING);
+
+    return out_string;
+}
+
+/// Generate a standalone patch: the delta and its `<symbol>_apply(base, base_size)` function as
+/// above, followed by the sizes and SHA-256 digests of the files on either side of it so that
+/// updaters can check what they start from and end up with
+pub fn generate_patch_src(
+    patch: &DeltaBase,
+    old_sha256: &[u8; 32],
+    new_len: usize,
+    new_sha256: &[u8; 32],
+    symbol_name: &str,
+    ns_name: Option<&str>,
+) -> String {
+    let options = SourceOptions {
+        language: Language::Cpp,
+        ns_name: ns_name.map(str::to_string),
+        guard_macro: None,
+        loader_path: None,
+        verifier: None,
+        incbin_path: None,
+        embed_path: None,
+        input_offset: None,
+        delta: None,
+        stream_accessor: false,
+        span_accessor: false,
+        comment: None,
+        char_type: CharType::Char,
+        text_view: false,
+        raw_text: false,
+        max_literal_len: DEFAULT_MAX_LITERAL_LEN,
+        internal_linkage: false,
+        extern_data: false,
+        align: None,
+        section: None,
+        null_terminate: false,
+        size_constant: false,
+        checksums: Vec::new(),
+        checksum_hex: false,
+        assert_size: None,
+        assert_size_max: None,
+        element_type: ElementType::Uint8,
+        xor_key: None,
+        byte_format: ByteFormat::default(),
+    };
+    let mut out_string = generate_src(patch, new_len, symbol_name, &options);
+
+    if let Some(namespace) = ns_name {
+        out_string.push_str(format!("namespace {}{{", namespace).as_str());
+    }
+    out_string.push_str(
+        format!(
+            "constexpr std::size_t {0}_old_size={1};constexpr std::array<std::uint8_t,32> {0}_old_sha256{{{2}}};",
+            symbol_name,
+            patch.len,
+            checksum::to_initialiser(old_sha256)
+        )
+        .as_str(),
+    );
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str(
+        format!(
+            "constexpr std::size_t {0}_new_size={1};constexpr std::array<std::uint8_t,32> {0}_new_sha256{{{2}}};",
+            symbol_name,
+            new_len,
+            checksum::to_initialiser(new_sha256)
+        )
+        .as_str(),
+    );
+    if ns_name.is_some() {
+        out_string.push('}');
+    }
+    out_string.push_str(LINE_ENDING);
+
+    return out_string;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_varint(ops: &[u8], at: &mut usize) -> usize {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = ops[*at];
+            *at += 1;
+            value |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                return value;
+            }
+            shift += 7;
+        }
+    }
+
+    /// Apply a delta the way the generated `<symbol>_apply()` does
+    fn apply(base: &[u8], ops: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut at = 0;
+        while at < ops.len() {
+            let op = read_varint(ops, &mut at);
+            let len = op >> 1;
+            match op & 1 {
+                0 => {
+                    out.extend_from_slice(&ops[at..at + len]);
+                    at += len;
+                }
+                _ => {
+                    let offset = read_varint(ops, &mut at);
+                    out.extend_from_slice(&base[offset..offset + len]);
+                }
+            }
+        }
+
+        return out;
+    }
+
+    /// Deterministic bytes which do not repeat within the lengths used here
+    fn noise(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        return (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                return (state >> 24) as u8;
+            })
+            .collect();
+    }
+
+    #[test]
+    fn deltas_reconstruct_the_target() {
+        let base = noise(5000, 1);
+        let mut patched = base.clone();
+        patched[100..110].copy_from_slice(b"0123456789");
+        let mut inserted = base[..2000].to_vec();
+        inserted.extend_from_slice(&noise(300, 2));
+        inserted.extend_from_slice(&base[2000..]);
+        let mut moved = base[3000..].to_vec();
+        moved.extend_from_slice(&base[..3000]);
+
+        for target in [
+            base.clone(),
+            patched,
+            inserted,
+            moved,
+            noise(1000, 3),
+            base[..5].to_vec(),
+            Vec::new(),
+        ] {
+            assert_eq!(apply(&base, &encode(&base, &target)), target);
+        }
+        assert_eq!(apply(&[], &encode(&[], b"no base")), b"no base");
+    }
+
+    #[test]
+    fn unchanged_regions_are_copied() {
+        let base = noise(5000, 1);
+        // One copy of the whole base: a two byte operation and a zero offset
+        assert_eq!(encode(&base, &base), vec![0x91, 0x4e, 0]);
+
+        let mut patched = base.clone();
+        patched[2500] ^= 0xff;
+        assert!(encode(&base, &patched).len() < 20);
+    }
+}
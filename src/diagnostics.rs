@@ -0,0 +1,254 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufWriter, IsTerminal, Write},
+    path::Path,
+    time::Instant,
+};
+
+use clap::ValueEnum;
+
+use bin2hpp::{InvalidIdentifier, cost::CompileCost};
+
+/// Inputs larger than this are reported as suspiciously large (64 MiB)
+pub const LARGE_INPUT_THRESHOLD: u64 = 0x400_0000;
+
+/// Questionable conditions which do not prevent a header from being generated
+#[derive(Debug)]
+pub enum Warning {
+    /// The input is larger than `LARGE_INPUT_THRESHOLD`
+    LargeInput { path: String, size: u64 },
+    /// The input contains no data
+    EmptyInput { path: String },
+    /// The generated header is estimated to be heavy or worse to compile
+    HighCompileCost { path: String, cost: CompileCost },
+    /// The input is embedded as text but is not valid UTF-8
+    NotUtf8 {
+        path: String,
+        fallback: &'static str,
+    },
+    /// The input does not have the size the header is to assert it has
+    SizeAssertionFails {
+        path: String,
+        size: usize,
+        expectation: String,
+    },
+    /// The input does not fill its last word, which is padded with zeros
+    PartialWord {
+        path: String,
+        size: usize,
+        word_bytes: usize,
+    },
+    /// The input path is absolute and cannot be made relative to the current directory, and so is
+    /// recorded in a header meant to be reproducible
+    AbsolutePath { path: String },
+    /// The symbol derived from the input's filename is not a valid identifier, so another is used
+    MangledSymbol {
+        path: String,
+        symbol: String,
+        mangled: String,
+        reason: InvalidIdentifier,
+    },
+    /// An earlier input of the batch generates the same output path, so under `--on-collision
+    /// rename` the symbols of this one are declared in a header of another name
+    RenamedSymbol { output: String, renamed: String },
+    /// A header holding the input alone exceeds --header-budget
+    OverBudget {
+        path: String,
+        size: usize,
+        budget: usize,
+    },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::LargeInput { path, size } => write!(
+                f,
+                "input file \"{}\" is suspiciously large ({} bytes), the generated header may be slow to compile",
+                path, size
+            ),
+            Warning::EmptyInput { path } => write!(f, "input file \"{}\" is empty", path),
+            Warning::HighCompileCost { path, cost } => write!(
+                f,
+                "header generated from \"{}\" is estimated to be expensive to compile: {}",
+                path, cost
+            ),
+            Warning::NotUtf8 { path, fallback } => write!(
+                f,
+                "input file \"{}\" is not valid UTF-8, {}",
+                path, fallback
+            ),
+            Warning::SizeAssertionFails {
+                path,
+                size,
+                expectation,
+            } => write!(
+                f,
+                "input file \"{}\" is {} bytes rather than {}, so the generated header will fail to compile",
+                path, size, expectation
+            ),
+            Warning::PartialWord {
+                path,
+                size,
+                word_bytes,
+            } => write!(
+                f,
+                "input file \"{}\" is {} bytes, not a multiple of {}, so its last word is padded with zeros",
+                path, size, word_bytes
+            ),
+            Warning::AbsolutePath { path } => write!(
+                f,
+                "input path \"{}\" is absolute and cannot be made relative to the current directory, so it is recorded in the metadata block as it is (pass --no-metadata to leave it out)",
+                path
+            ),
+            Warning::MangledSymbol {
+                path,
+                symbol,
+                mangled,
+                reason,
+            } => write!(
+                f,
+                "symbol \"{}\" derived from \"{}\" {}, so it is named \"{}\" instead (pass --symbol-name to name it otherwise)",
+                symbol, path, reason, mangled
+            ),
+            Warning::RenamedSymbol { output, renamed } => write!(
+                f,
+                "output file \"{}\" is also the output of an earlier input, so its symbols are declared in \"{}\" instead",
+                output, renamed
+            ),
+            Warning::OverBudget { path, size, budget } => write!(
+                f,
+                "a header holding \"{}\" alone is {} bytes, over the header budget of {} bytes, so it is given one of its own",
+                path, size, budget
+            ),
+        }
+    }
+}
+
+/// When diagnostics are coloured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Colour when stderr is a terminal and NO_COLOR is not set
+    Auto,
+    Always,
+    Never,
+}
+
+/// Decide whether diagnostics should be coloured
+pub fn use_color(choice: ColorChoice) -> bool {
+    return match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+            !no_color && io::stderr().is_terminal()
+        }
+    };
+}
+
+const RESET: &str = "\x1b[0m";
+const BOLD_RED: &str = "\x1b[1;31m";
+const BOLD_YELLOW: &str = "\x1b[1;33m";
+const BOLD_CYAN: &str = "\x1b[1;36m";
+const BOLD: &str = "\x1b[1m";
+
+/// Reports errors, warnings and notes as they occur and keeps count of the warnings
+///
+/// If a log file has been attached, everything reported is also written to it along with verbose
+/// messages which never appear on the console.
+#[derive(Debug)]
+pub struct Diagnostics {
+    color: bool,
+    warning_count: usize,
+    log_file: Option<BufWriter<File>>,
+    start: Instant,
+}
+
+impl Diagnostics {
+    pub fn new(color: bool) -> Self {
+        return Diagnostics {
+            color,
+            warning_count: 0,
+            log_file: None,
+            start: Instant::now(),
+        };
+    }
+
+    /// Start writing the full log to the given file, truncating it
+    pub fn open_log_file(&mut self, path: &Path) -> io::Result<()> {
+        let file = File::create(path)?;
+        self.log_file = Some(BufWriter::new(file));
+        return Ok(());
+    }
+
+    pub fn error(&mut self, message: impl fmt::Display) {
+        self.emit("error", BOLD_RED, message);
+    }
+
+    pub fn warn(&mut self, warning: Warning) {
+        self.emit("warning", BOLD_YELLOW, warning);
+        self.warning_count += 1;
+    }
+
+    pub fn note(&mut self, message: impl fmt::Display) {
+        self.emit("note", BOLD_CYAN, message);
+    }
+
+    /// Record a verbose message in the log file only
+    pub fn log(&mut self, message: impl fmt::Display) {
+        self.write_log("info", &message.to_string());
+    }
+
+    pub fn warning_count(&self) -> usize {
+        return self.warning_count;
+    }
+
+    fn emit(&mut self, level: &str, level_color: &str, message: impl fmt::Display) {
+        let message = message.to_string();
+        self.write_log(level, &message);
+        if self.color {
+            eprintln!(
+                "{}{}:{} {}",
+                level_color,
+                level,
+                RESET,
+                highlight_quoted(&message)
+            );
+        } else {
+            eprintln!("{}: {}", level, message);
+        }
+    }
+
+    /// Write a line to the log file (if any), prefixed with the time elapsed since startup
+    ///
+    /// Logging is best-effort, failing to write the log never fails the run.
+    fn write_log(&mut self, level: &str, message: &str) {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        if let Some(ref mut log_file) = self.log_file {
+            let _ = writeln!(log_file, "[{:>10.6}s] {}: {}", elapsed, level, message);
+            let _ = log_file.flush();
+        }
+    }
+}
+
+/// Embolden every double-quoted section of a message, which is how paths (and other
+/// user-supplied values) are presented in diagnostics
+fn highlight_quoted(message: &str) -> String {
+    let mut highlighted = String::with_capacity(message.len() + 0x20);
+    for (index, part) in message.split('"').enumerate() {
+        if index > 0 {
+            highlighted.push('"');
+        }
+        // Odd-numbered parts are the quoted ones (a trailing unpaired quote is left alone)
+        if index % 2 == 1 && !part.is_empty() {
+            highlighted.push_str(BOLD);
+            highlighted.push_str(part);
+            highlighted.push_str(RESET);
+        } else {
+            highlighted.push_str(part);
+        }
+    }
+
+    return highlighted;
+}
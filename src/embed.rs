@@ -0,0 +1,48 @@
+use crate::{ElementType, LINE_ENDING};
+
+/// Defined while the declarations are generated if the preprocessor can `#embed` the input
+const FOUND_MACRO: &str = "BIN2HPP_EMBED_FOUND";
+
+/// Generate a declaration which pulls the data in with `#embed` (C23, C++26 and an extension of
+/// recent Clang and GCC in C++) where the preprocessor supports it and finds the file, and falls
+/// back to the expanded array (`fallback_declaration`) everywhere else (or if `BIN2HPP_NO_EMBED`
+/// is defined)
+///
+/// The path is relative to the directory containing the header, as with `#include "..."` (and so
+/// cannot contain a `"`), and at most the size of the data at generation time is embedded so
+/// that a file which has since grown cannot overflow the array.
+pub fn declaration(
+    fallback_declaration: &str,
+    array_len: usize,
+    symbol_name: &str,
+    path: &str,
+    specifiers: &str,
+    element_type: ElementType,
+) -> String {
+    // Header names are taken literally, without escape sequences
+    let path_literal = format!("\"{}\"", path);
+
+    let lines = [
+        "#if defined(__has_embed) && !defined(BIN2HPP_NO_EMBED)".to_string(),
+        format!("#if __has_embed({}) == __STDC_EMBED_FOUND__", path_literal),
+        format!("#define {}", FOUND_MACRO),
+        "#endif".to_string(),
+        "#endif".to_string(),
+        format!("#ifndef {}", FOUND_MACRO),
+        fallback_declaration.to_string(),
+        "#else".to_string(),
+        format!(
+            "{}constexpr std::array<{},{}> {}{{",
+            specifiers,
+            element_type.name(),
+            array_len,
+            symbol_name
+        ),
+        format!("#embed {} limit({})", path_literal, array_len),
+        "};".to_string(),
+        format!("#undef {}", FOUND_MACRO),
+        "#endif".to_string(),
+    ];
+
+    return lines.join(LINE_ENDING);
+}
@@ -0,0 +1,309 @@
+/// Recover the bytes embedded under a symbol from a generated header (or the source file defining
+/// them with `--split`), as a compiler would see them, for `--self-check`
+///
+/// Only byte arrays and string literals are understood: the representations whose contents are
+/// spelled out in the header rather than packed, encoded or loaded from elsewhere.
+pub fn extract(src: &str, symbol_name: &str) -> Result<Vec<u8>, String> {
+    // Span accessors return the array they are declared over, whatever its element type
+    let accessor_prefix = format!("> {}() noexcept{{return {{bin2hpp_detail::", symbol_name);
+    if let Some(found) = src.find(&accessor_prefix) {
+        let rest = &src[found + accessor_prefix.len()..];
+        let storage_name = &rest[..rest.find('}').unwrap_or(rest.len())];
+        return extract(src, storage_name);
+    }
+
+    // Definitions are either `constexpr` or `extern const`, so only what follows is matched
+    let array_prefix = "std::array<";
+    let array_suffix = format!("> {}{{", symbol_name);
+    let view_prefix = format!(" std::string_view {}{{", symbol_name);
+    let u8_view_prefix = format!(" std::u8string_view {}{{", symbol_name);
+    let pointer_prefixes = [
+        format!("constexpr const char* {} = ", symbol_name),
+        format!("constexpr const char8_t* {} = ", symbol_name),
+        format!("extern const char* const {} = ", symbol_name),
+        format!("extern const char8_t* const {} = ", symbol_name),
+    ];
+
+    let mut search_from = 0;
+    while let Some(found) = src[search_from..].find(array_prefix) {
+        let type_start = search_from + found + array_prefix.len();
+        search_from = type_start;
+        let Some(type_len) = src[type_start..].find([',', '>']) else {
+            break;
+        };
+        if !src[type_start + type_len..].starts_with(',') {
+            continue;
+        }
+        let start = type_start + type_len + 1;
+
+        let len_end = start
+            + src[start..]
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(0);
+        if !src[len_end..].starts_with(&array_suffix) {
+            continue;
+        }
+        let len: usize = src[start..len_end]
+            .parse()
+            .map_err(|_| "array length is not a number".to_string())?;
+        let contents_start = len_end + array_suffix.len();
+        let Some(contents_len) = src[contents_start..].find('}') else {
+            return Err("array initialiser is not closed".to_string());
+        };
+        let data = parse_initialiser(&src[contents_start..contents_start + contents_len])?;
+        if data.len() != len {
+            return Err(format!(
+                "array declared with {} elements but initialised with {}",
+                len,
+                data.len()
+            ));
+        }
+        return Ok(data);
+    }
+
+    if let Some((found, prefix_len)) = src
+        .find(&view_prefix)
+        .map(|found| (found, view_prefix.len()))
+        .or_else(|| {
+            src.find(&u8_view_prefix)
+                .map(|found| (found, u8_view_prefix.len()))
+        })
+    {
+        let (data, rest) = parse_string_literals(&src[found + prefix_len..])?;
+        let len_str = rest.trim_start().strip_prefix(',').unwrap_or(rest);
+        let len_end = len_str.find('}').unwrap_or(len_str.len());
+        let len: usize = len_str[..len_end]
+            .trim()
+            .parse()
+            .map_err(|_| "string_view length is not a number".to_string())?;
+        if len > data.len() {
+            return Err(format!(
+                "string_view declared with {} bytes but its literal holds {}",
+                len,
+                data.len()
+            ));
+        }
+        return Ok(data[..len].to_vec());
+    }
+
+    for pointer_prefix in pointer_prefixes {
+        if let Some(found) = src.find(&pointer_prefix) {
+            let (data, _) = parse_string_literals(&src[found + pointer_prefix.len()..])?;
+            return Ok(data);
+        }
+    }
+
+    for c_prefix in [
+        format!("static const unsigned char {}[", symbol_name),
+        format!("static const char {}[", symbol_name),
+    ] {
+        if let Some(found) = src.find(&c_prefix) {
+            return extract_c(&src[found + c_prefix.len()..], symbol_name);
+        }
+    }
+
+    return Err(format!("no declaration of \"{}\" found", symbol_name));
+}
+
+/// Recover the bytes of a C array (following the `[` of its declaration), which is initialised
+/// from a list or string literal and only as long as its `<symbol>_len` constant says
+fn extract_c(src: &str, symbol_name: &str) -> Result<Vec<u8>, String> {
+    let Some(initialiser_start) = src.find("] = ") else {
+        return Err("array declaration is not initialised".to_string());
+    };
+    let initialiser = &src[initialiser_start + "] = ".len()..];
+    let data = match initialiser.strip_prefix('{') {
+        Some(contents) => match contents.find('}') {
+            Some(contents_len) => parse_initialiser(&contents[..contents_len])?,
+            None => return Err("array initialiser is not closed".to_string()),
+        },
+        None => parse_string_literals(initialiser)?.0,
+    };
+
+    let len_prefix = format!("static const size_t {}_len = ", symbol_name);
+    let Some(found) = src.find(&len_prefix) else {
+        return Err(format!("no declaration of \"{}_len\" found", symbol_name));
+    };
+    let len_str = &src[found + len_prefix.len()..];
+    let len: usize = len_str[..len_str.find(';').unwrap_or(len_str.len())]
+        .trim()
+        .parse()
+        .map_err(|_| "array length is not a number".to_string())?;
+    if len > data.len() {
+        return Err(format!(
+            "array declared with {} bytes but initialised with {}",
+            len,
+            data.len()
+        ));
+    }
+
+    return Ok(data[..len].to_vec());
+}
+
+/// Parse a comma-separated list of integer literals, each of which must fit in a byte
+fn parse_initialiser(contents: &str) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    for element in contents.split(',') {
+        let element = element.trim();
+        if element.is_empty() {
+            continue;
+        }
+        // Elements which do not convert implicitly are cast, as in `std::byte(0x80)`
+        let element = match element.strip_suffix(')').and_then(|e| e.split_once('(')) {
+            Some((_, literal)) => literal.trim(),
+            None => element,
+        };
+        let element = element.trim_end_matches(['u', 'U']);
+        let value = if let Some(hex) = element
+            .strip_prefix("0x")
+            .or_else(|| element.strip_prefix("0X"))
+        {
+            u8::from_str_radix(hex, 16)
+        } else if let Some(bin) = element
+            .strip_prefix("0b")
+            .or_else(|| element.strip_prefix("0B"))
+        {
+            u8::from_str_radix(bin, 2)
+        } else if element.len() > 1 && element.starts_with('0') {
+            u8::from_str_radix(&element[1..], 8)
+        } else {
+            element.parse()
+        };
+        match value {
+            Ok(b) => data.push(b),
+            Err(_) => return Err(format!("\"{}\" is not a byte", element)),
+        }
+    }
+
+    return Ok(data);
+}
+
+/// Parse one or more adjacent string literals (which the compiler concatenates), returning their
+/// bytes and whatever follows the last one
+///
+/// Literals may have a `u8` prefix, which does not change the bytes of a literal spelled out in
+/// ASCII with escapes.
+fn parse_string_literals(src: &str) -> Result<(Vec<u8>, &str), String> {
+    let mut data = Vec::new();
+    let mut rest = src.trim_start();
+    let unprefixed = |s: &str| {
+        s.trim_start_matches("u8")
+            .trim_start_matches('R')
+            .starts_with('"')
+    };
+    if !unprefixed(rest) {
+        return Err("expected a string literal".to_string());
+    }
+
+    while unprefixed(rest) {
+        let literal = rest.strip_prefix("u8").unwrap_or(rest);
+
+        // Raw literals hold their contents as they are, up to the closing delimiter
+        if let Some(raw) = literal.strip_prefix("R\"") {
+            let Some((delimiter, contents)) = raw.split_once('(') else {
+                return Err("raw string literal has no opening parenthesis".to_string());
+            };
+            let closing = format!("){}\"", delimiter);
+            let Some(end) = contents.find(closing.as_str()) else {
+                return Err("raw string literal is not closed".to_string());
+            };
+            data.extend_from_slice(&contents.as_bytes()[..end]);
+            rest = contents[end + closing.len()..].trim_start();
+            continue;
+        }
+
+        let literal = &literal[1..];
+        let mut chars = literal.char_indices();
+        let end = loop {
+            let Some((i, c)) = chars.next() else {
+                return Err("string literal is not closed".to_string());
+            };
+            match c {
+                '"' => break i,
+                '\\' => parse_escape(&mut chars, &mut data)?,
+                _ => {
+                    let mut encoded = [0; 4];
+                    data.extend_from_slice(c.encode_utf8(&mut encoded).as_bytes());
+                }
+            }
+        };
+        rest = literal[end + 1..].trim_start();
+    }
+
+    return Ok((data, rest));
+}
+
+/// Parse the escape sequence following a backslash, pushing the byte(s) it stands for
+fn parse_escape(chars: &mut std::str::CharIndices, data: &mut Vec<u8>) -> Result<(), String> {
+    let Some((_, c)) = chars.next() else {
+        return Err("escape sequence is not finished".to_string());
+    };
+
+    let simple = match c {
+        '\'' | '"' | '?' | '\\' => Some(c as u8),
+        'a' => Some(0x07),
+        'b' => Some(0x08),
+        'f' => Some(0x0c),
+        'n' => Some(b'\n'),
+        'r' => Some(b'\r'),
+        't' => Some(b'\t'),
+        'v' => Some(0x0b),
+        _ => None,
+    };
+    if let Some(b) = simple {
+        data.push(b);
+        return Ok(());
+    }
+
+    let (radix, max_digits, first_digit) = match c {
+        '0'..='7' => (8, 3, Some(c)),
+        'x' => (16, usize::MAX, None),
+        'u' => (16, 4, None),
+        'U' => (16, 8, None),
+        _ => return Err(format!("unknown escape sequence \"\\{}\"", c)),
+    };
+
+    // Delimited escapes, as in "\u{1F600}"
+    let mut digits = String::new();
+    let mut lookahead = chars.clone();
+    if c == 'u' && lookahead.next().map(|(_, c)| c) == Some('{') {
+        *chars = lookahead;
+        loop {
+            match chars.next() {
+                Some((_, '}')) => break,
+                Some((_, d)) => digits.push(d),
+                None => return Err("delimited escape sequence is not closed".to_string()),
+            }
+        }
+    } else {
+        digits.extend(first_digit);
+        while digits.len() < max_digits {
+            let mut lookahead = chars.clone();
+            match lookahead.next() {
+                Some((_, d)) if d.is_digit(radix) => {
+                    digits.push(d);
+                    *chars = lookahead;
+                }
+                _ => break,
+            }
+        }
+    }
+
+    let value = u32::from_str_radix(&digits, radix)
+        .map_err(|_| format!("escape sequence \"\\{}{}\" is malformed", c, digits))?;
+    match c {
+        'u' | 'U' => match char::from_u32(value) {
+            Some(code_point) => {
+                let mut encoded = [0; 4];
+                data.extend_from_slice(code_point.encode_utf8(&mut encoded).as_bytes());
+            }
+            None => return Err(format!("\"\\{}{}\" is not a code point", c, digits)),
+        },
+        _ => match u8::try_from(value) {
+            Ok(b) => data.push(b),
+            Err(_) => return Err(format!("\"\\{}{}\" does not fit in a byte", c, digits)),
+        },
+    }
+
+    return Ok(());
+}
@@ -0,0 +1,247 @@
+use std::{
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::glob;
+
+/// One pattern of a `.gitignore` file
+struct Rule {
+    /// Pattern components, of which `**` matches any number of path components
+    components: Vec<String>,
+    /// Whether the pattern is matched against the whole path below the directory of the
+    /// `.gitignore` file rather than against the file name alone
+    anchored: bool,
+    /// Whether the pattern only matches directories (it ended with `/`)
+    dir_only: bool,
+    /// Whether the pattern re-includes what an earlier one excluded (it started with `!`)
+    negated: bool,
+}
+
+/// The patterns of one `.gitignore` file, which apply to paths below the directory containing it
+pub struct Gitignore {
+    base: PathBuf,
+    rules: Vec<Rule>,
+}
+
+impl Gitignore {
+    /// Parse the contents of a `.gitignore` file in the directory `base`
+    ///
+    /// Blank lines and `#` comments are skipped, and trailing spaces are trimmed unless they are
+    /// escaped with a backslash, as are a leading `#` or `!` which is part of the pattern.
+    pub fn parse(base: &Path, contents: &str) -> Gitignore {
+        let mut rules = Vec::new();
+        for line in contents.lines() {
+            let mut pattern = line.trim_end_matches('\r');
+            if pattern.starts_with('#') {
+                continue;
+            }
+            while pattern.ends_with(' ') && !pattern.ends_with("\\ ") {
+                pattern = &pattern[..pattern.len() - 1];
+            }
+            let mut pattern = pattern.replace("\\ ", " ");
+            if pattern.is_empty() {
+                continue;
+            }
+
+            let negated = pattern.starts_with('!');
+            if negated || pattern.starts_with("\\!") || pattern.starts_with("\\#") {
+                pattern.remove(0);
+            }
+            let dir_only = pattern.ends_with('/');
+            let pattern = pattern.trim_end_matches('/');
+            // A separator anywhere but at the end ties the pattern to this directory
+            let anchored = pattern.contains('/');
+            let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+            if pattern.is_empty() {
+                continue;
+            }
+
+            rules.push(Rule {
+                components: pattern.split('/').map(|c| c.to_string()).collect(),
+                anchored,
+                dir_only,
+                negated,
+            });
+        }
+
+        return Gitignore {
+            base: base.to_path_buf(),
+            rules,
+        };
+    }
+
+    /// Read the `.gitignore` file of a directory, if it has one
+    pub fn read(dir: &Path) -> io::Result<Option<Gitignore>> {
+        return read_file(dir, &dir.join(".gitignore"));
+    }
+
+    /// Whether this file ignores (`Some(true)`) or re-includes (`Some(false)`) a path, decided by
+    /// the last of its patterns which matches, or nothing if none does or the path is not below
+    /// its directory
+    fn decide(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let components: Vec<String> = path
+            .strip_prefix(&self.base)
+            .ok()?
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+                _ => None,
+            })
+            .collect();
+        let name = components.last()?;
+
+        return self
+            .rules
+            .iter()
+            .rev()
+            .filter(|rule| is_dir || !rule.dir_only)
+            .find(|rule| match rule.anchored {
+                true => matches_components(&rule.components, &components),
+                false => matches_name(&rule.components[0], name),
+            })
+            .map(|rule| !rule.negated);
+    }
+}
+
+fn read_file(base: &Path, path: &Path) -> io::Result<Option<Gitignore>> {
+    return match fs::read(path) {
+        Ok(contents) => Ok(Some(Gitignore::parse(
+            base,
+            &String::from_utf8_lossy(&contents),
+        ))),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error),
+    };
+}
+
+/// The ignore files applying to a directory from outside it: if it is inside a git repository,
+/// the repository's `.git/info/exclude` and the `.gitignore` files of every directory from the
+/// root of the repository down to its parent, outermost first
+///
+/// `dir` should be canonical, so that its ancestors are the directories it really is in.
+pub fn enclosing(dir: &Path) -> io::Result<Vec<Gitignore>> {
+    let Some(root) = dir
+        .ancestors()
+        .find(|ancestor| ancestor.join(".git").exists())
+    else {
+        return Ok(Vec::new());
+    };
+
+    let mut ignores = Vec::new();
+    ignores.extend(read_file(root, &root.join(".git/info/exclude"))?);
+    let mut ancestors: Vec<&Path> = dir
+        .ancestors()
+        .skip(1)
+        .take_while(|ancestor| ancestor.starts_with(root))
+        .collect();
+    ancestors.reverse();
+    for ancestor in ancestors {
+        ignores.extend(Gitignore::read(ancestor)?);
+    }
+
+    return Ok(ignores);
+}
+
+/// Whether a path is ignored by the given files, of which those in deeper directories must come
+/// later so that they take precedence (files in directories the path is not below are skipped)
+pub fn is_ignored(ignores: &[Gitignore], path: &Path, is_dir: bool) -> bool {
+    return ignores
+        .iter()
+        .rev()
+        .find_map(|ignore| ignore.decide(path, is_dir))
+        .unwrap_or(false);
+}
+
+fn matches_components(pattern: &[String], components: &[String]) -> bool {
+    return match pattern.split_first() {
+        None => components.is_empty(),
+        // A trailing `**` matches everything inside a directory, but not the directory itself
+        Some((first, rest)) if first == "**" && rest.is_empty() => !components.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=components.len()).any(|skipped| matches_components(rest, &components[skipped..]))
+        }
+        Some((first, rest)) => match components.split_first() {
+            Some((component, components)) => {
+                matches_name(first, component) && matches_components(rest, components)
+            }
+            None => false,
+        },
+    };
+}
+
+/// Whether a single path component matches a pattern component, which unlike in glob patterns
+/// may match a leading `.` with a wildcard
+fn matches_name(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    return glob::matches_chars(&pattern, &name);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ignored(contents: &str, path: &str, is_dir: bool) -> bool {
+        let ignore = Gitignore::parse(Path::new("/repo"), contents);
+        return is_ignored(&[ignore], &Path::new("/repo").join(path), is_dir);
+    }
+
+    #[test]
+    fn unanchored_patterns_match_names_at_any_depth() {
+        assert!(ignored("*.tmp", "a.tmp", false));
+        assert!(ignored("*.tmp", "sub/dir/a.tmp", false));
+        assert!(ignored("*.tmp", ".hidden.tmp", false));
+        assert!(!ignored("*.tmp", "a.tmp.bin", false));
+    }
+
+    #[test]
+    fn anchored_patterns_match_below_the_directory() {
+        assert!(ignored("/build", "build", true));
+        assert!(!ignored("/build", "sub/build", true));
+        assert!(ignored("doc/*.html", "doc/index.html", false));
+        assert!(!ignored("doc/*.html", "doc/api/index.html", false));
+        assert!(ignored("doc/**/*.html", "doc/api/index.html", false));
+        assert!(ignored("**/cache", "a/b/cache", true));
+    }
+
+    #[test]
+    fn trailing_double_stars_match_inside_the_directory_only() {
+        assert!(ignored("foo/**", "foo/a", false));
+        assert!(ignored("foo/**", "foo/a/b", false));
+        assert!(!ignored("foo/**", "foo", true));
+        assert!(!ignored("foo/**", "foo", false));
+        assert!(ignored("foo/**/bar", "foo/bar", false));
+    }
+
+    #[test]
+    fn directory_patterns_only_match_directories() {
+        assert!(ignored("out/", "out", true));
+        assert!(!ignored("out/", "out", false));
+    }
+
+    #[test]
+    fn later_patterns_take_precedence() {
+        assert!(!ignored("*.tmp\n!keep.tmp", "keep.tmp", false));
+        assert!(ignored("!keep.tmp\n*.tmp", "keep.tmp", false));
+        let outer = Gitignore::parse(Path::new("/repo"), "*.log");
+        let inner = Gitignore::parse(Path::new("/repo/sub"), "!debug.log");
+        let ignores = [outer, inner];
+        assert!(!is_ignored(
+            &ignores,
+            Path::new("/repo/sub/debug.log"),
+            false
+        ));
+        assert!(is_ignored(&ignores, Path::new("/repo/debug.log"), false));
+    }
+
+    #[test]
+    fn comments_blank_lines_and_escapes() {
+        let contents = "# comment\n\n\\#hash\n\\!bang\ntrailing  \nspace\\ \n";
+        assert!(ignored(contents, "#hash", false));
+        assert!(ignored(contents, "!bang", false));
+        assert!(ignored(contents, "trailing", false));
+        assert!(ignored(contents, "space ", false));
+        assert!(!ignored(contents, "comment", false));
+    }
+}
@@ -0,0 +1,126 @@
+use std::{
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::input::{self, SymlinkPolicy};
+
+/// Whether an input path is a glob pattern rather than a path to be taken literally
+pub fn is_pattern(path: &Path) -> bool {
+    return path.to_string_lossy().contains(['*', '?', '[']);
+}
+
+/// Find every file matching a glob pattern, sorted by `input::path_sort_key`
+///
+/// `*` matches anything within a path component, `?` a single character and `[abc]`/`[a-z]` (or
+/// `[!abc]`) one character of a set, while a `**` component matches any number of directories.
+/// As in shells, wildcards never match a leading `.` so hidden files are only found by patterns
+/// which spell it out.
+pub fn expand(pattern: &Path, policy: SymlinkPolicy) -> io::Result<Vec<PathBuf>> {
+    // Components up to the first one with a wildcard name the directory to search
+    let mut base = PathBuf::new();
+    let mut pattern_components: Vec<String> = Vec::new();
+    for component in pattern.components() {
+        let is_wildcard = match component {
+            Component::Normal(name) => is_pattern(Path::new(name)),
+            _ => false,
+        };
+        if is_wildcard || !pattern_components.is_empty() {
+            pattern_components.push(component.as_os_str().to_string_lossy().to_string());
+        } else {
+            base.push(component);
+        }
+    }
+
+    let search_dir = match base.as_os_str().is_empty() {
+        true => Path::new("."),
+        false => base.as_path(),
+    };
+    let matches = input::walk_dir(search_dir, policy, false)?
+        .into_iter()
+        .filter(|relative_path| {
+            let components: Vec<String> = relative_path
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect();
+            return matches_components(&pattern_components, &components);
+        })
+        .map(|relative_path| base.join(relative_path))
+        .collect();
+
+    return Ok(matches);
+}
+
+fn matches_components(pattern: &[String], components: &[String]) -> bool {
+    return match pattern.split_first() {
+        None => components.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            // Any number of directories, but never a hidden one
+            (0..=components.len())
+                .take_while(|&skipped| skipped == 0 || !components[skipped - 1].starts_with('.'))
+                .any(|skipped| matches_components(rest, &components[skipped..]))
+        }
+        Some((first, rest)) => match components.split_first() {
+            Some((component, components)) => {
+                matches_name(first, component) && matches_components(rest, components)
+            }
+            None => false,
+        },
+    };
+}
+
+/// Whether a single path component matches a pattern component
+fn matches_name(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    return matches_chars(&pattern, &name);
+}
+
+pub(crate) fn matches_chars(pattern: &[char], name: &[char]) -> bool {
+    let Some((&first, rest)) = pattern.split_first() else {
+        return name.is_empty();
+    };
+
+    return match first {
+        '*' => (0..=name.len()).any(|skipped| matches_chars(rest, &name[skipped..])),
+        '?' => !name.is_empty() && matches_chars(rest, &name[1..]),
+        '[' => match (parse_class(rest), name.split_first()) {
+            (Some((matches, rest)), Some((&c, name))) => matches(c) && matches_chars(rest, name),
+            // An unclosed bracket is just a bracket
+            (None, Some(('[', name))) => matches_chars(rest, name),
+            _ => false,
+        },
+        c => name.first() == Some(&c) && matches_chars(rest, &name[1..]),
+    };
+}
+
+/// Parse a character class following a `[`, returning a predicate for it and the rest of the
+/// pattern
+fn parse_class(pattern: &[char]) -> Option<(impl Fn(char) -> bool, &[char])> {
+    let (negated, pattern) = match pattern.first() {
+        Some('!') | Some('^') => (true, &pattern[1..]),
+        _ => (false, pattern),
+    };
+    // A `]` straight after the opening bracket is part of the set
+    let end = 1 + pattern.get(1..)?.iter().position(|&c| c == ']')?;
+    let set = &pattern[..end];
+
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i < set.len() {
+        if i + 2 < set.len() && set[i + 1] == '-' {
+            ranges.push((set[i], set[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((set[i], set[i]));
+            i += 1;
+        }
+    }
+
+    let matches = move |c: char| ranges.iter().any(|&(low, high)| low <= c && c <= high) != negated;
+    return Some((matches, &pattern[end + 1..]));
+}
@@ -0,0 +1,54 @@
+use clap::ValueEnum;
+
+use crate::LINE_ENDING;
+
+/// How a generated header protects itself against being included more than once (`--guard`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GuardStyle {
+    /// `#pragma once`, supported by every major compiler though not part of either standard
+    Pragma,
+    /// `#ifndef`/`#define`/`#endif` around the whole header
+    Ifdef,
+    /// No protection, for headers which are only ever included once or guarded by hand
+    None,
+}
+
+/// Guard macro derived from the name of what a header declares (its symbol, or the stem of an
+/// amalgamation), such as `BIN2HPP_LOGO_PNG_HPP`
+pub fn default_macro(name: &str, extension: &str) -> String {
+    return format!(
+        "BIN2HPP_{}_{}",
+        name.to_ascii_uppercase(),
+        extension.to_ascii_uppercase()
+    );
+}
+
+/// Lines opening the guard, before everything the header declares
+pub fn open(style: GuardStyle, guard_macro: &str) -> String {
+    return match style {
+        GuardStyle::Pragma => format!("#pragma once{}", LINE_ENDING),
+        GuardStyle::Ifdef => format!("#ifndef {0}{1}#define {0}{1}", guard_macro, LINE_ENDING),
+        GuardStyle::None => String::new(),
+    };
+}
+
+/// Line closing the guard, after everything the header declares
+pub fn close(style: GuardStyle) -> String {
+    return match style {
+        GuardStyle::Ifdef => format!("#endif{}", LINE_ENDING),
+        GuardStyle::Pragma | GuardStyle::None => String::new(),
+    };
+}
+
+/// Surround a whole generated header with a guard
+pub fn wrap(src: &str, style: GuardStyle, guard_macro: &str) -> String {
+    let mut out_string = String::with_capacity(src.len() + guard_macro.len() * 2 + 0x20);
+    out_string.push_str(&open(style, guard_macro));
+    out_string.push_str(src);
+    if !src.is_empty() && !src.ends_with('\n') {
+        out_string.push_str(LINE_ENDING);
+    }
+    out_string.push_str(&close(style));
+
+    return out_string;
+}
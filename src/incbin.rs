@@ -0,0 +1,97 @@
+use crate::{LINE_ENDING, SourceOptions, format_as_escaped};
+
+/// Alignment of data included with `.incbin` (unless more is asked for), enough for any SIMD load
+/// or word-stream API
+const INCBIN_ALIGNMENT: usize = 16;
+
+/// Generate a declaration which pulls the data in with the assembler's `.incbin` directive on ELF
+/// and Mach-O targets with GNU-style inline assembly, exposing it as a reference to a
+/// `std::array` so consumers can use it the same way as the expanded array, and falls back to the
+/// expanded array (`fallback_declaration`) everywhere else (or if `BIN2HPP_NO_INCBIN` is defined)
+///
+/// The data is emitted as a weak symbol (in a COMDAT group on ELF) so the header can be included
+/// in any number of translation units. A section asked for only applies on ELF, as Mach-O names
+/// its sections differently.
+pub fn declaration(
+    fallback_declaration: &str,
+    array_len: usize,
+    symbol_name: &str,
+    path: &str,
+    options: &SourceOptions,
+) -> String {
+    // The symbol has C linkage so the namespace has to be folded into its name
+    let asm_symbol = match options.ns_name.as_deref() {
+        Some(namespace) => format!(
+            "bin2hpp_incbin_{}_{}",
+            namespace.replace("::", "_"),
+            symbol_name
+        ),
+        None => format!("bin2hpp_incbin_{}", symbol_name),
+    };
+    // Escaped once for the assembler here, and again for the C++ string literal below
+    let asm_path = path.replace('\\', "\\\\").replace('"', "\\\"");
+    let array_type = format!("std::array<{},{}>", options.element_type.name(), array_len);
+    let alignment = options.align.unwrap_or(0).max(INCBIN_ALIGNMENT);
+    // Only the part of the file which was embedded is included if it was not all of it
+    let incbin_directive = match options.input_offset {
+        Some(offset) => format!(".incbin \"{}\",{},{}", asm_path, offset, array_len),
+        None => format!(".incbin \"{}\"", asm_path),
+    };
+
+    let elf_section = match options.section.as_deref() {
+        Some(name) => name.to_string(),
+        None => format!(".rodata.{}", asm_symbol),
+    };
+
+    let elf_asm = [
+        format!(
+            ".pushsection {},\"aG\",@progbits,{},comdat",
+            elf_section, asm_symbol
+        ),
+        format!(".weak {}", asm_symbol),
+        format!(".type {},@object", asm_symbol),
+        format!(".balign {}", alignment),
+        format!("{}:", asm_symbol),
+        incbin_directive.clone(),
+        format!(".size {},{}", asm_symbol, array_len),
+        ".popsection".to_string(),
+    ];
+    // Mach-O prefixes C symbols with an underscore
+    let macho_asm = [
+        ".pushsection __DATA,__const".to_string(),
+        format!(".globl _{}", asm_symbol),
+        format!(".weak_definition _{}", asm_symbol),
+        format!(".p2align {}", alignment.trailing_zeros()),
+        format!("_{}:", asm_symbol),
+        incbin_directive,
+        ".popsection".to_string(),
+    ];
+
+    let extern_declaration = format!("extern \"C\" const unsigned char {}[];", asm_symbol);
+    let reference_declaration = format!(
+        "inline const {0}& {1}=*reinterpret_cast<const {0}*>({2});",
+        array_type, symbol_name, asm_symbol
+    );
+
+    let lines = [
+        "#if defined(__ELF__) && defined(__GNUC__) && !defined(BIN2HPP_NO_INCBIN)".to_string(),
+        extern_declaration.clone(),
+        format!(
+            "__asm__(\"{}\");",
+            format_as_escaped((elf_asm.join("\n") + "\n").as_bytes())
+        ),
+        reference_declaration.clone(),
+        "#elif defined(__APPLE__) && defined(__GNUC__) && !defined(BIN2HPP_NO_INCBIN)".to_string(),
+        extern_declaration,
+        format!(
+            "__asm__(\"{}\");",
+            format_as_escaped((macho_asm.join("\n") + "\n").as_bytes())
+        ),
+        reference_declaration,
+        "#else".to_string(),
+        fallback_declaration.to_string(),
+        "#endif".to_string(),
+    ];
+
+    return lines.join(LINE_ENDING);
+}
@@ -0,0 +1,91 @@
+use crate::{LINE_ENDING, format_as_escaped, push_namespace_close, push_namespace_open};
+
+/// A generated header listed in an index header
+#[derive(Debug, Clone)]
+pub struct IndexEntry {
+    /// Path under which the input is looked up
+    pub name: String,
+    /// Path by which the index includes the header
+    pub include: String,
+    /// Expression evaluating to a `std::span<const std::uint8_t>` over the embedded bytes
+    pub span_expr: String,
+    /// Whether the span is a constant expression, which it is not for data only the assembler or
+    /// another translation unit defines
+    pub constant: bool,
+}
+
+/// Generate an index header including every listed header, with a table mapping the path of each
+/// input to a span over its data (`<prefix>_index`) and a `<prefix>_find(path)` lookup
+///
+/// The table and lookup are `constexpr` so long as every span in it is a constant expression.
+pub fn generate_src(
+    entries: &[IndexEntry],
+    prefix: &str,
+    ns_name: Option<&str>,
+    internal_linkage: bool,
+) -> String {
+    let mut out_string = String::with_capacity(0x80 * entries.len() + 0x200);
+    for header in ["array", "cstdint", "span", "string_view"] {
+        out_string.push_str(format!("#include <{}>", header).as_str());
+        out_string.push_str(LINE_ENDING);
+    }
+    let mut includes: Vec<&str> = Vec::with_capacity(entries.len());
+    for entry in entries {
+        if includes.contains(&entry.include.as_str()) {
+            continue;
+        }
+        includes.push(&entry.include);
+        out_string.push_str(
+            format!(
+                "#include \"{}\"",
+                format_as_escaped(entry.include.as_bytes())
+            )
+            .as_str(),
+        );
+        out_string.push_str(LINE_ENDING);
+    }
+
+    let (table_specifier, find_specifier) = match entries.iter().all(|entry| entry.constant) {
+        true => ("constexpr", "constexpr"),
+        false => ("inline const", "inline"),
+    };
+    let rows: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            return format!(
+                "{{\"{}\",{}}}",
+                format_as_escaped(entry.name.as_bytes()),
+                entry.span_expr
+            );
+        })
+        .collect();
+
+    push_namespace_open(&mut out_string, ns_name, internal_linkage);
+    out_string.push_str(
+        format!(
+            "struct {0}_entry{{std::string_view path;std::span<const std::uint8_t> data;}};{1} std::array<{0}_entry,{2}> {0}_index{{{{{3}}}}};",
+            prefix,
+            table_specifier,
+            entries.len(),
+            rows.join(",")
+        )
+        .as_str(),
+    );
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str(
+        format!(
+            "{1} const {0}_entry* {0}_find(std::string_view path) noexcept{{for(const auto& entry:{0}_index){{if(entry.path==path)return &entry;}}return nullptr;}}",
+            prefix, find_specifier
+        )
+        .as_str(),
+    );
+    out_string.push_str(LINE_ENDING);
+    push_namespace_close(&mut out_string, ns_name, internal_linkage);
+
+    // Trailing newline
+    if !out_string.ends_with(LINE_ENDING) {
+        out_string.push_str(LINE_ENDING);
+    }
+
+    return out_string;
+}
@@ -0,0 +1,322 @@
+use std::{
+    collections::HashSet,
+    fmt, fs, io,
+    path::{Component, Path, PathBuf},
+    process,
+};
+
+use crate::gitignore::{self, Gitignore};
+
+/// Maximum number of symbolic links followed while resolving a single path, matching Linux's
+/// `MAXSYMLINKS`
+const MAX_SYMLINK_DEPTH: usize = 40;
+
+/// Whether symbolic links given as (or found while searching for) inputs are followed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    Follow,
+    NoFollow,
+}
+
+#[derive(Debug)]
+pub enum SymlinkError {
+    /// The path is a symbolic link but the policy forbids following it
+    NotFollowed(PathBuf),
+    /// Following the path leads back to a link which has already been visited
+    Loop(PathBuf),
+    /// The link target could not be read
+    Io(PathBuf, io::Error),
+}
+
+impl fmt::Display for SymlinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SymlinkError::NotFollowed(path) => write!(
+                f,
+                "file path \"{}\" is a symbolic link and symbolic links are not being followed",
+                path.to_string_lossy()
+            ),
+            SymlinkError::Loop(path) => write!(
+                f,
+                "file path \"{}\" is part of a symbolic link loop",
+                path.to_string_lossy()
+            ),
+            SymlinkError::Io(path, error) => write!(
+                f,
+                "failed to resolve symbolic link \"{}\": {}",
+                path.to_string_lossy(),
+                error
+            ),
+        }
+    }
+}
+
+/// Resolve a path according to the symlink policy, returning the path which should actually be
+/// read
+///
+/// Paths which are not symbolic links (including ones which do not exist) are returned unchanged,
+/// links are followed one step at a time so that loops can be reported clearly.
+pub fn resolve_symlinks(path: &Path, policy: SymlinkPolicy) -> Result<PathBuf, SymlinkError> {
+    let mut current = path.to_path_buf();
+    let mut visited: HashSet<PathBuf> = HashSet::new();
+
+    loop {
+        let is_symlink = match fs::symlink_metadata(&current) {
+            Ok(metadata) => metadata.file_type().is_symlink(),
+            Err(_) => return Ok(current),
+        };
+        if !is_symlink {
+            return Ok(current);
+        }
+
+        if policy == SymlinkPolicy::NoFollow {
+            return Err(SymlinkError::NotFollowed(current));
+        }
+
+        if !visited.insert(current.clone()) || visited.len() > MAX_SYMLINK_DEPTH {
+            return Err(SymlinkError::Loop(path.to_path_buf()));
+        }
+
+        let target = match fs::read_link(&current) {
+            Ok(target) => target,
+            Err(error) => return Err(SymlinkError::Io(current, error)),
+        };
+
+        // Relative targets are relative to the directory containing the link
+        let next = match current.parent() {
+            Some(parent) if target.is_relative() => parent.join(target),
+            _ => target,
+        };
+
+        // Dangling links and "magic" links such as /dev/fd/N (whose targets look like
+        // "pipe:[1234]") are left for the OS to resolve when the input is opened
+        if fs::symlink_metadata(&next).is_err() {
+            return Ok(current);
+        }
+        current = next;
+    }
+}
+
+/// Read a NUL-delimited list of paths (as produced by `find -print0`) from a file, or from stdin
+/// if the path is `-`
+///
+/// Paths are taken byte for byte, so anything but a NUL can appear in them, and empty entries
+/// (such as the one following the final NUL) are ignored.
+pub fn read_file_list(source: &Path) -> io::Result<Vec<PathBuf>> {
+    let list = match source.as_os_str() == "-" {
+        true => {
+            let mut list = Vec::new();
+            io::Read::read_to_end(&mut io::stdin().lock(), &mut list)?;
+            list
+        }
+        false => fs::read(source)?,
+    };
+
+    return list
+        .split(|&b| b == 0)
+        .filter(|entry| !entry.is_empty())
+        .map(path_from_bytes)
+        .collect();
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> io::Result<PathBuf> {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    return Ok(PathBuf::from(OsStr::from_bytes(bytes)));
+}
+
+/// Elsewhere paths are not arbitrary bytes, so the list has to be UTF-8 (as on Windows, where
+/// this is what tools emitting NUL-delimited lists produce)
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> io::Result<PathBuf> {
+    return match std::str::from_utf8(bytes) {
+        Ok(s) => Ok(PathBuf::from(s)),
+        Err(_) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "file list entry is not valid UTF-8",
+        )),
+    };
+}
+
+/// Key which sorts paths bytewise, with `/` as the separator on every platform, so that anything
+/// generated from a list of paths comes out the same on Linux, macOS and Windows
+pub fn path_sort_key(path: &Path) -> Vec<u8> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        return path.as_os_str().as_bytes().to_vec();
+    }
+    #[cfg(not(unix))]
+    {
+        return path.to_string_lossy().replace('\\', "/").into_bytes();
+    }
+}
+
+/// Find every file below a directory, returning their paths relative to it sorted by
+/// `path_sort_key`
+///
+/// Symbolic links are only followed under `SymlinkPolicy::Follow`, and then each directory is
+/// only visited once so that links pointing back up the tree cannot recurse forever. If
+/// `respect_gitignore` is set, whatever the `.gitignore` files found along the way (and those of
+/// the repository the directory is in) ignore is skipped, along with those files themselves and
+/// `.git` directories.
+pub fn walk_dir(
+    dir: &Path,
+    policy: SymlinkPolicy,
+    respect_gitignore: bool,
+) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let canonical_dir = fs::canonicalize(dir)?;
+    let mut visited: HashSet<PathBuf> = HashSet::new();
+    visited.insert(canonical_dir.clone());
+    // Files of deeper directories are always read after those of the directories above them
+    let mut ignores = match respect_gitignore {
+        true => gitignore::enclosing(&canonical_dir)?,
+        false => Vec::new(),
+    };
+
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative_dir) = pending.pop() {
+        if respect_gitignore {
+            ignores.extend(Gitignore::read(&canonical_dir.join(&relative_dir))?);
+        }
+        for entry in fs::read_dir(dir.join(&relative_dir))? {
+            let entry = entry?;
+            let relative_path = relative_dir.join(entry.file_name());
+            let mut file_type = entry.file_type()?;
+            if file_type.is_symlink() {
+                if policy == SymlinkPolicy::NoFollow {
+                    continue;
+                }
+                // Dangling links have nothing to list
+                match fs::metadata(entry.path()) {
+                    Ok(metadata) => file_type = metadata.file_type(),
+                    Err(_) => continue,
+                }
+            }
+
+            if respect_gitignore
+                && (entry.file_name() == ".git"
+                    || entry.file_name() == ".gitignore"
+                    || gitignore::is_ignored(
+                        &ignores,
+                        &canonical_dir.join(&relative_path),
+                        file_type.is_dir(),
+                    ))
+            {
+                continue;
+            }
+
+            if file_type.is_dir() {
+                if visited.insert(fs::canonicalize(entry.path())?) {
+                    pending.push(relative_path);
+                }
+            } else {
+                files.push(relative_path);
+            }
+        }
+    }
+    files.sort_by_cached_key(|path| path_sort_key(path));
+
+    return Ok(files);
+}
+
+/// Canonical form of a path which is about to be written, so only its parent needs to exist
+///
+/// Used to recognise the output among the files found by `walk_dir` when generating into the
+/// directory being walked.
+pub fn canonicalize_output(path: &Path) -> Option<PathBuf> {
+    let (parent, name) = (path.parent()?, path.file_name()?);
+    let parent = match parent.as_os_str().is_empty() {
+        true => Path::new("."),
+        false => parent,
+    };
+
+    return fs::canonicalize(parent).ok().map(|p| p.join(name));
+}
+
+/// Path of a file relative to a directory, with `/` as the separator on every platform, for
+/// paths written into generated sources (both must already be canonical)
+///
+/// Nothing is returned if there is no relative path between them, as for paths on different
+/// drives on Windows.
+pub fn relative_path(path: &Path, base_dir: &Path) -> Option<String> {
+    let path_components: Vec<Component> = path.components().collect();
+    let base_components: Vec<Component> = base_dir.components().collect();
+    let common = path_components
+        .iter()
+        .zip(&base_components)
+        .take_while(|(a, b)| a == b)
+        .count();
+    if common == 0 {
+        return None;
+    }
+
+    let mut parts = vec!["..".to_string(); base_components.len() - common];
+    parts.extend(
+        path_components[common..]
+            .iter()
+            .map(|component| component.as_os_str().to_string_lossy().to_string()),
+    );
+
+    return Some(parts.join("/"));
+}
+
+/// Run a command through the platform's shell and collect what it writes to stdout, leaving its
+/// stderr attached to ours so that tool errors stay visible
+pub fn run_command(command: &str) -> io::Result<Vec<u8>> {
+    #[cfg(windows)]
+    let mut shell = process::Command::new("cmd");
+    #[cfg(windows)]
+    shell.arg("/C");
+    #[cfg(not(windows))]
+    let mut shell = process::Command::new("sh");
+    #[cfg(not(windows))]
+    shell.arg("-c");
+
+    let output = shell
+        .arg(command)
+        .stdin(process::Stdio::null())
+        .stderr(process::Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "command exited with {}",
+            output.status
+        )));
+    }
+
+    return Ok(output.stdout);
+}
+
+/// Length from which Windows paths only work in their verbatim (`\\?\`) form
+#[cfg(windows)]
+const MAX_PATH: usize = 260;
+
+/// Strip the verbatim prefix which `canonicalize` adds on Windows (`\\?\C:\...`,
+/// `\\?\UNC\server\...`) unless the path is too long to work without it, since tools such as
+/// assemblers often reject verbatim paths outright
+pub fn strip_verbatim_prefix(path: &Path) -> PathBuf {
+    #[cfg(windows)]
+    {
+        let path_str = path.to_string_lossy();
+        let stripped = if let Some(rest) = path_str.strip_prefix(r"\\?\UNC\") {
+            Some(format!(r"\\{}", rest))
+        } else {
+            path_str
+                .strip_prefix(r"\\?\")
+                .filter(|rest| rest.as_bytes().get(1) == Some(&b':'))
+                .map(str::to_string)
+        };
+        if let Some(stripped) = stripped
+            && stripped.len() < MAX_PATH
+            && path.to_str().is_some()
+        {
+            return PathBuf::from(stripped);
+        }
+    }
+
+    return path.to_path_buf();
+}
@@ -0,0 +1,312 @@
+use crate::structured::{ParseError, Value};
+
+/// Parse a JSON document
+///
+/// Integers which fit in an `i64` are kept as integers, every other number becomes a float.
+pub fn parse(text: &str) -> Result<Value, ParseError> {
+    let mut parser = Parser {
+        bytes: text.as_bytes(),
+        text,
+        pos: 0,
+    };
+
+    parser.skip_whitespace();
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.pos < parser.bytes.len() {
+        return Err(parser.error("unexpected trailing characters"));
+    }
+
+    return Ok(value);
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    text: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> ParseError {
+        let line = self.text[..self.pos].matches('\n').count() + 1;
+        return ParseError {
+            line,
+            message: message.to_string(),
+        };
+    }
+
+    fn peek(&self) -> Option<u8> {
+        return self.bytes.get(self.pos).copied();
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<(), ParseError> {
+        if self.text[self.pos..].starts_with(literal) {
+            self.pos += literal.len();
+            return Ok(());
+        }
+
+        return Err(self.error(format!("expected \"{}\"", literal).as_str()));
+    }
+
+    fn parse_value(&mut self) -> Result<Value, ParseError> {
+        return match self.peek() {
+            Some(b'{') => self.parse_object(),
+            Some(b'[') => self.parse_array(),
+            Some(b'"') => Ok(Value::String(self.parse_string()?)),
+            Some(b't') => self.expect("true").map(|_| Value::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| Value::Bool(false)),
+            Some(b'n') => self.expect("null").map(|_| Value::Null),
+            Some(b'-' | b'0'..=b'9') => self.parse_number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        };
+    }
+
+    fn parse_object(&mut self) -> Result<Value, ParseError> {
+        self.pos += 1; // '{'
+        let mut entries: Vec<(String, Value)> = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(Value::Table(entries));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected a string key"));
+            }
+            let key = self.parse_string()?;
+            if entries.iter().any(|(k, _)| *k == key) {
+                return Err(self.error(format!("duplicate key \"{}\"", key).as_str()));
+            }
+
+            self.skip_whitespace();
+            self.expect(":")?;
+            self.skip_whitespace();
+            entries.push((key, self.parse_value()?));
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Value::Table(entries));
+                }
+                _ => return Err(self.error("expected \",\" or \"}\"")),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Value, ParseError> {
+        self.pos += 1; // '['
+        let mut elements = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Value::Array(elements));
+        }
+
+        loop {
+            self.skip_whitespace();
+            elements.push(self.parse_value()?);
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Value::Array(elements));
+                }
+                _ => return Err(self.error("expected \",\" or \"]\"")),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, ParseError> {
+        self.pos += 1; // opening quote
+        let mut string = String::new();
+
+        loop {
+            let c = match self.text[self.pos..].chars().next() {
+                Some(c) => c,
+                None => return Err(self.error("unterminated string")),
+            };
+            self.pos += c.len_utf8();
+
+            match c {
+                '"' => return Ok(string),
+                '\\' => string.push(self.parse_escape()?),
+                '\u{0}'..='\u{1f}' => return Err(self.error("control character in string")),
+                _ => string.push(c),
+            }
+        }
+    }
+
+    fn parse_escape(&mut self) -> Result<char, ParseError> {
+        let escape = match self.peek() {
+            Some(b) => b,
+            None => return Err(self.error("unterminated string")),
+        };
+        self.pos += 1;
+
+        return match escape {
+            b'"' => Ok('"'),
+            b'\\' => Ok('\\'),
+            b'/' => Ok('/'),
+            b'b' => Ok('\u{8}'),
+            b'f' => Ok('\u{c}'),
+            b'n' => Ok('\n'),
+            b'r' => Ok('\r'),
+            b't' => Ok('\t'),
+            b'u' => {
+                let high = self.parse_hex4()?;
+                if !(0xd800..0xdc00).contains(&high) {
+                    return char::from_u32(high).ok_or_else(|| self.error("invalid \\u escape"));
+                }
+
+                // Characters outside the BMP are written as a UTF-16 surrogate pair
+                self.expect("\\u")?;
+                let low = self.parse_hex4()?;
+                if !(0xdc00..0xe000).contains(&low) {
+                    return Err(self.error("invalid surrogate pair"));
+                }
+                let code_point = 0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00);
+                char::from_u32(code_point).ok_or_else(|| self.error("invalid surrogate pair"))
+            }
+            _ => Err(self.error("invalid escape sequence")),
+        };
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, ParseError> {
+        let digits = match self.text.get(self.pos..self.pos + 4) {
+            Some(digits) => digits,
+            None => return Err(self.error("invalid \\u escape")),
+        };
+        let value = match u32::from_str_radix(digits, 16) {
+            Ok(value) => value,
+            Err(_) => return Err(self.error("invalid \\u escape")),
+        };
+        self.pos += 4;
+
+        return Ok(value);
+    }
+
+    fn parse_number(&mut self) -> Result<Value, ParseError> {
+        let start = self.pos;
+        let mut is_float = false;
+
+        while let Some(b) = self.peek() {
+            match b {
+                b'0'..=b'9' | b'-' | b'+' => (),
+                b'.' | b'e' | b'E' => is_float = true,
+                _ => break,
+            }
+            self.pos += 1;
+        }
+
+        let literal = &self.text[start..self.pos];
+        if !is_float && let Ok(integer) = literal.parse::<i64>() {
+            return Ok(Value::Integer(integer));
+        }
+
+        return match literal.parse::<f64>() {
+            Ok(float) => Ok(Value::Float(float)),
+            Err(_) => Err(self.error(format!("invalid number \"{}\"", literal).as_str())),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Value {
+        return Value::String(s.to_string());
+    }
+
+    #[test]
+    fn values_of_every_kind() {
+        let value =
+            parse(r#" {"a": [1, -2.5, 1e3, true, false, null], "b": {"c": "d"}} "#).unwrap();
+        assert_eq!(
+            value,
+            Value::Table(vec![
+                (
+                    "a".to_string(),
+                    Value::Array(vec![
+                        Value::Integer(1),
+                        Value::Float(-2.5),
+                        Value::Float(1000.0),
+                        Value::Bool(true),
+                        Value::Bool(false),
+                        Value::Null,
+                    ])
+                ),
+                (
+                    "b".to_string(),
+                    Value::Table(vec![("c".to_string(), string("d"))])
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn entries_keep_document_order() {
+        let Value::Table(entries) = parse(r#"{"z": 1, "a": 2, "m": 3}"#).unwrap() else {
+            panic!("expected a table");
+        };
+        let keys: Vec<&str> = entries.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, ["z", "a", "m"]);
+    }
+
+    #[test]
+    fn integers_too_large_become_floats() {
+        assert_eq!(
+            parse("9223372036854775807").unwrap(),
+            Value::Integer(i64::MAX)
+        );
+        assert_eq!(
+            parse("9223372036854775808").unwrap(),
+            Value::Float(9223372036854775808.0)
+        );
+    }
+
+    #[test]
+    fn string_escapes() {
+        assert_eq!(
+            parse(r#""\"\\\/\b\f\n\r\t\u00e9""#).unwrap(),
+            string("\"\\/\u{8}\u{c}\n\r\té")
+        );
+        // Characters outside the BMP as surrogate pairs
+        assert_eq!(parse(r#""\ud83d\ude00""#).unwrap(), string("😀"));
+        assert!(parse(r#""\ud83d""#).is_err());
+        assert!(parse(r#""\ud83d\u0041""#).is_err());
+        assert!(parse(r#""\x""#).is_err());
+    }
+
+    #[test]
+    fn malformed_documents_report_their_line() {
+        for (text, line) in [
+            ("{\"a\": 1,\n\"b\" 2}", 2),
+            ("[1, 2", 1),
+            ("\"unterminated", 1),
+            ("\"tab\there\"", 1),
+            ("{}\n\n{}", 3),
+            ("[1.2.3]", 1),
+        ] {
+            match parse(text) {
+                Ok(value) => panic!("{:?} parsed as {:?}", text, value),
+                Err(error) => assert_eq!(error.line, line, "{:?}: {}", text, error),
+            }
+        }
+    }
+}
@@ -0,0 +1,2292 @@
+//! Generation of C++ headers embedding the contents of files, as done by the `bin2hpp` CLI
+//!
+//! Build scripts and other tooling can use [`Generator`] rather than running the binary:
+//!
+//! ```no_run
+//! bin2hpp::Generator::new("assets/logo.png")
+//!     .symbol("logo")
+//!     .namespace("assets")
+//!     .binary(true)
+//!     .write_to("include/logo.hpp")
+//!     .expect("failed to generate logo.hpp");
+//! ```
+
+#![allow(clippy::needless_return)]
+
+use std::{
+    borrow::Cow,
+    ffi::OsStr,
+    fmt,
+    fs::File,
+    io::{self, BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+
+use crate::{backend::Backend, checksum::ChecksumAlgorithm};
+
+pub mod amalgamate;
+pub mod asm;
+pub mod backend;
+pub mod bits;
+pub mod build;
+pub mod c;
+pub mod checksum;
+pub mod compress;
+pub mod cost;
+pub mod csharp;
+pub mod csv;
+pub mod dedupe;
+pub mod delta;
+pub mod embed;
+pub mod extract;
+pub mod gitignore;
+pub mod glob;
+pub mod guard;
+pub mod incbin;
+pub mod index;
+pub mod input;
+pub mod json;
+pub mod locale;
+pub mod manifest;
+pub mod metadata;
+pub mod mmap;
+pub mod obfuscate;
+pub mod object;
+pub mod output;
+pub mod python;
+pub mod rc;
+pub mod region;
+pub mod rust;
+pub mod section;
+pub mod split;
+pub mod stream;
+pub mod structured;
+pub mod template;
+pub mod testgen;
+pub mod toc;
+pub mod toml;
+pub mod watch;
+pub mod words;
+pub mod xxd;
+
+#[cfg(windows)]
+pub const LINE_ENDING: &str = "\r\n";
+#[cfg(not(windows))]
+pub const LINE_ENDING: &str = "\n";
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// Text mode was asked for but the input is not valid UTF-8
+    NotUtf8(PathBuf),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "{}", error),
+            Error::NotUtf8(path) => write!(
+                f,
+                "input file \"{}\" is not valid UTF-8, which text mode requires",
+                path.to_string_lossy()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        return Error::Io(error);
+    }
+}
+
+/// Builder for the header embedding one input, equivalent to running the CLI with `-i`, `-s`,
+/// `-n`, `-b` and `--comment`
+#[derive(Debug, Clone)]
+pub struct Generator {
+    input_path: PathBuf,
+    symbol_name: Option<String>,
+    ns_name: Option<String>,
+    binary: bool,
+    comment: Option<String>,
+}
+
+impl Generator {
+    /// Embed the file at this path, in text mode and with the symbol name derived from its
+    /// filename unless told otherwise
+    pub fn new(input_path: impl Into<PathBuf>) -> Generator {
+        return Generator {
+            input_path: input_path.into(),
+            symbol_name: None,
+            ns_name: None,
+            binary: false,
+            comment: None,
+        };
+    }
+
+    /// Name of the C++ symbol
+    pub fn symbol(mut self, symbol_name: impl Into<String>) -> Generator {
+        self.symbol_name = Some(symbol_name.into());
+        return self;
+    }
+
+    /// Namespace in which to put the symbol
+    pub fn namespace(mut self, ns_name: impl Into<String>) -> Generator {
+        self.ns_name = Some(ns_name.into());
+        return self;
+    }
+
+    /// Whether to embed the input as bytes rather than as a string literal
+    pub fn binary(mut self, binary: bool) -> Generator {
+        self.binary = binary;
+        return self;
+    }
+
+    /// Documentation comment emitted above the symbol
+    pub fn comment(mut self, comment: impl Into<String>) -> Generator {
+        self.comment = Some(comment.into());
+        return self;
+    }
+
+    /// Generate the header, returning its source
+    pub fn generate(&self) -> Result<String, Error> {
+        let data = read_file(&File::open(&self.input_path)?)?;
+        let representation = match self.binary {
+            true => Representation::BraceInitialiser,
+            false if std::str::from_utf8(&data).is_ok() => Representation::StringLiteral,
+            false => return Err(Error::NotUtf8(self.input_path.clone())),
+        };
+
+        let symbol_name = match self.symbol_name {
+            Some(ref s) => s.clone(),
+            None => sanitise_os_identifier(self.input_path.file_name().unwrap_or_default()),
+        };
+        let options = SourceOptions {
+            language: Language::Cpp,
+            ns_name: self.ns_name.clone(),
+            guard_macro: None,
+            loader_path: None,
+            verifier: None,
+            incbin_path: None,
+            embed_path: None,
+            input_offset: None,
+            delta: None,
+            stream_accessor: false,
+            span_accessor: false,
+            comment: self.comment.clone(),
+            char_type: CharType::Char,
+            text_view: false,
+            raw_text: false,
+            max_literal_len: DEFAULT_MAX_LITERAL_LEN,
+            internal_linkage: false,
+            extern_data: false,
+            align: None,
+            section: None,
+            null_terminate: false,
+            size_constant: false,
+            checksums: Vec::new(),
+            checksum_hex: false,
+            assert_size: None,
+            assert_size_max: None,
+            element_type: ElementType::Uint8,
+            xor_key: None,
+            byte_format: ByteFormat::default(),
+        };
+        let src = guard::wrap(
+            &generate_src(&data, representation, &symbol_name, &options),
+            guard::GuardStyle::Pragma,
+            "",
+        );
+
+        return Ok(metadata::prepend(
+            &src,
+            &metadata::Metadata {
+                arguments: &self.arguments(),
+                source: &self.input_path.to_string_lossy(),
+                size: data.len(),
+                sha256: checksum::sha256(&data),
+                symbols: &[symbol_name],
+                language: options.language,
+                banner: None,
+                provenance: true,
+            },
+        ));
+    }
+
+    /// Generate the header and write it to a file, replacing it atomically if it already exists
+    pub fn write_to(&self, output_path: impl AsRef<Path>) -> Result<(), Error> {
+        let src = self.generate()?;
+        output::write_atomically(output_path.as_ref(), src.as_bytes())?;
+        return Ok(());
+    }
+
+    /// The CLI arguments which would generate the same header, recorded in the metadata block
+    fn arguments(&self) -> Vec<String> {
+        let mut arguments = vec![
+            "-i".to_string(),
+            self.input_path.to_string_lossy().to_string(),
+        ];
+        if let Some(ref symbol_name) = self.symbol_name {
+            arguments.extend(["-s".to_string(), symbol_name.clone()]);
+        }
+        if let Some(ref ns_name) = self.ns_name {
+            arguments.extend(["-n".to_string(), ns_name.clone()]);
+        }
+        if self.binary {
+            arguments.push("-b".to_string());
+        }
+        if let Some(ref comment) = self.comment {
+            arguments.extend(["--comment".to_string(), comment.clone()]);
+        }
+
+        return arguments;
+    }
+}
+
+/// Read the whole of an input, which need not be a regular file
+pub fn read_file(f: &File) -> io::Result<Vec<u8>> {
+    // Only regular files have a meaningful length, pipes and devices report zero (or nonsense) so
+    // the buffer is just grown as data arrives
+    let buf_size: u64 = match f.metadata() {
+        Ok(metadata) if metadata.is_file() => metadata.len(),
+        _ => 0x1000, // just preallocate 4 KiB otherwise
+    };
+    let mut buf: Vec<u8> = Vec::with_capacity(buf_size as usize);
+
+    let mut reader = BufReader::new(f);
+    reader.read_to_end(&mut buf)?;
+
+    return Ok(buf);
+}
+
+/// Replace every character which cannot appear in a C++ identifier with an underscore
+pub fn sanitise_identifier(name: &str) -> String {
+    return name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+}
+
+/// Like `sanitise_identifier`, but for filenames which need not be valid Unicode
+///
+/// Rather than every invalid sequence collapsing into the same `_` (and distinct files colliding
+/// on one symbol), each stray byte becomes `_xHH` on Unix and each unpaired surrogate `_uHHHH`
+/// on Windows.
+pub fn sanitise_os_identifier(name: &OsStr) -> String {
+    if let Some(name) = name.to_str() {
+        return sanitise_identifier(name);
+    }
+
+    let mut sanitised = String::with_capacity(name.len() * 2);
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        for chunk in name.as_bytes().utf8_chunks() {
+            sanitised.push_str(&sanitise_identifier(chunk.valid()));
+            for b in chunk.invalid() {
+                sanitised.push_str(format!("_x{:02x}", b).as_str());
+            }
+        }
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::OsStrExt;
+
+        for unit in char::decode_utf16(name.encode_wide()) {
+            match unit {
+                Ok(c) => sanitised.push(if c.is_ascii_alphanumeric() { c } else { '_' }),
+                Err(error) => {
+                    sanitised.push_str(format!("_u{:04x}", error.unpaired_surrogate()).as_str())
+                }
+            }
+        }
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        sanitised.push_str(&sanitise_identifier(&name.to_string_lossy()));
+    }
+
+    return sanitised;
+}
+
+/// Case in which symbol names derived from filenames are written (`--symbol-case`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SymbolCase {
+    /// `logo_png`
+    Snake,
+    /// `logoPng`
+    Camel,
+    /// `LogoPng`
+    Pascal,
+    /// `LOGO_PNG`
+    Screaming,
+}
+
+impl SymbolCase {
+    /// Write a sanitised identifier in this case, taking its words to be separated by
+    /// underscores or to start at an upper case letter following a lower case one or a digit
+    pub fn apply(self, identifier: &str) -> String {
+        let mut words: Vec<String> = Vec::new();
+        for part in identifier.split('_').filter(|part| !part.is_empty()) {
+            let mut word = String::new();
+            let mut previous: Option<char> = None;
+            for c in part.chars() {
+                if c.is_ascii_uppercase()
+                    && previous.is_some_and(|p| p.is_ascii_lowercase() || p.is_ascii_digit())
+                {
+                    words.push(std::mem::take(&mut word));
+                }
+                word.push(c);
+                previous = Some(c);
+            }
+            words.push(word);
+        }
+        // An identifier made of underscores alone has no words to change the case of
+        if words.is_empty() {
+            return identifier.to_string();
+        }
+
+        let capitalise = |word: &str| {
+            let lower = word.to_ascii_lowercase();
+            return lower[..1].to_ascii_uppercase() + &lower[1..];
+        };
+        return match self {
+            SymbolCase::Snake => words.join("_").to_ascii_lowercase(),
+            SymbolCase::Screaming => words.join("_").to_ascii_uppercase(),
+            SymbolCase::Camel => words
+                .iter()
+                .enumerate()
+                .map(|(position, word)| match position {
+                    0 => word.to_ascii_lowercase(),
+                    _ => capitalise(word),
+                })
+                .collect(),
+            SymbolCase::Pascal => words.iter().map(|word| capitalise(word)).collect(),
+        };
+    }
+}
+
+/// Why a symbol derived from a filename cannot be used as it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidIdentifier {
+    /// It starts with a digit (or is empty)
+    LeadingDigit,
+    /// It is a keyword of the language
+    Keyword,
+    /// It is reserved for the implementation, as C and C++ reserve those beginning with an
+    /// underscore or containing two in a row
+    Reserved,
+}
+
+impl fmt::Display for InvalidIdentifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidIdentifier::LeadingDigit => write!(f, "does not start with a letter"),
+            InvalidIdentifier::Keyword => write!(f, "is a keyword"),
+            InvalidIdentifier::Reserved => write!(f, "is reserved for the implementation"),
+        }
+    }
+}
+
+/// Make a symbol derived from a filename valid in the language of a backend, returning the
+/// symbol to use instead and why, if it is not valid as it is
+///
+/// Reserved identifiers lose their leading underscores and have runs of them collapsed into one,
+/// those starting with a digit are prefixed with `file_` (written in the case of the rest), and
+/// keywords are suffixed with an underscore.
+pub fn mangle_identifier(
+    identifier: &str,
+    backend: &dyn Backend,
+) -> Option<(String, InvalidIdentifier)> {
+    let mut mangled = identifier.to_string();
+    let mut reason = None;
+    if backend.reserves_underscores() && (mangled.starts_with('_') || mangled.contains("__")) {
+        let mut collapsed = String::with_capacity(mangled.len());
+        for c in mangled.chars() {
+            if c != '_' || !(collapsed.is_empty() || collapsed.ends_with('_')) {
+                collapsed.push(c);
+            }
+        }
+        mangled = collapsed;
+        reason = Some(InvalidIdentifier::Reserved);
+    }
+    if mangled.is_empty() || mangled.starts_with(|c: char| c.is_ascii_digit()) {
+        let prefix = if !mangled.chars().any(|c| c.is_ascii_lowercase()) {
+            "FILE_"
+        } else if !mangled.contains('_') && mangled.chars().any(|c| c.is_ascii_uppercase()) {
+            "File"
+        } else {
+            "file_"
+        };
+        mangled.insert_str(0, prefix);
+        reason.get_or_insert(InvalidIdentifier::LeadingDigit);
+    }
+    if backend.keywords().contains(&mangled.as_str()) {
+        mangled.push('_');
+        reason.get_or_insert(InvalidIdentifier::Keyword);
+    }
+
+    return reason.map(|reason| (mangled, reason));
+}
+
+/// Placeholder contents used in stub mode: a single zero byte for arrays, nothing for strings
+pub fn stub_data(binary: bool) -> Vec<u8> {
+    if binary {
+        return vec![0];
+    }
+
+    return Vec::new();
+}
+
+/// Bytes of input formatted at a time before being written out
+const FORMAT_CHUNK_LEN: usize = 0x1_0000;
+
+/// Inputs larger than this are formatted on several threads (4 MiB)
+const PARALLEL_FORMAT_THRESHOLD: usize = 0x40_0000;
+
+/// Bytes of input formatted by each thread at a time when formatting in parallel (1 MiB)
+const PARALLEL_CHUNK_LEN: usize = 0x10_0000;
+
+/// Stands in for the data in the source generated by `write_src`, which cannot otherwise contain
+/// a NUL
+pub(crate) const DATA_MARKER: &str = "\0bin2hpp:data\0";
+
+/// Default number of elements on each line of an array initialiser list
+pub const DEFAULT_BYTES_PER_LINE: usize = 16;
+
+/// Default indentation of each line of elements
+pub const DEFAULT_INDENT: &str = "    ";
+
+/// Base in which the elements of array initialiser lists are written (`--radix`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Radix {
+    /// `0xab`
+    Hex,
+    /// `171`
+    Dec,
+    /// `0b10101011`, which needs C++14
+    Bin,
+    /// `0253`
+    Oct,
+}
+
+/// How the elements of an array initialiser list are laid out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ByteFormat {
+    /// Number of elements on each line, or 0 to keep every element on the declaration's line
+    pub bytes_per_line: usize,
+    /// Whitespace before each line of elements
+    pub indent: String,
+    pub radix: Radix,
+    /// Whether hex digits are uppercase (`0xAB`)
+    pub uppercase: bool,
+    /// Whether every element has the same number of digits (`0x0a` rather than `0xa`), which
+    /// decimal elements get by being padded with spaces instead since a leading zero would make
+    /// them octal
+    pub zero_pad: bool,
+    /// Whether elements on the same line are separated by a space as well as a comma
+    pub space_after_comma: bool,
+}
+
+impl ByteFormat {
+    /// Every element on the declaration's line, for small tables generated alongside the data
+    pub fn compact() -> ByteFormat {
+        return ByteFormat {
+            bytes_per_line: 0,
+            indent: String::new(),
+            ..ByteFormat::default()
+        };
+    }
+}
+
+impl Default for ByteFormat {
+    fn default() -> ByteFormat {
+        return ByteFormat {
+            bytes_per_line: DEFAULT_BYTES_PER_LINE,
+            indent: DEFAULT_INDENT.to_string(),
+            radix: Radix::Hex,
+            uppercase: false,
+            zero_pad: false,
+            space_after_comma: false,
+        };
+    }
+}
+
+/// Longest literal a byte is formatted as (`0b` followed by eight binary digits, cast to
+/// `std::int8_t`)
+const MAX_LITERAL_LEN: usize = 23;
+
+/// Every byte as a literal, padded to `MAX_LITERAL_LEN` bytes, along with the literal's length
+type ElementLiterals = [([u8; MAX_LITERAL_LEN], usize); 256];
+
+/// Precompute the literal of every byte in the given format, cast to the element type where it
+/// has to be, so that formatting the data is just a matter of copying them
+fn element_literals(format: &ByteFormat, element_type: ElementType) -> ElementLiterals {
+    let digit_chars: &[u8; 16] = match format.uppercase {
+        true => b"0123456789ABCDEF",
+        false => b"0123456789abcdef",
+    };
+    let (prefix, radix, width): (&[u8], usize, usize) = match format.radix {
+        Radix::Hex => (b"0x", 16, 2),
+        Radix::Dec => (b"", 10, 3),
+        Radix::Bin => (b"0b", 2, 8),
+        Radix::Oct => (b"0", 8, 3),
+    };
+
+    let mut literals = [([0; MAX_LITERAL_LEN], 0); 256];
+    for (b, (literal, len)) in literals.iter_mut().enumerate() {
+        let cast = element_type.needs_cast(b as u8);
+        *len = 0;
+        if cast {
+            for &c in element_type.name().as_bytes().iter().chain(b"(") {
+                literal[*len] = c;
+                *len += 1;
+            }
+        }
+
+        // The octal prefix is itself a zero
+        if format.radix == Radix::Oct && b == 0 && !format.zero_pad {
+            literal[*len] = b'0';
+            *len += 1;
+            if cast {
+                literal[*len] = b')';
+                *len += 1;
+            }
+            continue;
+        }
+
+        let mut digits = [0; 8];
+        let mut digit_count = 0;
+        let mut value = b;
+        loop {
+            digits[digit_count] = digit_chars[value % radix];
+            digit_count += 1;
+            value /= radix;
+            if value == 0 {
+                break;
+            }
+        }
+
+        literal[*len..*len + prefix.len()].copy_from_slice(prefix);
+        *len += prefix.len();
+        if format.zero_pad {
+            let padding = match format.radix {
+                Radix::Dec => b' ',
+                _ => b'0',
+            };
+            for _ in digit_count..width {
+                literal[*len] = padding;
+                *len += 1;
+            }
+        }
+        for &digit in digits[..digit_count].iter().rev() {
+            literal[*len] = digit;
+            *len += 1;
+        }
+        if cast {
+            literal[*len] = b')';
+            *len += 1;
+        }
+    }
+
+    return literals;
+}
+
+/// Format a slice of bytes into an array-of-bytes initialiser list, all on one line
+pub fn format_as_binary(data: &[u8]) -> String {
+    return format_as_binary_with(data, &ByteFormat::compact());
+}
+
+/// Format a slice of bytes into an array-of-bytes initialiser list laid out as given
+pub fn format_as_binary_with(data: &[u8], format: &ByteFormat) -> String {
+    return format_as_elements(data, format, ElementType::Uint8);
+}
+
+/// Format a slice of bytes into the initialiser list of an array of the given element type, laid
+/// out as given
+pub fn format_as_elements(data: &[u8], format: &ByteFormat, element_type: ElementType) -> String {
+    let mut formatted = Vec::with_capacity(data.len() * 5);
+    write_as_binary(&mut formatted, data, format, element_type)
+        .expect("writing to a Vec cannot fail");
+
+    return String::from_utf8(formatted).expect("formatted data is valid UTF-8");
+}
+
+/// Write a slice of bytes as an array-of-bytes initialiser list
+///
+/// When wrapping, every line of elements (including the first) starts on a new line and the
+/// list ends with one, so the braces around it sit on the declaration's lines.
+fn write_as_binary<W: io::Write + ?Sized>(
+    out: &mut W,
+    data: &[u8],
+    format: &ByteFormat,
+    element_type: ElementType,
+) -> io::Result<()> {
+    let literals = element_literals(format, element_type);
+    write_formatted(out, data, |formatted, chunk, offset| {
+        format_binary_chunk(formatted, chunk, offset, format, &literals)
+    })?;
+    if format.bytes_per_line > 0 && !data.is_empty() {
+        out.write_all(LINE_ENDING.as_bytes())?;
+    }
+
+    return Ok(());
+}
+
+/// Format part of the data which starts `offset` bytes into it as initialiser list elements
+fn format_binary_chunk(
+    formatted: &mut Vec<u8>,
+    data: &[u8],
+    offset: usize,
+    format: &ByteFormat,
+    literals: &ElementLiterals,
+) {
+    formatted.reserve(data.len() * (MAX_LITERAL_LEN + 2));
+    for (index, &b) in (offset..).zip(data) {
+        let wraps = format.bytes_per_line > 0 && index % format.bytes_per_line == 0;
+        if index > 0 {
+            formatted.push(b',');
+            if format.space_after_comma && !wraps {
+                formatted.push(b' ');
+            }
+        }
+        if wraps {
+            formatted.extend_from_slice(LINE_ENDING.as_bytes());
+            formatted.extend_from_slice(format.indent.as_bytes());
+        }
+        let (literal, len) = &literals[usize::from(b)];
+        formatted.extend_from_slice(&literal[..*len]);
+    }
+}
+
+/// Format a slice of arbitrary bytes into a string literal (without quotes), escaping anything
+/// which is not printable ASCII
+pub fn format_as_escaped(data: &[u8]) -> String {
+    return format_as_escaped_split(data, 0);
+}
+
+/// Like `format_as_escaped`, but closing the literal and opening another on the next line every
+/// `max_literal_len` bytes (unless it is 0)
+pub fn format_as_escaped_split(data: &[u8], max_literal_len: usize) -> String {
+    let mut formatted = Vec::with_capacity(data.len() * 4);
+    write_as_escaped(&mut formatted, data, max_literal_len).expect("writing to a Vec cannot fail");
+
+    return String::from_utf8(formatted).expect("formatted data is ASCII");
+}
+
+/// Format a slice of arbitrary bytes into a Rust or Python byte string literal (without quotes),
+/// in which only printable ASCII can appear as it is and hex escapes are always two digits
+pub fn format_as_byte_string(data: &[u8]) -> String {
+    let mut formatted = String::with_capacity(data.len() * 2);
+    for &byte in data {
+        match byte {
+            b'"' => formatted.push_str("\\\""),
+            b'\\' => formatted.push_str("\\\\"),
+            b'\n' => formatted.push_str("\\n"),
+            b'\r' => formatted.push_str("\\r"),
+            b'\t' => formatted.push_str("\\t"),
+            b' '..=b'~' => formatted.push(byte as char),
+            _ => formatted.push_str(format!("\\x{:02x}", byte).as_str()),
+        }
+    }
+
+    return formatted;
+}
+
+/// Write a slice of arbitrary bytes as the contents of a string literal, split every
+/// `max_literal_len` bytes as for `format_as_escaped_split`
+fn write_as_escaped<W: io::Write + ?Sized>(
+    out: &mut W,
+    data: &[u8],
+    max_literal_len: usize,
+) -> io::Result<()> {
+    return write_formatted(out, data, |formatted, chunk, offset| {
+        format_escaped_chunk(formatted, chunk, offset, max_literal_len)
+    });
+}
+
+/// Format part of the data which starts `offset` bytes into it as the contents of string literals
+fn format_escaped_chunk(
+    formatted: &mut Vec<u8>,
+    data: &[u8],
+    offset: usize,
+    max_literal_len: usize,
+) {
+    for (index, &b) in (offset..).zip(data) {
+        if max_literal_len > 0 && index > 0 && index % max_literal_len == 0 {
+            formatted.push(b'"');
+            formatted.extend_from_slice(LINE_ENDING.as_bytes());
+            formatted.push(b'"');
+        }
+        match b {
+            b'"' | b'\\' | b'?' => formatted.extend_from_slice(&[b'\\', b]),
+            // Octal escapes never exceed three digits, unlike hex escapes which would consume any
+            // hex digit following them
+            0x20..=0x7e => formatted.push(b),
+            _ => formatted.extend_from_slice(&[
+                b'\\',
+                b'0' + (b >> 6),
+                b'0' + ((b >> 3) & 7),
+                b'0' + (b & 7),
+            ]),
+        }
+    }
+}
+
+/// Format data a chunk at a time with the given formatter, writing each chunk out in order
+///
+/// Large inputs are split between as many threads as there are cores, a batch of one chunk per
+/// thread at a time so that only a few chunks are ever held in memory.
+fn write_formatted<W, F>(out: &mut W, data: &[u8], format_chunk: F) -> io::Result<()>
+where
+    W: io::Write + ?Sized,
+    F: Fn(&mut Vec<u8>, &[u8], usize) + Sync,
+{
+    let thread_count = match data.len() > PARALLEL_FORMAT_THRESHOLD {
+        true => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        false => 1,
+    };
+
+    return write_formatted_on(out, data, thread_count, format_chunk);
+}
+
+/// Format data as `write_formatted` does, on the given number of threads (one formatting it all
+/// in order)
+fn write_formatted_on<W, F>(
+    out: &mut W,
+    data: &[u8],
+    thread_count: usize,
+    format_chunk: F,
+) -> io::Result<()>
+where
+    W: io::Write + ?Sized,
+    F: Fn(&mut Vec<u8>, &[u8], usize) + Sync,
+{
+    if thread_count == 1 {
+        let mut formatted = Vec::with_capacity(FORMAT_CHUNK_LEN.min(data.len()) * 5);
+        for (i, chunk) in data.chunks(FORMAT_CHUNK_LEN).enumerate() {
+            formatted.clear();
+            format_chunk(&mut formatted, chunk, i * FORMAT_CHUNK_LEN);
+            out.write_all(&formatted)?;
+        }
+        return Ok(());
+    }
+
+    let batch_len = PARALLEL_CHUNK_LEN * thread_count;
+    let mut buffers: Vec<Vec<u8>> = vec![Vec::new(); thread_count];
+    for (batch_index, batch) in data.chunks(batch_len).enumerate() {
+        let chunk_count = batch.len().div_ceil(PARALLEL_CHUNK_LEN);
+        std::thread::scope(|scope| {
+            for (i, (chunk, formatted)) in batch
+                .chunks(PARALLEL_CHUNK_LEN)
+                .zip(buffers.iter_mut())
+                .enumerate()
+            {
+                let offset = batch_index * batch_len + i * PARALLEL_CHUNK_LEN;
+                let format_chunk = &format_chunk;
+                scope.spawn(move || {
+                    formatted.clear();
+                    format_chunk(formatted, chunk, offset);
+                });
+            }
+        });
+        for formatted in &buffers[..chunk_count] {
+            out.write_all(formatted)?;
+        }
+    }
+
+    return Ok(());
+}
+
+/// Format a slice of bytes into a string literal (without quotes)
+///
+/// Characters outside printable ASCII are written as octal escapes of their UTF-8 bytes, which
+/// is what both plain and `u8` literals hold whatever the compiler's source character set. Bytes
+/// which are not valid UTF-8 are written as octal escapes too, so the data comes through as it is
+/// (hex escapes would swallow any hex digits following them).
+pub fn format_as_text(data: &[u8]) -> String {
+    let mut formatted = String::with_capacity(data.len() + data.len() / 8);
+    for chunk in data.utf8_chunks() {
+        push_text(&mut formatted, chunk.valid());
+        for b in chunk.invalid() {
+            formatted.push_str(format!("\\{:03o}", b).as_str());
+        }
+    }
+
+    return formatted;
+}
+
+/// MSVC rejects string literals longer than this many bytes before adjacent literals are joined
+/// (error C2026)
+pub const DEFAULT_MAX_LITERAL_LEN: usize = 16380;
+
+/// Split data into pieces of at most `max_len` bytes (unless it is 0) for separate literals,
+/// moving each split back to the start of any UTF-8 sequence it would fall in
+pub(crate) fn literal_pieces(data: &[u8], max_len: usize) -> Vec<&[u8]> {
+    if max_len == 0 || data.len() <= max_len {
+        return vec![data];
+    }
+
+    let mut pieces = Vec::with_capacity(data.len() / max_len + 1);
+    let mut rest = data;
+    while rest.len() > max_len {
+        let mut split = max_len;
+        // UTF-8 sequences are at most four bytes long, the first of which is not a continuation
+        while split > max_len.saturating_sub(3).max(1) && rest[split] & 0xc0 == 0x80 {
+            split -= 1;
+        }
+        let (piece, remainder) = rest.split_at(split);
+        pieces.push(piece);
+        rest = remainder;
+    }
+    pieces.push(rest);
+
+    return pieces;
+}
+
+/// Format text as adjacent string literals (with quotes) on lines of their own, each holding at
+/// most `max_literal_len` bytes of it (unless it is 0)
+pub fn format_as_text_literals(data: &[u8], char_type: CharType, max_literal_len: usize) -> String {
+    let literals: Vec<String> = literal_pieces(data, max_literal_len)
+        .into_iter()
+        .map(|piece| string_literal(&format_as_text(piece), char_type))
+        .collect();
+
+    return literals.join(LINE_ENDING);
+}
+
+/// Push valid text into a string literal, escaped as described for `format_as_text`
+///
+/// A `?` following another is escaped too, so that text such as `??=` does not become a trigraph
+/// where they are still translated (before C++17, or with `-trigraphs`).
+fn push_text(formatted: &mut String, text: &str) {
+    let mut previous = None;
+    for c in text.chars() {
+        match c {
+            '?' if previous == Some('?') => formatted.push_str("\\?"),
+            '\t' => formatted.push_str("\\t"),
+            '\r' => formatted.push_str("\\r"),
+            '\n' => formatted.push_str("\\n"),
+            '\'' | '"' | '\\' => {
+                formatted.push('\\');
+                formatted.push(c);
+            }
+            ' '..='~' => formatted.push(c),
+            _ => {
+                let mut encoded = [0; 4];
+                for b in c.encode_utf8(&mut encoded).bytes() {
+                    formatted.push_str(format!("\\{:03o}", b).as_str());
+                }
+            }
+        }
+        previous = Some(c);
+    }
+}
+
+/// Delimiter of raw string literals, numbered if the text itself contains `)bin2hpp"`
+const RAW_DELIMITER: &str = "bin2hpp";
+
+/// Format a slice of bytes into raw string literals (`R"bin2hpp(...)bin2hpp"`, with quotes), so
+/// that text such as shaders or SQL stays readable in the header
+///
+/// Control characters other than tabs and newlines cannot be written raw (compilers translate
+/// carriage returns, and the others are unreadable anyway), so they are written as escaped literals
+/// between the raw ones. Other characters are written as they are, so the header must be compiled
+/// as UTF-8 (the default of GCC and Clang, `/utf-8` on MSVC). As with `format_as_text_literals`,
+/// the literals are split onto lines holding at most `max_literal_len` bytes each.
+pub fn format_as_raw(data: &[u8], char_type: CharType, max_literal_len: usize) -> String {
+    let text = String::from_utf8_lossy(data);
+    let mut delimiter = RAW_DELIMITER.to_string();
+    let mut suffix = 0;
+    while text.contains(format!("){}\"", delimiter).as_str()) {
+        suffix += 1;
+        delimiter = format!("{}{}", RAW_DELIMITER, suffix);
+    }
+
+    // Adjacent literals are separated by spaces, as a literal directly followed by `R` or `u8R`
+    // would read as a user-defined literal
+    let prefix = char_type.literal_prefix();
+    let mut lines = Vec::new();
+    for piece in literal_pieces(data, max_literal_len) {
+        let mut literals = Vec::new();
+        for chunk in piece.utf8_chunks() {
+            let mut rest = chunk.valid();
+            while !rest.is_empty() {
+                let escaped = |c: char| c.is_control() && c != '\t' && c != '\n';
+                let raw_len = rest.find(escaped).unwrap_or(rest.len());
+                if raw_len > 0 {
+                    literals.push(format!(
+                        "{0}R\"{1}({2}){1}\"",
+                        prefix,
+                        delimiter,
+                        &rest[..raw_len]
+                    ));
+                }
+                rest = &rest[raw_len..];
+
+                let escaped_len = rest.find(|c| !escaped(c)).unwrap_or(rest.len());
+                if escaped_len > 0 {
+                    literals.push(string_literal(
+                        &format_as_text(&rest.as_bytes()[..escaped_len]),
+                        char_type,
+                    ));
+                }
+                rest = &rest[escaped_len..];
+            }
+
+            // Bytes which are not valid UTF-8 can only be escaped
+            if !chunk.invalid().is_empty() {
+                literals.push(string_literal(&format_as_text(chunk.invalid()), char_type));
+            }
+        }
+        if literals.is_empty() {
+            literals.push(string_literal("", char_type));
+        }
+        lines.push(literals.join(" "));
+    }
+
+    return lines.join(LINE_ENDING);
+}
+
+/// How the data is represented in the generated source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Representation {
+    /// `std::array` with a brace initialiser list (binary mode)
+    BraceInitialiser,
+    /// `std::string_view` over an escaped string literal, which is much cheaper to compile than a
+    /// brace initialiser (binary mode)
+    EscapedStringLiteral,
+    /// `const char*` string literal (text mode)
+    StringLiteral,
+    /// `std::array` of 64-bit words with the data packed bit by bit (`--encode bits`)
+    PackedBits,
+    /// Reference to a `std::array` included by the assembler, with a brace initialiser fallback
+    /// (`--encode incbin`)
+    Incbin,
+    /// Delta against another file and a function applying it (`--delta-base`)
+    Delta,
+    /// `std::array` filled by the preprocessor with `#embed`, with a brace initialiser fallback
+    /// (`--encode embed`)
+    Embed,
+    /// `std::array` of 16, 32 or 64-bit words with the data packed into them in either byte order
+    /// (`--encode u16`, `u32` or `u64`)
+    Words(words::WordLayout),
+    /// `std::array` of the data compressed with the given algorithm, and functions decompressing
+    /// it at runtime (`--compress`)
+    Compressed(compress::CompressionAlgorithm),
+}
+
+/// Inputs larger than this are emitted as escaped string literals by `--auto-optimize` (1 MiB)
+pub const AUTO_STRING_LITERAL_THRESHOLD: u64 = 0x10_0000;
+
+/// Pick the cheapest representation of binary data to compile for the given input size
+pub fn select_representation(input_len: u64) -> Representation {
+    if input_len > AUTO_STRING_LITERAL_THRESHOLD {
+        return Representation::EscapedStringLiteral;
+    }
+
+    return Representation::BraceInitialiser;
+}
+
+/// Language of the generated header (`--language`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Language {
+    Cpp,
+    /// C99, in which only byte arrays and string literals can be generated (other
+    /// representations are still generated as C++)
+    C,
+    /// A Rust module declaring the data as `pub const` byte slices, string slices or
+    /// `include_bytes!` of the input
+    Rust,
+    /// A C# file declaring the data in a static class named after the symbol, as a `byte[]` or
+    /// (with `--span-accessor`) a `ReadOnlySpan<byte>` property, or text as a `string` constant
+    #[value(name = "csharp")]
+    CSharp,
+    /// A Python module declaring the data as a `bytes` constant and its size, for tooling which
+    /// must see exactly the bytes embedded elsewhere
+    Python,
+}
+
+impl Language {
+    /// Backend generating the language
+    pub fn backend(self) -> &'static dyn Backend {
+        return match self {
+            Language::Cpp => &CppBackend,
+            Language::C => &c::CBackend,
+            Language::Rust => &rust::RustBackend,
+            Language::CSharp => &csharp::CSharpBackend,
+            Language::Python => &python::PythonBackend,
+        };
+    }
+
+    /// Extension of the files generated in the language
+    pub fn extension(self) -> &'static str {
+        return self.backend().extension();
+    }
+
+    pub fn name(self) -> &'static str {
+        return match self {
+            Language::Cpp => "cpp",
+            Language::C => "c",
+            Language::Rust => "rust",
+            Language::CSharp => "csharp",
+            Language::Python => "python",
+        };
+    }
+
+    /// What starts a comment running to the end of the line
+    pub fn line_comment(self) -> &'static str {
+        return self.backend().line_comment();
+    }
+}
+
+/// Character type of text embedded as a string literal (`--char-type`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CharType {
+    /// `const char*` and `std::string_view`
+    Char,
+    /// `const char8_t*` and `std::u8string_view` over `u8` literals, for C++20 code with
+    /// `char8_t`
+    U8,
+}
+
+impl CharType {
+    pub fn name(self) -> &'static str {
+        return match self {
+            CharType::Char => "char",
+            CharType::U8 => "char8_t",
+        };
+    }
+
+    /// Encoding prefix of string literals of this type
+    pub fn literal_prefix(self) -> &'static str {
+        return match self {
+            CharType::Char => "",
+            CharType::U8 => "u8",
+        };
+    }
+
+    /// View over strings of this type
+    pub fn view_type(self) -> &'static str {
+        return match self {
+            CharType::Char => "std::string_view",
+            CharType::U8 => "std::u8string_view",
+        };
+    }
+}
+
+/// Type of the elements of C++ byte arrays (`--element-type`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ElementType {
+    /// `std::uint8_t`
+    #[default]
+    #[value(name = "uint8_t", alias = "std::uint8_t")]
+    Uint8,
+    /// `unsigned char`
+    #[value(name = "unsigned-char", alias = "unsigned char")]
+    UnsignedChar,
+    /// `char`, for APIs taking `const char*`, whose bytes above 0x7f are cast as it may be signed
+    Char,
+    /// `std::int8_t`, whose bytes above 0x7f are cast
+    #[value(name = "int8_t", alias = "std::int8_t")]
+    Int8,
+    /// `std::byte` (C++17), every element of which is cast
+    #[value(name = "byte", alias = "std::byte")]
+    Byte,
+}
+
+impl ElementType {
+    pub fn name(self) -> &'static str {
+        return match self {
+            ElementType::Uint8 => "std::uint8_t",
+            ElementType::UnsignedChar => "unsigned char",
+            ElementType::Char => "char",
+            ElementType::Int8 => "std::int8_t",
+            ElementType::Byte => "std::byte",
+        };
+    }
+
+    /// Whether an element holding this byte has to be cast from its literal, which is an `int`
+    fn needs_cast(self, b: u8) -> bool {
+        return match self {
+            ElementType::Uint8 | ElementType::UnsignedChar => false,
+            ElementType::Char | ElementType::Int8 => b > 0x7f,
+            ElementType::Byte => true,
+        };
+    }
+}
+
+/// Options affecting the generated source, shared by the array and string generators
+pub struct SourceOptions {
+    pub language: Language,
+    /// Namespace in which to put the symbol
+    pub ns_name: Option<String>,
+    /// Macro which must be defined for the real data to be embedded
+    pub guard_macro: Option<String>,
+    /// Path from which debug builds load the data at runtime
+    pub loader_path: Option<String>,
+    /// Checksum checked by a generated `<symbol>_verify()` function
+    pub verifier: Option<Verifier>,
+    /// Absolute path of the input, included by the assembler (`--encode incbin`)
+    pub incbin_path: Option<String>,
+    /// Path of the input relative to the header, embedded by the preprocessor (`--encode embed`)
+    pub embed_path: Option<String>,
+    /// Offset into the input file of the data when only part of it is embedded (`--offset` and
+    /// `--length`), from which the assembler includes just the data
+    pub input_offset: Option<usize>,
+    /// Base the data is stored as a delta against (`--delta-base`)
+    pub delta: Option<delta::DeltaBase>,
+    /// Whether to generate a `<symbol>_stream()` function
+    pub stream_accessor: bool,
+    /// Whether to hide the array in a detail namespace behind a `<symbol>()` function returning a
+    /// `std::span` over it (array representations only)
+    pub span_accessor: bool,
+    /// Documentation comment for the symbol
+    pub comment: Option<String>,
+    /// Character type of string literals
+    pub char_type: CharType,
+    /// Whether text is declared as a `std::string_view` of explicit length rather than as a
+    /// `const char*`, keeping any NUL bytes and its length
+    pub text_view: bool,
+    /// Whether text is written as raw string literals rather than escaped ones
+    pub raw_text: bool,
+    /// String literals longer than this many bytes are split into adjacent literals (0 for no
+    /// limit)
+    pub max_literal_len: usize,
+    /// Whether the symbols are put in an anonymous namespace, giving them internal linkage
+    pub internal_linkage: bool,
+    /// Whether the data is only declared `extern`, leaving its definition to a separate source
+    /// file (`split::write_definition_src`)
+    pub extern_data: bool,
+    /// Alignment of the array in bytes (`alignas`), for data which needs more than the alignment
+    /// of its elements
+    pub align: Option<usize>,
+    /// Linker section the array is placed in
+    pub section: Option<String>,
+    /// Whether byte arrays are followed by a NUL element, which `<symbol>_size` does not count
+    pub null_terminate: bool,
+    /// Whether a `<symbol>_size` constant holding the size of the data in bytes is declared next
+    /// to it (as it always is with `null_terminate`)
+    pub size_constant: bool,
+    /// Checksums of the data declared as `<symbol>_<algorithm>` constants next to it
+    pub checksums: Vec<Verifier>,
+    /// Whether each checksum constant is accompanied by a `<symbol>_<algorithm>_hex` string
+    pub checksum_hex: bool,
+    /// Size the data is asserted (with `static_assert`) to have, declaring `<symbol>_size`
+    pub assert_size: Option<usize>,
+    /// Size the data is asserted (with `static_assert`) not to exceed, declaring `<symbol>_size`
+    pub assert_size_max: Option<usize>,
+    /// Type of the elements of byte arrays
+    pub element_type: ElementType,
+    /// Key byte arrays are XORed with, which a generated `<symbol>_decode()` function takes back
+    /// out of them
+    pub xor_key: Option<Vec<u8>>,
+    /// Layout of array initialiser lists
+    pub byte_format: ByteFormat,
+}
+
+/// Checksum of the embedded data, baked into the generated source
+pub struct Verifier {
+    pub algorithm: ChecksumAlgorithm,
+    pub digest: Vec<u8>,
+}
+
+/// Format the data and generate the source for it in the given representation, in the language
+/// of the options
+pub fn generate_src(
+    data: &[u8],
+    representation: Representation,
+    symbol_name: &str,
+    options: &SourceOptions,
+) -> String {
+    return options
+        .language
+        .backend()
+        .generate_src(data, representation, symbol_name, options);
+}
+
+/// Generate the source for the data in the given representation straight into a writer, so
+/// that large inputs are never formatted into memory as a whole
+///
+/// The output is exactly what `generate_src` would return.
+pub fn write_src<W: io::Write + ?Sized>(
+    out: &mut W,
+    data: &[u8],
+    representation: Representation,
+    symbol_name: &str,
+    options: &SourceOptions,
+) -> io::Result<()> {
+    let backend = options.language.backend();
+    let array_len = data.len() + usize::from(options.null_terminate);
+    return match backend.template(array_len, representation, symbol_name, options) {
+        Some(template) => write_template(
+            out,
+            &template,
+            &array_data(data, options),
+            representation,
+            options,
+        ),
+        None => out.write_all(
+            backend
+                .generate_src(data, representation, symbol_name, options)
+                .as_bytes(),
+        ),
+    };
+}
+
+/// Backend generating C++, in every representation there is
+pub struct CppBackend;
+
+/// Keywords of C++20, including the alternative tokens such as `and`
+const CPP_KEYWORDS: [&str; 92] = [
+    "alignas",
+    "alignof",
+    "and",
+    "and_eq",
+    "asm",
+    "auto",
+    "bitand",
+    "bitor",
+    "bool",
+    "break",
+    "case",
+    "catch",
+    "char",
+    "char8_t",
+    "char16_t",
+    "char32_t",
+    "class",
+    "co_await",
+    "co_return",
+    "co_yield",
+    "compl",
+    "concept",
+    "const",
+    "const_cast",
+    "consteval",
+    "constexpr",
+    "constinit",
+    "continue",
+    "decltype",
+    "default",
+    "delete",
+    "do",
+    "double",
+    "dynamic_cast",
+    "else",
+    "enum",
+    "explicit",
+    "export",
+    "extern",
+    "false",
+    "float",
+    "for",
+    "friend",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "mutable",
+    "namespace",
+    "new",
+    "noexcept",
+    "not",
+    "not_eq",
+    "nullptr",
+    "operator",
+    "or",
+    "or_eq",
+    "private",
+    "protected",
+    "public",
+    "register",
+    "reinterpret_cast",
+    "requires",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "static_assert",
+    "static_cast",
+    "struct",
+    "switch",
+    "template",
+    "this",
+    "thread_local",
+    "throw",
+    "true",
+    "try",
+    "typedef",
+    "typeid",
+    "typename",
+    "union",
+    "unsigned",
+    "using",
+    "virtual",
+    "void",
+    "volatile",
+    "wchar_t",
+    "while",
+    "xor",
+    "xor_eq",
+];
+
+impl Backend for CppBackend {
+    fn extension(&self) -> &'static str {
+        return "hpp";
+    }
+
+    fn keywords(&self) -> &'static [&'static str] {
+        return &CPP_KEYWORDS;
+    }
+
+    fn generate_src(
+        &self,
+        data: &[u8],
+        representation: Representation,
+        symbol_name: &str,
+        options: &SourceOptions,
+    ) -> String {
+        return match representation {
+            Representation::BraceInitialiser | Representation::Incbin | Representation::Embed => {
+                let data = array_data(data, options);
+                generate_src_for_array(
+                    &format_as_elements(&data, &options.byte_format, options.element_type),
+                    data.len(),
+                    symbol_name,
+                    options,
+                )
+            }
+            Representation::EscapedStringLiteral => generate_src_for_string(
+                &string_literal(
+                    &format_as_escaped_split(data, options.max_literal_len),
+                    options.char_type,
+                ),
+                data.len(),
+                true,
+                symbol_name,
+                options,
+            ),
+            Representation::StringLiteral => generate_src_for_string(
+                &match options.raw_text {
+                    true => format_as_raw(data, options.char_type, options.max_literal_len),
+                    false => {
+                        format_as_text_literals(data, options.char_type, options.max_literal_len)
+                    }
+                },
+                data.len(),
+                options.text_view,
+                symbol_name,
+                options,
+            ),
+            Representation::PackedBits => bits::generate_src(data, symbol_name, options),
+            Representation::Words(layout) => {
+                words::generate_src(data, layout, symbol_name, options)
+            }
+            Representation::Compressed(algorithm) => {
+                compress::generate_src(data, algorithm, symbol_name, options)
+            }
+            Representation::Delta => delta::generate_src(
+                options
+                    .delta
+                    .as_ref()
+                    .expect("delta representation without a delta base"),
+                data.len(),
+                symbol_name,
+                options,
+            ),
+        };
+    }
+
+    fn template(
+        &self,
+        array_len: usize,
+        representation: Representation,
+        symbol_name: &str,
+        options: &SourceOptions,
+    ) -> Option<String> {
+        return match representation {
+            Representation::BraceInitialiser | Representation::Incbin | Representation::Embed => {
+                Some(generate_src_for_array(
+                    DATA_MARKER,
+                    array_len,
+                    symbol_name,
+                    options,
+                ))
+            }
+            Representation::EscapedStringLiteral => Some(generate_src_for_string(
+                &string_literal(DATA_MARKER, options.char_type),
+                array_len,
+                true,
+                symbol_name,
+                options,
+            )),
+            _ => None,
+        };
+    }
+}
+
+/// The data as laid out in a byte array: obfuscated if there is a key and followed by a NUL if
+/// it is to be terminated, either of which costs a copy of it
+pub(crate) fn array_data<'a>(data: &'a [u8], options: &SourceOptions) -> Cow<'a, [u8]> {
+    let data = match options.xor_key {
+        Some(ref key) => Cow::Owned(obfuscate::xor(data, key)),
+        None => Cow::Borrowed(data),
+    };
+
+    return match options.null_terminate {
+        true => Cow::Owned([&data[..], &[0]].concat()),
+        false => data,
+    };
+}
+
+/// Write a template, formatting the data into it wherever it has a `DATA_MARKER`
+pub(crate) fn write_template<W: io::Write + ?Sized>(
+    out: &mut W,
+    template: &str,
+    data: &[u8],
+    representation: Representation,
+    options: &SourceOptions,
+) -> io::Result<()> {
+    let mut pieces = template.split(DATA_MARKER);
+    out.write_all(pieces.next().unwrap_or_default().as_bytes())?;
+    for piece in pieces {
+        match representation {
+            Representation::EscapedStringLiteral => {
+                write_as_escaped(out, data, options.max_literal_len)?
+            }
+            _ => write_as_binary(out, data, &options.byte_format, options.element_type)?,
+        }
+        out.write_all(piece.as_bytes())?;
+    }
+
+    return Ok(());
+}
+
+fn generate_src_for_array(
+    array_contents: &str,
+    array_len: usize,
+    symbol_name: &str,
+    options: &SourceOptions,
+) -> String {
+    // Includes
+    let mut out_string: String = String::with_capacity(array_contents.len() + 0x100);
+    out_string.push_str("#include <array>");
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str("#include <cstdint>");
+    out_string.push_str(LINE_ENDING);
+    if options.loader_path.is_some() || options.span_accessor {
+        out_string.push_str("#include <span>");
+        out_string.push_str(LINE_ENDING);
+    }
+    if options.loader_path.is_some() {
+        push_loader_includes(&mut out_string, "vector");
+    }
+    if options.verifier.is_some()
+        || declares_size(options)
+        || options.element_type == ElementType::Byte
+        || options.xor_key.is_some()
+    {
+        out_string.push_str("#include <cstddef>");
+        out_string.push_str(LINE_ENDING);
+    }
+    if let Some(ref verifier) = options.verifier {
+        push_verifier_helper(&mut out_string, verifier.algorithm);
+    }
+    if options.stream_accessor {
+        stream::push_helper(&mut out_string);
+    }
+    if options.section.is_some() {
+        section::push_helper(&mut out_string);
+    }
+
+    // Namespace
+    push_namespace_open(
+        &mut out_string,
+        options.ns_name.as_deref(),
+        options.internal_linkage,
+    );
+
+    // Behind a span accessor the array itself is an implementation detail
+    let (storage_name, view_expr) = match options.span_accessor {
+        true => {
+            out_string.push_str("namespace bin2hpp_detail{");
+            (
+                format!("{}_storage", symbol_name),
+                format!("{}()", symbol_name),
+            )
+        }
+        false => (symbol_name.to_string(), symbol_name.to_string()),
+    };
+
+    // Array declaration
+    let declaration = match options.incbin_path {
+        Some(ref path) => {
+            if !out_string.ends_with(LINE_ENDING) {
+                out_string.push_str(LINE_ENDING);
+            }
+            incbin::declaration(
+                &array_declaration(array_contents, array_len, &storage_name, options),
+                array_len,
+                &storage_name,
+                path,
+                options,
+            )
+        }
+        None => match options.embed_path {
+            Some(ref path) => {
+                if !out_string.ends_with(LINE_ENDING) {
+                    out_string.push_str(LINE_ENDING);
+                }
+                embed::declaration(
+                    &array_declaration(array_contents, array_len, &storage_name, options),
+                    array_len,
+                    &storage_name,
+                    path,
+                    &array_specifiers(options),
+                    options.element_type,
+                )
+            }
+            None => match options.extern_data {
+                true => format!(
+                    "{}extern const std::array<{},{}> {};",
+                    alignment_specifier(options.align),
+                    options.element_type.name(),
+                    array_len,
+                    storage_name
+                ),
+                false => array_declaration(array_contents, array_len, &storage_name, options),
+            },
+        },
+    };
+    let stub = stub_data(true);
+    if !options.span_accessor {
+        push_doc_comment(&mut out_string, options.comment.as_deref());
+    }
+    push_declaration(
+        &mut out_string,
+        &declaration,
+        &array_declaration(
+            &format_as_elements(&stub, &ByteFormat::compact(), options.element_type),
+            stub.len(),
+            &storage_name,
+            options,
+        ),
+        options.guard_macro.as_deref(),
+    );
+    if (options.incbin_path.is_some() || options.embed_path.is_some())
+        && !out_string.ends_with(LINE_ENDING)
+    {
+        out_string.push_str(LINE_ENDING);
+    }
+
+    // Span accessor
+    if options.span_accessor {
+        out_string.push('}');
+        out_string.push_str(LINE_ENDING);
+        push_doc_comment(&mut out_string, options.comment.as_deref());
+        out_string.push_str(
+            format!(
+                "constexpr std::span<const {0}> {1}() noexcept{{return {{bin2hpp_detail::{2}}};}}",
+                options.element_type.name(),
+                symbol_name,
+                storage_name
+            )
+            .as_str(),
+        );
+        out_string.push_str(LINE_ENDING);
+    }
+
+    // Size (without any terminator)
+    let data_len = match options.null_terminate {
+        true => array_len.saturating_sub(1),
+        false => array_len,
+    };
+    push_size_constant(&mut out_string, symbol_name, data_len, stub.len(), options);
+
+    // Checksums
+    push_checksum_constants(&mut out_string, symbol_name, &stub, options);
+
+    // De-obfuscation
+    if let Some(ref key) = options.xor_key {
+        obfuscate::push_decoder(
+            &mut out_string,
+            symbol_name,
+            key,
+            options.element_type.name(),
+            !options.extern_data,
+        );
+    }
+
+    // Hybrid loader
+    if let Some(ref path) = options.loader_path {
+        push_loader(
+            &mut out_string,
+            symbol_name,
+            path,
+            &format!("std::span<const {}>", options.element_type.name()),
+            &format!("std::vector<{}>", options.element_type.name()),
+        );
+    }
+
+    // Integrity check
+    if let Some(ref verifier) = options.verifier {
+        push_verifier(&mut out_string, symbol_name, &view_expr, verifier);
+    }
+
+    // Stream accessor
+    if options.stream_accessor {
+        stream::push_accessor(&mut out_string, symbol_name, &view_expr);
+    }
+
+    // Close namespace (if need be)
+    push_namespace_close(
+        &mut out_string,
+        options.ns_name.as_deref(),
+        options.internal_linkage,
+    );
+
+    // Trailing newline
+    if !out_string.ends_with(LINE_ENDING) {
+        out_string.push_str(LINE_ENDING);
+    }
+
+    return out_string;
+}
+
+/// Generate a string literal symbol, as a `std::string_view` of the data's length (so that
+/// embedded NUL bytes are preserved) or a `const char*`
+///
+/// The literal is given complete with its quotes, as it may be several adjacent literals.
+fn generate_src_for_string(
+    string_literal: &str,
+    data_len: usize,
+    text_view: bool,
+    symbol_name: &str,
+    options: &SourceOptions,
+) -> String {
+    let string_len = text_view.then_some(data_len);
+
+    // Includes
+    let mut out_string: String = String::with_capacity(string_literal.len() + 0x100);
+    if declares_size(options) {
+        out_string.push_str("#include <cstddef>");
+        out_string.push_str(LINE_ENDING);
+    }
+    if string_len.is_some()
+        || options.loader_path.is_some()
+        || options.verifier.is_some()
+        || options.stream_accessor
+    {
+        out_string.push_str("#include <string_view>");
+        out_string.push_str(LINE_ENDING);
+    }
+    if options.loader_path.is_some() {
+        push_loader_includes(&mut out_string, "string");
+    }
+    if options.verifier.is_some() || !options.checksums.is_empty() {
+        out_string.push_str("#include <array>");
+        out_string.push_str(LINE_ENDING);
+        out_string.push_str("#include <cstddef>");
+        out_string.push_str(LINE_ENDING);
+        out_string.push_str("#include <cstdint>");
+        out_string.push_str(LINE_ENDING);
+    }
+    if let Some(ref verifier) = options.verifier {
+        push_verifier_helper(&mut out_string, verifier.algorithm);
+    }
+    if options.stream_accessor {
+        stream::push_helper(&mut out_string);
+    }
+
+    // Namespace
+    push_namespace_open(
+        &mut out_string,
+        options.ns_name.as_deref(),
+        options.internal_linkage,
+    );
+
+    // String initialisation
+    push_doc_comment(&mut out_string, options.comment.as_deref());
+    let declaration = match options.extern_data {
+        true => extern_string_declaration(string_len, symbol_name, options.char_type),
+        false => string_declaration(string_literal, string_len, symbol_name, options.char_type),
+    };
+    push_declaration(
+        &mut out_string,
+        &declaration,
+        &string_declaration(
+            &self::string_literal("", options.char_type),
+            string_len.map(|_| 0),
+            symbol_name,
+            options.char_type,
+        ),
+        options.guard_macro.as_deref(),
+    );
+
+    // Size
+    push_size_constant(&mut out_string, symbol_name, data_len, 0, options);
+
+    // Checksums
+    push_checksum_constants(&mut out_string, symbol_name, &stub_data(false), options);
+
+    // Hybrid loader
+    if let Some(ref path) = options.loader_path {
+        push_loader(
+            &mut out_string,
+            symbol_name,
+            path,
+            "std::string_view",
+            "std::string",
+        );
+    }
+
+    // Integrity check
+    let view_expr = match string_len {
+        Some(_) => symbol_name.to_string(),
+        None => format!("{}{{{}}}", options.char_type.view_type(), symbol_name),
+    };
+    if let Some(ref verifier) = options.verifier {
+        push_verifier(&mut out_string, symbol_name, &view_expr, verifier);
+    }
+
+    // Stream accessor
+    if options.stream_accessor {
+        stream::push_accessor(&mut out_string, symbol_name, &view_expr);
+    }
+
+    // Close namespace (if need be)
+    push_namespace_close(
+        &mut out_string,
+        options.ns_name.as_deref(),
+        options.internal_linkage,
+    );
+
+    // Trailing newline
+    if !out_string.ends_with(LINE_ENDING) {
+        out_string.push_str(LINE_ENDING);
+    }
+
+    return out_string;
+}
+
+fn array_declaration(
+    array_contents: &str,
+    array_len: usize,
+    symbol_name: &str,
+    options: &SourceOptions,
+) -> String {
+    return format!(
+        "{}constexpr std::array<{},{}> {}{{{}}};",
+        array_specifiers(options),
+        options.element_type.name(),
+        array_len,
+        symbol_name,
+        array_contents
+    );
+}
+
+/// Specifiers starting the definition of an array: its alignment and section, if it has them
+pub(crate) fn array_specifiers(options: &SourceOptions) -> String {
+    return format!(
+        "{}{}",
+        alignment_specifier(options.align),
+        section::specifier(options.section.as_deref())
+    );
+}
+
+/// `alignas` specifier (followed by a space) starting a declaration, if it needs one
+pub(crate) fn alignment_specifier(align: Option<usize>) -> String {
+    return match align {
+        Some(align) => format!("alignas({}) ", align),
+        None => String::new(),
+    };
+}
+
+fn string_declaration(
+    string_literal: &str,
+    string_len: Option<usize>,
+    symbol_name: &str,
+    char_type: CharType,
+) -> String {
+    return match string_len {
+        Some(len) => format!(
+            "constexpr {} {}{{{},{}}};",
+            char_type.view_type(),
+            symbol_name,
+            string_literal,
+            len
+        ),
+        None => format!(
+            "constexpr const {}* {} = {};",
+            char_type.name(),
+            symbol_name,
+            string_literal
+        ),
+    };
+}
+
+/// Declaration of a string symbol defined elsewhere, as with `string_declaration`
+fn extern_string_declaration(
+    string_len: Option<usize>,
+    symbol_name: &str,
+    char_type: CharType,
+) -> String {
+    return match string_len {
+        Some(_) => format!("extern const {} {};", char_type.view_type(), symbol_name),
+        None => format!("extern const {}* const {};", char_type.name(), symbol_name),
+    };
+}
+
+/// Quote string literal contents, with the encoding prefix of the character type
+pub(crate) fn string_literal(string_contents: &str, char_type: CharType) -> String {
+    return format!("{}\"{}\"", char_type.literal_prefix(), string_contents);
+}
+
+/// Push the opening of the namespace the symbols go in (if any), followed by an anonymous
+/// namespace if they are to have internal linkage
+pub(crate) fn push_namespace_open(
+    out_string: &mut String,
+    ns_name: Option<&str>,
+    internal_linkage: bool,
+) {
+    if let Some(namespace) = ns_name {
+        out_string.push_str(format!("namespace {}{{", namespace).as_str());
+    }
+    if internal_linkage {
+        out_string.push_str("namespace{");
+    }
+}
+
+/// Push the closing braces of the namespaces opened by `push_namespace_open`
+pub(crate) fn push_namespace_close(
+    out_string: &mut String,
+    ns_name: Option<&str>,
+    internal_linkage: bool,
+) {
+    if internal_linkage {
+        out_string.push('}');
+    }
+    if ns_name.is_some() {
+        out_string.push('}');
+    }
+}
+
+/// Push a user-supplied comment as a `///` doc comment on lines of its own
+pub(crate) fn push_doc_comment(out_string: &mut String, comment: Option<&str>) {
+    let Some(comment) = comment else {
+        return;
+    };
+
+    if !out_string.is_empty() && !out_string.ends_with(LINE_ENDING) {
+        out_string.push_str(LINE_ENDING);
+    }
+    for line in comment.lines() {
+        // A trailing backslash would splice the following line of the header into the comment
+        let line = line.trim_end().trim_end_matches('\\').trim_end();
+        out_string.push_str("///");
+        if !line.is_empty() {
+            out_string.push(' ');
+            out_string.push_str(line);
+        }
+        out_string.push_str(LINE_ENDING);
+    }
+}
+
+/// Push a declaration, wrapped in an `#ifdef` with the stub declaration as the fallback if a
+/// guard macro has been given
+pub(crate) fn push_declaration(
+    out_string: &mut String,
+    declaration: &str,
+    stub_declaration: &str,
+    guard_macro: Option<&str>,
+) {
+    match guard_macro {
+        Some(guard) => {
+            if !out_string.is_empty() && !out_string.ends_with(LINE_ENDING) {
+                out_string.push_str(LINE_ENDING);
+            }
+            out_string.push_str(format!("#ifdef {}", guard).as_str());
+            out_string.push_str(LINE_ENDING);
+            out_string.push_str(declaration);
+            out_string.push_str(LINE_ENDING);
+            out_string.push_str("#else");
+            out_string.push_str(LINE_ENDING);
+            out_string.push_str(stub_declaration);
+            out_string.push_str(LINE_ENDING);
+            out_string.push_str("#endif");
+            out_string.push_str(LINE_ENDING);
+        }
+        None => out_string.push_str(declaration),
+    }
+}
+
+/// Whether a `<symbol>_size` constant is declared, as asked for or needed by other options
+pub(crate) fn declares_size(options: &SourceOptions) -> bool {
+    return options.size_constant
+        || options.null_terminate
+        || options.assert_size.is_some()
+        || options.assert_size_max.is_some();
+}
+
+/// Push a `<symbol>_size` constant if one is declared, followed by the assertions on it, with the
+/// size of the stub (and no assertions) as the fallback if a guard macro has been given
+pub(crate) fn push_size_constant(
+    out_string: &mut String,
+    symbol_name: &str,
+    size: usize,
+    stub_size: usize,
+    options: &SourceOptions,
+) {
+    if !declares_size(options) {
+        return;
+    }
+
+    let size_declaration =
+        |size: usize| format!("constexpr std::size_t {}_size={};", symbol_name, size);
+    let mut declaration = size_declaration(size);
+    if let Some(expected) = options.assert_size {
+        declaration.push_str(
+            format!(
+                "static_assert({0}_size=={1},\"{0} is not {1} bytes\");",
+                symbol_name, expected
+            )
+            .as_str(),
+        );
+    }
+    if let Some(max) = options.assert_size_max {
+        declaration.push_str(
+            format!(
+                "static_assert({0}_size<={1},\"{0} is larger than {1} bytes\");",
+                symbol_name, max
+            )
+            .as_str(),
+        );
+    }
+    push_declaration(
+        out_string,
+        &declaration,
+        &size_declaration(stub_size),
+        options.guard_macro.as_deref(),
+    );
+}
+
+/// Push a `<symbol>_<algorithm>` constant for each checksum (along with its `_hex` string if asked
+/// for), with checksums of the stub as the fallback if a guard macro has been given
+pub(crate) fn push_checksum_constants(
+    out_string: &mut String,
+    symbol_name: &str,
+    stub: &[u8],
+    options: &SourceOptions,
+) {
+    for checksum in &options.checksums {
+        let checksum_declaration = |digest: &[u8]| {
+            let name = checksum.algorithm.name();
+            let mut declaration = match checksum.algorithm {
+                ChecksumAlgorithm::Crc32 => {
+                    let mut crc = [0; 4];
+                    crc.copy_from_slice(digest);
+                    format!(
+                        "constexpr std::uint32_t {}_{}={:#010x}u;",
+                        symbol_name,
+                        name,
+                        u32::from_be_bytes(crc)
+                    )
+                }
+                ChecksumAlgorithm::Sha256 => format!(
+                    "constexpr std::array<std::uint8_t,32> {}_{}{};",
+                    symbol_name,
+                    name,
+                    checksum::to_initialiser(digest)
+                ),
+            };
+            if options.checksum_hex {
+                declaration.push_str(
+                    format!(
+                        "constexpr const char* {}_{}_hex=\"{}\";",
+                        symbol_name,
+                        name,
+                        checksum::to_hex(digest)
+                    )
+                    .as_str(),
+                );
+            }
+            return declaration;
+        };
+        push_declaration(
+            out_string,
+            &checksum_declaration(&checksum.digest),
+            &checksum_declaration(&checksum.algorithm.digest(stub)),
+            options.guard_macro.as_deref(),
+        );
+    }
+}
+
+/// Push the headers only needed by the hybrid loader in debug builds
+fn push_loader_includes(out_string: &mut String, container_header: &str) {
+    out_string.push_str("#ifndef NDEBUG");
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str("#include <fstream>");
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str("#include <iterator>");
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str(format!("#include <{}>", container_header).as_str());
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str("#endif");
+    out_string.push_str(LINE_ENDING);
+}
+
+/// Push an accessor which, in debug builds, reads the data from disk on every call (falling back
+/// to the embedded data if the file cannot be opened) and otherwise returns the embedded data
+///
+/// The view returned in debug builds is only valid until the next call to the accessor.
+fn push_loader(
+    out_string: &mut String,
+    symbol_name: &str,
+    path: &str,
+    view_type: &str,
+    container_type: &str,
+) {
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str(format!("inline {} {}_load(){{", view_type, symbol_name).as_str());
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str("#ifndef NDEBUG");
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str(
+        format!(
+            "static {} loaded;std::ifstream file(\"{}\",std::ios::binary);",
+            container_type,
+            format_as_text(path.as_bytes())
+        )
+        .as_str(),
+    );
+    out_string.push_str(
+        "if(file){loaded.assign(std::istreambuf_iterator<char>(file),{});return {loaded.data(),loaded.size()};}",
+    );
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str("#endif");
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str(format!("return {};", symbol_name).as_str());
+    out_string.push_str(LINE_ENDING);
+    out_string.push('}');
+    out_string.push_str(LINE_ENDING);
+}
+
+/// Push the checksum function used by `<symbol>_verify()`, guarded so that several generated
+/// headers can be included in the same translation unit
+pub(crate) fn push_verifier_helper(out_string: &mut String, algorithm: ChecksumAlgorithm) {
+    let (guard, function) = match algorithm {
+        ChecksumAlgorithm::Crc32 => (
+            "BIN2HPP_DETAIL_CRC32",
+            checksum::cpp_crc32_function("crc32"),
+        ),
+        ChecksumAlgorithm::Sha256 => (
+            "BIN2HPP_DETAIL_SHA256",
+            checksum::cpp_sha256_function("sha256"),
+        ),
+    };
+
+    out_string.push_str(format!("#ifndef {}", guard).as_str());
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str(format!("#define {}", guard).as_str());
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str("namespace bin2hpp_detail{");
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str(&function);
+    out_string.push('}');
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str("#endif");
+    out_string.push_str(LINE_ENDING);
+}
+
+/// Push a `<symbol>_verify()` function which recomputes the checksum of the embedded data and
+/// compares it against the one computed at generation time
+fn push_verifier(out_string: &mut String, symbol_name: &str, view_expr: &str, verifier: &Verifier) {
+    let (function, expected) = match verifier.algorithm {
+        ChecksumAlgorithm::Crc32 => {
+            let mut crc = [0; 4];
+            crc.copy_from_slice(&verifier.digest);
+            ("crc32", format!("{:#010x}u", u32::from_be_bytes(crc)))
+        }
+        ChecksumAlgorithm::Sha256 => (
+            "sha256",
+            format!(
+                "std::array<std::uint8_t,32>{}",
+                checksum::to_initialiser(&verifier.digest)
+            ),
+        ),
+    };
+
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str(
+        format!(
+            "inline bool {}_verify(){{const auto& view={};return ::bin2hpp_detail::{}(reinterpret_cast<const unsigned char*>(view.data()),view.size())=={};}}",
+            symbol_name, view_expr, function, expected
+        )
+        .as_str(),
+    );
+    out_string.push_str(LINE_ENDING);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_never_spells_out_a_trigraph() {
+        assert_eq!(format_as_text(b"??="), "?\\?=");
+        assert_eq!(format_as_text(b"what???!"), "what?\\?\\?!");
+        assert_eq!(format_as_text(b"? ?"), "? ?");
+        assert_eq!(format_as_escaped(b"??="), "\\?\\?=");
+    }
+
+    fn formatted_on(
+        data: &[u8],
+        thread_count: usize,
+        format_chunk: impl Fn(&mut Vec<u8>, &[u8], usize) + Sync,
+    ) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_formatted_on(&mut out, data, thread_count, format_chunk).unwrap();
+
+        return out;
+    }
+
+    #[test]
+    fn formatting_in_parallel_writes_what_formatting_serially_does() {
+        // Several batches of chunks, the last of them partial, whose boundaries fall in the middle
+        // of lines and of literals
+        let mut state: u32 = 1;
+        let data: Vec<u8> = (0..PARALLEL_CHUNK_LEN * 5 + 0x1235)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                return (state >> 16) as u8;
+            })
+            .collect();
+        let format = ByteFormat {
+            bytes_per_line: 7,
+            space_after_comma: true,
+            ..ByteFormat::compact()
+        };
+        let literals = element_literals(&format, ElementType::Uint8);
+        let binary = |formatted: &mut Vec<u8>, chunk: &[u8], offset: usize| {
+            format_binary_chunk(formatted, chunk, offset, &format, &literals)
+        };
+        let escaped = |formatted: &mut Vec<u8>, chunk: &[u8], offset: usize| {
+            format_escaped_chunk(formatted, chunk, offset, 1000)
+        };
+
+        let serial = (
+            formatted_on(&data, 1, binary),
+            formatted_on(&data, 1, escaped),
+        );
+        for thread_count in [2, 3, 4] {
+            assert!(formatted_on(&data, thread_count, binary) == serial.0);
+            assert!(formatted_on(&data, thread_count, escaped) == serial.1);
+        }
+    }
+
+    fn mangled(identifier: &str, language: Language) -> Option<(String, InvalidIdentifier)> {
+        return mangle_identifier(identifier, language.backend());
+    }
+
+    #[test]
+    fn valid_identifiers_are_kept() {
+        for identifier in ["data", "data_bin", "Data", "x86", "a_b_c"] {
+            assert_eq!(mangled(identifier, Language::Cpp), None);
+        }
+    }
+
+    #[test]
+    fn leading_digits_get_a_prefix_in_the_case_of_the_rest() {
+        for (identifier, expected) in [
+            ("3d_model", "file_3d_model"),
+            ("3D_MODEL", "FILE_3D_MODEL"),
+            ("3dModel", "File3dModel"),
+            ("", "FILE_"),
+        ] {
+            assert_eq!(
+                mangled(identifier, Language::Cpp),
+                Some((expected.to_string(), InvalidIdentifier::LeadingDigit))
+            );
+        }
+    }
+
+    #[test]
+    fn keywords_of_the_language_get_an_underscore() {
+        assert_eq!(
+            mangled("new", Language::Cpp),
+            Some(("new_".to_string(), InvalidIdentifier::Keyword))
+        );
+        assert_eq!(mangled("new", Language::C), None);
+        assert_eq!(
+            mangled("fn", Language::Rust),
+            Some(("fn_".to_string(), InvalidIdentifier::Keyword))
+        );
+        assert_eq!(
+            mangled("lambda", Language::Python),
+            Some(("lambda_".to_string(), InvalidIdentifier::Keyword))
+        );
+    }
+
+    #[test]
+    fn reserved_identifiers_lose_their_underscores_where_reserved() {
+        assert_eq!(
+            mangled("_data", Language::Cpp),
+            Some(("data".to_string(), InvalidIdentifier::Reserved))
+        );
+        assert_eq!(
+            mangled("a__b", Language::C),
+            Some(("a_b".to_string(), InvalidIdentifier::Reserved))
+        );
+        assert_eq!(mangled("_data", Language::Rust), None);
+        // Whatever is left after collapsing them is mangled further, for the first reason found
+        assert_eq!(
+            mangled("__3d", Language::Cpp),
+            Some(("file_3d".to_string(), InvalidIdentifier::Reserved))
+        );
+        assert_eq!(
+            mangled("_int", Language::Cpp),
+            Some(("int_".to_string(), InvalidIdentifier::Reserved))
+        );
+    }
+
+    #[test]
+    fn symbol_case_splits_on_underscores_and_case_changes() {
+        for (identifier, snake, camel, pascal, screaming) in [
+            ("logo_png", "logo_png", "logoPng", "LogoPng", "LOGO_PNG"),
+            (
+                "myFile2Data",
+                "my_file2_data",
+                "myFile2Data",
+                "MyFile2Data",
+                "MY_FILE2_DATA",
+            ),
+            (
+                "HTTPServer",
+                "httpserver",
+                "httpserver",
+                "Httpserver",
+                "HTTPSERVER",
+            ),
+            ("__a__b__", "a_b", "aB", "AB", "A_B"),
+            ("x", "x", "x", "X", "X"),
+        ] {
+            assert_eq!(SymbolCase::Snake.apply(identifier), snake);
+            assert_eq!(SymbolCase::Camel.apply(identifier), camel);
+            assert_eq!(SymbolCase::Pascal.apply(identifier), pascal);
+            assert_eq!(SymbolCase::Screaming.apply(identifier), screaming);
+        }
+    }
+
+    #[test]
+    fn symbol_case_keeps_identifiers_without_words() {
+        assert_eq!(SymbolCase::Pascal.apply("___"), "___");
+    }
+}
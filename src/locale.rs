@@ -0,0 +1,149 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    LINE_ENDING, format_as_escaped_split, input, push_doc_comment, push_namespace_close,
+    push_namespace_open, sanitise_identifier,
+};
+
+/// Case-insensitive comparison of language tags which also accepts `_` for `-` (`en_US`)
+const TAG_EQUAL: &str = "constexpr bool tag_equal(std::string_view a,std::string_view b){if(a.size()!=b.size())return false;for(std::size_t i=0;i<a.size();++i){char x=a[i]=='_'?'-':a[i];char y=b[i]=='_'?'-':b[i];if(x>='A'&&x<='Z')x=char(x-'A'+'a');if(y>='A'&&y<='Z')y=char(y-'A'+'a');if(x!=y)return false;}return true;}";
+
+/// Whether a file stem looks like a language tag (`en`, `pt-BR`, `zh_Hant_TW`): a two or three
+/// letter language code followed by any number of one to eight character subtags
+fn is_language_tag(stem: &str) -> bool {
+    let mut subtags = stem.split(['-', '_']);
+    let language = subtags.next().unwrap_or_default();
+
+    return (2..=3).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_alphabetic())
+        && subtags.all(|subtag| {
+            (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+        });
+}
+
+/// Find the variants in a locale directory: every file directly inside it whose stem is a
+/// language tag, sorted by tag
+pub fn collect_variants(dir: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    let mut variants: Vec<(String, PathBuf)> = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        if is_language_tag(stem) {
+            variants.push((stem.to_string(), path));
+        }
+    }
+    // Tags are ASCII so they sort the same everywhere, unlike whatever order the directory is read in
+    variants.sort_by_cached_key(|(tag, path)| (tag.clone(), input::path_sort_key(path)));
+
+    return Ok(variants);
+}
+
+/// Generate one `std::string_view` per variant and a `<symbol>_lookup(tag)` function which finds
+/// the variant for a language tag, falling back to ever shorter prefixes of it (`de-CH-1996`,
+/// `de-CH`, `de`) and then to the default locale
+///
+/// Returns the tag of the offending variant if two of them map onto the same symbol.
+pub fn generate_src(
+    variants: &[(String, Vec<u8>)],
+    default_locale: &str,
+    symbol_name: &str,
+    ns_name: Option<&str>,
+    comment: Option<&str>,
+    internal_linkage: bool,
+    max_literal_len: usize,
+) -> Result<String, String> {
+    let variant_symbols: Vec<String> = variants
+        .iter()
+        .map(|(tag, _)| format!("{}_{}", symbol_name, sanitise_identifier(tag)))
+        .collect();
+    for (index, variant_symbol) in variant_symbols.iter().enumerate() {
+        if variant_symbols[..index].contains(variant_symbol) {
+            return Err(variants[index].0.clone());
+        }
+    }
+
+    // Includes
+    let total_len: usize = variants.iter().map(|(_, data)| data.len()).sum();
+    let mut out_string = String::with_capacity(total_len * 4 + 0x400);
+    for header in ["array", "cstddef", "string_view"] {
+        out_string.push_str(format!("#include <{}>", header).as_str());
+        out_string.push_str(LINE_ENDING);
+    }
+    out_string.push_str("#ifndef BIN2HPP_DETAIL_TAG_EQUAL");
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str("#define BIN2HPP_DETAIL_TAG_EQUAL");
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str("namespace bin2hpp_detail{");
+    out_string.push_str(TAG_EQUAL);
+    out_string.push('}');
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str("#endif");
+    out_string.push_str(LINE_ENDING);
+
+    // Namespace
+    push_namespace_open(&mut out_string, ns_name, internal_linkage);
+
+    // Variants
+    for ((_, data), variant_symbol) in variants.iter().zip(&variant_symbols) {
+        out_string.push_str(
+            format!(
+                "constexpr std::string_view {}{{\"{}\",{}}};",
+                variant_symbol,
+                format_as_escaped_split(data, max_literal_len),
+                data.len()
+            )
+            .as_str(),
+        );
+        out_string.push_str(LINE_ENDING);
+    }
+
+    // Table
+    let entries: Vec<String> = variants
+        .iter()
+        .zip(&variant_symbols)
+        .map(|((tag, _), variant_symbol)| format!("{{\"{}\",{}}}", tag, variant_symbol))
+        .collect();
+    out_string.push_str(
+        format!(
+            "struct {0}_locale{{std::string_view tag;std::string_view data;}};constexpr std::array<{0}_locale,{1}> {0}_locales{{{{{2}}}}};",
+            symbol_name,
+            variants.len(),
+            entries.join(",")
+        )
+        .as_str(),
+    );
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str(
+        format!(
+            "constexpr std::string_view {}_default_locale=\"{}\";",
+            symbol_name, default_locale
+        )
+        .as_str(),
+    );
+    out_string.push_str(LINE_ENDING);
+
+    // Lookup
+    push_doc_comment(&mut out_string, comment);
+    let default_symbol = format!("{}_{}", symbol_name, sanitise_identifier(default_locale));
+    out_string.push_str(
+        format!(
+            "constexpr std::string_view {0}_lookup(std::string_view tag){{for(;;){{for(const auto& locale:{0}_locales){{if(::bin2hpp_detail::tag_equal(locale.tag,tag))return locale.data;}}const auto cut=tag.find_last_of(\"-_\");if(cut==std::string_view::npos)break;tag=tag.substr(0,cut);}}return {1};}}",
+            symbol_name, default_symbol
+        )
+        .as_str(),
+    );
+
+    // Close namespace (if need be)
+    push_namespace_close(&mut out_string, ns_name, internal_linkage);
+    out_string.push_str(LINE_ENDING);
+
+    return Ok(out_string);
+}
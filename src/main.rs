@@ -1,251 +1,4279 @@
+#![allow(clippy::needless_return)]
+
 use std::{
-    fs::{File, OpenOptions},
-    io::{self, BufReader, BufWriter, Read, Write},
-    path::PathBuf,
+    borrow::Cow,
+    collections::HashSet,
+    ffi::OsStr,
+    fs::OpenOptions,
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
     process::ExitCode,
+    time::{Duration, Instant},
+};
+
+use clap::{ArgAction, ArgGroup, Parser, Subcommand, ValueEnum};
+
+use bin2hpp::{
+    ByteFormat, CharType, DEFAULT_BYTES_PER_LINE, DEFAULT_MAX_LITERAL_LEN, ElementType,
+    LINE_ENDING, Language, Radix, Representation, SourceOptions, SymbolCase, Verifier, amalgamate,
+    asm,
+    backend::{Backend, Features},
+    build::{self, BuildSystem},
+    checksum,
+    checksum::ChecksumAlgorithm,
+    compress::CompressionAlgorithm,
+    cost::{self, CostClass},
+    csv, dedupe, delta, extract, generate_src, glob, guard,
+    guard::GuardStyle,
+    index,
+    index::IndexEntry,
+    input,
+    input::SymlinkPolicy,
+    locale, mangle_identifier, manifest, metadata, mmap,
+    mmap::InputData,
+    obfuscate,
+    object::{self, ObjectArch, ObjectFormat},
+    output,
+    output::{CollisionPolicy, JobOutcome, Resolution, UnixLineEndings, WriteMode},
+    rc, region,
+    region::RegionStyle,
+    sanitise_identifier, sanitise_os_identifier, select_representation, split, structured,
+    structured::StructuredFormat,
+    stub_data, template, testgen,
+    testgen::TestFramework,
+    toc, watch,
+    words::{Endian, WordLayout, WordSize},
+    xxd,
 };
 
-use clap::{ArgAction, Parser};
+use crate::diagnostics::{ColorChoice, Diagnostics, LARGE_INPUT_THRESHOLD, Warning};
 
-#[cfg(windows)]
-const LINE_ENDING: &'static str = "\r\n";
-#[cfg(not(windows))]
-const LINE_ENDING: &'static str = "\n";
+mod config;
+mod diagnostics;
+mod tui;
+mod version;
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+// Options which other options conflict with together are grouped, so that a conflict names the group
+#[command(group(ArgGroup::new("placeholder").args(["stub", "guard_macro"]).multiple(true)))]
+#[command(group(ArgGroup::new("accessors").args(["hybrid_loader", "verify_fn", "emit_test", "stream_accessor", "span_accessor"]).multiple(true)))]
+#[command(group(ArgGroup::new("decoding").args(["decode_csv", "decode_struct"])))]
+#[command(group(ArgGroup::new("directory").args(["locales", "manifest"])))]
+#[command(group(ArgGroup::new("storage").args(["delta_base", "compress", "split"])))]
+#[command(group(ArgGroup::new("batching").args(["amalgamate", "index", "dedupe"]).multiple(true)))]
 struct CliArgs {
-    /// Input file path
-    #[arg(short, long)]
-    input_path: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Input file path, glob pattern (`assets/**/*.png`) or `-` for stdin, repeated to generate
+    /// several inputs at once (into one header if an output path is given)
+    #[arg(short, long, action = ArgAction::Append, required_unless_present_any = ["config", "files_from", "input_cmd"])]
+    input_path: Vec<PathBuf>,
+    /// Embed every file below input paths which are directories, as if each had been given with
+    /// -i
+    #[arg(short, long, action = ArgAction::SetTrue, conflicts_with_all = ["directory", "input_cmd"])]
+    recursive: bool,
+    /// Generate each file found by --recursive into namespaces nested after the directories it is
+    /// in below the one walked (inside --namespace, if given), such as `assets::ui::icons`
+    #[arg(long, action = ArgAction::SetTrue, requires = "recursive")]
+    nested_namespaces: bool,
+    /// Skip files which `.gitignore` files (those in the directories walked by --recursive or
+    /// --manifest, and those of the git repository they are in) or `.git/info/exclude` ignore;
+    /// `core.excludesFile` (and so the global `~/.config/git/ignore`) is not read, and a
+    /// backslash only escapes a leading `#` or `!` or a trailing space
+    #[arg(long, action = ArgAction::SetTrue)]
+    respect_gitignore: bool,
+    /// Run this command through the shell and embed what it writes to stdout instead of reading
+    /// an input file, with the symbol name derived from the output path
+    #[arg(long, value_name = "COMMAND", requires = "output_path", conflicts_with_all = ["input_path", "config", "files_from", "stub", "hybrid_loader", "directory"])]
+    input_cmd: Option<String>,
     /// Output file path
     #[arg(short, long)]
     output_path: Option<PathBuf>,
+    /// Directory in which to write the output, with its filename derived from the input's
+    #[arg(short = 'd', long, value_name = "DIR", conflicts_with = "output_path")]
+    output_dir: Option<PathBuf>,
+    /// Language of the generated header, C headers declaring `static const` arrays and a
+    /// `<symbol>_len` constant, Rust modules `pub const` slices (named in upper case unless
+    /// --symbol-name is given), C# files a static class (named in Pascal case) and Python modules
+    /// a `bytes` constant (named in upper case)
+    #[arg(long, value_enum, default_value_t = Language::Cpp)]
+    language: Language,
     /// Name of the C++ symbol
     #[arg(short, long)]
     symbol_name: Option<String>,
+    /// Template from which symbol names are derived, made of text and the placeholders {name}
+    /// (the input's filename), {stem}, {ext}, {dir} (the name of the directory containing it) and
+    /// {hash8} (the first 8 hex digits of the SHA-256 of the input path as given)
+    #[arg(long, value_name = "TEMPLATE", conflicts_with = "symbol_name")]
+    symbol_template: Option<String>,
+    /// Case in which symbol names derived from filenames (or from --symbol-template) are written,
+    /// rather than that of the language (default: snake for C and C++, screaming for Rust and
+    /// Python, pascal for C#)
+    #[arg(
+        long,
+        value_enum,
+        value_name = "CASE",
+        conflicts_with = "symbol_name",
+        global = true
+    )]
+    symbol_case: Option<SymbolCase>,
+    /// Text put before every symbol name derived from a filename (or from --symbol-template),
+    /// after its case is converted, as in `res_` or `k` (for `kLogoPng` with --symbol-case pascal),
+    /// which is kept as it is given so it must leave the symbol a valid identifier
+    #[arg(
+        long,
+        value_name = "TEXT",
+        conflicts_with = "symbol_name",
+        global = true
+    )]
+    symbol_prefix: Option<String>,
+    /// Text put after every symbol name derived from a filename (or from --symbol-template),
+    /// after its case is converted, which is kept as it is given like --symbol-prefix
+    #[arg(
+        long,
+        value_name = "TEXT",
+        conflicts_with = "symbol_name",
+        global = true
+    )]
+    symbol_suffix: Option<String>,
     /// Namespace in which to put the symbol
     #[arg(short, long)]
     namespace: Option<String>,
     /// Whether to operate in binary mode as opposed to text mode (default: text mode)
     #[arg(short, long, action = ArgAction::SetTrue)]
     binary: Option<bool>,
+    /// Generate exactly what `xxd -i` would, byte for byte: an `unsigned char` array and an
+    /// `unsigned int <symbol>_len`, named after the input path as given unless --symbol-name is
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["placeholder", "accessors", "directory", "decoding", "storage", "batching", "obfuscate_xor", "symbol_template", "namespace", "language"])]
+    xxd_compat: bool,
+    /// Generate the output from a template instead, in a subset of Tera's syntax, with the
+    /// variables payload (the data formatted as initialiser list elements), symbol, namespace,
+    /// size, checksums.crc32, checksums.sha256 (both in hex), source and version
+    ///
+    /// Only `{{ variable }}` through the upper, lower and trim filters, `{% if variable %}` (or
+    /// `if not`, true unless the variable is empty) with `{% else %}` and `{% endif %}`, `{#
+    /// comments #}` and `-` trimming whitespace next to a delimiter are supported: there are no
+    /// loops, `elif`, comparisons or other expressions, literals, `set`, macros, includes,
+    /// inheritance or raw blocks, nor any other filter
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["guard_macro", "accessors", "directory", "decoding", "storage", "batching", "encode", "auto_optimize", "xxd_compat", "object", "format", "self_check"])]
+    template: Option<PathBuf>,
+    /// What to generate for each input
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Header)]
+    format: OutputFormat,
+    /// Object format the assembler file of --format asm is written for (default: that of this
+    /// machine)
+    #[arg(long, value_enum, value_name = "FORMAT", requires = "format")]
+    asm_target: Option<ObjectFormat>,
+    /// Write the data into a relocatable object file of this format next to the header (named
+    /// after it, `.o` or `.obj`) to be linked in, the header only declaring `<symbol>` and its
+    /// `<symbol>_size`, which skips compiling the data altogether (a section only applies to ELF)
+    #[arg(long, value_enum, value_name = "FORMAT", conflicts_with_all = ["placeholder", "accessors", "directory", "decoding", "storage", "batching", "obfuscate_xor", "xxd_compat", "internal_linkage", "encode"])]
+    object: Option<ObjectFormat>,
+    /// Architecture of the object file written by --object (default: that of this machine)
+    #[arg(long, value_enum, value_name = "ARCH", requires = "object")]
+    object_arch: Option<ObjectArch>,
+    /// Emit the symbol with placeholder contents instead of the input data, for fast developer builds
+    #[arg(long, action = ArgAction::SetTrue)]
+    stub: bool,
+    /// Only embed the real data when this macro is defined, falling back to a stub otherwise
+    #[arg(long, value_name = "MACRO")]
+    guard_macro: Option<String>,
+    /// Also generate a `<symbol>_load()` accessor which reads the data from this path at runtime
+    /// when NDEBUG is not defined and returns the embedded data otherwise
+    #[arg(long, value_name = "PATH")]
+    hybrid_loader: Option<PathBuf>,
+    /// Number of bytes on each line of array initialiser lists, or 0 to keep them all on one line
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_BYTES_PER_LINE)]
+    bytes_per_line: usize,
+    /// Indentation of each line of bytes, as a number of spaces or `tab`
+    #[arg(long, value_name = "WIDTH", default_value = "4", value_parser = parse_indent)]
+    indent: String,
+    /// Base in which the bytes of array initialiser lists are written
+    #[arg(long, value_enum, default_value_t = Radix::Hex)]
+    radix: Radix,
+    /// Write hex digits in uppercase (`0xAB`)
+    #[arg(long, action = ArgAction::SetTrue)]
+    uppercase_hex: bool,
+    /// Give every byte the same number of digits (`0x0a` rather than `0xa`)
+    #[arg(long, action = ArgAction::SetTrue)]
+    zero_pad: bool,
+    /// Separate bytes on the same line with a space as well as a comma
+    #[arg(long, action = ArgAction::SetTrue)]
+    space_after_comma: bool,
+    /// Treat warnings as errors, failing before any output is written
+    #[arg(long, action = ArgAction::SetTrue)]
+    fail_on_warning: bool,
+    /// Pick the representation of binary data based on the input size to keep compile times
+    /// reasonable
+    #[arg(long, action = ArgAction::SetTrue)]
+    auto_optimize: bool,
+    /// Print an estimate of how expensive the generated header will be to compile
+    #[arg(long, action = ArgAction::SetTrue)]
+    report_cost: bool,
+    /// Emit a table of contents comment listing every symbol in the header
+    #[arg(long, action = ArgAction::SetTrue)]
+    toc: bool,
+    /// Follow symbolic links given as inputs (default)
+    #[arg(long, action = ArgAction::SetTrue, overrides_with = "no_follow_symlinks")]
+    follow_symlinks: bool,
+    /// Refuse to read inputs which are symbolic links
+    #[arg(long, action = ArgAction::SetTrue, overrides_with = "follow_symlinks")]
+    no_follow_symlinks: bool,
+    /// When to colour diagnostics (NO_COLOR is respected in auto mode)
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+    /// Write a verbose log (decisions and timings) to this file
+    #[arg(long, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+    /// Decode the input as CSV with the given comma-separated column types (i8-i64, u8-u64, f32,
+    /// f64, bool, string) into a constexpr array of structs
+    #[arg(long, value_name = "TYPES", conflicts_with_all = ["binary", "placeholder", "hybrid_loader", "auto_optimize"])]
+    decode_csv: Option<String>,
+    /// Treat the first CSV row as data rather than as the names of the struct's fields
+    #[arg(long, action = ArgAction::SetTrue, requires = "decode_csv")]
+    csv_no_header: bool,
+    /// Decode the input as JSON or TOML into constexpr structs, arrays and scalars
+    #[arg(long, value_enum, value_name = "FORMAT", conflicts_with_all = ["binary", "placeholder", "hybrid_loader", "auto_optimize"])]
+    decode_struct: Option<StructuredFormat>,
+    /// Also generate a unit test checking the size and SHA-256 digest of the embedded data
+    #[arg(long, value_enum, value_name = "FRAMEWORK", conflicts_with_all = ["decoding", "stub"])]
+    emit_test: Option<TestFramework>,
+    /// Path of the generated unit test (default: <output stem>_test.cpp next to the output)
+    #[arg(long, value_name = "PATH", requires = "emit_test")]
+    test_output_path: Option<PathBuf>,
+    /// Also generate a `<symbol>_verify()` function which checks the embedded data against a
+    /// checksum computed at generation time
+    #[arg(long, value_enum, value_name = "ALGORITHM", conflicts_with_all = ["decoding", "stub"])]
+    verify_fn: Option<ChecksumAlgorithm>,
+    /// How the data is encoded (bits: packed 64-bit words with a bit count, for bitmaps and masks)
+    #[arg(long, value_enum, default_value_t = Encoding::Bytes)]
+    encode: Encoding,
+    /// Byte order of the words the data is packed into by --encode u16, u32 or u64 (default:
+    /// little)
+    #[arg(long, value_enum)]
+    endian: Option<Endian>,
+    /// What to do when two inputs of a batch generate the same output path (output files left by
+    /// earlier runs are always replaced)
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t = CollisionPolicy::Error)]
+    on_collision: CollisionPolicy,
+    /// Let the output of a later input overwrite that of an earlier one generating the same path,
+    /// short for --on-collision overwrite
+    #[arg(long, visible_alias = "overwrite", action = ArgAction::SetTrue, conflicts_with = "on_collision")]
+    force: bool,
+    /// Leave the output file untouched (keeping its modification time) if it already holds
+    /// exactly what would be written, so build systems do not rebuild everything including it
+    #[arg(long, action = ArgAction::SetTrue)]
+    if_changed: bool,
+    /// Write nothing, instead failing with a summary of the differences if the output file is
+    /// missing or does not hold exactly what would be written (to catch stale headers in CI)
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with = "if_changed")]
+    check: bool,
+    /// Generate exactly the same files from the same inputs on every machine: with `\n` line
+    /// endings on every platform, with absolute paths recorded in metadata blocks relative to the
+    /// current directory, and without the absolute paths of --encode incbin and --format (nothing
+    /// generated holds a timestamp, and inputs found by patterns, directory walks and file lists
+    /// are always sorted)
+    #[arg(long, action = ArgAction::SetTrue)]
+    reproducible: bool,
+    /// Keep running after generating, regenerating the headers (only rewriting those which change)
+    /// whenever an input, delta base, config file or file list changes
+    ///
+    /// Changes are found by polling the modification time and size of every watched file each
+    /// --watch-interval rather than through the platform's file notifications, so they are noticed
+    /// up to two intervals late, each poll reads the metadata of every file (which adds up for
+    /// large directory trees), and a rewrite keeping both the size and the modification time (as
+    /// on filesystems with coarse timestamps) goes unnoticed
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["check", "input_cmd"])]
+    watch: bool,
+    /// How often --watch checks the inputs for changes, in milliseconds
+    #[arg(long, value_name = "MS", default_value_t = 500, requires = "watch")]
+    watch_interval: u64,
+    /// Print every file which would be written as JSON to stdout, per input and in total, without
+    /// writing anything, for build systems which must declare outputs up front (such as a Meson
+    /// `custom_target()`)
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["check", "if_changed", "watch", "stamp", "batch_report"])]
+    list_outputs: bool,
+    /// Print a snippet generating the inputs with the other options given from this build system
+    /// (a Meson generator or custom target, or a Bazel rule and the BUILD targets using it),
+    /// without generating anything (each header is named after its input with the extension
+    /// replaced, as Meson's `@BASENAME@.hpp` is)
+    #[arg(long, value_enum, value_name = "SYSTEM", conflicts_with_all = ["watch", "list_outputs", "config", "files_from", "input_cmd", "emit_test", "index", "nested_namespaces", "stamp", "batch_report"])]
+    build_snippet: Option<BuildSystem>,
+    /// Store the input as a delta against this file (e.g. the other image of an A/B pair, or the
+    /// default locale), generating a `<symbol>_apply()` function which rebuilds it from the base
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["decoding", "placeholder", "accessors", "auto_optimize"])]
+    delta_base: Option<PathBuf>,
+    /// Write this stamp file, listing the generated files, once every output has been written
+    #[arg(long, value_name = "PATH")]
+    stamp: Option<PathBuf>,
+    /// Generate every `[[embed]]` entry of this TOML file, each of which may override the output,
+    /// symbol, namespace, mode, encoding and per-file options such as checksum, align, offset or
+    /// compress of its `[defaults]` (other options apply to every entry)
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["input_path", "output_path", "symbol_name"])]
+    config: Option<PathBuf>,
+    /// Generate a header for every path in this NUL-delimited list (`-` for stdin), as produced by
+    /// `find -print0`
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["input_path", "output_path", "symbol_name", "config"])]
+    files_from: Option<PathBuf>,
+    /// Also generate a `<symbol>_stream()` function returning a `std::istream` over the embedded
+    /// data, for code written against streams
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["decoding", "delta_base"])]
+    stream_accessor: bool,
+    /// Character type of text embedded as a string literal (u8: `char8_t` and `u8` literals, for
+    /// C++20 code with `char8_t` enabled)
+    #[arg(long, value_enum, value_name = "TYPE", default_value_t = CharType::Char, conflicts_with_all = ["binary", "hybrid_loader", "decoding", "delta_base", "directory"])]
+    char_type: CharType,
+    /// Declare text as a `std::string_view` of explicit length rather than a `const char*`, which
+    /// keeps any NUL bytes in it and saves consumers a strlen
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["binary", "decoding", "delta_base", "directory"])]
+    string_view: bool,
+    /// Write text as raw string literals (`R"bin2hpp(...)bin2hpp"`) so that shaders, SQL or JSON
+    /// stay readable in the header, escaping only control characters other than tabs and newlines
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["binary", "decoding", "delta_base", "directory"])]
+    raw_string: bool,
+    /// Split string literals into adjacent literals of at most this many bytes, as MSVC rejects
+    /// longer ones (0 for no limit)
+    #[arg(long, value_name = "BYTES", default_value_t = DEFAULT_MAX_LITERAL_LEN)]
+    max_literal_len: usize,
+    /// What to do in text mode with input which is not valid UTF-8, which is warned about
+    /// unless replacing the invalid sequences is asked for
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t = InvalidUtf8::Escape)]
+    invalid_utf8: InvalidUtf8,
+    /// Hide the array in a detail namespace behind a `<symbol>()` function returning a
+    /// `std::span` over it (C++20), so that its representation can change without touching
+    /// consumers; in C#, declare the data as a `ReadOnlySpan<byte>` property instead of an array,
+    /// so that it is never copied out of the assembly
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["decoding", "delta_base", "hybrid_loader"])]
+    span_accessor: bool,
+    /// Treat the input path as a directory of per-locale variants named after their language tag
+    /// (`en.json`, `pt-BR.json`) and generate a `<symbol>_lookup(tag)` function selecting one
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["binary", "placeholder", "accessors", "auto_optimize", "decoding", "encode", "storage", "toc"])]
+    locales: bool,
+    /// Locale used when no variant matches the requested tag (default: en)
+    #[arg(long, value_name = "TAG", requires = "locales")]
+    default_locale: Option<String>,
+    /// Documentation comment emitted above the symbol, for reviewers and IDE tooltips
+    #[arg(long, value_name = "TEXT")]
+    comment: Option<String>,
+    /// Start every generated file with the contents of this file (a license or "do not edit"
+    /// notice) as a comment block, commented as the language comments
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["banner_text", "xxd_compat", "template"])]
+    banner: Option<PathBuf>,
+    /// Start every generated file with this text as a comment block, as --banner does
+    #[arg(long, value_name = "TEXT", conflicts_with_all = ["xxd_compat", "template"])]
+    banner_text: Option<String>,
+    /// Leave out the comment block every generated file starts with recording its provenance:
+    /// the version of the tool, its arguments, and the path, size and SHA-256 digest of the input
+    #[arg(long, action = ArgAction::SetTrue)]
+    no_metadata: bool,
+    /// Write a JSON report of which inputs succeeded (and the files written for them) and which
+    /// failed to this file
+    #[arg(long, value_name = "PATH")]
+    batch_report: Option<PathBuf>,
+    /// Generate every input into this one self-contained header, along with an index of them and
+    /// a `<stem>_find(name)` lookup
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["output_path", "output_dir", "emit_test", "directory"])]
+    amalgamate: Option<PathBuf>,
+    /// Split the amalgamation into as few headers as possible, `<stem>_1`, `<stem>_2` and so on,
+    /// keeping each (its guard, includes, metadata and index too) under this many bytes (`K`, `M`
+    /// and `G` or `KiB`, `MiB` and `GiB` suffixes multiplying by powers of 1024, `kB`, `MB` and
+    /// `GB` by 1000)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, requires = "amalgamate", conflicts_with = "build_snippet")]
+    header_budget: Option<usize>,
+    /// Also write an index header here, including the header generated for every input and
+    /// mapping the path of each (relative to the directory walked by --recursive) to a span over
+    /// its data, with a `<stem>_find(path)` lookup (C++20)
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["amalgamate", "directory"])]
+    index: Option<PathBuf>,
+    /// Embed the content of inputs which are identical to an earlier one only once, declaring
+    /// their symbols as references to the first
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["placeholder", "accessors", "decoding", "storage", "directory", "obfuscate_xor"])]
+    dedupe: bool,
+    /// Add a `<stem>_version` constant to the amalgamation's index, read from a Cargo.toml or
+    /// CMakeLists.txt or given literally
+    #[arg(long, value_name = "SOURCE", requires = "amalgamate")]
+    project_version_from: Option<String>,
+    /// Treat the input path as a directory and generate a table of the relative path, size and
+    /// SHA-256 digest of every file below it, without embedding their contents
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["binary", "placeholder", "accessors", "auto_optimize", "decoding", "encode", "storage", "toc"])]
+    manifest: bool,
+    /// Surround each symbol (and an amalgamation's index) with region markers so that editors can
+    /// fold the data away
+    #[arg(long, value_name = "STYLE")]
+    regions: Option<RegionStyle>,
+    /// How the header guards against being included twice (default: pragma, or ifdef for
+    /// --language c)
+    #[arg(long, value_enum, value_name = "STYLE")]
+    guard: Option<GuardStyle>,
+    /// Macro of an ifdef guard (default: BIN2HPP_<SYMBOL>_HPP, or _H for --language c)
+    #[arg(long, value_name = "MACRO")]
+    guard_name: Option<String>,
+    /// Put the symbols in an anonymous namespace, giving them internal linkage so that nothing
+    /// leaks out of the translation unit including the header (C declarations are always static)
+    #[arg(long, action = ArgAction::SetTrue)]
+    internal_linkage: bool,
+    /// Only declare the data `extern` in the header and define it in a `.cpp` file next to it, so
+    /// that the initialiser is compiled once however many files include the header
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["placeholder", "decoding", "directory", "amalgamate", "internal_linkage"])]
+    split: bool,
+    /// Align the embedded array to this many bytes (a power of two) with `alignas`, for data such
+    /// as SPIR-V or flatbuffers which is reinterpreted as words
+    #[arg(long, value_name = "BYTES", conflicts_with_all = ["decoding", "delta_base", "directory"])]
+    align: Option<usize>,
+    /// Place the embedded array in this linker section (such as .rodata.assets), so that a linker
+    /// script can steer it into a particular memory region
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["decoding", "delta_base", "directory"])]
+    section: Option<String>,
+    /// Follow binary data with a NUL element, which `<symbol>_size` does not count, so that it
+    /// can be handed straight to C APIs expecting NUL-terminated buffers
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["decoding", "delta_base", "directory", "placeholder", "accessors"])]
+    null_terminate: bool,
+    /// Also declare a `<symbol>_size` constant holding the size of the data in bytes, for
+    /// static_asserts and C interop (delta-encoded data always has one)
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["decoding", "directory"])]
+    size_constant: bool,
+    /// Also declare the checksums of the input computed with these comma-separated algorithms as
+    /// `<symbol>_<algorithm>` constants, to compare against at runtime
+    #[arg(long, value_enum, value_name = "ALGORITHMS", value_delimiter = ',', conflicts_with_all = ["decoding", "delta_base", "directory"])]
+    checksum: Vec<ChecksumAlgorithm>,
+    /// Accompany each checksum constant with a `<symbol>_<algorithm>_hex` string
+    #[arg(long, action = ArgAction::SetTrue, requires = "checksum")]
+    checksum_hex: bool,
+    /// Assert with a static_assert that the data is exactly this many bytes, so that a header
+    /// regenerated from the wrong or a truncated file fails to compile
+    #[arg(long, value_name = "BYTES", conflicts_with_all = ["decoding", "delta_base", "directory"])]
+    assert_size: Option<usize>,
+    /// Assert with a static_assert that the data is at most this many bytes
+    #[arg(long, value_name = "BYTES", conflicts_with_all = ["decoding", "delta_base", "directory"])]
+    assert_size_max: Option<usize>,
+    /// Type of the elements of the embedded array (C++ only), such as char for APIs taking
+    /// `const char*` or byte for `std::byte` spans, which saves reinterpret_casts
+    #[arg(long, value_enum, value_name = "TYPE", default_value_t = ElementType::Uint8, conflicts_with_all = ["decoding", "delta_base", "directory"])]
+    element_type: ElementType,
+    /// Embed the input from this many bytes into it, such as to pull a partition out of a
+    /// combined firmware image
+    #[arg(long, value_name = "BYTES", conflicts_with_all = ["hybrid_loader", "directory"])]
+    offset: Option<usize>,
+    /// Embed at most this many bytes of the input (after --offset), failing if it is shorter
+    #[arg(long, value_name = "BYTES", conflicts_with_all = ["hybrid_loader", "directory"])]
+    length: Option<usize>,
+    /// XOR the embedded bytes with this key (in hex, repeated over the data) and generate a
+    /// `<symbol>_decode()` function restoring them, to keep strings out of the binary's plain sight
+    #[arg(long, value_name = "KEY", conflicts_with_all = ["decoding", "delta_base", "directory", "accessors", "null_terminate"])]
+    obfuscate_xor: Option<String>,
+    /// Embed the input compressed, along with functions decompressing it at runtime: zstd and
+    /// gzip need libzstd or zlib to be linked in, lz4 comes with a small decoder in the header
+    #[arg(long, value_enum, value_name = "ALGORITHM", conflicts_with_all = ["decoding", "directory", "accessors"])]
+    compress: Option<CompressionAlgorithm>,
+    /// Memory-map input files of at least this many bytes instead of reading them into memory,
+    /// which must then not be truncated until the header has been written
+    #[arg(long, value_name = "BYTES", default_value_t = mmap::DEFAULT_MMAP_THRESHOLD)]
+    mmap_threshold: u64,
+    /// Read the header back after writing it and check that the data embedded in it is exactly
+    /// the input, failing otherwise
+    #[arg(long, action = ArgAction::SetTrue)]
+    self_check: bool,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Interactively preview the header generated from an input beside a hex view of it, toggling
+    /// options to see their effect
+    Tui {
+        /// Input file path
+        input_path: PathBuf,
+    },
+    /// Generate a header containing a delta turning one file into another, along with both of
+    /// their sizes and SHA-256 digests, for shipping compact update images
+    Patch {
+        /// File the patch applies to
+        old_path: PathBuf,
+        /// File the patch produces
+        new_path: PathBuf,
+        /// Output file path (default: the new file's name with a .patch.hpp extension)
+        #[arg(short, long)]
+        output_path: Option<PathBuf>,
+        /// Name of the C++ symbol (default: derived from the new file's name followed by `_patch`
+        /// as the symbol of an input is, in --symbol-case between --symbol-prefix and
+        /// --symbol-suffix)
+        #[arg(short, long)]
+        symbol_name: Option<String>,
+        /// Namespace in which to put the symbol
+        #[arg(short, long)]
+        namespace: Option<String>,
+    },
+}
+
+/// What is generated for each input, as given to `--format`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// A header embedding the data
+    Header,
+    /// An assembler file pulling the data in with `.incbin` (written next to the header, named
+    /// after it with a `.s` extension) and a header declaring its `<symbol>_start`,
+    /// `<symbol>_end` and `<symbol>_size`
+    Asm,
+    /// A Windows resource script compiling the input into an `RCDATA` resource (written next to
+    /// the header, named after it with a `.rc` extension) and a header with a `<symbol>()`
+    /// accessor loading it with `FindResource`/`LoadResource`
+    Rc,
+}
+
+impl OutputFormat {
+    fn name(self) -> &'static str {
+        return match self {
+            OutputFormat::Header => "header",
+            OutputFormat::Asm => "asm",
+            OutputFormat::Rc => "rc",
+        };
+    }
+}
+
+/// Encoding of the embedded data, as given to `--encode`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Encoding {
+    /// A byte array (binary mode) or string literal (text mode)
+    Bytes,
+    /// Packed 64-bit words, accessible bit by bit or as a `std::bitset`
+    Bits,
+    /// Included by the assembler with `.incbin` where supported (GCC and Clang on ELF and Mach-O
+    /// targets), falling back to a byte array everywhere else
+    Incbin,
+    /// Filled by the preprocessor with `#embed` where supported (C23 and C++26 preprocessors),
+    /// falling back to a byte array everywhere else
+    Embed,
+    /// An array of 16-bit words, in the byte order given by --endian
+    U16,
+    /// An array of 32-bit words, in the byte order given by --endian
+    U32,
+    /// An array of 64-bit words, in the byte order given by --endian
+    U64,
+}
+
+impl Encoding {
+    /// Width of the words the data is packed into, for the word encodings
+    fn word_size(self) -> Option<WordSize> {
+        return match self {
+            Encoding::U16 => Some(WordSize::U16),
+            Encoding::U32 => Some(WordSize::U32),
+            Encoding::U64 => Some(WordSize::U64),
+            Encoding::Bytes | Encoding::Bits | Encoding::Incbin | Encoding::Embed => None,
+        };
+    }
+}
+
+/// What text mode does with input which is not valid UTF-8, as given to `--invalid-utf8`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum InvalidUtf8 {
+    /// Write the invalid bytes as octal escapes, keeping the data exactly as it is
+    Escape,
+    /// Replace each invalid sequence with U+FFFD, so that the embedded text is valid UTF-8
+    Lossy,
+    /// Embed the input as binary data instead
+    Binary,
+}
+
+/// Parse the width given to `--indent` into the whitespace it stands for
+fn parse_indent(width: &str) -> Result<String, String> {
+    if width == "tab" {
+        return Ok("\t".to_string());
+    }
+
+    return match width.parse::<usize>() {
+        Ok(spaces) => Ok(" ".repeat(spaces)),
+        Err(_) => Err("expected a number of spaces or \"tab\"".to_string()),
+    };
+}
+
+fn parse_size(size: &str) -> Result<usize, String> {
+    const SUFFIXES: [(&str, usize); 10] = [
+        ("KiB", 1 << 10),
+        ("MiB", 1 << 20),
+        ("GiB", 1 << 30),
+        ("kB", 1000),
+        ("MB", 1000 * 1000),
+        ("GB", 1000 * 1000 * 1000),
+        ("K", 1 << 10),
+        ("M", 1 << 20),
+        ("G", 1 << 30),
+        ("B", 1),
+    ];
+
+    let (digits, multiplier) = SUFFIXES
+        .iter()
+        .find_map(|(suffix, multiplier)| {
+            return size
+                .strip_suffix(suffix)
+                .map(|digits| (digits, *multiplier));
+        })
+        .unwrap_or((size, 1));
+    return match digits.trim_end().parse::<usize>() {
+        Ok(0) => Err("expected a size of at least one byte".to_string()),
+        Ok(count) => count
+            .checked_mul(multiplier)
+            .ok_or_else(|| "size is too large".to_string()),
+        Err(_) => Err(
+            "expected a number of bytes, optionally followed by a suffix such as KiB or MB"
+                .to_string(),
+        ),
+    };
 }
 
 fn main() -> ExitCode {
-    let cli_args = CliArgs::parse();
+    let mut cli_args = CliArgs::parse();
+    if cli_args.force {
+        cli_args.on_collision = CollisionPolicy::Overwrite;
+    }
+    let mut diagnostics = Diagnostics::new(diagnostics::use_color(cli_args.color));
 
-    if !cli_args.input_path.exists() {
-        eprintln!(
-            "file path \"{}\" does not exist",
-            cli_args.input_path.to_string_lossy()
-        );
+    if let Some(ref log_path) = cli_args.log_file
+        && let Err(error) = diagnostics.open_log_file(log_path)
+    {
+        diagnostics.error(format!(
+            "failed to open log file \"{}\" for writing: {}",
+            log_path.to_string_lossy(),
+            error
+        ));
         return ExitCode::FAILURE;
     }
+    diagnostics.log(format!("arguments: {:?}", cli_args));
 
-    if !cli_args.input_path.is_file() {
-        eprintln!(
-            "file path \"{}\" is not a file",
-            cli_args.input_path.to_string_lossy()
-        );
+    if let Some(Command::Tui { ref input_path }) = cli_args.command {
+        if let Err(error) = tui::run(input_path) {
+            diagnostics.error(format!(
+                "preview of \"{}\" failed: {}",
+                input_path.to_string_lossy(),
+                error
+            ));
+            return ExitCode::FAILURE;
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    if let Some(Command::Patch {
+        ref old_path,
+        ref new_path,
+        ref output_path,
+        ref symbol_name,
+        ref namespace,
+    }) = cli_args.command
+    {
+        return match generate_patch(
+            old_path,
+            new_path,
+            output_path.as_deref(),
+            symbol_name.as_deref(),
+            namespace.as_deref(),
+            &cli_args,
+            &mut diagnostics,
+        ) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(()) => ExitCode::FAILURE,
+        };
+    }
+
+    if cli_args.watch {
+        return watch(cli_args, &mut diagnostics);
+    }
+
+    return generate_all(cli_args, &mut diagnostics);
+}
+
+/// Generate everything, then again whenever any of the files it was generated from changes, until
+/// interrupted
+///
+/// Failures are reported but do not stop the watch, as the next change may well fix them.
+fn watch(mut cli_args: CliArgs, diagnostics: &mut Diagnostics) -> ExitCode {
+    if cli_args
+        .input_path
+        .iter()
+        .any(|path| path.as_os_str() == "-")
+        || cli_args.files_from.as_deref() == Some(Path::new("-"))
+    {
+        diagnostics.error("--watch cannot watch stdin");
         return ExitCode::FAILURE;
     }
+    // Headers whose inputs did not change are left untouched, so builds only redo what they must
+    cli_args.if_changed = true;
+    let symlink_policy = match cli_args.no_follow_symlinks {
+        true => SymlinkPolicy::NoFollow,
+        false => SymlinkPolicy::Follow,
+    };
+    let interval = Duration::from_millis(cli_args.watch_interval);
 
-    // Derive output path from cwd & original filename if not provided in CLI
+    generate_all(cli_args.clone(), diagnostics);
+    let mut snapshot = watch::Snapshot::take(&watched_paths(&cli_args), symlink_policy);
+    diagnostics.note(format!(
+        "watching {} file(s) for changes, interrupt to stop",
+        snapshot.file_count()
+    ));
+    loop {
+        std::thread::sleep(interval);
+        let latest = watch::Snapshot::take(&watched_paths(&cli_args), symlink_policy);
+        if latest == snapshot {
+            continue;
+        }
 
-    let cwd = match std::env::current_dir() {
-        Ok(p) => p,
-        Err(_) => {
-            eprintln!("environment's current working directory is unavailable");
-            return ExitCode::FAILURE;
+        // Editors and build steps often write a file in several goes, so wait for it to settle
+        let mut settled = latest;
+        loop {
+            std::thread::sleep(interval);
+            let next = watch::Snapshot::take(&watched_paths(&cli_args), symlink_policy);
+            if next == settled {
+                break;
+            }
+            settled = next;
         }
+        let changed = settled.changed_since(&snapshot);
+        diagnostics.note(format!(
+            "{} changed, regenerating",
+            match changed.as_slice() {
+                [path] => format!("\"{}\"", path.to_string_lossy()),
+                paths => format!("{} files", paths.len()),
+            }
+        ));
+        generate_all(cli_args.clone(), diagnostics);
+        // What was just written may be among the files watched
+        snapshot = watch::Snapshot::take(&watched_paths(&cli_args), symlink_policy);
+    }
+}
+
+/// Every file (or directory) the headers are generated from, worked out afresh on each poll so
+/// that files matching a pattern or listed in a config file after the watch began are watched too
+fn watched_paths(cli_args: &CliArgs) -> Vec<PathBuf> {
+    let symlink_policy = match cli_args.no_follow_symlinks {
+        true => SymlinkPolicy::NoFollow,
+        false => SymlinkPolicy::Follow,
     };
 
-    let input_filename = match cli_args.input_path.file_name() {
-        Some(f) => f,
-        None => {
-            eprintln!(
-                "input file path \"{}\" does not contain a valid filename",
-                cli_args.input_path.to_string_lossy()
-            );
-            return ExitCode::FAILURE;
+    let mut paths = Vec::new();
+    for path in &cli_args.input_path {
+        match glob::is_pattern(path) && !path.exists() {
+            true => paths.extend(glob::expand(path, symlink_policy).unwrap_or_default()),
+            false => paths.push(path.clone()),
         }
-    };
+    }
+    if let Some(ref config_path) = cli_args.config {
+        paths.push(config_path.clone());
+        if let Ok(entries) = config::load(config_path) {
+            paths.extend(entries.iter().map(|entry| entry.input_path().to_path_buf()));
+        }
+    }
+    if let Some(ref list_path) = cli_args.files_from {
+        paths.push(list_path.clone());
+        paths.extend(input::read_file_list(list_path).unwrap_or_default());
+    }
+    paths.extend(cli_args.delta_base.clone());
+    paths.extend(cli_args.banner.clone());
 
-    let output_path = match cli_args.output_path {
-        Some(p) => p,
-        None => cwd.join(input_filename).with_extension("hpp"),
-    };
+    return paths;
+}
 
-    let symbol_name = match cli_args.symbol_name {
-        Some(s) => s,
-        None => input_filename
-            .to_string_lossy()
-            .to_string()
-            .chars()
-            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+/// Generate every input (expanding patterns and walking directories), along with the
+/// amalgamation, index, batch report and stamp if asked for
+fn generate_all(mut cli_args: CliArgs, diagnostics: &mut Diagnostics) -> ExitCode {
+    if cli_args.reproducible
+        && !cli_args.no_metadata
+        && let Some(path) = recorded_arguments(true).1.first()
+    {
+        diagnostics.error(format!(
+            "path \"{}\" cannot be recorded relative to the current directory, so the metadata block would differ from one machine to the next (pass it relative to the current directory, or --no-metadata)",
+            path
+        ));
+        return ExitCode::FAILURE;
+    }
+
+    // The banner is read once, rather than for every file it starts
+    if let Some(ref banner_path) = cli_args.banner {
+        match std::fs::read_to_string(banner_path) {
+            Ok(banner) => cli_args.banner_text = Some(banner),
+            Err(error) => {
+                diagnostics.error(format!(
+                    "failed to read banner \"{}\": {}",
+                    banner_path.to_string_lossy(),
+                    error
+                ));
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    // Patterns are expanded here for the shells (and CI scripts on Windows) which leave them be,
+    // unless a file really has that name
+    if cli_args
+        .input_path
+        .iter()
+        .any(|path| glob::is_pattern(path))
+    {
+        let symlink_policy = match cli_args.no_follow_symlinks {
+            true => SymlinkPolicy::NoFollow,
+            false => SymlinkPolicy::Follow,
+        };
+        let mut input_paths = Vec::with_capacity(cli_args.input_path.len());
+        for path in &cli_args.input_path {
+            if !glob::is_pattern(path) || path.exists() {
+                input_paths.push(path.clone());
+                continue;
+            }
+            match glob::expand(path, symlink_policy) {
+                Ok(matches) if matches.is_empty() => {
+                    diagnostics.error(format!(
+                        "pattern \"{}\" does not match any files",
+                        path.to_string_lossy()
+                    ));
+                    return ExitCode::FAILURE;
+                }
+                Ok(matches) => {
+                    diagnostics.log(format!(
+                        "pattern \"{}\" matched {} files",
+                        path.to_string_lossy(),
+                        matches.len()
+                    ));
+                    input_paths.extend(matches);
+                }
+                Err(error) => {
+                    diagnostics.error(format!(
+                        "failed to expand pattern \"{}\": {}",
+                        path.to_string_lossy(),
+                        error
+                    ));
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        cli_args.input_path = input_paths;
+    }
+
+    // Paths in the index and nested namespaces are relative to the directories walked
+    let walked_dirs: Vec<PathBuf> = match cli_args.recursive {
+        true => cli_args
+            .input_path
+            .iter()
+            .filter(|path| path.is_dir())
+            .cloned()
             .collect(),
+        false => Vec::new(),
     };
 
-    let input_file = match OpenOptions::new().read(true).open(cli_args.input_path) {
-        Ok(f) => f,
-        Err(error) => {
-            eprintln!("failed to open input file for reading: {}", error);
+    if cli_args.recursive {
+        let symlink_policy = match cli_args.no_follow_symlinks {
+            true => SymlinkPolicy::NoFollow,
+            false => SymlinkPolicy::Follow,
+        };
+        // Regenerating into the directory being walked must not embed the previous output
+        let canonical_output_path = cli_args
+            .output_path
+            .as_deref()
+            .or(cli_args.amalgamate.as_deref())
+            .and_then(input::canonicalize_output);
+
+        let mut input_paths = Vec::with_capacity(cli_args.input_path.len());
+        for path in &cli_args.input_path {
+            if !path.is_dir() {
+                input_paths.push(path.clone());
+                continue;
+            }
+            match input::walk_dir(path, symlink_policy, cli_args.respect_gitignore) {
+                Ok(relative_paths) => input_paths.extend(
+                    relative_paths
+                        .into_iter()
+                        .map(|relative_path| path.join(relative_path))
+                        .filter(|file_path| {
+                            canonical_output_path.is_none()
+                                || std::fs::canonicalize(file_path).ok() != canonical_output_path
+                        }),
+                ),
+                Err(error) => {
+                    diagnostics.error(format!(
+                        "failed to read directory \"{}\": {}",
+                        path.to_string_lossy(),
+                        error
+                    ));
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        diagnostics.log(format!("{} input files found", input_paths.len()));
+        cli_args.input_path = input_paths;
+    }
+
+    // Several inputs given with -i are generated like a file list, all into the one header if an
+    // output path was given
+    if cli_args.input_path.len() > 1 {
+        if cli_args.symbol_name.is_some() {
+            diagnostics.error(
+                "--symbol-name cannot be given with several inputs, derive their symbols with --symbol-template instead",
+            );
             return ExitCode::FAILURE;
         }
-    };
+        if cli_args.amalgamate.is_none()
+            && let Some(output_path) = cli_args.output_path.take()
+        {
+            if cli_args.emit_test.is_some() || cli_args.locales || cli_args.manifest {
+                diagnostics.error(
+                    "--emit-test, --locales and --manifest cannot be combined with generating several inputs into one header",
+                );
+                return ExitCode::FAILURE;
+            }
+            cli_args.amalgamate = Some(output_path);
+        }
+    }
 
-    let buf = match read_file(&input_file) {
-        Ok(data) => data,
-        Err(error) => {
-            eprintln!("failed read input file: {}", error);
+    if let Some(build_system) = cli_args.build_snippet {
+        return print_build_snippet(build_system, &cli_args, diagnostics);
+    }
+
+    if cli_args.index.is_some() {
+        if cli_args.amalgamate.is_some() {
+            diagnostics.error(
+                "--index lists headers generated separately, which several inputs generated into one header are not (pass --output-dir instead of --output)",
+            );
+            return ExitCode::FAILURE;
+        }
+        if cli_args.language != Language::Cpp {
+            diagnostics.error(format!(
+                "--index generates a C++ header, which --language {} cannot include",
+                cli_args.language.name()
+            ));
             return ExitCode::FAILURE;
         }
+    }
+
+    let jobs = match cli_args.config {
+        Some(ref config_path) => match config::load(config_path) {
+            Ok(entries) => entries.iter().map(|entry| entry.apply(&cli_args)).collect(),
+            Err(error) => {
+                diagnostics.error(error);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => match cli_args.files_from {
+            Some(ref list_path) => match input::read_file_list(list_path) {
+                Ok(mut paths) => {
+                    // Lists usually come from directory walks, whose order varies between
+                    // platforms and file systems
+                    paths.sort_by_cached_key(|path| input::path_sort_key(path));
+                    paths
+                        .into_iter()
+                        .map(|path| CliArgs {
+                            input_path: vec![path],
+                            files_from: None,
+                            ..cli_args.clone()
+                        })
+                        .collect()
+                }
+                Err(error) => {
+                    diagnostics.error(format!(
+                        "failed to read file list \"{}\": {}",
+                        list_path.to_string_lossy(),
+                        error
+                    ));
+                    return ExitCode::FAILURE;
+                }
+            },
+            None if cli_args.input_path.len() > 1 => cli_args
+                .input_path
+                .iter()
+                .map(|path| CliArgs {
+                    input_path: vec![path.clone()],
+                    ..cli_args.clone()
+                })
+                .collect(),
+            None => vec![cli_args.clone()],
+        },
     };
 
-    let formatted = match cli_args.binary {
-        Some(true) => format_as_binary(&buf),
-        _ => format_as_text(&buf),
+    let jobs: Vec<CliArgs> = match cli_args.nested_namespaces {
+        true => jobs
+            .into_iter()
+            .map(|job| {
+                let namespace = nested_namespace(
+                    job.namespace.as_deref(),
+                    job.input_path
+                        .first()
+                        .map_or(Path::new(""), PathBuf::as_path),
+                    &walked_dirs,
+                    job.language.backend(),
+                );
+                return CliArgs { namespace, ..job };
+            })
+            .collect(),
+        false => jobs,
     };
 
-    let out_src = match cli_args.binary {
-        Some(true) => {
-            generate_src_for_array(&formatted, buf.len(), &symbol_name, cli_args.namespace)
-        }
-        _ => generate_src_for_string(&formatted, &symbol_name, cli_args.namespace),
+    let project_version = match cli_args.project_version_from {
+        Some(ref spec) => match version::resolve(spec) {
+            Ok(version) => {
+                diagnostics.log(format!("project version: {}", version));
+                Some(version)
+            }
+            Err(error) => {
+                diagnostics.error(error);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => None,
     };
 
-    let output_file = match OpenOptions::new()
-        .write(true)
-        .truncate(true)
-        .create(true)
-        .open(output_path)
+    if jobs.is_empty() {
+        diagnostics.note("file list is empty, nothing to generate");
+    }
+
+    // Every entry is attempted even if some fail, outputs are only ever replaced by complete files
+    // so the ones which succeeded are left in a consistent state
+    let job_count = jobs.len();
+    let mut outcomes = Vec::with_capacity(job_count);
+    let mut parts = Vec::new();
+    let mut index_entries = Vec::new();
+    let mut originals = Vec::new();
+    let mut outputs = Outputs::new(&cli_args);
+    for job in jobs {
+        let input = job.input_path.first().cloned().unwrap_or_default();
+        let batch = Batch {
+            amalgamation: cli_args.amalgamate.as_ref().map(|_| &mut parts),
+            index: cli_args.index.as_ref().map(|_| &mut index_entries),
+            originals: cli_args.dedupe.then_some(&mut originals),
+            outputs: &mut outputs,
+        };
+        let written = generate(job, batch, diagnostics).ok();
+        outcomes.push(JobOutcome { input, written });
+    }
+
+    if let Some(ref report_path) = cli_args.batch_report
+        && let Err(error) =
+            output::write_atomically(report_path, output::batch_report(&outcomes).as_bytes())
     {
-        Ok(f) => f,
-        Err(error) => {
-            eprintln!("failed to open output file for writing: {}", error);
-            return ExitCode::FAILURE;
+        diagnostics.error(format!(
+            "failed to write batch report \"{}\": {}",
+            report_path.to_string_lossy(),
+            error
+        ));
+        return ExitCode::FAILURE;
+    }
+
+    let failed: Vec<String> = outcomes
+        .iter()
+        .filter(|outcome| outcome.written.is_none())
+        .map(|outcome| format!("\"{}\"", outcome.input.to_string_lossy()))
+        .collect();
+    if !failed.is_empty() {
+        if job_count > 1 {
+            diagnostics.error(format!(
+                "{} of {} inputs failed: {}",
+                failed.len(),
+                job_count,
+                failed.join(", ")
+            ));
         }
-    };
+        return ExitCode::FAILURE;
+    }
 
-    let mut writer = BufWriter::new(output_file);
-    match writer.write_all(out_src.as_bytes()) {
-        Ok(_) => (),
-        Err(error) => {
-            eprintln!("failed to write to output file: {}", error);
+    let mut written: Vec<PathBuf> = outcomes
+        .iter()
+        .flat_map(|outcome| outcome.written.clone().unwrap_or_default())
+        .collect();
+
+    if let Some(ref amalgamation_path) = cli_args.amalgamate {
+        if check_distinct_symbols(&parts, diagnostics).is_err() {
             return ExitCode::FAILURE;
         }
-    };
+        let headers = match cli_args.header_budget {
+            Some(budget) => pack_headers(
+                amalgamation_path,
+                parts,
+                budget,
+                project_version.as_deref(),
+                &cli_args,
+                diagnostics,
+            ),
+            None => vec![(amalgamation_path.clone(), parts)],
+        };
+        for (path, parts) in headers {
+            match write_amalgamation(
+                &path,
+                &parts,
+                project_version.as_deref(),
+                &cli_args,
+                &mut outputs,
+                diagnostics,
+            ) {
+                Ok(Some(path)) => written.push(path),
+                Ok(None) => (),
+                Err(()) => return ExitCode::FAILURE,
+            }
+        }
+    }
+
+    if let Some(ref index_path) = cli_args.index {
+        for entry in &mut index_entries {
+            entry.name = index_name(&entry.name, &walked_dirs);
+        }
+        match write_index(
+            index_path,
+            &index_entries,
+            &cli_args,
+            &mut outputs,
+            diagnostics,
+        ) {
+            Ok(Some(path)) => written.push(path),
+            Ok(None) => (),
+            Err(()) => return ExitCode::FAILURE,
+        }
+    }
+
+    if cli_args.list_outputs {
+        print!("{}", output::outputs_listing(&outcomes, &written));
+        return ExitCode::SUCCESS;
+    }
+
+    return write_stamp(diagnostics, cli_args.stamp.as_deref(), &written);
+}
+
+/// Print a snippet for the build system generating the inputs (already expanded) as this run
+/// would, passing on every other option given
+fn print_build_snippet(
+    build_system: BuildSystem,
+    cli_args: &CliArgs,
+    diagnostics: &mut Diagnostics,
+) -> ExitCode {
+    if cli_args
+        .input_path
+        .iter()
+        .any(|path| path.as_os_str() == "-")
+    {
+        diagnostics.error("--build-snippet needs input files, not stdin");
+        return ExitCode::FAILURE;
+    }
+    if cli_args.input_path.is_empty() {
+        diagnostics.error("--build-snippet needs at least one input");
+        return ExitCode::FAILURE;
+    }
+
+    let inputs: Vec<String> = cli_args
+        .input_path
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    let amalgamation = cli_args
+        .amalgamate
+        .as_ref()
+        .map(|path| path.to_string_lossy().to_string());
+    let header_extension = cli_args.language.extension();
+    let mut extensions = vec![header_extension];
+    if cli_args.split {
+        extensions.push("cpp");
+    }
+    if let Some(object_format) = cli_args.object {
+        extensions.push(object_format.extension());
+    }
+    match cli_args.format {
+        OutputFormat::Header => (),
+        OutputFormat::Asm => extensions.push("s"),
+        OutputFormat::Rc => extensions.push("rc"),
+    }
+    print!(
+        "{}",
+        build::generate_snippet(
+            build_system,
+            &build::SnippetJob {
+                inputs: &inputs,
+                arguments: &snippet_arguments(),
+                amalgamation: amalgamation.as_deref(),
+                extensions: &extensions,
+            },
+        )
+    );
 
     return ExitCode::SUCCESS;
 }
 
-fn read_file(f: &File) -> io::Result<Vec<u8>> {
-    let buf_size: u64 = match f.metadata() {
-        Ok(metadata) => metadata.len(),
-        Err(_) => 0x1000, // just preallocate 4 KiB otherwise
-    };
-    let mut buf: Vec<u8> = Vec::with_capacity(buf_size as usize);
+/// The arguments the tool was run with other than its inputs and outputs (which the build system
+/// substitutes) and --build-snippet itself, to be passed on by the snippet
+fn snippet_arguments() -> Vec<String> {
+    const WITH_VALUE: [&str; 8] = [
+        "-i",
+        "--input-path",
+        "-o",
+        "--output-path",
+        "-d",
+        "--output-dir",
+        "--amalgamate",
+        "--build-snippet",
+    ];
+
+    let mut arguments = Vec::new();
+    let mut skip_value = false;
+    for argument in std::env::args_os().skip(1) {
+        let argument = argument.to_string_lossy().to_string();
+        if std::mem::take(&mut skip_value) {
+            continue;
+        }
+        let (flag, inline_value) = match argument.split_once('=') {
+            Some((flag, _)) => (flag, true),
+            None => (argument.as_str(), false),
+        };
+        match flag {
+            "-r" | "--recursive" => (),
+            _ if WITH_VALUE.contains(&flag) => skip_value = !inline_value,
+            _ => arguments.push(argument),
+        }
+    }
 
-    let mut reader = BufReader::new(f);
-    reader.read_to_end(&mut buf)?;
+    return arguments;
+}
 
-    return Ok(buf);
+/// What is collected across the inputs of a batch as each is generated
+struct Batch<'a> {
+    /// Sources of the inputs, if they are being amalgamated
+    amalgamation: Option<&'a mut Vec<amalgamate::Part>>,
+    /// Headers to list in an index, if one is being generated
+    index: Option<&'a mut Vec<IndexEntry>>,
+    /// Data generated so far, if identical inputs are deduplicated
+    originals: Option<&'a mut Vec<dedupe::Original>>,
+    outputs: &'a mut Outputs,
 }
 
-/// Format a slice of bytes into an array-of-bytes initialiser list
-fn format_as_binary(data: &[u8]) -> String {
-    let mut formatted = data
-        .iter()
-        .map(|b| format!("{:#x},", b))
-        .collect::<String>();
-    if !formatted.is_empty() {
-        formatted.pop().unwrap(); // remove trailing ','
+/// Generate the header (and any other outputs) for a single input, returning the paths written
+///
+/// If an amalgamation is being built the source is added to it instead of being written, and if an
+/// index is the header written is listed in it. Errors have already been reported through
+/// `diagnostics` when this fails.
+fn generate(
+    cli_args: CliArgs,
+    batch: Batch,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<PathBuf>, ()> {
+    let amalgamating = batch.amalgamation.is_some();
+    let target = resolve_target(&cli_args, amalgamating, diagnostics)?;
+
+    if cli_args.manifest {
+        let (out_src, digests) = generate_manifest(
+            &target.input_path,
+            &target.output_path,
+            target.symlink_policy,
+            &target.symbol_name,
+            &cli_args,
+            diagnostics,
+        )?;
+        // The per-file digests are hashed back to back in path order, so the metadata changes
+        // whenever any listed file does
+        let symbols = [
+            target.symbol_name.clone(),
+            format!("{}_find", target.symbol_name),
+        ];
+        return write_wrapped(
+            &cli_args,
+            &target,
+            &out_src,
+            &digests,
+            &symbols,
+            batch.outputs,
+            diagnostics,
+        );
+    }
+
+    if cli_args.locales {
+        let (out_src, variants) = generate_locales(
+            &target.input_path,
+            &target.symbol_name,
+            &cli_args,
+            diagnostics,
+        )?;
+        // The variants are hashed back to back in tag order, as if they were one input
+        let symbols = [format!("{}_lookup", target.symbol_name)];
+        return write_wrapped(
+            &cli_args,
+            &target,
+            &out_src,
+            &variants,
+            &symbols,
+            batch.outputs,
+            diagnostics,
+        );
+    }
+
+    let input = read_input(&cli_args, &target, diagnostics)?;
+
+    if cli_args.xxd_compat {
+        if amalgamating {
+            diagnostics
+                .error("--xxd-compat generates one header per input, not several inputs in one");
+            return Err(());
+        }
+        let symbol_name = match cli_args.symbol_name {
+            Some(ref s) => s.clone(),
+            None => xxd::symbol_name(&target.input_name),
+        };
+        let out_src = xxd::generate_src(&input.data, &symbol_name);
+        return write_generated(
+            &target.output_path,
+            Generated {
+                src: &out_src,
+                provenance: None,
+                companion: None,
+            },
+            &cli_args,
+            target.initial_warning_count,
+            batch.outputs,
+            diagnostics,
+        );
+    }
+
+    let embedding = embedding(&cli_args, &target, input, amalgamating, diagnostics)?;
+
+    // A template decides everything about the output but the data formatted into it
+    if let Some(ref template_path) = cli_args.template {
+        if amalgamating {
+            diagnostics.error("--template generates one file per input, not several inputs in one");
+            return Err(());
+        }
+        return write_template(
+            &cli_args,
+            &target,
+            template_path,
+            &embedding,
+            batch.outputs,
+            diagnostics,
+        );
+    }
+
+    // The data goes into an object file linked in next to the header, which only declares it
+    if let Some(object_format) = cli_args.object {
+        if amalgamating {
+            diagnostics.error("--object generates one header per input, not several inputs in one");
+            return Err(());
+        }
+        return write_object(
+            &cli_args,
+            &target,
+            object_format,
+            &embedding,
+            batch.outputs,
+            diagnostics,
+        );
+    }
+
+    if cli_args.format != OutputFormat::Header {
+        return write_assembled(&cli_args, &target, &embedding, batch.outputs, diagnostics);
     }
 
-    return formatted;
+    return write_embedding(&cli_args, &target, &embedding, batch, diagnostics);
 }
 
-/// Format a slice of bytes into a string literal (without quotes)
-fn format_as_text(data: &[u8]) -> String {
-    // FIXME: this will currently panic if the input file was not UTF-8 encoded!
-    return String::from_utf8(data.to_vec())
-        .unwrap()
-        .escape_default()
-        .collect();
+/// An input as every kind of output generated for it sees it, from where it is read to the
+/// header it ends up in
+struct Target {
+    input_path: PathBuf,
+    /// What is read, the input path with its symlinks followed
+    resolved_input_path: PathBuf,
+    /// How the input is referred to in diagnostics and metadata
+    input_name: String,
+    reads_stdin: bool,
+    symlink_policy: SymlinkPolicy,
+    output_path: PathBuf,
+    symbol_name: String,
+    guard_style: GuardStyle,
+    guard_macro: String,
+    /// Warnings issued before this input, only those after it count towards --fail-on-warning
+    initial_warning_count: usize,
 }
 
-fn generate_src_for_array(
-    array_contents: &str,
-    array_len: usize,
-    symbol_name: &str,
-    ns_name: Option<String>,
-) -> String {
-    // Includes
-    let mut out_string: String = String::with_capacity(array_contents.len() + 0x100);
-    out_string.push_str("#include <array>");
-    out_string.push_str(LINE_ENDING);
-    out_string.push_str("#include <cstdint>");
-    out_string.push_str(LINE_ENDING);
-
-    // Namespace
-    match ns_name {
-        Some(ref namespace) => out_string.push_str(format!("namespace {}{{", namespace).as_str()),
-        None => (),
-    };
-
-    // Array declaration
-    out_string.push_str(
-        format!(
-            "constexpr std::array<std::uint8_t,{}> {}{{{}}};",
-            array_len, symbol_name, array_contents
-        )
-        .as_str(),
-    );
+/// Find the input and the header generated for it, checking that the options asked for can be
+/// generated together
+fn resolve_target(
+    cli_args: &CliArgs,
+    amalgamating: bool,
+    diagnostics: &mut Diagnostics,
+) -> Result<Target, ()> {
+    let initial_warning_count = diagnostics.warning_count();
+    // The command stands in for the input path in diagnostics and metadata
+    let input_path = match cli_args.input_cmd {
+        Some(ref command) => PathBuf::from(command),
+        None => cli_args
+            .input_path
+            .first()
+            .cloned()
+            .expect("input path is required without --config, --files-from or --input-cmd"),
+    };
+    let reads_stdin = cli_args.input_cmd.is_none() && input_path.as_os_str() == "-";
+    // Neither commands nor stdin have a file to check or resolve
+    let reads_stream = cli_args.input_cmd.is_some() || reads_stdin;
+
+    let symlink_policy = match cli_args.no_follow_symlinks {
+        true => SymlinkPolicy::NoFollow,
+        false => SymlinkPolicy::Follow,
+    };
+
+    let resolved_input_path = match input::resolve_symlinks(&input_path, symlink_policy) {
+        _ if reads_stream => input_path.clone(),
+        Ok(p) => p,
+        Err(error) => {
+            diagnostics.error(error);
+            return Err(());
+        }
+    };
+
+    if resolved_input_path != input_path {
+        diagnostics.log(format!(
+            "input \"{}\" resolved to \"{}\"",
+            input_path.to_string_lossy(),
+            resolved_input_path.to_string_lossy()
+        ));
+    }
+
+    if !reads_stream && !input_path.exists() {
+        diagnostics.error(format!(
+            "file path \"{}\" does not exist",
+            input_path.to_string_lossy()
+        ));
+        return Err(());
+    }
 
-    // Close namespace (if need be)
-    match ns_name {
-        Some(_) => out_string.push_str("}"),
-        None => (),
+    // Anything other than a directory is accepted, so that named pipes, character devices and
+    // process substitutions can be read from
+    if !reads_stream && input_path.is_dir() != (cli_args.locales || cli_args.manifest) {
+        if cli_args.manifest {
+            diagnostics.error(format!(
+                "file path \"{}\" is not a directory, which --manifest expects",
+                input_path.to_string_lossy()
+            ));
+            return Err(());
+        }
+        if cli_args.locales {
+            diagnostics.error(format!(
+                "file path \"{}\" is not a directory, which --locales expects",
+                input_path.to_string_lossy()
+            ));
+            return Err(());
+        }
+        diagnostics.error(format!(
+            "file path \"{}\" is a directory (pass --recursive to embed every file in it)",
+            input_path.to_string_lossy()
+        ));
+        return Err(());
+    }
+
+    check_options(
+        cli_args,
+        &input_path,
+        reads_stream,
+        amalgamating,
+        diagnostics,
+    )?;
+
+    // Commands have no filename, so the output's stands in for it (it is required alongside
+    // --input-cmd), and neither does stdin, for which the symbol name is required instead
+    let named_path = match cli_args.input_cmd {
+        Some(_) => cli_args
+            .output_path
+            .as_deref()
+            .expect("output path is required with --input-cmd")
+            .with_extension(""),
+        None if reads_stdin => match cli_args.symbol_name {
+            Some(ref s) => PathBuf::from(s),
+            None => {
+                diagnostics.error("reading the input from stdin (-i -) requires --symbol-name");
+                return Err(());
+            }
+        },
+        None => input_path.clone(),
+    };
+    let input_filename = match named_path.file_name() {
+        Some(f) => f,
+        None => {
+            diagnostics.error(format!(
+                "input file path \"{}\" does not contain a valid filename",
+                input_path.to_string_lossy()
+            ));
+            return Err(());
+        }
     };
 
-    // Trailing newline
-    out_string.push_str(LINE_ENDING);
+    let output_path = output_path(cli_args, input_filename, amalgamating, diagnostics)?;
+
+    let symbol_name = derive_symbol(cli_args, &named_path, "", &input_path, diagnostics)?;
+
+    let (guard_style, guard_macro) = header_guard(
+        cli_args.guard,
+        cli_args.guard_name.as_deref(),
+        cli_args.language,
+        &symbol_name,
+    );
 
-    return out_string;
+    return Ok(Target {
+        input_name: input_path.to_string_lossy().to_string(),
+        input_path,
+        resolved_input_path,
+        reads_stdin,
+        symlink_policy,
+        output_path,
+        symbol_name,
+        guard_style,
+        guard_macro,
+        initial_warning_count,
+    });
 }
 
-fn generate_src_for_string(
-    string_contents: &str,
-    symbol_name: &str,
-    ns_name: Option<String>,
-) -> String {
-    // Includes
-    let mut out_string: String = String::with_capacity(string_contents.len() + 0x100);
+/// Check the options which cannot be generated together, whatever the input holds
+fn check_options(
+    cli_args: &CliArgs,
+    input_path: &Path,
+    reads_stream: bool,
+    amalgamating: bool,
+    diagnostics: &mut Diagnostics,
+) -> Result<(), ()> {
+    if let Some(reason) = cli_args
+        .language
+        .backend()
+        .unsupported(&requested_features(cli_args, amalgamating))
+    {
+        diagnostics.error(format!(
+            "--language {} {}",
+            cli_args.language.name(),
+            reason
+        ));
+        return Err(());
+    }
+
+    if cli_args.format != OutputFormat::Header
+        && (reads_stream
+            || cli_args.object.is_some()
+            || cli_args.xxd_compat
+            || cli_args.stub
+            || cli_args.guard_macro.is_some()
+            || cli_args.hybrid_loader.is_some()
+            || cli_args.verify_fn.is_some()
+            || cli_args.stream_accessor
+            || cli_args.span_accessor
+            || cli_args.encode != Encoding::Bytes
+            || cli_args.delta_base.is_some()
+            || cli_args.decode_csv.is_some()
+            || cli_args.decode_struct.is_some()
+            || cli_args.locales
+            || cli_args.manifest
+            || cli_args.emit_test.is_some()
+            || cli_args.split
+            || cli_args.compress.is_some()
+            || cli_args.obfuscate_xor.is_some()
+            || cli_args.dedupe
+            || cli_args.internal_linkage
+            || amalgamating)
+    {
+        diagnostics.error(format!(
+            "--format {} only includes an input file as it is, without stdin, --input-cmd, --object, --xxd-compat, --stub, --guard-macro, --hybrid-loader, --verify-fn, --stream-accessor, --span-accessor, --encode, --delta-base, --decode-csv, --decode-struct, --locales, --manifest, --emit-test, --split, --compress, --obfuscate-xor, --dedupe, --internal-linkage or several inputs in one header",
+            cli_args.format.name()
+        ));
+        return Err(());
+    }
+
+    // Resources are always whole files, and only Windows has them
+    if cli_args.format == OutputFormat::Rc
+        && (cli_args.offset.is_some()
+            || cli_args.length.is_some()
+            || cli_args.null_terminate
+            || cli_args.language == Language::C)
+    {
+        diagnostics.error(
+            "--format rc embeds the whole input in a C++ header, without --offset, --length, --null-terminate or --language c",
+        );
+        return Err(());
+    }
+
+    // The assembler resolves a relative path against its own working directory, so these include
+    // the input by an absolute path, which differs from one machine to the next
+    if cli_args.reproducible
+        && (cli_args.encode == Encoding::Incbin || cli_args.format != OutputFormat::Header)
+    {
+        diagnostics.error(
+            "--reproducible cannot be combined with --encode incbin or --format, which include the input by its absolute path",
+        );
+        return Err(());
+    }
+    if cli_args.reproducible
+        && !cli_args.no_metadata
+        && relative_to_current_dir(input_path).is_none()
+    {
+        diagnostics.warn(Warning::AbsolutePath {
+            path: input_path.to_string_lossy().to_string(),
+        });
+    }
+
+    if cli_args.guard_name.is_some()
+        && matches!(cli_args.guard, Some(GuardStyle::Pragma | GuardStyle::None))
+    {
+        diagnostics.error("--guard-name only applies to --guard ifdef");
+        return Err(());
+    }
+
+    return Ok(());
+}
 
-    // Namespace
-    match ns_name {
-        Some(ref namespace) => out_string.push_str(format!("namespace {}{{", namespace).as_str()),
-        None => (),
+/// Path of the header generated for an input, derived from the current working directory and the
+/// input's filename if neither an output path nor directory is given
+fn output_path(
+    cli_args: &CliArgs,
+    input_filename: &OsStr,
+    amalgamating: bool,
+    diagnostics: &mut Diagnostics,
+) -> Result<PathBuf, ()> {
+    let cwd = match std::env::current_dir() {
+        Ok(p) => p,
+        Err(_) => {
+            diagnostics.error("environment's current working directory is unavailable");
+            return Err(());
+        }
     };
 
-    // String initialisation
-    out_string.push_str(
-        format!(
-            "constexpr const char* {} = \"{}\";",
-            symbol_name, string_contents
-        )
-        .as_str(),
+    let extension = cli_args.language.extension();
+    return match (cli_args.output_path.clone(), cli_args.output_dir.as_ref()) {
+        (Some(p), _) => Ok(p),
+        (None, Some(dir)) => {
+            if !dir.is_dir() {
+                diagnostics.error(format!(
+                    "output directory \"{}\" does not exist or is not a directory",
+                    dir.to_string_lossy()
+                ));
+                return Err(());
+            }
+            let p = dir.join(input_filename).with_extension(extension);
+            diagnostics.log(format!(
+                "output path derived as \"{}\"",
+                p.to_string_lossy()
+            ));
+            Ok(p)
+        }
+        // Never written, the source ends up in the amalgamation
+        (None, None) if amalgamating => Ok(cwd.join(input_filename).with_extension(extension)),
+        (None, None) => {
+            // Build wrappers often run from unexpected directories, so say where the output went
+            let p = cwd.join(input_filename).with_extension(extension);
+            diagnostics.note(format!(
+                "no output path or directory given, writing to \"{}\"",
+                p.to_string_lossy()
+            ));
+            Ok(p)
+        }
+    };
+}
+
+/// Wrap a header generated for a whole directory in its regions and guard, and write it
+fn write_wrapped(
+    cli_args: &CliArgs,
+    target: &Target,
+    out_src: &str,
+    data: &[u8],
+    symbols: &[String],
+    outputs: &mut Outputs,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<PathBuf>, ()> {
+    let out_src = match cli_args.regions {
+        Some(style) => region::wrap(out_src, &target.symbol_name, style),
+        None => out_src.to_string(),
+    };
+    let out_src = guard::wrap(&out_src, target.guard_style, &target.guard_macro);
+    return write_generated(
+        &target.output_path,
+        Generated {
+            src: &out_src,
+            provenance: Some(Provenance {
+                source: &target.input_name,
+                data,
+                symbols,
+            }),
+            companion: None,
+        },
+        cli_args,
+        target.initial_warning_count,
+        outputs,
+        diagnostics,
     );
+}
+
+/// The data read for an input
+struct Input {
+    data: InputData,
+    /// Size of the input, which in stub mode is not that of the data
+    len: u64,
+}
+
+/// Read the input, or the part of it asked for with --offset and --length
+fn read_input(
+    cli_args: &CliArgs,
+    target: &Target,
+    diagnostics: &mut Diagnostics,
+) -> Result<Input, ()> {
+    // In stub mode the input is never read, a placeholder of the same type is emitted instead
+    let mut input_len: u64;
+    let read_start = Instant::now();
+    let mut buf = if cli_args.stub {
+        input_len = match target.input_path.metadata() {
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        };
+
+        InputData::Owned(stub_data(cli_args.binary == Some(true)))
+    } else if let Some(ref command) = cli_args.input_cmd {
+        let data = match input::run_command(command) {
+            Ok(data) => data,
+            Err(error) => {
+                diagnostics.error(format!("input command \"{}\" failed: {}", command, error));
+                return Err(());
+            }
+        };
+        input_len = data.len() as u64;
+        InputData::Owned(data)
+    } else if target.reads_stdin {
+        let mut data = Vec::new();
+        if let Err(error) = io::stdin().lock().read_to_end(&mut data) {
+            diagnostics.error(format!("failed to read input from stdin: {}", error));
+            return Err(());
+        }
+        input_len = data.len() as u64;
+        InputData::Owned(data)
+    } else {
+        let input_file = match OpenOptions::new()
+            .read(true)
+            .open(&target.resolved_input_path)
+        {
+            Ok(f) => f,
+            Err(error) => {
+                diagnostics.error(format!("failed to open input file for reading: {}", error));
+                return Err(());
+            }
+        };
 
-    // Close namespace (if need be)
-    match ns_name {
-        Some(_) => out_string.push_str("}"),
-        None => (),
+        let data = match mmap::read(&input_file, cli_args.mmap_threshold) {
+            Ok(data) => data,
+            Err(error) => {
+                diagnostics.error(format!("failed read input file: {}", error));
+                return Err(());
+            }
+        };
+        input_len = data.len() as u64;
+        if let InputData::Mapped(_) = data {
+            diagnostics.log("input memory-mapped");
+        }
+        data
     };
+    diagnostics.log(format!(
+        "read {} bytes in {:.3?}",
+        input_len,
+        read_start.elapsed()
+    ));
+
+    // Only part of the input is embedded, as if it had been cut out of it beforehand
+    if cli_args.offset.is_some() || cli_args.length.is_some() {
+        let offset = cli_args.offset.unwrap_or(0);
+        let available = (input_len as usize).checked_sub(offset);
+        let len = match (available, cli_args.length) {
+            (Some(available), None) => available,
+            (Some(available), Some(length)) if length <= available => length,
+            _ if cli_args.stub => 0,
+            _ => {
+                diagnostics.error(format!(
+                    "input file \"{}\" is only {} bytes, too short for {}",
+                    target.input_name,
+                    input_len,
+                    match cli_args.length {
+                        Some(length) => format!("--offset {} --length {}", offset, length),
+                        None => format!("--offset {}", offset),
+                    }
+                ));
+                return Err(());
+            }
+        };
+        if !cli_args.stub {
+            buf = buf.slice(offset..offset + len);
+        }
+        input_len = len as u64;
+        diagnostics.log(format!(
+            "embedding {} bytes from offset {}",
+            input_len, offset
+        ));
+    }
 
-    // Trailing newline
-    out_string.push_str(LINE_ENDING);
+    if input_len == 0 {
+        diagnostics.warn(Warning::EmptyInput {
+            path: target.input_name.clone(),
+        });
+    } else if input_len > LARGE_INPUT_THRESHOLD {
+        diagnostics.warn(Warning::LargeInput {
+            path: target.input_name.clone(),
+            size: input_len,
+        });
+    }
+
+    return Ok(Input {
+        data: buf,
+        len: input_len,
+    });
+}
+
+/// The data of an input, and how it is embedded
+struct Embedding {
+    data: InputData,
+    representation: Representation,
+    options: SourceOptions,
+}
+
+/// Decide how the data read for an input is embedded, checking that it can be with the options
+/// asked for
+fn embedding(
+    cli_args: &CliArgs,
+    target: &Target,
+    mut input: Input,
+    amalgamating: bool,
+    diagnostics: &mut Diagnostics,
+) -> Result<Embedding, ()> {
+    check_encoding(cli_args, target, &input.data, diagnostics)?;
 
-    return out_string;
+    let delta = match cli_args.delta_base {
+        Some(ref base_path) => {
+            let base = match std::fs::read(base_path) {
+                Ok(data) => data,
+                Err(error) => {
+                    diagnostics.error(format!(
+                        "failed to read delta base \"{}\": {}",
+                        base_path.to_string_lossy(),
+                        error
+                    ));
+                    return Err(());
+                }
+            };
+            let ops = delta::encode(&base, &input.data);
+            diagnostics.log(format!(
+                "delta against \"{}\" is {} bytes for {} bytes of input",
+                base_path.to_string_lossy(),
+                ops.len(),
+                input.data.len()
+            ));
+            Some(delta::DeltaBase {
+                len: base.len(),
+                crc32: checksum::crc32(&base),
+                ops,
+            })
+        }
+        None => None,
+    };
+
+    let representation =
+        choose_representation(cli_args, target, &mut input, delta.is_some(), diagnostics);
+    check_representation(cli_args, representation, amalgamating, diagnostics)?;
+    let Input {
+        data: buf,
+        len: input_len,
+    } = input;
+
+    let xor_key = match cli_args.obfuscate_xor {
+        Some(ref key) => match obfuscate::parse_key(key) {
+            Ok(key) => Some(key),
+            Err(error) => {
+                diagnostics.error(format!("--obfuscate-xor {}: {}", key, error));
+                return Err(());
+            }
+        },
+        None => None,
+    };
+    if xor_key.is_some() && representation != Representation::BraceInitialiser {
+        diagnostics.error(
+            "--obfuscate-xor needs the data embedded as a byte array (pass --binary, without --encode or --auto-optimize)",
+        );
+        return Err(());
+    }
+
+    // The header is still generated, so that the build fails where the assertion is
+    let failed_assertion = match (cli_args.assert_size, cli_args.assert_size_max) {
+        (Some(expected), _) if buf.len() != expected => Some(format!("exactly {}", expected)),
+        (_, Some(max)) if buf.len() > max => Some(format!("at most {}", max)),
+        _ => None,
+    };
+    if let Some(expectation) = failed_assertion {
+        diagnostics.warn(Warning::SizeAssertionFails {
+            path: target.input_name.clone(),
+            size: buf.len(),
+            expectation,
+        });
+    }
+
+    if cli_args.auto_optimize && input_len > LARGE_INPUT_THRESHOLD {
+        diagnostics.note(format!(
+            "input file \"{}\" is large enough that linking it as an object file would compile faster than any header",
+            target.input_name
+        ));
+    }
+
+    let embedded_len = match delta {
+        Some(ref delta) => delta.ops.len(),
+        None => buf.len(),
+    };
+    let compile_cost = cost::estimate(representation, embedded_len as u64);
+    if cli_args.report_cost {
+        diagnostics.note(format!("estimated compile cost: {}", compile_cost));
+    }
+    if compile_cost.class >= CostClass::Heavy {
+        diagnostics.warn(Warning::HighCompileCost {
+            path: target.input_name.clone(),
+            cost: compile_cost,
+        });
+    }
+
+    diagnostics.log(format!("representation: {:?}", representation));
+
+    let (incbin_path, embed_path) = included_paths(cli_args, target, representation, diagnostics)?;
+
+    let options = SourceOptions {
+        language: cli_args.language,
+        ns_name: cli_args.namespace.clone(),
+        guard_macro: cli_args.guard_macro.clone(),
+        loader_path: cli_args
+            .hybrid_loader
+            .as_ref()
+            .map(|p| p.to_string_lossy().to_string()),
+        verifier: cli_args.verify_fn.map(|algorithm| Verifier {
+            algorithm,
+            digest: algorithm.digest(&buf),
+        }),
+        incbin_path,
+        embed_path,
+        input_offset: cli_args.offset.or(cli_args.length.map(|_| 0)),
+        delta,
+        stream_accessor: cli_args.stream_accessor,
+        span_accessor: cli_args.span_accessor,
+        comment: cli_args.comment.clone(),
+        char_type: cli_args.char_type,
+        text_view: cli_args.string_view,
+        raw_text: cli_args.raw_string,
+        max_literal_len: cli_args.max_literal_len,
+        internal_linkage: cli_args.internal_linkage,
+        extern_data: cli_args.split,
+        align: cli_args.align,
+        section: cli_args.section.clone(),
+        null_terminate: cli_args.null_terminate,
+        size_constant: cli_args.size_constant,
+        checksums: checksums(&cli_args.checksum, &buf),
+        checksum_hex: cli_args.checksum_hex,
+        assert_size: cli_args.assert_size,
+        assert_size_max: cli_args.assert_size_max,
+        element_type: cli_args.element_type,
+        xor_key,
+        byte_format: ByteFormat {
+            bytes_per_line: cli_args.bytes_per_line,
+            indent: cli_args.indent.clone(),
+            radix: cli_args.radix,
+            uppercase: cli_args.uppercase_hex,
+            zero_pad: cli_args.zero_pad,
+            space_after_comma: cli_args.space_after_comma,
+        },
+    };
+
+    return Ok(Embedding {
+        data: buf,
+        representation,
+        options,
+    });
+}
+
+/// Check that the data can be encoded as asked for by --encode
+fn check_encoding(
+    cli_args: &CliArgs,
+    target: &Target,
+    buf: &[u8],
+    diagnostics: &mut Diagnostics,
+) -> Result<(), ()> {
+    if cli_args.endian.is_some() && cli_args.encode.word_size().is_none() {
+        diagnostics.error("--endian only applies to --encode u16, u32 or u64");
+        return Err(());
+    }
+
+    // The accessors all see words rather than bytes
+    if cli_args.encode.word_size().is_some()
+        && (cli_args.hybrid_loader.is_some()
+            || cli_args.verify_fn.is_some()
+            || cli_args.emit_test.is_some()
+            || cli_args.stream_accessor
+            || cli_args.span_accessor)
+    {
+        diagnostics.error(
+            "--encode u16, u32 and u64 cannot be combined with --hybrid-loader, --verify-fn, --emit-test, --stream-accessor or --span-accessor",
+        );
+        return Err(());
+    }
+    if let Some(word_size) = cli_args.encode.word_size()
+        && !buf.len().is_multiple_of(word_size.bytes())
+    {
+        diagnostics.warn(Warning::PartialWord {
+            path: target.input_name.clone(),
+            size: buf.len(),
+            word_bytes: word_size.bytes(),
+        });
+    }
+
+    if cli_args.encode == Encoding::Bits
+        && (cli_args.hybrid_loader.is_some()
+            || cli_args.verify_fn.is_some()
+            || cli_args.emit_test.is_some()
+            || cli_args.stream_accessor)
+    {
+        diagnostics.error(
+            "--encode bits cannot be combined with --hybrid-loader, --verify-fn, --emit-test or --stream-accessor",
+        );
+        return Err(());
+    }
+
+    if cli_args.delta_base.is_some() && cli_args.encode != Encoding::Bytes {
+        diagnostics.error(
+            "--delta-base cannot be combined with --encode bits, incbin, embed, u16, u32 or u64",
+        );
+        return Err(());
+    }
+
+    if cli_args.split && cli_args.encode != Encoding::Bytes {
+        diagnostics
+            .error("--split cannot be combined with --encode bits, incbin, embed, u16, u32 or u64");
+        return Err(());
+    }
+
+    if cli_args.compress.is_some() && cli_args.encode != Encoding::Bytes {
+        diagnostics.error(
+            "--compress cannot be combined with --encode bits, incbin, embed, u16, u32 or u64",
+        );
+        return Err(());
+    }
+
+    return Ok(());
+}
+
+/// Pick how the data is represented in the source, replacing text which is not UTF-8 if asked to
+fn choose_representation(
+    cli_args: &CliArgs,
+    target: &Target,
+    input: &mut Input,
+    delta: bool,
+    diagnostics: &mut Diagnostics,
+) -> Representation {
+    let mut representation = match cli_args.binary {
+        // Object, assembler and resource files (and templates) hold the bytes as they are,
+        // whatever the mode
+        _ if cli_args.object.is_some()
+            || cli_args.format != OutputFormat::Header
+            || cli_args.template.is_some() =>
+        {
+            Representation::BraceInitialiser
+        }
+        _ if delta => Representation::Delta,
+        _ if let Some(algorithm) = cli_args.compress => Representation::Compressed(algorithm),
+        _ if cli_args.encode == Encoding::Bits => Representation::PackedBits,
+        _ if cli_args.encode == Encoding::Incbin => Representation::Incbin,
+        _ if cli_args.encode == Encoding::Embed => Representation::Embed,
+        _ if let Some(size) = cli_args.encode.word_size() => Representation::Words(WordLayout {
+            size,
+            endian: cli_args.endian.unwrap_or(Endian::Little),
+        }),
+        Some(true) if cli_args.auto_optimize => select_representation(input.len),
+        Some(true) => Representation::BraceInitialiser,
+        _ => Representation::StringLiteral,
+    };
+
+    if representation == Representation::StringLiteral && std::str::from_utf8(&input.data).is_err()
+    {
+        match cli_args.invalid_utf8 {
+            InvalidUtf8::Escape => diagnostics.warn(Warning::NotUtf8 {
+                path: target.input_name.clone(),
+                fallback: "its invalid bytes are escaped",
+            }),
+            InvalidUtf8::Lossy => {
+                input.data = InputData::Owned(
+                    String::from_utf8_lossy(&input.data)
+                        .into_owned()
+                        .into_bytes(),
+                );
+                input.len = input.data.len() as u64;
+            }
+            InvalidUtf8::Binary => {
+                diagnostics.warn(Warning::NotUtf8 {
+                    path: target.input_name.clone(),
+                    fallback: "embedding it as binary data",
+                });
+                representation = Representation::BraceInitialiser;
+            }
+        }
+    }
+
+    return representation;
+}
+
+/// Check that the options asked for apply to the data as it is represented
+fn check_representation(
+    cli_args: &CliArgs,
+    representation: Representation,
+    amalgamating: bool,
+    diagnostics: &mut Diagnostics,
+) -> Result<(), ()> {
+    if cli_args.span_accessor
+        && !matches!(
+            representation,
+            Representation::BraceInitialiser | Representation::Embed
+        )
+    {
+        diagnostics.error(format!(
+            "--span-accessor needs the data embedded as a byte array, not as {}",
+            match representation {
+                Representation::StringLiteral => "a string literal (pass --binary)",
+                Representation::EscapedStringLiteral =>
+                    "a string literal (as picked by --auto-optimize)",
+                Representation::PackedBits => "packed bits",
+                _ => "a reference to assembler data",
+            }
+        ));
+        return Err(());
+    }
+
+    // Skipping into a file with #embed needs vendor extensions, though its limit is standard
+    if representation == Representation::Embed && cli_args.offset.is_some_and(|offset| offset > 0) {
+        diagnostics.error("--offset cannot be combined with --encode embed");
+        return Err(());
+    }
+
+    if let Some(align) = cli_args.align {
+        if !align.is_power_of_two() {
+            diagnostics.error(format!("--align {} is not a power of two", align));
+            return Err(());
+        }
+        // Every C representation is an array, but C++ text is a pointer or a view
+        if cli_args.language == Language::Cpp
+            && !matches!(
+                representation,
+                Representation::BraceInitialiser
+                    | Representation::Incbin
+                    | Representation::Embed
+                    | Representation::Words(_)
+                    | Representation::Compressed(_)
+            )
+        {
+            diagnostics.error(format!(
+                "--align needs the data embedded as a byte array, not as {}",
+                match representation {
+                    Representation::StringLiteral => "a string literal (pass --binary)",
+                    Representation::EscapedStringLiteral =>
+                        "a string literal (as picked by --auto-optimize)",
+                    _ => "packed bits",
+                }
+            ));
+            return Err(());
+        }
+    }
+
+    if let Some(ref section) = cli_args.section {
+        if section.is_empty() || section.contains(['"', '\\']) {
+            diagnostics.error(format!(
+                "--section \"{}\" is not a valid section name",
+                section
+            ));
+            return Err(());
+        }
+        if cli_args.language == Language::Cpp
+            && !matches!(
+                representation,
+                Representation::BraceInitialiser
+                    | Representation::Incbin
+                    | Representation::Embed
+                    | Representation::Words(_)
+                    | Representation::Compressed(_)
+            )
+        {
+            diagnostics.error(
+                "--section needs the data embedded as a byte array, not as a string literal or packed bits",
+            );
+            return Err(());
+        }
+    }
+
+    if cli_args.element_type != ElementType::Uint8 {
+        if !matches!(
+            representation,
+            Representation::BraceInitialiser | Representation::Incbin | Representation::Embed
+        ) {
+            diagnostics.error(
+                "--element-type needs the data embedded as a byte array, not as a string literal or packed bits",
+            );
+            return Err(());
+        }
+        // #embed expands to int literals, which only initialise unsigned elements implicitly
+        if representation == Representation::Embed
+            && !matches!(cli_args.element_type, ElementType::UnsignedChar)
+        {
+            diagnostics.error(format!(
+                "--element-type {} cannot be initialised by #embed (pass --element-type unsigned-char or another --encode)",
+                cli_args.element_type.name()
+            ));
+            return Err(());
+        }
+        // The loader reads the file through a char iterator
+        if cli_args.element_type == ElementType::Byte && cli_args.hybrid_loader.is_some() {
+            diagnostics.error("--element-type byte cannot be used with --hybrid-loader");
+            return Err(());
+        }
+    }
+
+    // String literals are NUL-terminated anyway
+    if cli_args.null_terminate && representation != Representation::BraceInitialiser {
+        diagnostics.error("--null-terminate only applies to binary data embedded as a byte array");
+        return Err(());
+    }
+
+    if cli_args.char_type != CharType::Char && representation != Representation::StringLiteral {
+        diagnostics.error("--char-type only applies to text embedded as a string literal");
+        return Err(());
+    }
+
+    if cli_args.raw_string {
+        if representation != Representation::StringLiteral {
+            diagnostics.error("--raw-string only applies to text embedded as a string literal");
+            return Err(());
+        }
+        // Merging the headers rewrites their lines, which would reach into the text
+        if amalgamating {
+            diagnostics.error(
+                "--raw-string cannot be used when generating several inputs into one header",
+            );
+            return Err(());
+        }
+    }
+
+    return Ok(());
+}
+
+/// Paths by which the assembler (for .incbin) or the preprocessor (for #embed) include the input,
+/// if it is represented as either
+fn included_paths(
+    cli_args: &CliArgs,
+    target: &Target,
+    representation: Representation,
+    diagnostics: &mut Diagnostics,
+) -> Result<(Option<String>, Option<String>), ()> {
+    // The assembler resolves relative paths against its own working directory, which is rarely the
+    // one the header was generated from
+    let incbin_path = match representation {
+        Representation::Incbin => match target.resolved_input_path.canonicalize() {
+            Ok(p) => Some(
+                input::strip_verbatim_prefix(&p)
+                    .to_string_lossy()
+                    .to_string(),
+            ),
+            Err(error) => {
+                diagnostics.error(format!(
+                    "failed to resolve the absolute path of \"{}\" for .incbin: {}",
+                    target.input_name, error
+                ));
+                return Err(());
+            }
+        },
+        _ => None,
+    };
+
+    // The preprocessor resolves the path against the directory containing the header, wherever
+    // it is compiled from
+    let embed_path = match representation {
+        Representation::Embed => {
+            let header_path = cli_args
+                .amalgamate
+                .as_deref()
+                .unwrap_or(&target.output_path);
+            let relative_path = match (
+                target.resolved_input_path.canonicalize(),
+                input::canonicalize_output(header_path),
+            ) {
+                (Ok(p), Some(header_path)) => header_path
+                    .parent()
+                    .and_then(|header_dir| input::relative_path(&p, header_dir)),
+                _ => None,
+            };
+            match relative_path {
+                Some(p) if !p.contains('"') => Some(p),
+                Some(p) => {
+                    diagnostics.error(format!(
+                        "path \"{}\" cannot be embedded with #embed as it contains a double quote",
+                        p
+                    ));
+                    return Err(());
+                }
+                None => {
+                    diagnostics.error(format!(
+                        "failed to find the path of \"{}\" relative to \"{}\" for #embed",
+                        target.input_name,
+                        header_path.to_string_lossy()
+                    ));
+                    return Err(());
+                }
+            }
+        }
+        _ => None,
+    };
+
+    return Ok((incbin_path, embed_path));
+}
+
+/// Format the data into a template (--template) and write the result as it is
+fn write_template(
+    cli_args: &CliArgs,
+    target: &Target,
+    template_path: &Path,
+    embedding: &Embedding,
+    outputs: &mut Outputs,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<PathBuf>, ()> {
+    let template_src = match std::fs::read_to_string(template_path) {
+        Ok(template_src) => template_src,
+        Err(error) => {
+            diagnostics.error(format!(
+                "failed to read template \"{}\": {}",
+                template_path.to_string_lossy(),
+                error
+            ));
+            return Err(());
+        }
+    };
+    let variables = template::Variables::new(
+        &embedding.data,
+        &target.symbol_name,
+        &target.input_name,
+        &embedding.options,
+    );
+    let out_src = match template::render(&template_src, &variables) {
+        Ok(out_src) => out_src,
+        Err(error) => {
+            diagnostics.error(format!(
+                "template \"{}\", {}",
+                template_path.to_string_lossy(),
+                error
+            ));
+            return Err(());
+        }
+    };
+    return write_generated(
+        &target.output_path,
+        Generated {
+            src: &out_src,
+            provenance: None,
+            companion: None,
+        },
+        cli_args,
+        target.initial_warning_count,
+        outputs,
+        diagnostics,
+    );
+}
+
+/// Write the data into an object file (--object), next to a header declaring it
+fn write_object(
+    cli_args: &CliArgs,
+    target: &Target,
+    object_format: ObjectFormat,
+    embedding: &Embedding,
+    outputs: &mut Outputs,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<PathBuf>, ()> {
+    let generate_start = Instant::now();
+    let buf = &embedding.data;
+    let symbol_name = &target.symbol_name;
+    let object_arch = cli_args.object_arch.unwrap_or_else(ObjectArch::host);
+    let object_src = match object::generate_object(
+        buf,
+        symbol_name,
+        object_format,
+        object_arch,
+        &embedding.options,
+    ) {
+        Ok(object_src) => object_src,
+        Err(error) => {
+            diagnostics.error(error);
+            return Err(());
+        }
+    };
+    let out_src = object::generate_header_src(buf, symbol_name, &embedding.options);
+    let out_src = guard::wrap(&out_src, target.guard_style, &target.guard_macro);
+    diagnostics.log(format!(
+        "generated {} byte object file in {:.3?}",
+        object_src.len(),
+        generate_start.elapsed()
+    ));
+    return write_generated(
+        &target.output_path,
+        Generated {
+            src: &out_src,
+            provenance: Some(Provenance {
+                source: &target.input_name,
+                data: buf,
+                symbols: &[symbol_name.clone(), format!("{}_size", symbol_name)],
+            }),
+            companion: Some((object_format.extension(), &object_src)),
+        },
+        cli_args,
+        target.initial_warning_count,
+        outputs,
+        diagnostics,
+    );
+}
+
+/// Write an assembler or resource file including the input (--format), next to a header
+/// declaring its data
+fn write_assembled(
+    cli_args: &CliArgs,
+    target: &Target,
+    embedding: &Embedding,
+    outputs: &mut Outputs,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<PathBuf>, ()> {
+    let symbol_name = &target.symbol_name;
+    let source_options = &embedding.options;
+    let absolute_path = match target.resolved_input_path.canonicalize() {
+        Ok(p) => input::strip_verbatim_prefix(&p)
+            .to_string_lossy()
+            .to_string(),
+        Err(error) => {
+            diagnostics.error(format!(
+                "failed to resolve the absolute path of \"{}\" for --format {}: {}",
+                target.input_name,
+                cli_args.format.name(),
+                error
+            ));
+            return Err(());
+        }
+    };
+    let (companion_src, extension, out_src, symbols) = match cli_args.format {
+        OutputFormat::Asm => (
+            asm::generate_src(
+                &absolute_path,
+                embedding.data.len(),
+                symbol_name,
+                cli_args.asm_target.unwrap_or_else(ObjectFormat::host),
+                source_options,
+            ),
+            "s",
+            asm::generate_header_src(symbol_name, source_options),
+            ["start", "end", "size"]
+                .iter()
+                .map(|suffix| format!("{}_{}", symbol_name, suffix))
+                .collect(),
+        ),
+        OutputFormat::Rc => (
+            rc::generate_src(&absolute_path, symbol_name, source_options),
+            "rc",
+            rc::generate_header_src(symbol_name, source_options),
+            vec![symbol_name.clone()],
+        ),
+        OutputFormat::Header => unreachable!("headers embedding the data are written elsewhere"),
+    };
+    let out_src = guard::wrap(&out_src, target.guard_style, &target.guard_macro);
+    return write_generated(
+        &target.output_path,
+        Generated {
+            src: &out_src,
+            provenance: Some(Provenance {
+                source: &target.input_name,
+                data: &embedding.data,
+                symbols: &symbols,
+            }),
+            companion: Some((extension, companion_src.as_bytes())),
+        },
+        cli_args,
+        target.initial_warning_count,
+        outputs,
+        diagnostics,
+    );
+}
+
+/// Write a header embedding the data (or add its source to the amalgamation), along with the
+/// definitions, index entry, self-check and unit test asked for
+fn write_embedding(
+    cli_args: &CliArgs,
+    target: &Target,
+    embedding: &Embedding,
+    batch: Batch,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<PathBuf>, ()> {
+    let Batch {
+        amalgamation,
+        index,
+        originals,
+        outputs,
+    } = batch;
+    let generate_start = Instant::now();
+    let buf = &embedding.data;
+    let representation = embedding.representation;
+    let source_options = &embedding.options;
+    let symbol_name = &target.symbol_name;
+    let output_path = &target.output_path;
+
+    let qualified_symbol = match source_options.ns_name {
+        Some(ref namespace) => format!("::{}::{}", namespace, symbol_name),
+        None => format!("::{}", symbol_name),
+    };
+
+    // Inputs identical to an earlier one refer to its data rather than embedding it again
+    let content_key = originals
+        .as_ref()
+        .map(|_| dedupe::ContentKey::new(buf, representation, source_options));
+    let duplicate_of = match (&originals, &content_key) {
+        (Some(originals), Some(key)) => originals
+            .iter()
+            .find(|original| original.key == *key)
+            .cloned(),
+        _ => None,
+    };
+    if let Some(ref original) = duplicate_of {
+        diagnostics.log(format!(
+            "\"{}\" has the same content as \"{}\", referring to \"{}\"",
+            target.input_name, original.name, original.symbol
+        ));
+    }
+
+    // Data embedded as bytes is formatted straight into the output file rather than into memory
+    // first whenever nothing needs the whole source, which saves several times the size of large
+    // inputs in memory
+    let streamable = cli_args.decode_csv.is_none()
+        && cli_args.decode_struct.is_none()
+        && !cli_args.toc
+        && cli_args.regions.is_none()
+        && outputs.write_mode == WriteMode::Always
+        && amalgamation.is_none()
+        && duplicate_of.is_none()
+        && matches!(
+            representation,
+            Representation::BraceInitialiser
+                | Representation::EscapedStringLiteral
+                | Representation::Incbin
+                | Representation::Embed
+        );
+
+    let written_output = if streamable {
+        let metadata_block = Provenance {
+            source: &target.input_name,
+            data: buf,
+            symbols: std::slice::from_ref(symbol_name),
+        }
+        .block(cli_args);
+        let fatal_warning_count =
+            fatal_warning_count(cli_args, target.initial_warning_count, diagnostics);
+        let written_output =
+            outputs.write_with(output_path, fatal_warning_count, diagnostics, |writer| {
+                writer.write_all(metadata_block.as_bytes())?;
+                writer
+                    .write_all(guard::open(target.guard_style, &target.guard_macro).as_bytes())?;
+                bin2hpp::write_src(writer, buf, representation, symbol_name, source_options)?;
+                return writer.write_all(guard::close(target.guard_style).as_bytes());
+            })?;
+        diagnostics.log(format!(
+            "generated and wrote source in {:.3?}",
+            generate_start.elapsed()
+        ));
+        written_output
+    } else {
+        let out_src = embedded_src(cli_args, target, embedding, &duplicate_of, diagnostics)?;
+
+        diagnostics.log(format!(
+            "generated {} bytes of source in {:.3?}",
+            out_src.len(),
+            generate_start.elapsed()
+        ));
+
+        let out_src = match cli_args.regions {
+            Some(style) => region::wrap(&out_src, symbol_name, style),
+            None => out_src,
+        };
+
+        // The symbol through which the data is accessed
+        let access_symbol = match representation {
+            Representation::Delta => format!("{}_apply", symbol_name),
+            _ => symbol_name.clone(),
+        };
+
+        let toc_entry = toc::TocEntry {
+            symbol: access_symbol.clone(),
+            source: target.input_name.clone(),
+            size: buf.len(),
+        };
+
+        if let Some(parts) = amalgamation {
+            let view_expr = match representation {
+                _ if cli_args.decode_csv.is_some() || cli_args.decode_struct.is_some() => None,
+                _ if cli_args.span_accessor => Some(format!("{}()", qualified_symbol)),
+                Representation::BraceInitialiser
+                | Representation::EscapedStringLiteral
+                | Representation::Incbin
+                | Representation::Embed => Some(qualified_symbol.clone()),
+                Representation::StringLiteral => Some(format!(
+                    "{}{{{}}}",
+                    cli_args.char_type.view_type(),
+                    qualified_symbol
+                )),
+                Representation::PackedBits
+                | Representation::Words(_)
+                | Representation::Compressed(_)
+                | Representation::Delta => None,
+            };
+            if let (Some(originals), Some(key), None) = (originals, content_key, &duplicate_of) {
+                originals.push(dedupe::Original {
+                    key,
+                    name: target.input_name.clone(),
+                    symbol: qualified_symbol.clone(),
+                    header: None,
+                });
+            }
+            parts.push(amalgamate::Part {
+                src: out_src,
+                name: target.input_name.clone(),
+                view_expr,
+                access_symbol,
+                ns_name: source_options.ns_name.clone(),
+                toc_entry,
+                // What the index points at is the data as embedded
+                data: match source_options.xor_key {
+                    Some(ref key) => obfuscate::xor(buf, key),
+                    None => buf.to_vec(),
+                },
+            });
+            return Ok(Vec::new());
+        }
+
+        let out_src = guard::wrap(&out_src, target.guard_style, &target.guard_macro);
+        let out_src = if cli_args.toc {
+            toc::prepend_toc(
+                &out_src,
+                &[toc_entry],
+                metadata::line_count(cli_args.banner_text.as_deref(), !cli_args.no_metadata),
+            )
+        } else {
+            out_src
+        };
+
+        // Without a companion, at most the header itself is written
+        write_generated(
+            output_path,
+            Generated {
+                src: &out_src,
+                provenance: Some(Provenance {
+                    source: &target.input_name,
+                    data: buf,
+                    symbols: &[access_symbol],
+                }),
+                companion: None,
+            },
+            cli_args,
+            target.initial_warning_count,
+            outputs,
+            diagnostics,
+        )?
+        .pop()
+    };
+    // Headers which are up to date are referred to all the same
+    let header_path = written_output.as_deref().unwrap_or(output_path);
+
+    let definition_output = match cli_args.split {
+        true => write_definitions(
+            cli_args,
+            target,
+            embedding,
+            header_path,
+            outputs,
+            diagnostics,
+        )?,
+        false => None,
+    };
+
+    if let Some(entries) = index {
+        let byte_array = cli_args.decode_csv.is_none()
+            && cli_args.decode_struct.is_none()
+            && source_options.xor_key.is_none()
+            && source_options.element_type == ElementType::Uint8
+            && matches!(
+                representation,
+                Representation::BraceInitialiser | Representation::Incbin | Representation::Embed
+            );
+        match byte_array {
+            true => {
+                let index_dir = cli_args
+                    .index
+                    .as_deref()
+                    .and_then(Path::parent)
+                    .unwrap_or(Path::new(""));
+                entries.push(IndexEntry {
+                    name: target.input_name.clone(),
+                    include: index_name(&header_path.to_string_lossy(), &[index_dir.to_path_buf()]),
+                    span_expr: match source_options.span_accessor {
+                        true => format!("{}()", qualified_symbol),
+                        false => format!("std::span<const std::uint8_t>{{{}}}", qualified_symbol),
+                    },
+                    // Assembler data is only reached through a reinterpret_cast
+                    constant: representation != Representation::Incbin && !cli_args.split,
+                });
+            }
+            false => diagnostics.note(format!(
+                "leaving \"{}\" out of the index, its data is not embedded as a byte array",
+                target.input_name
+            )),
+        }
+    }
+
+    if let (Some(originals), Some(key), None) = (originals, content_key, &duplicate_of) {
+        originals.push(dedupe::Original {
+            key,
+            name: target.input_name.clone(),
+            symbol: qualified_symbol.clone(),
+            header: Some(header_path.to_path_buf()),
+        });
+    }
+
+    let Some(output_path) = written_output else {
+        return Ok(definition_output.into_iter().collect());
+    };
+
+    // With --split the data is only spelled out in the definitions, which have no span accessor
+    // leading to the storage
+    let (check_path, check_symbol) = match cli_args.split {
+        true if cli_args.span_accessor => (
+            definition_output.clone(),
+            format!("{}_storage", symbol_name),
+        ),
+        true => (definition_output.clone(), symbol_name.clone()),
+        false => (Some(output_path.clone()), symbol_name.clone()),
+    };
+    if cli_args.self_check
+        && let Some(check_path) = check_path
+    {
+        let checkable = cli_args.decode_csv.is_none()
+            && cli_args.decode_struct.is_none()
+            && !cli_args.stub
+            && duplicate_of.is_none()
+            && matches!(
+                representation,
+                Representation::BraceInitialiser
+                    | Representation::EscapedStringLiteral
+                    | Representation::StringLiteral
+                    | Representation::Incbin
+                    | Representation::Embed
+                    | Representation::Compressed(_)
+            );
+        match checkable {
+            true => {
+                // C arrays are read back only as far as their `<symbol>_len`
+                let mut expected = match (representation, &source_options.xor_key) {
+                    (Representation::Compressed(algorithm), _) => algorithm.compress(buf),
+                    (_, Some(key)) => obfuscate::xor(buf, key),
+                    _ => buf.to_vec(),
+                };
+                if cli_args.null_terminate && cli_args.language == Language::Cpp {
+                    expected.push(0);
+                }
+                self_check(&check_path, &[(&check_symbol, &expected)], diagnostics)?
+            }
+            false => diagnostics.note(format!(
+                "skipping self-check of \"{}\", its data is not embedded as bytes or a string literal",
+                check_path.to_string_lossy()
+            )),
+        }
+    }
+
+    let mut written = vec![output_path.clone()];
+    written.extend(definition_output);
+
+    if let Some(framework) = cli_args.emit_test {
+        let test_path = write_test(
+            cli_args,
+            target,
+            framework,
+            embedding,
+            &output_path,
+            outputs.write_mode,
+            diagnostics,
+        )?;
+        written.push(test_path);
+    }
+
+    return Ok(written);
+}
+
+/// Source of a header embedding the data, decoded from CSV or a structured format or referring to
+/// an earlier input with the same contents if asked to
+fn embedded_src(
+    cli_args: &CliArgs,
+    target: &Target,
+    embedding: &Embedding,
+    duplicate_of: &Option<dedupe::Original>,
+    diagnostics: &mut Diagnostics,
+) -> Result<String, ()> {
+    let buf = &embedding.data;
+    let symbol_name = &target.symbol_name;
+    let source_options = &embedding.options;
+    let out_src = match (&cli_args.decode_csv, cli_args.decode_struct, duplicate_of) {
+        (Some(column_spec), _, _) => {
+            let generated = csv::parse_column_types(column_spec).and_then(|column_types| {
+                return csv::generate_src(
+                    buf,
+                    &column_types,
+                    !cli_args.csv_no_header,
+                    symbol_name,
+                    source_options.ns_name.as_deref(),
+                    source_options.comment.as_deref(),
+                    source_options.internal_linkage,
+                );
+            });
+            match generated {
+                Ok(src) => src,
+                Err(error) => {
+                    diagnostics.error(error);
+                    return Err(());
+                }
+            }
+        }
+        (None, Some(format), _) => match structured::generate_src(
+            buf,
+            format,
+            symbol_name,
+            source_options.ns_name.as_deref(),
+            source_options.comment.as_deref(),
+            source_options.internal_linkage,
+        ) {
+            Ok(src) => src,
+            Err(error) => {
+                diagnostics.error(error);
+                return Err(());
+            }
+        },
+        (None, None, Some(original)) => {
+            // Included by its path relative to this header
+            let include = original.header.as_ref().map(|header_path| {
+                let relative_path = match (
+                    header_path.canonicalize(),
+                    input::canonicalize_output(&target.output_path),
+                ) {
+                    (Ok(p), Some(output_path)) => output_path
+                        .parent()
+                        .and_then(|output_dir| input::relative_path(&p, output_dir)),
+                    _ => None,
+                };
+                return relative_path.unwrap_or_else(|| header_path.to_string_lossy().to_string());
+            });
+            dedupe::generate_src(
+                original,
+                include.as_deref(),
+                buf,
+                symbol_name,
+                source_options,
+            )
+        }
+        (None, None, None) => {
+            generate_src(buf, embedding.representation, symbol_name, source_options)
+        }
+    };
+
+    return Ok(out_src);
+}
+
+/// Write the definitions of the data next to the header declaring it (--split), which they include
+/// by the name it was written under
+fn write_definitions(
+    cli_args: &CliArgs,
+    target: &Target,
+    embedding: &Embedding,
+    header_path: &Path,
+    outputs: &mut Outputs,
+    diagnostics: &mut Diagnostics,
+) -> Result<Option<PathBuf>, ()> {
+    let header_name = header_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let definition_path = header_path.with_extension("cpp");
+    let metadata_block = Provenance {
+        source: &target.input_name,
+        data: &embedding.data,
+        symbols: std::slice::from_ref(&target.symbol_name),
+    }
+    .block(cli_args);
+    let fatal_warning_count =
+        fatal_warning_count(cli_args, target.initial_warning_count, diagnostics);
+    let write_definition = |writer: &mut dyn io::Write| {
+        writer.write_all(metadata_block.as_bytes())?;
+        return split::write_definition_src(
+            writer,
+            &embedding.data,
+            embedding.representation,
+            &target.symbol_name,
+            &embedding.options,
+            &header_name,
+        );
+    };
+    return match outputs.write_mode {
+        WriteMode::Always => outputs.write_with(
+            &definition_path,
+            fatal_warning_count,
+            diagnostics,
+            write_definition,
+        ),
+        _ => {
+            let mut definition_src = Vec::new();
+            write_definition(&mut definition_src).expect("writing to a Vec cannot fail");
+            outputs.write(
+                &definition_path,
+                &definition_src,
+                fatal_warning_count,
+                diagnostics,
+            )
+        }
+    };
+}
+
+/// Write a unit test checking the data embedded in the header written (--emit-test), returning
+/// its path
+fn write_test(
+    cli_args: &CliArgs,
+    target: &Target,
+    framework: TestFramework,
+    embedding: &Embedding,
+    output_path: &Path,
+    write_mode: WriteMode,
+    diagnostics: &mut Diagnostics,
+) -> Result<PathBuf, ()> {
+    let source_options = &embedding.options;
+    let symbol_name = &target.symbol_name;
+    let test_path = match cli_args.test_output_path {
+        Some(ref p) => p.clone(),
+        None => {
+            let stem = output_path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy();
+            output_path.with_file_name(format!("{}_test.cpp", stem))
+        }
+    };
+
+    let qualified_symbol = match source_options.ns_name {
+        Some(ref namespace) => format!("{}::{}", namespace, symbol_name),
+        None => symbol_name.clone(),
+    };
+    let header_name = output_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let test_src = testgen::generate_test_src(
+        framework,
+        &testgen::TestSubject {
+            header_name: &header_name,
+            view_expr: match embedding.representation {
+                _ if source_options.span_accessor => format!("{}()", qualified_symbol),
+                Representation::StringLiteral => format!(
+                    "{}{{{}}}",
+                    source_options.char_type.view_type(),
+                    qualified_symbol
+                ),
+                _ => qualified_symbol,
+            },
+            test_name: symbol_name,
+            guard_macro: source_options.guard_macro.as_deref(),
+            size: embedding.data.len(),
+            sha256: checksum::sha256(&embedding.data),
+        },
+    );
+
+    if write_mode != WriteMode::DryRun
+        && let Err(error) = output::write_atomically(&test_path, test_src.as_bytes())
+    {
+        diagnostics.error(format!(
+            "failed to write unit test \"{}\": {}",
+            test_path.to_string_lossy(),
+            error
+        ));
+        return Err(());
+    }
+    diagnostics.log(format!("wrote \"{}\"", test_path.to_string_lossy()));
+
+    return Ok(test_path);
+}
+
+/// Where the contents of a header generated for one input came from, recorded in its metadata
+/// block
+struct Provenance<'a> {
+    source: &'a str,
+    data: &'a [u8],
+    /// Symbols through which the data is accessed
+    symbols: &'a [String],
+}
+
+impl Provenance<'_> {
+    /// The banner and metadata block of a header generated with the given arguments
+    fn block(&self, cli_args: &CliArgs) -> String {
+        // The command stands in for the input path of --input-cmd
+        let source = match cli_args.reproducible && cli_args.input_cmd.is_none() {
+            true => recorded_path(self.source),
+            false => self.source.to_string(),
+        };
+        return metadata::block(&metadata::Metadata {
+            arguments: &command_line_arguments(cli_args.reproducible),
+            source: &source,
+            size: self.data.len(),
+            sha256: checksum::sha256(self.data),
+            symbols: self.symbols,
+            language: cli_args.language,
+            banner: cli_args.banner_text.as_deref(),
+            provenance: !cli_args.no_metadata,
+        });
+    }
+}
+
+/// A header generated for one input, waiting to be written
+struct Generated<'a> {
+    src: &'a str,
+    /// Where its contents came from, if it is to start with a metadata block (templates and
+    /// --xxd-compat headers are written as they are)
+    provenance: Option<Provenance<'a>>,
+    /// Extension and contents of a file to write next to it
+    companion: Option<(&'a str, &'a [u8])>,
+}
+
+/// Number of warnings issued while generating an input which fail it, all of them under
+/// --fail-on-warning and none otherwise
+fn fatal_warning_count(
+    cli_args: &CliArgs,
+    initial_warning_count: usize,
+    diagnostics: &Diagnostics,
+) -> usize {
+    return match cli_args.fail_on_warning {
+        true => diagnostics.warning_count() - initial_warning_count,
+        false => 0,
+    };
+}
+
+/// Write a header generated for one input (and its companion file, if any), returning the paths
+/// written
+fn write_generated(
+    output_path: &Path,
+    header: Generated,
+    cli_args: &CliArgs,
+    initial_warning_count: usize,
+    outputs: &mut Outputs,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<PathBuf>, ()> {
+    let out_src = match header.provenance {
+        Some(provenance) => provenance.block(cli_args) + header.src,
+        None => header.src.to_string(),
+    };
+    let fatal_warning_count = fatal_warning_count(cli_args, initial_warning_count, diagnostics);
+
+    return match header.companion {
+        Some(companion) => outputs.write_with_companion(
+            output_path,
+            &out_src,
+            companion,
+            fatal_warning_count,
+            diagnostics,
+        ),
+        None => Ok(outputs
+            .write(output_path, &out_src, fatal_warning_count, diagnostics)?
+            .into_iter()
+            .collect()),
+    };
+}
+
+/// Checksums of the data with each algorithm asked for, ignoring any repeats
+fn checksums(algorithms: &[ChecksumAlgorithm], data: &[u8]) -> Vec<Verifier> {
+    let mut checksums: Vec<Verifier> = Vec::new();
+    for &algorithm in algorithms {
+        if !checksums.iter().any(|c| c.algorithm == algorithm) {
+            checksums.push(Verifier {
+                algorithm,
+                digest: algorithm.digest(data),
+            });
+        }
+    }
+
+    return checksums;
+}
+
+/// Where the outputs of a batch go: how they are written, and which paths the inputs of the batch
+/// have taken so far, to which the collision policy applies
+///
+/// Files left by earlier runs are not collisions, they are simply replaced (or left alone if they
+/// are unchanged, with `WriteMode::IfChanged`).
+struct Outputs {
+    on_collision: CollisionPolicy,
+    write_mode: WriteMode,
+    /// Whether text is written with `\n` line endings on every platform (`--reproducible`)
+    unix_line_endings: bool,
+    /// Whether renaming an output is fatal, as any other warning is (`--fail-on-warning`)
+    fail_on_warning: bool,
+    /// Canonical paths of the outputs taken so far
+    claimed: HashSet<PathBuf>,
+}
+
+impl Outputs {
+    fn new(cli_args: &CliArgs) -> Outputs {
+        return Outputs {
+            on_collision: cli_args.on_collision,
+            write_mode: write_mode(cli_args),
+            unix_line_endings: cli_args.reproducible,
+            fail_on_warning: cli_args.fail_on_warning,
+            claimed: HashSet::new(),
+        };
+    }
+
+    /// Take an output path, applying the collision policy if another input of the batch already
+    /// took it, returning the path taken (which differs from the one given if the output was
+    /// renamed) or nothing if the output is to be skipped
+    fn claim(
+        &mut self,
+        output_path: &Path,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<Option<PathBuf>, ()> {
+        let key = |path: &Path| {
+            return input::canonicalize_output(path).unwrap_or_else(|| path.to_path_buf());
+        };
+        let resolution = output::resolve_collision(output_path, self.on_collision, |path| {
+            return self.claimed.contains(&key(path));
+        });
+        return match resolution {
+            Resolution::Write(path) if path != output_path => {
+                self.claimed.insert(key(&path));
+                diagnostics.warn(Warning::RenamedSymbol {
+                    output: output_path.to_string_lossy().to_string(),
+                    renamed: path.to_string_lossy().to_string(),
+                });
+                check_fatal_warnings(self.fail_on_warning as usize, diagnostics)?;
+                Ok(Some(path))
+            }
+            Resolution::Write(path) => {
+                self.claimed.insert(key(&path));
+                Ok(Some(path))
+            }
+            Resolution::Skip => {
+                diagnostics.note(format!(
+                    "output file \"{}\" is also the output of an earlier input, skipping",
+                    output_path.to_string_lossy()
+                ));
+                Ok(None)
+            }
+            Resolution::Collision => {
+                diagnostics.error(format!(
+                    "output file \"{}\" is also the output of an earlier input (pass --force to overwrite it, or --on-collision rename or skip)",
+                    output_path.to_string_lossy()
+                ));
+                Err(())
+            }
+        };
+    }
+
+    /// Write a generated file, applying the collision policy, returning the path written (which
+    /// differs from the one given if the output was renamed) or nothing if it was skipped
+    ///
+    /// Nothing is written if any warnings are to be treated as errors, nor if the output already
+    /// has exactly these contents with `WriteMode::IfChanged`, nor ever with `WriteMode::Check`
+    /// (which instead fails if the output is stale) or `WriteMode::DryRun`.
+    fn write(
+        &mut self,
+        output_path: &Path,
+        out_src: impl AsRef<[u8]>,
+        fatal_warning_count: usize,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<Option<PathBuf>, ()> {
+        check_fatal_warnings(fatal_warning_count, diagnostics)?;
+        let Some(output_path) = self.claim(output_path, diagnostics)? else {
+            return Ok(None);
+        };
+        let out_src = match self.unix_line_endings {
+            true => Cow::Owned(output::unix_line_endings(out_src.as_ref())),
+            false => Cow::Borrowed(out_src.as_ref()),
+        };
+
+        return self.write_claimed(output_path, &out_src, diagnostics);
+    }
+
+    /// Write a generated file as `write` does, with its contents written piece by piece by the
+    /// given function (only ever used with `WriteMode::Always`)
+    fn write_with(
+        &mut self,
+        output_path: &Path,
+        fatal_warning_count: usize,
+        diagnostics: &mut Diagnostics,
+        write: impl FnOnce(&mut dyn io::Write) -> io::Result<()>,
+    ) -> Result<Option<PathBuf>, ()> {
+        check_fatal_warnings(fatal_warning_count, diagnostics)?;
+        let Some(output_path) = self.claim(output_path, diagnostics)? else {
+            return Ok(None);
+        };
+
+        let unix_line_endings = self.unix_line_endings;
+        return write_file(&output_path, diagnostics, |writer| {
+            if !unix_line_endings {
+                return write(writer);
+            }
+            let mut writer = UnixLineEndings::new(writer);
+            write(&mut writer)?;
+            return writer.finish();
+        });
+    }
+
+    /// Write a header along with the file it declares what is defined in (an object, assembler or
+    /// resource file), which is named after the header with the extension given even if the
+    /// header was renamed and written as it is, returning the paths written
+    fn write_with_companion(
+        &mut self,
+        output_path: &Path,
+        out_src: &str,
+        (extension, companion_src): (&str, &[u8]),
+        fatal_warning_count: usize,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<Vec<PathBuf>, ()> {
+        let Some(header_path) =
+            self.write(output_path, out_src, fatal_warning_count, diagnostics)?
+        else {
+            return Ok(Vec::new());
+        };
+        let companion_path = header_path.with_extension(extension);
+        let mut written = vec![header_path];
+        let Some(companion_path) = self.claim(&companion_path, diagnostics)? else {
+            return Ok(written);
+        };
+        written.extend(self.write_claimed(companion_path, companion_src, diagnostics)?);
+
+        return Ok(written);
+    }
+
+    /// Write a file to a path already claimed, as the write mode says to
+    fn write_claimed(
+        &self,
+        output_path: PathBuf,
+        out_src: &[u8],
+        diagnostics: &mut Diagnostics,
+    ) -> Result<Option<PathBuf>, ()> {
+        match self.write_mode {
+            WriteMode::Check => {
+                return match std::fs::read(&output_path) {
+                    Ok(existing) if existing == out_src => {
+                        diagnostics.log(format!(
+                            "output file \"{}\" is up to date",
+                            output_path.to_string_lossy()
+                        ));
+                        Ok(None)
+                    }
+                    Ok(existing) => {
+                        diagnostics.error(format!(
+                            "output file \"{}\" is out of date: {}",
+                            output_path.to_string_lossy(),
+                            output::diff_summary(
+                                &String::from_utf8_lossy(&existing),
+                                &String::from_utf8_lossy(out_src)
+                            )
+                        ));
+                        Err(())
+                    }
+                    Err(error) => {
+                        diagnostics.error(format!(
+                            "output file \"{}\" cannot be checked: {}",
+                            output_path.to_string_lossy(),
+                            error
+                        ));
+                        Err(())
+                    }
+                };
+            }
+            WriteMode::DryRun => {
+                diagnostics.log(format!("would write \"{}\"", output_path.to_string_lossy()));
+                return Ok(Some(output_path));
+            }
+            WriteMode::IfChanged
+                if std::fs::read(&output_path).is_ok_and(|existing| existing == out_src) =>
+            {
+                diagnostics.log(format!(
+                    "output file \"{}\" is unchanged, not rewriting it",
+                    output_path.to_string_lossy()
+                ));
+                return Ok(Some(output_path));
+            }
+            WriteMode::IfChanged | WriteMode::Always => {
+                return write_file(&output_path, diagnostics, |writer| {
+                    return writer.write_all(out_src);
+                });
+            }
+        }
+    }
+}
+
+fn check_fatal_warnings(
+    fatal_warning_count: usize,
+    diagnostics: &mut Diagnostics,
+) -> Result<(), ()> {
+    if fatal_warning_count > 0 {
+        diagnostics.error(format!(
+            "{} warning(s) treated as errors, not writing output",
+            fatal_warning_count
+        ));
+        return Err(());
+    }
+
+    return Ok(());
+}
+
+/// Write a file atomically with the given function, replacing whatever was there
+fn write_file(
+    output_path: &Path,
+    diagnostics: &mut Diagnostics,
+    write: impl FnOnce(&mut dyn io::Write) -> io::Result<()>,
+) -> Result<Option<PathBuf>, ()> {
+    let write_start = Instant::now();
+    if let Err(error) = output::write_atomically_with(output_path, write) {
+        diagnostics.error(format!(
+            "failed to write output file \"{}\": {}",
+            output_path.to_string_lossy(),
+            error
+        ));
+        return Err(());
+    }
+
+    diagnostics.log(format!(
+        "wrote \"{}\" in {:.3?}",
+        output_path.to_string_lossy(),
+        write_start.elapsed()
+    ));
+
+    return Ok(Some(output_path.to_path_buf()));
+}
+
+/// Generate the index header listing every header written and write it, returning the path
+/// written or nothing if it was skipped
+fn write_index(
+    path: &Path,
+    entries: &[IndexEntry],
+    cli_args: &CliArgs,
+    outputs: &mut Outputs,
+    diagnostics: &mut Diagnostics,
+) -> Result<Option<PathBuf>, ()> {
+    for (position, entry) in entries.iter().enumerate() {
+        if let Some(other) = entries[..position]
+            .iter()
+            .find(|other| other.name == entry.name)
+        {
+            diagnostics.error(format!(
+                "\"{}\" is listed in the index twice, from \"{}\" and \"{}\"",
+                entry.name, other.include, entry.include
+            ));
+            return Err(());
+        }
+    }
+
+    let prefix = sanitise_os_identifier(path.file_stem().unwrap_or_default());
+    let out_src = index::generate_src(
+        entries,
+        &prefix,
+        cli_args.namespace.as_deref(),
+        cli_args.internal_linkage,
+    );
+    let (guard_style, guard_macro) = header_guard(
+        cli_args.guard,
+        cli_args.guard_name.as_deref(),
+        cli_args.language,
+        &prefix,
+    );
+    let out_src = guard::wrap(&out_src, guard_style, &guard_macro);
+    diagnostics.log(format!("index lists {} inputs", entries.len()));
+
+    let fatal_warning_count = match cli_args.fail_on_warning {
+        true => diagnostics.warning_count(),
+        false => 0,
+    };
+    return outputs.write(path, &out_src, fatal_warning_count, diagnostics);
+}
+
+/// Namespace of a file found in one of the walked directories: the given one with a namespace
+/// nested in it for every directory between the walked one and the file, whose names are mangled
+/// like derived symbols (`3d` becoming `file_3d`, `new` becoming `new_`)
+fn nested_namespace(
+    namespace: Option<&str>,
+    path: &Path,
+    walked_dirs: &[PathBuf],
+    backend: &dyn Backend,
+) -> Option<String> {
+    let Some(relative) = walked_dirs
+        .iter()
+        .find_map(|dir| path.strip_prefix(dir).ok())
+    else {
+        return namespace.map(str::to_string);
+    };
+
+    let mut names: Vec<String> = namespace.map(str::to_string).into_iter().collect();
+    for component in relative.parent().unwrap_or(Path::new("")).components() {
+        let Component::Normal(dir_name) = component else {
+            continue;
+        };
+        let name = sanitise_os_identifier(dir_name);
+        names.push(match mangle_identifier(&name, backend) {
+            Some((mangled, _)) => mangled,
+            None => name,
+        });
+    }
+
+    return match names.is_empty() {
+        true => None,
+        false => Some(names.join("::")),
+    };
+}
+
+/// Path relative to the first of the given directories it is below, with forward slashes on
+/// every platform
+fn index_name(path: &str, roots: &[PathBuf]) -> String {
+    let path = Path::new(path);
+    let relative = roots
+        .iter()
+        .find_map(|root| path.strip_prefix(root).ok())
+        .unwrap_or(path);
+    return relative.to_string_lossy().replace('\\', "/");
+}
+
+/// Inputs with the same filename in different directories derive the same symbol, which no two
+/// parts of an amalgamation (or of the headers it is split into) may share
+fn check_distinct_symbols(
+    parts: &[amalgamate::Part],
+    diagnostics: &mut Diagnostics,
+) -> Result<(), ()> {
+    for (index, part) in parts.iter().enumerate() {
+        if let Some(other) = parts[..index].iter().find(|other| {
+            other.access_symbol == part.access_symbol && other.ns_name == part.ns_name
+        }) {
+            diagnostics.error(format!(
+                "\"{}\" and \"{}\" would both be embedded as \"{}\" (pass --symbol-template with {{dir}} or {{hash8}} to tell them apart)",
+                other.name, part.name, part.access_symbol
+            ));
+            return Err(());
+        }
+    }
+
+    return Ok(());
+}
+
+/// Pack the parts of an amalgamation into as few headers as fit them under `budget`, numbering
+/// them after the amalgamation's path
+fn pack_headers(
+    path: &Path,
+    parts: Vec<amalgamate::Part>,
+    budget: usize,
+    project_version: Option<&str>,
+    cli_args: &CliArgs,
+    diagnostics: &mut Diagnostics,
+) -> Vec<(PathBuf, Vec<amalgamate::Part>)> {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let numbered = |number: usize| {
+        let mut file_name = format!("{}_{}", stem, number);
+        if let Some(extension) = path.extension() {
+            file_name.push('.');
+            file_name.push_str(&extension.to_string_lossy());
+        }
+        return path.with_file_name(file_name);
+    };
+    let rendered_size = |path: &Path, bin: &[usize]| {
+        let bin_parts: Vec<amalgamate::Part> =
+            bin.iter().map(|&index| parts[index].clone()).collect();
+        return render_amalgamation(path, &bin_parts, project_version, cli_args).len();
+    };
+
+    // Each part costs what it adds to a header holding nothing else, the guard, includes,
+    // metadata and lookup being reserved out of the budget (under the longest name a header
+    // could be given)
+    let widest = numbered(parts.len());
+    let overhead = rendered_size(&widest, &[]);
+    let sizes: Vec<usize> = (0..parts.len())
+        .map(|index| rendered_size(&widest, &[index]).saturating_sub(overhead))
+        .collect();
+    for (part, size) in parts.iter().zip(&sizes) {
+        if overhead + size > budget {
+            diagnostics.warn(Warning::OverBudget {
+                path: part.name.clone(),
+                size: overhead + size,
+                budget,
+            });
+        }
+    }
+
+    // What the parts of a header share is not quite the sum of what they add alone (the size and
+    // digest in the metadata, say), so headers are measured as rendered and any over the budget
+    // give up their last parts to be packed again
+    let mut bins = Vec::new();
+    let mut unpacked: Vec<usize> = (0..parts.len()).collect();
+    while !unpacked.is_empty() {
+        let unpacked_sizes: Vec<usize> = unpacked.iter().map(|&index| sizes[index]).collect();
+        let packed = amalgamate::pack(&unpacked_sizes, budget.saturating_sub(overhead));
+        let mut evicted = Vec::new();
+        for bin in packed {
+            let mut bin: Vec<usize> = bin.into_iter().map(|index| unpacked[index]).collect();
+            while bin.len() > 1 && rendered_size(&numbered(bins.len() + 1), &bin) > budget {
+                evicted.extend(bin.pop());
+            }
+            bins.push(bin);
+        }
+        evicted.sort_unstable();
+        unpacked = evicted;
+    }
+
+    let mut parts: Vec<Option<amalgamate::Part>> = parts.into_iter().map(Some).collect();
+    return bins
+        .into_iter()
+        .enumerate()
+        .map(|(number, bin)| {
+            let bin_parts = bin
+                .into_iter()
+                .filter_map(|index| parts[index].take())
+                .collect();
+            return (numbered(number + 1), bin_parts);
+        })
+        .collect();
+}
+
+/// Amalgamate the sources generated for the given inputs into the header written to `path`
+fn render_amalgamation(
+    path: &Path,
+    parts: &[amalgamate::Part],
+    project_version: Option<&str>,
+    cli_args: &CliArgs,
+) -> String {
+    let prefix = sanitise_os_identifier(path.file_stem().unwrap_or_default());
+    let out_src = amalgamate::generate_src(
+        parts,
+        &prefix,
+        cli_args.namespace.as_deref(),
+        project_version,
+        cli_args.regions,
+        cli_args.internal_linkage,
+    );
+    let (guard_style, guard_macro) = header_guard(
+        cli_args.guard,
+        cli_args.guard_name.as_deref(),
+        cli_args.language,
+        &prefix,
+    );
+    let out_src = guard::wrap(&out_src, guard_style, &guard_macro);
+
+    let out_src = if cli_args.toc {
+        let entries: Vec<toc::TocEntry> = parts.iter().map(|part| part.toc_entry.clone()).collect();
+        toc::prepend_toc(
+            &out_src,
+            &entries,
+            metadata::line_count(cli_args.banner_text.as_deref(), !cli_args.no_metadata),
+        )
+    } else {
+        out_src
+    };
+
+    // The inputs are hashed back to back, as if they were one input
+    let data: Vec<u8> = parts
+        .iter()
+        .flat_map(|part| part.data.iter().copied())
+        .collect();
+    let mut symbols: Vec<String> = parts
+        .iter()
+        .map(|part| part.access_symbol.clone())
+        .collect();
+    symbols.push(format!("{}_find", prefix));
+    let out_src = metadata::prepend(
+        &out_src,
+        &metadata::Metadata {
+            arguments: &command_line_arguments(cli_args.reproducible),
+            source: &format!("{} inputs", parts.len()),
+            size: data.len(),
+            sha256: checksum::sha256(&data),
+            symbols: &symbols,
+            language: cli_args.language,
+            banner: cli_args.banner_text.as_deref(),
+            provenance: !cli_args.no_metadata,
+        },
+    );
+
+    return out_src;
+}
+
+/// Amalgamate the sources generated for every input and write them, returning the path written or
+/// nothing if it was skipped
+fn write_amalgamation(
+    path: &Path,
+    parts: &[amalgamate::Part],
+    project_version: Option<&str>,
+    cli_args: &CliArgs,
+    outputs: &mut Outputs,
+    diagnostics: &mut Diagnostics,
+) -> Result<Option<PathBuf>, ()> {
+    let out_src = render_amalgamation(path, parts, project_version, cli_args);
+    let fatal_warning_count = match cli_args.fail_on_warning {
+        true => diagnostics.warning_count(),
+        false => 0,
+    };
+    let written = outputs.write(path, &out_src, fatal_warning_count, diagnostics)?;
+
+    // Only the parts listed in the index have their data spelled out as bytes
+    if cli_args.self_check
+        && let Some(ref written_path) = written
+    {
+        let checks: Vec<(&str, &[u8])> = parts
+            .iter()
+            .filter(|part| part.view_expr.is_some())
+            .map(|part| (part.access_symbol.as_str(), part.data.as_slice()))
+            .collect();
+        self_check(written_path, &checks, diagnostics)?;
+    }
+
+    return Ok(written);
+}
+
+/// Generate the header for a `--locales` directory, returning it along with the contents of every
+/// variant concatenated in tag order
+fn generate_locales(
+    dir: &Path,
+    symbol_name: &str,
+    cli_args: &CliArgs,
+    diagnostics: &mut Diagnostics,
+) -> Result<(String, Vec<u8>), ()> {
+    let variant_paths = match locale::collect_variants(dir) {
+        Ok(variants) => variants,
+        Err(error) => {
+            diagnostics.error(format!(
+                "failed to read locale directory \"{}\": {}",
+                dir.to_string_lossy(),
+                error
+            ));
+            return Err(());
+        }
+    };
+
+    let mut variants = Vec::with_capacity(variant_paths.len());
+    for (tag, path) in variant_paths {
+        match std::fs::read(&path) {
+            Ok(data) => {
+                diagnostics.log(format!(
+                    "locale \"{}\" read from \"{}\"",
+                    tag,
+                    path.to_string_lossy()
+                ));
+                variants.push((tag, data));
+            }
+            Err(error) => {
+                diagnostics.error(format!(
+                    "failed to read locale variant \"{}\": {}",
+                    path.to_string_lossy(),
+                    error
+                ));
+                return Err(());
+            }
+        }
+    }
+
+    let requested_default = cli_args.default_locale.as_deref().unwrap_or("en");
+    let Some((default_tag, _)) = variants.iter().find(|(tag, _)| {
+        tag.replace('_', "-")
+            .eq_ignore_ascii_case(&requested_default.replace('_', "-"))
+    }) else {
+        diagnostics.error(format!(
+            "locale directory \"{}\" has no variant for the default locale \"{}\" (pass --default-locale to choose another)",
+            dir.to_string_lossy(),
+            requested_default
+        ));
+        return Err(());
+    };
+
+    return match locale::generate_src(
+        &variants,
+        default_tag,
+        symbol_name,
+        cli_args.namespace.as_deref(),
+        cli_args.comment.as_deref(),
+        cli_args.internal_linkage,
+        cli_args.max_literal_len,
+    ) {
+        Ok(src) => Ok((
+            src,
+            variants.into_iter().flat_map(|(_, data)| data).collect(),
+        )),
+        Err(tag) => {
+            diagnostics.error(format!(
+                "locale variant \"{}\" maps onto the same symbol as another variant",
+                tag
+            ));
+            Err(())
+        }
+    };
+}
+
+/// Read a header back from disk and check that each symbol in it decodes to the expected data
+fn self_check(
+    path: &Path,
+    checks: &[(&str, &[u8])],
+    diagnostics: &mut Diagnostics,
+) -> Result<(), ()> {
+    let src = match std::fs::read_to_string(path) {
+        Ok(src) => src,
+        Err(error) => {
+            diagnostics.error(format!(
+                "failed to read back \"{}\" for self-check: {}",
+                path.to_string_lossy(),
+                error
+            ));
+            return Err(());
+        }
+    };
+
+    for &(symbol_name, expected) in checks {
+        let mismatch = match extract::extract(&src, symbol_name) {
+            Ok(data) if data == expected => None,
+            Ok(data) => Some(match data.iter().zip(expected).position(|(a, b)| a != b) {
+                Some(offset) => format!("the data differs from the input at offset {}", offset),
+                None => format!(
+                    "the data is {} bytes but the input is {}",
+                    data.len(),
+                    expected.len()
+                ),
+            }),
+            Err(error) => Some(error),
+        };
+        if let Some(reason) = mismatch {
+            diagnostics.error(format!(
+                "self-check of \"{}\" in \"{}\" failed: {}",
+                symbol_name,
+                path.to_string_lossy(),
+                reason
+            ));
+            return Err(());
+        }
+    }
+    diagnostics.log(format!(
+        "self-check of \"{}\" passed for {} symbol(s)",
+        path.to_string_lossy(),
+        checks.len()
+    ));
+
+    return Ok(());
+}
+
+/// Generate the header for the `patch` subcommand, whose symbol is derived (and written) as the
+/// header of an input would be
+fn generate_patch(
+    old_path: &Path,
+    new_path: &Path,
+    output_path: Option<&Path>,
+    symbol_name: Option<&str>,
+    ns_name: Option<&str>,
+    cli_args: &CliArgs,
+    diagnostics: &mut Diagnostics,
+) -> Result<(), ()> {
+    let mut contents = Vec::with_capacity(2);
+    for path in [old_path, new_path] {
+        match std::fs::read(path) {
+            Ok(data) => contents.push(data),
+            Err(error) => {
+                diagnostics.error(format!(
+                    "failed to read input file \"{}\": {}",
+                    path.to_string_lossy(),
+                    error
+                ));
+                return Err(());
+            }
+        }
+    }
+    let (old, new) = (&contents[0], &contents[1]);
+
+    let Some(new_filename) = new_path.file_name() else {
+        diagnostics.error(format!(
+            "input file path \"{}\" does not contain a valid filename",
+            new_path.to_string_lossy()
+        ));
+        return Err(());
+    };
+    let output_path = match output_path {
+        Some(p) => p.to_path_buf(),
+        None => {
+            let p = PathBuf::from(new_filename).with_extension("patch.hpp");
+            diagnostics.note(format!(
+                "no output path provided, writing to \"{}\"",
+                p.to_string_lossy()
+            ));
+            p
+        }
+    };
+    let symbol_name = match symbol_name {
+        Some(s) => s.to_string(),
+        None => derive_symbol(cli_args, new_path, "_patch", new_path, diagnostics)?,
+    };
+
+    let ops = delta::encode(old, new);
+    diagnostics.log(format!(
+        "patch from \"{}\" to \"{}\" is {} bytes for {} bytes of output",
+        old_path.to_string_lossy(),
+        new_path.to_string_lossy(),
+        ops.len(),
+        new.len()
+    ));
+    let patch = delta::DeltaBase {
+        len: old.len(),
+        crc32: checksum::crc32(old),
+        ops,
+    };
+    let new_sha256 = checksum::sha256(new);
+    let out_src = delta::generate_patch_src(
+        &patch,
+        &checksum::sha256(old),
+        new.len(),
+        &new_sha256,
+        &symbol_name,
+        ns_name,
+    );
+    let out_src = guard::wrap(&out_src, GuardStyle::Pragma, "");
+    let out_src = metadata::prepend(
+        &out_src,
+        &metadata::Metadata {
+            arguments: &command_line_arguments(false),
+            source: &new_path.to_string_lossy(),
+            size: new.len(),
+            sha256: new_sha256,
+            symbols: &[format!("{}_apply", symbol_name)],
+            language: Language::Cpp,
+            banner: None,
+            provenance: true,
+        },
+    );
+    Outputs::new(cli_args).write(&output_path, &out_src, 0, diagnostics)?;
+
+    return Ok(());
+}
+
+/// Hash every file below a directory and generate the manifest of them, returning the source
+/// along with the concatenated digests
+///
+/// The output file is left out if it is inside the directory, otherwise regenerating the manifest
+/// would list the previous one.
+fn generate_manifest(
+    dir: &Path,
+    output_path: &Path,
+    symlink_policy: SymlinkPolicy,
+    symbol_name: &str,
+    cli_args: &CliArgs,
+    diagnostics: &mut Diagnostics,
+) -> Result<(String, Vec<u8>), ()> {
+    let relative_paths = match input::walk_dir(dir, symlink_policy, cli_args.respect_gitignore) {
+        Ok(paths) => paths,
+        Err(error) => {
+            diagnostics.error(format!(
+                "failed to read directory \"{}\": {}",
+                dir.to_string_lossy(),
+                error
+            ));
+            return Err(());
+        }
+    };
+
+    let canonical_output_path = input::canonicalize_output(output_path);
+
+    let mut entries = Vec::with_capacity(relative_paths.len());
+    for relative_path in relative_paths {
+        let path = dir.join(&relative_path);
+        if canonical_output_path.is_some()
+            && std::fs::canonicalize(&path).ok() == canonical_output_path
+        {
+            diagnostics.log(format!(
+                "output file \"{}\" left out of the manifest",
+                path.to_string_lossy()
+            ));
+            continue;
+        }
+        match std::fs::read(&path) {
+            Ok(data) => entries.push(manifest::ManifestEntry {
+                path: input::path_sort_key(&relative_path),
+                size: data.len(),
+                sha256: checksum::sha256(&data),
+            }),
+            Err(error) => {
+                diagnostics.error(format!(
+                    "failed to read input file \"{}\": {}",
+                    path.to_string_lossy(),
+                    error
+                ));
+                return Err(());
+            }
+        }
+    }
+    diagnostics.log(format!("{} files listed in the manifest", entries.len()));
+
+    let src = manifest::generate_src(
+        &entries,
+        symbol_name,
+        cli_args.namespace.as_deref(),
+        cli_args.comment.as_deref(),
+        cli_args.internal_linkage,
+    );
+    return Ok((src, entries.iter().flat_map(|entry| entry.sha256).collect()));
+}
+
+/// Write the stamp file (if one was asked for) listing the files written, only called once every
+/// output has been generated so build systems can depend on it in place of all of them
+fn write_stamp(
+    diagnostics: &mut Diagnostics,
+    stamp_path: Option<&Path>,
+    written: &[PathBuf],
+) -> ExitCode {
+    let Some(stamp_path) = stamp_path else {
+        return ExitCode::SUCCESS;
+    };
+
+    let mut contents = String::new();
+    for path in written {
+        contents.push_str(&path.to_string_lossy());
+        contents.push_str(LINE_ENDING);
+    }
+
+    if let Err(error) = output::write_atomically(stamp_path, contents.as_bytes()) {
+        diagnostics.error(format!(
+            "failed to write stamp file \"{}\": {}",
+            stamp_path.to_string_lossy(),
+            error
+        ));
+        return ExitCode::FAILURE;
+    }
+    diagnostics.log(format!("wrote \"{}\"", stamp_path.to_string_lossy()));
+
+    return ExitCode::SUCCESS;
+}
+
+/// Guard style and macro of a header declaring `name` (its symbol, or an amalgamation's stem)
+fn header_guard(
+    guard: Option<GuardStyle>,
+    guard_name: Option<&str>,
+    language: Language,
+    name: &str,
+) -> (GuardStyle, String) {
+    let style = match guard {
+        Some(style) => style,
+        None if !language.backend().guarded() => GuardStyle::None,
+        None if guard_name.is_some() || language == Language::C => GuardStyle::Ifdef,
+        None => GuardStyle::Pragma,
+    };
+    let guard_macro = match guard_name {
+        Some(guard_macro) => guard_macro.to_string(),
+        None => guard::default_macro(name, language.extension()),
+    };
+
+    return (style, guard_macro);
+}
+
+fn write_mode(cli_args: &CliArgs) -> WriteMode {
+    if cli_args.list_outputs {
+        return WriteMode::DryRun;
+    }
+    return match (cli_args.check, cli_args.if_changed) {
+        (true, _) => WriteMode::Check,
+        (false, true) => WriteMode::IfChanged,
+        (false, false) => WriteMode::Always,
+    };
+}
+
+/// Options taking a path, which --reproducible records relative to the current directory
+const PATH_OPTIONS: [&str; 19] = [
+    "-i",
+    "--input-path",
+    "-o",
+    "--output-path",
+    "-d",
+    "--output-dir",
+    "--template",
+    "--hybrid-loader",
+    "--log-file",
+    "--test-output-path",
+    "--delta-base",
+    "--stamp",
+    "--config",
+    "--files-from",
+    "--banner",
+    "--batch-report",
+    "--amalgamate",
+    "--index",
+    "--project-version-from",
+];
+
+/// The arguments the tool was run with, recorded in the metadata block
+///
+/// Flags which only affect whether the output is written (or how the input is read) are left
+/// out, so that checking a header (or regenerating it only if changed) compares it against what a
+/// plain run would write. If `reproducible` is set, absolute paths given to options taking paths
+/// are recorded relative to the current directory.
+fn command_line_arguments(reproducible: bool) -> Vec<String> {
+    return recorded_arguments(reproducible).0;
+}
+
+/// The arguments recorded in the metadata block, along with the absolute paths which were to be
+/// recorded relative to the current directory but cannot be
+fn recorded_arguments(reproducible: bool) -> (Vec<String>, Vec<String>) {
+    let mut arguments = Vec::new();
+    let mut absolute_paths = Vec::new();
+    let record_path = |path: &str, absolute_paths: &mut Vec<String>| {
+        if !reproducible {
+            return path.to_string();
+        }
+        let recorded = recorded_path(path);
+        if Path::new(&recorded).is_absolute() {
+            absolute_paths.push(recorded.clone());
+        }
+        return recorded;
+    };
+
+    let mut skip_value = false;
+    let mut path_value = false;
+    for argument in std::env::args_os().skip(1) {
+        let argument = argument.to_string_lossy().to_string();
+        if std::mem::take(&mut skip_value) {
+            continue;
+        }
+        if std::mem::take(&mut path_value) {
+            arguments.push(record_path(&argument, &mut absolute_paths));
+            continue;
+        }
+        match argument.as_str() {
+            "--check" | "--if-changed" => (),
+            "--mmap-threshold" => skip_value = true,
+            _ if argument.starts_with("--mmap-threshold=") => (),
+            option if PATH_OPTIONS.contains(&option) => {
+                path_value = true;
+                arguments.push(argument);
+            }
+            _ => {
+                match argument.split_once('=') {
+                    Some((option, path)) if PATH_OPTIONS.contains(&option) => arguments.push(
+                        format!("{}={}", option, record_path(path, &mut absolute_paths)),
+                    ),
+                    _ => arguments.push(argument),
+                }
+            }
+        }
+    }
+
+    return (arguments, absolute_paths);
+}
+
+/// A path as --reproducible records it in the metadata block: relative to the current directory
+/// if it is absolute (and can be, which it cannot on another drive on Windows)
+fn recorded_path(path: &str) -> String {
+    return relative_to_current_dir(Path::new(path)).unwrap_or_else(|| path.to_string());
+}
+
+/// A path relative to the current directory, worked out without touching the file system so that
+/// paths which do not exist yet (or are symbolic links) are recorded as given
+fn relative_to_current_dir(path: &Path) -> Option<String> {
+    if !path.is_absolute() {
+        return Some(path.to_string_lossy().to_string());
+    }
+
+    let current_dir = std::env::current_dir().ok()?;
+    return input::relative_path(path, &current_dir).map(|relative| match relative.is_empty() {
+        true => ".".to_string(),
+        false => relative,
+    });
+}
+
+/// Options asked for which the language generated may not have
+fn requested_features(cli_args: &CliArgs, amalgamation: bool) -> Features {
+    return Features {
+        namespace: cli_args.namespace.is_some(),
+        guard: cli_args
+            .guard
+            .is_some_and(|guard| guard != GuardStyle::None)
+            || cli_args.guard_name.is_some(),
+        guard_macro: cli_args.guard_macro.is_some(),
+        hybrid_loader: cli_args.hybrid_loader.is_some(),
+        verify_fn: cli_args.verify_fn.is_some(),
+        stream_accessor: cli_args.stream_accessor,
+        span_accessor: cli_args.span_accessor,
+        char_type: cli_args.char_type != CharType::Char,
+        raw_string: cli_args.raw_string,
+        octal: cli_args.radix == Radix::Oct,
+        regions: cli_args.regions.is_some(),
+        pragma_regions: cli_args.regions == Some(RegionStyle::Pragma),
+        toc: cli_args.toc,
+        encoding: cli_args.encode != Encoding::Bytes,
+        included_encoding: matches!(cli_args.encode, Encoding::Incbin | Encoding::Embed),
+        partial_input: cli_args.offset.is_some() || cli_args.length.is_some(),
+        delta_base: cli_args.delta_base.is_some(),
+        decoding: cli_args.decode_csv.is_some() || cli_args.decode_struct.is_some(),
+        locales: cli_args.locales,
+        manifest: cli_args.manifest,
+        emit_test: cli_args.emit_test.is_some(),
+        split: cli_args.split,
+        size_assertion: cli_args.assert_size.is_some() || cli_args.assert_size_max.is_some(),
+        element_type: cli_args.element_type != ElementType::Uint8,
+        align: cli_args.align.is_some(),
+        section: cli_args.section.is_some(),
+        obfuscate_xor: cli_args.obfuscate_xor.is_some(),
+        compress: cli_args.compress.is_some(),
+        dedupe: cli_args.dedupe,
+        self_check: cli_args.self_check,
+        object: cli_args.object.is_some(),
+        format: cli_args.format != OutputFormat::Header,
+        internal_linkage: cli_args.internal_linkage,
+        amalgamation,
+    };
+}
+
+/// Derive the symbol an input's data is declared under from the path it is named after (unless
+/// --symbol-name gives it), through --symbol-template or its filename followed by `role`, in
+/// --symbol-case or that of the language, between --symbol-prefix and --symbol-suffix
+fn derive_symbol(
+    cli_args: &CliArgs,
+    named_path: &Path,
+    role: &str,
+    input_path: &Path,
+    diagnostics: &mut Diagnostics,
+) -> Result<String, ()> {
+    if let Some(ref symbol_name) = cli_args.symbol_name {
+        return Ok(symbol_name.clone());
+    }
+
+    let symbol_name = match cli_args.symbol_template {
+        Some(ref template) => match expand_symbol_template(template, named_path) {
+            Ok(s) => {
+                let s = format!("{}{}", s, role);
+                let s = match cli_args.symbol_case {
+                    Some(case) => case.apply(&s),
+                    None => s,
+                };
+                diagnostics.log(format!("symbol name derived from template as \"{}\"", s));
+                s
+            }
+            Err(error) => {
+                diagnostics.error(format!(
+                    "invalid symbol template \"{}\": {}",
+                    template, error
+                ));
+                return Err(());
+            }
+        },
+        None => {
+            let s = sanitise_os_identifier(named_path.file_name().unwrap_or_default()) + role;
+            let s = match cli_args.symbol_case {
+                Some(case) => case.apply(&s),
+                None => cli_args.language.backend().derived_symbol(s),
+            };
+            diagnostics.log(format!("symbol name derived as \"{}\"", s));
+            s
+        }
+    };
+
+    return affix_symbol(&symbol_name, cli_args, input_path, diagnostics);
+}
+
+/// Put the --symbol-prefix and --symbol-suffix around a symbol derived from a filename (or from
+/// --symbol-template), mangling the derived part if the symbol is not a valid identifier
+///
+/// Filenames make for any identifier at all, which need not be one the language can use, but the
+/// prefix and suffix are kept as they were given: if the symbol is still not valid with only the
+/// derived part mangled, it is an error.
+fn affix_symbol(
+    derived: &str,
+    cli_args: &CliArgs,
+    input_path: &Path,
+    diagnostics: &mut Diagnostics,
+) -> Result<String, ()> {
+    let backend = cli_args.language.backend();
+    let prefix = cli_args.symbol_prefix.as_deref().unwrap_or_default();
+    let suffix = cli_args.symbol_suffix.as_deref().unwrap_or_default();
+    for (option, text) in [("--symbol-prefix", prefix), ("--symbol-suffix", suffix)] {
+        if !text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            diagnostics.error(format!(
+                "{} \"{}\" must be made of ASCII letters, digits and underscores",
+                option, text
+            ));
+            return Err(());
+        }
+    }
+
+    // A prefix may make a derived part starting with a digit valid, as does either of them one
+    // which is a keyword, so the derived part is only mangled if the whole symbol needs it
+    let symbol_name = format!("{}{}{}", prefix, derived, suffix);
+    if cli_args.xxd_compat || mangle_identifier(&symbol_name, backend).is_none() {
+        return Ok(symbol_name);
+    }
+    let (mangled, reason) = match mangle_identifier(derived, backend) {
+        Some((mangled, reason)) => (format!("{}{}{}", prefix, mangled, suffix), Some(reason)),
+        None => (symbol_name.clone(), None),
+    };
+
+    // Reported as the user put it together, before the derived part was mangled
+    if mangle_identifier(&mangled, backend).is_some()
+        && let Some((_, invalid)) = mangle_identifier(&symbol_name, backend)
+    {
+        let affixes: Vec<String> = [("--symbol-prefix", prefix), ("--symbol-suffix", suffix)]
+            .iter()
+            .filter(|(_, text)| !text.is_empty())
+            .map(|(option, text)| format!("{} \"{}\"", option, text))
+            .collect();
+        diagnostics.error(format!(
+            "symbol \"{}\" derived from \"{}\" with {} {} (pass another prefix or suffix, or --symbol-name)",
+            symbol_name,
+            input_path.to_string_lossy(),
+            affixes.join(" and "),
+            invalid
+        ));
+        return Err(());
+    }
+    if let Some(reason) = reason {
+        diagnostics.warn(Warning::MangledSymbol {
+            path: input_path.to_string_lossy().to_string(),
+            symbol: symbol_name,
+            mangled: mangled.clone(),
+            reason,
+        });
+    }
+
+    return Ok(mangled);
+}
+
+/// Expand a `--symbol-template` for an input path, sanitising both the text and the values
+/// substituted into it
+fn expand_symbol_template(template: &str, path: &Path) -> Result<String, String> {
+    let mut expanded = String::with_capacity(template.len() * 2);
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&sanitise_identifier(&rest[..start]));
+        let Some(len) = rest[start..].find('}') else {
+            return Err("unclosed \"{\"".to_string());
+        };
+        let placeholder = &rest[start + 1..start + len];
+        let value = match placeholder {
+            "name" => path.file_name().map(OsStr::to_os_string),
+            "stem" => path.file_stem().map(OsStr::to_os_string),
+            "ext" => path.extension().map(OsStr::to_os_string),
+            "dir" => path
+                .parent()
+                .and_then(|parent| {
+                    // A relative path in the current directory names no directory of its own
+                    match parent.as_os_str().is_empty() {
+                        true => std::env::current_dir().ok(),
+                        false => std::fs::canonicalize(parent).ok(),
+                    }
+                })
+                .and_then(|parent| parent.file_name().map(OsStr::to_os_string)),
+            "hash8" => {
+                let digest = checksum::sha256(&input::path_sort_key(path));
+                expanded.push_str(&checksum::to_hex(&digest[..4]));
+                rest = &rest[start + len + 1..];
+                continue;
+            }
+            _ => {
+                return Err(format!(
+                    "unknown placeholder \"{{{}}}\" (expected name, stem, ext, dir or hash8)",
+                    placeholder
+                ));
+            }
+        };
+        expanded.push_str(&sanitise_os_identifier(&value.unwrap_or_default()));
+        rest = &rest[start + len + 1..];
+    }
+    expanded.push_str(&sanitise_identifier(rest));
+
+    if expanded.is_empty() {
+        return Err("expands to an empty symbol name".to_string());
+    }
+    return Ok(expanded);
 }
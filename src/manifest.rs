@@ -0,0 +1,80 @@
+use crate::{
+    LINE_ENDING, checksum, format_as_escaped, push_doc_comment, push_namespace_close,
+    push_namespace_open,
+};
+
+/// A file listed in a manifest
+pub struct ManifestEntry {
+    /// Path relative to the scanned directory, always separated by `/` and kept byte for byte
+    /// when it is not valid Unicode
+    pub path: Vec<u8>,
+    pub size: usize,
+    pub sha256: [u8; 32],
+}
+
+/// Generate a constexpr table of the path, size and SHA-256 digest of every file, along with a
+/// `<symbol>_find(path)` lookup, without embedding the files themselves
+pub fn generate_src(
+    entries: &[ManifestEntry],
+    symbol_name: &str,
+    ns_name: Option<&str>,
+    comment: Option<&str>,
+    internal_linkage: bool,
+) -> String {
+    // Includes
+    let mut out_string = String::with_capacity(entries.len() * 0x100 + 0x400);
+    for header in ["array", "cstddef", "cstdint", "string_view"] {
+        out_string.push_str(format!("#include <{}>", header).as_str());
+        out_string.push_str(LINE_ENDING);
+    }
+
+    // Namespace
+    push_namespace_open(&mut out_string, ns_name, internal_linkage);
+
+    // Table
+    out_string.push_str(
+        format!(
+            "struct {}_file{{std::string_view path;std::size_t size;std::array<std::uint8_t,32> sha256;}};",
+            symbol_name
+        )
+        .as_str(),
+    );
+    out_string.push_str(LINE_ENDING);
+    let rows: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            format!(
+                "{{\"{}\",{},{}}}",
+                format_as_escaped(&entry.path),
+                entry.size,
+                checksum::to_initialiser(&entry.sha256)
+            )
+        })
+        .collect();
+    push_doc_comment(&mut out_string, comment);
+    out_string.push_str(
+        format!(
+            "constexpr std::array<{0}_file,{1}> {0}{{{{{2}}}}};",
+            symbol_name,
+            entries.len(),
+            rows.join(",")
+        )
+        .as_str(),
+    );
+    out_string.push_str(LINE_ENDING);
+
+    // Lookup
+    out_string.push_str(
+        format!(
+            "constexpr const {0}_file* {0}_find(std::string_view path){{for(const auto& file:{0}){{if(file.path==path)return &file;}}return nullptr;}}",
+            symbol_name
+        )
+        .as_str(),
+    );
+
+    // Close namespace (if need be)
+    push_namespace_close(&mut out_string, ns_name, internal_linkage);
+    out_string.push_str(LINE_ENDING);
+
+    return out_string;
+}
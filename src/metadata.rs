@@ -0,0 +1,105 @@
+use crate::{LINE_ENDING, Language, checksum};
+
+/// Number of lines in the metadata block, which is always the first thing in a generated file
+/// after the banner (if there is one)
+pub const LINE_COUNT: usize = 8;
+const BEGIN_MARKER: &str = "bin2hpp:begin";
+const END_MARKER: &str = "bin2hpp:end";
+
+/// What a generated file records about how it was generated
+pub struct Metadata<'a> {
+    /// Command line arguments the tool was run with
+    pub arguments: &'a [String],
+    pub source: &'a str,
+    pub size: usize,
+    pub sha256: [u8; 32],
+    /// Symbols through which the data is accessed
+    pub symbols: &'a [String],
+    /// Language of the file, whose comments the block is written in
+    pub language: Language,
+    /// Text (a license or "do not edit" notice) commented out above the block
+    pub banner: Option<&'a str>,
+    /// Whether to write the block recording where the file came from, or only the banner
+    pub provenance: bool,
+}
+
+/// Number of lines the banner and metadata block take up together
+pub fn line_count(banner: Option<&str>, provenance: bool) -> usize {
+    let block_line_count = match provenance {
+        true => LINE_COUNT,
+        false => 0,
+    };
+
+    return banner.map_or(0, |banner| banner.lines().count()) + block_line_count;
+}
+
+/// Quote an argument the way a POSIX shell would need it, if it needs quoting at all
+fn quote_argument(argument: &str) -> String {
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || "_-+=/.,:@%".contains(c);
+    if !argument.is_empty() && argument.chars().all(is_plain) {
+        return argument.to_string();
+    }
+
+    return format!("'{}'", argument.replace('\'', "'\\''"));
+}
+
+/// Prepend the metadata block to a generated file
+pub fn prepend(src: &str, metadata: &Metadata) -> String {
+    let mut out_string = block(metadata);
+    out_string.push_str(src);
+
+    return out_string;
+}
+
+/// The metadata block itself (after the banner), for files written piece by piece
+///
+/// The block is a fixed number of `// key=value` lines (commented as the language comments them)
+/// between begin and end markers, with control characters in values escaped so that every value
+/// stays on its line.
+pub fn block(metadata: &Metadata) -> String {
+    let arguments: Vec<String> = metadata
+        .arguments
+        .iter()
+        .map(|a| quote_argument(a))
+        .collect();
+    let fields = [
+        ("version", env!("CARGO_PKG_VERSION").to_string()),
+        ("arguments", arguments.join(" ")),
+        ("source", metadata.source.to_string()),
+        ("size", metadata.size.to_string()),
+        ("sha256", checksum::to_hex(&metadata.sha256)),
+        ("symbols", metadata.symbols.join(",")),
+    ];
+
+    let comment = metadata.language.line_comment();
+    let mut out_string = String::with_capacity(0x200);
+    for line in metadata.banner.unwrap_or_default().lines() {
+        out_string.push_str(comment);
+        if !line.trim_end().is_empty() {
+            out_string.push(' ');
+            out_string.push_str(line.trim_end());
+        }
+        out_string.push_str(LINE_ENDING);
+    }
+    if !metadata.provenance {
+        return out_string;
+    }
+    out_string.push_str(format!("{} {}", comment, BEGIN_MARKER).as_str());
+    out_string.push_str(LINE_ENDING);
+    for (key, value) in fields {
+        let value: String = value
+            .chars()
+            .map(|c| match c {
+                '\\' => "\\\\".to_string(),
+                c if c.is_control() => c.escape_default().to_string(),
+                c => c.to_string(),
+            })
+            .collect();
+        out_string.push_str(format!("{} {}={}", comment, key, value).as_str());
+        out_string.push_str(LINE_ENDING);
+    }
+    out_string.push_str(format!("{} {}", comment, END_MARKER).as_str());
+    out_string.push_str(LINE_ENDING);
+
+    return out_string;
+}
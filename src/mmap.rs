@@ -0,0 +1,146 @@
+use std::{
+    fs::File,
+    io,
+    ops::{Deref, Range},
+};
+
+use memmap2::{Mmap, MmapOptions};
+
+use crate::read_file;
+
+/// Inputs at least this large are memory-mapped rather than read into memory (64 MiB)
+pub const DEFAULT_MMAP_THRESHOLD: u64 = 0x400_0000;
+
+/// The contents of an input, either read into memory or mapped from the file
+pub enum InputData {
+    Owned(Vec<u8>),
+    Mapped(Mapping),
+}
+
+impl Deref for InputData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        return match self {
+            InputData::Owned(data) => data,
+            InputData::Mapped(mapping) => mapping,
+        };
+    }
+}
+
+impl InputData {
+    /// Narrow the data down to a range of it, which must be within its bounds, without copying
+    /// the part which is kept
+    pub fn slice(self, range: Range<usize>) -> InputData {
+        return match self {
+            InputData::Owned(mut data) => {
+                data.truncate(range.end);
+                data.drain(..range.start);
+                InputData::Owned(data)
+            }
+            InputData::Mapped(mut mapping) => {
+                let start = mapping.range.start;
+                mapping.range = start + range.start..start + range.end;
+                InputData::Mapped(mapping)
+            }
+        };
+    }
+}
+
+/// Read the whole of an input, mapping it instead if it is a regular file of at least
+/// `threshold` bytes
+///
+/// Files which cannot be mapped (on platforms without support, say) are read as usual.
+pub fn read(f: &File, threshold: u64) -> io::Result<InputData> {
+    if let Ok(metadata) = f.metadata()
+        && metadata.is_file()
+        && metadata.len() > 0
+        && metadata.len() >= threshold
+        && let Ok(mapping) = Mapping::new(f, metadata.len())
+    {
+        return Ok(InputData::Mapped(mapping));
+    }
+
+    return Ok(InputData::Owned(read_file(f)?));
+}
+
+/// Read-only memory map of a whole file
+///
+/// The file must not be truncated while it is mapped, otherwise reading the missing pages kills
+/// the process.
+pub struct Mapping {
+    map: Mmap,
+    /// Part of the file taken as the input's data
+    range: Range<usize>,
+}
+
+impl Mapping {
+    fn new(f: &File, len: u64) -> io::Result<Mapping> {
+        let len = usize::try_from(len).map_err(io::Error::other)?;
+        // SAFETY: the data is handed out as a `&[u8]`, which is only sound while nothing else
+        // writes to or truncates the file; inputs are only ever read here, and one changing under
+        // a run is documented as unsupported rather than read into memory to guard against it
+        let map = unsafe { MmapOptions::new().len(len).map(f)? };
+
+        return Ok(Mapping { map, range: 0..len });
+    }
+}
+
+impl Deref for Mapping {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        return &self.map[self.range.clone()];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Seek, Write};
+
+    use super::*;
+
+    /// Temporary file holding some data which does not repeat too often
+    fn input_file(name: &str) -> (File, Vec<u8>) {
+        let path = std::env::temp_dir().join(format!("bin2hpp-{}-{}", name, std::process::id()));
+        let data: Vec<u8> = (0..0x3000u32).map(|i| (i * 7 + i / 251) as u8).collect();
+        let mut f = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        f.write_all(&data).unwrap();
+        f.rewind().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        return (f, data);
+    }
+
+    #[test]
+    fn mapped_data_is_the_data_read() {
+        let (mut f, data) = input_file("mmap-whole");
+        let mapped = read(&f, 0).unwrap();
+        assert!(matches!(mapped, InputData::Mapped(_)));
+        f.rewind().unwrap();
+        let owned = read(&f, u64::MAX).unwrap();
+        assert!(matches!(owned, InputData::Owned(_)));
+
+        assert_eq!(&*mapped, &data[..]);
+        assert_eq!(&*owned, &data[..]);
+    }
+
+    #[test]
+    fn mapped_slices_are_the_slices_read() {
+        let (mut f, data) = input_file("mmap-slice");
+        for range in [0..0, 0x100..0x1234, 0x2fff..0x3000, 0..0x3000] {
+            f.rewind().unwrap();
+            let mapped = read(&f, 0).unwrap().slice(range.clone());
+            f.rewind().unwrap();
+            let owned = read(&f, u64::MAX).unwrap().slice(range.clone());
+            assert_eq!(&*mapped, &data[range.clone()]);
+            assert_eq!(&*owned, &*mapped);
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use crate::{LINE_ENDING, checksum};
+
+/// XOR data with a key, repeated over it as many times as it takes
+///
+/// Doing so twice with the same key gives back the original data.
+pub fn xor(data: &[u8], key: &[u8]) -> Vec<u8> {
+    return data
+        .iter()
+        .zip(key.iter().cycle())
+        .map(|(b, k)| b ^ k)
+        .collect();
+}
+
+/// Parse a key given in hexadecimal (with or without a `0x` prefix) into its bytes
+pub fn parse_key(hex: &str) -> Result<Vec<u8>, String> {
+    let digits = hex
+        .strip_prefix("0x")
+        .or_else(|| hex.strip_prefix("0X"))
+        .unwrap_or(hex);
+    if digits.is_empty()
+        || !digits.len().is_multiple_of(2)
+        || !digits.bytes().all(|c| c.is_ascii_hexdigit())
+    {
+        return Err("expected an even number of hex digits, such as 5a or 0xdeadbeef".to_string());
+    }
+
+    return Ok((0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).expect("checked hex digits"))
+        .collect());
+}
+
+/// Push a `<symbol>_decode()` function returning a copy of the array with the key XORed back out
+/// of it
+///
+/// The function is `constexpr` unless the array is only declared here (`--split`), so the data can
+/// be recovered at compile time too, at the cost of the plain bytes ending up in the binary.
+pub(crate) fn push_decoder(
+    out_string: &mut String,
+    symbol_name: &str,
+    key: &[u8],
+    element_type: &str,
+    constant: bool,
+) {
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str(
+        format!(
+            "{0} auto {1}_decode() noexcept{{constexpr std::array<std::uint8_t,{2}> key{3};auto out={1};for(std::size_t i=0;i<out.size();++i)out[i]=static_cast<{4}>(static_cast<std::uint8_t>(out[i])^key[i%{2}]);return out;}}",
+            match constant {
+                true => "constexpr",
+                false => "inline",
+            },
+            symbol_name,
+            key.len(),
+            checksum::to_initialiser(key),
+            element_type
+        )
+        .as_str(),
+    );
+    out_string.push_str(LINE_ENDING);
+}
@@ -0,0 +1,564 @@
+use clap::ValueEnum;
+
+use crate::{
+    LINE_ENDING, Language, SourceOptions, push_doc_comment, push_namespace_close,
+    push_namespace_open,
+};
+
+/// Alignment of the data in the object file (unless more is asked for), as for `.incbin`
+const OBJECT_ALIGNMENT: usize = 16;
+
+/// Format of an object file written in place of the data in the header (`--object`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ObjectFormat {
+    /// ELF, as linked on Linux and the BSDs
+    Elf,
+    /// COFF, as linked by MSVC and MinGW
+    Coff,
+    /// Mach-O, as linked on macOS and iOS
+    #[value(name = "mach-o", alias = "macho")]
+    MachO,
+}
+
+impl ObjectFormat {
+    /// Format of the objects linked on the platform the tool runs on
+    pub fn host() -> ObjectFormat {
+        if cfg!(target_vendor = "apple") {
+            return ObjectFormat::MachO;
+        }
+        return match cfg!(windows) {
+            true => ObjectFormat::Coff,
+            false => ObjectFormat::Elf,
+        };
+    }
+
+    /// Extension of object files of this format
+    pub fn extension(self) -> &'static str {
+        return match self {
+            ObjectFormat::Elf | ObjectFormat::MachO => "o",
+            ObjectFormat::Coff => "obj",
+        };
+    }
+}
+
+/// Architecture an object file is written for (`--object-arch`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ObjectArch {
+    #[value(name = "x86-64", alias = "x86_64", alias = "amd64")]
+    X86_64,
+    #[value(name = "aarch64", alias = "arm64")]
+    Aarch64,
+}
+
+impl ObjectArch {
+    /// Architecture the tool runs on, or x86-64 if it is neither
+    pub fn host() -> ObjectArch {
+        return match cfg!(target_arch = "aarch64") {
+            true => ObjectArch::Aarch64,
+            false => ObjectArch::X86_64,
+        };
+    }
+}
+
+/// Name the data is defined under in the object file, with C linkage, so the namespace (if any)
+/// is folded into it; the size is defined as `<name>_size`
+pub fn object_symbol(symbol_name: &str, ns_name: Option<&str>) -> String {
+    return match ns_name {
+        Some(namespace) => format!("{}_{}", namespace.replace("::", "_"), symbol_name),
+        None => symbol_name.to_string(),
+    };
+}
+
+/// Bytes placed in the object file: the data, NUL-terminated if asked for (and always if it is
+/// empty, as arrays cannot be)
+fn payload(data: &[u8], options: &SourceOptions) -> Vec<u8> {
+    let mut payload = data.to_vec();
+    if options.null_terminate || data.is_empty() {
+        payload.push(0);
+    }
+
+    return payload;
+}
+
+/// Generate a header declaring the data and its size defined by the object file, which is linked
+/// in rather than compiled, and (in C++) referring to them as `<symbol>` and `<symbol>_size` in
+/// the namespace if one was given
+pub fn generate_header_src(data: &[u8], symbol_name: &str, options: &SourceOptions) -> String {
+    let object_symbol = object_symbol(symbol_name, options.ns_name.as_deref());
+    let array_len = payload(data, options).len();
+
+    let mut out_string = String::with_capacity(0x200);
+    match options.language {
+        Language::Cpp => {
+            out_string.push_str("#include <cstddef>");
+            out_string.push_str(LINE_ENDING);
+            out_string.push_str("#include <cstdint>");
+            out_string.push_str(LINE_ENDING);
+            if options.ns_name.is_none() {
+                push_doc_comment(&mut out_string, options.comment.as_deref());
+            }
+            out_string.push_str(
+                format!(
+                    "extern \"C\" const {} {}[{}];",
+                    options.element_type.name(),
+                    object_symbol,
+                    array_len
+                )
+                .as_str(),
+            );
+            out_string.push_str(
+                format!("extern \"C\" const std::size_t {}_size;", object_symbol).as_str(),
+            );
+            out_string.push_str(LINE_ENDING);
+            if options.ns_name.is_some() {
+                push_namespace_open(&mut out_string, options.ns_name.as_deref(), false);
+                push_doc_comment(&mut out_string, options.comment.as_deref());
+                out_string.push_str(
+                    format!(
+                        "constexpr const auto& {0}=::{1};constexpr const auto& {0}_size=::{1}_size;",
+                        symbol_name, object_symbol
+                    )
+                    .as_str(),
+                );
+                push_namespace_close(&mut out_string, options.ns_name.as_deref(), false);
+                out_string.push_str(LINE_ENDING);
+            }
+        }
+        Language::C => {
+            out_string.push_str("#include <stddef.h>");
+            out_string.push_str(LINE_ENDING);
+            push_doc_comment(&mut out_string, options.comment.as_deref());
+            out_string.push_str(
+                format!(
+                    "extern const unsigned char {0}[{1}];extern const size_t {0}_size;",
+                    object_symbol, array_len
+                )
+                .as_str(),
+            );
+            out_string.push_str(LINE_ENDING);
+        }
+        Language::Rust | Language::CSharp | Language::Python => {
+            unreachable!("--object generates C and C++ headers only")
+        }
+    }
+
+    return out_string;
+}
+
+/// Generate a relocatable object file defining the data as a read-only `<symbol>` and its size
+/// in bytes as a `<symbol>_size` of pointer width, named as `object_symbol` names them
+///
+/// Nothing is left for the compiler to do, so that embedding hundreds of megabytes costs as
+/// little as linking them. A section asked for only applies to ELF, as for `.incbin`.
+pub fn generate_object(
+    data: &[u8],
+    symbol_name: &str,
+    format: ObjectFormat,
+    arch: ObjectArch,
+    options: &SourceOptions,
+) -> Result<Vec<u8>, String> {
+    let object_symbol = object_symbol(symbol_name, options.ns_name.as_deref());
+    let alignment = options
+        .align
+        .unwrap_or(0)
+        .max(OBJECT_ALIGNMENT)
+        .next_power_of_two();
+    // The size follows the data in the same section, aligned for its width
+    let mut contents = payload(data, options);
+    let payload_len = contents.len();
+    contents.resize(contents.len().next_multiple_of(8), 0);
+    let size_offset = contents.len();
+    contents.extend_from_slice(&(data.len() as u64).to_le_bytes());
+
+    let symbols = [
+        (object_symbol.clone(), 0, payload_len),
+        (format!("{}_size", object_symbol), size_offset, 8),
+    ];
+    return match format {
+        ObjectFormat::Elf => Ok(elf(&contents, &symbols, alignment, arch, options)),
+        ObjectFormat::Coff => coff(&contents, &symbols, alignment, arch),
+        ObjectFormat::MachO => macho(&contents, &symbols, alignment, arch),
+    };
+}
+
+/// A symbol defined in the data section: its name, offset and size
+type Symbol = (String, usize, usize);
+
+/// Little-endian writes into an object file being built up
+trait PutLe {
+    fn put_u8(&mut self, value: u8);
+    fn put_u16(&mut self, value: u16);
+    fn put_u32(&mut self, value: u32);
+    fn put_u64(&mut self, value: u64);
+    /// Put a name padded with NULs to a fixed width
+    fn put_name(&mut self, name: &str, width: usize);
+    /// Pad with NULs up to an alignment
+    fn align_to(&mut self, alignment: usize);
+}
+
+impl PutLe for Vec<u8> {
+    fn put_u8(&mut self, value: u8) {
+        self.push(value);
+    }
+
+    fn put_u16(&mut self, value: u16) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_u32(&mut self, value: u32) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_u64(&mut self, value: u64) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_name(&mut self, name: &str, width: usize) {
+        let mut bytes = name.as_bytes().to_vec();
+        bytes.resize(width, 0);
+        self.extend_from_slice(&bytes);
+    }
+
+    fn align_to(&mut self, alignment: usize) {
+        self.resize(self.len().next_multiple_of(alignment), 0);
+    }
+}
+
+/// Append a NUL-terminated name to a string table, returning its offset in it
+fn push_string(table: &mut Vec<u8>, name: &str) -> usize {
+    let offset = table.len();
+    table.extend_from_slice(name.as_bytes());
+    table.push(0);
+
+    return offset;
+}
+
+/// ELF64 relocatable object with the data in its own section (for `--gc-sections`), and an empty
+/// `.note.GNU-stack` so that linking it does not make the stack executable
+fn elf(
+    contents: &[u8],
+    symbols: &[Symbol],
+    alignment: usize,
+    arch: ObjectArch,
+    options: &SourceOptions,
+) -> Vec<u8> {
+    const HEADER_SIZE: usize = 64;
+    const SECTION_HEADER_SIZE: u16 = 64;
+    const SYMBOL_SIZE: usize = 24;
+    const SHT_PROGBITS: u32 = 1;
+    const SHT_SYMTAB: u32 = 2;
+    const SHT_STRTAB: u32 = 3;
+    const SHF_ALLOC: u64 = 2;
+    const STB_GLOBAL_STT_OBJECT: u8 = 0x11;
+
+    let data_section = match options.section.as_deref() {
+        Some(name) => name.to_string(),
+        None => format!(".rodata.{}", symbols[0].0),
+    };
+
+    let mut strtab = vec![0];
+    let mut symtab = vec![0; SYMBOL_SIZE];
+    for (name, offset, size) in symbols {
+        symtab.put_u32(push_string(&mut strtab, name) as u32);
+        symtab.put_u8(STB_GLOBAL_STT_OBJECT);
+        symtab.put_u8(0);
+        symtab.put_u16(1);
+        symtab.put_u64(*offset as u64);
+        symtab.put_u64(*size as u64);
+    }
+    let mut shstrtab = vec![0];
+    let names = [
+        push_string(&mut shstrtab, &data_section),
+        push_string(&mut shstrtab, ".note.GNU-stack"),
+        push_string(&mut shstrtab, ".symtab"),
+        push_string(&mut shstrtab, ".strtab"),
+        push_string(&mut shstrtab, ".shstrtab"),
+    ];
+
+    let mut body = vec![0; HEADER_SIZE];
+    body.align_to(alignment);
+    let data_offset = body.len();
+    body.extend_from_slice(contents);
+    body.align_to(8);
+    let symtab_offset = body.len();
+    body.extend_from_slice(&symtab);
+    let strtab_offset = body.len();
+    body.extend_from_slice(&strtab);
+    let shstrtab_offset = body.len();
+    body.extend_from_slice(&shstrtab);
+    body.align_to(8);
+    let section_headers_offset = body.len();
+
+    // Name, type, flags, offset, size, link, info, alignment and entry size of each section
+    let sections = [
+        (0, 0, 0, 0, 0, 0, 0, 0, 0),
+        (
+            names[0],
+            SHT_PROGBITS,
+            SHF_ALLOC,
+            data_offset,
+            contents.len(),
+            0,
+            0,
+            alignment,
+            0,
+        ),
+        (names[1], SHT_PROGBITS, 0, data_offset, 0, 0, 0, 1, 0),
+        (
+            names[2],
+            SHT_SYMTAB,
+            0,
+            symtab_offset,
+            symtab.len(),
+            4,
+            1,
+            8,
+            SYMBOL_SIZE,
+        ),
+        (
+            names[3],
+            SHT_STRTAB,
+            0,
+            strtab_offset,
+            strtab.len(),
+            0,
+            0,
+            1,
+            0,
+        ),
+        (
+            names[4],
+            SHT_STRTAB,
+            0,
+            shstrtab_offset,
+            shstrtab.len(),
+            0,
+            0,
+            1,
+            0,
+        ),
+    ];
+    for (name, kind, flags, offset, size, link, info, align, entry_size) in sections {
+        body.put_u32(name as u32);
+        body.put_u32(kind);
+        body.put_u64(flags);
+        body.put_u64(0);
+        body.put_u64(offset as u64);
+        body.put_u64(size as u64);
+        body.put_u32(link);
+        body.put_u32(info);
+        body.put_u64(align as u64);
+        body.put_u64(entry_size as u64);
+    }
+
+    let mut header = Vec::with_capacity(HEADER_SIZE);
+    // Magic, 64-bit, little-endian, version 1, System V ABI
+    header.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+    header.put_u64(0);
+    // Relocatable
+    header.put_u16(1);
+    header.put_u16(match arch {
+        ObjectArch::X86_64 => 62,
+        ObjectArch::Aarch64 => 183,
+    });
+    header.put_u32(1);
+    // Entry point and program headers, which relocatable objects have none of
+    header.put_u64(0);
+    header.put_u64(0);
+    header.put_u64(section_headers_offset as u64);
+    header.put_u32(0);
+    header.put_u16(HEADER_SIZE as u16);
+    header.put_u16(0);
+    header.put_u16(0);
+    header.put_u16(SECTION_HEADER_SIZE);
+    header.put_u16(sections.len() as u16);
+    header.put_u16(sections.len() as u16 - 1);
+    body[..HEADER_SIZE].copy_from_slice(&header);
+
+    return body;
+}
+
+/// COFF object with the data in `.rdata`, which limits it to 4 GiB
+fn coff(
+    contents: &[u8],
+    symbols: &[Symbol],
+    alignment: usize,
+    arch: ObjectArch,
+) -> Result<Vec<u8>, String> {
+    const HEADER_SIZE: usize = 20;
+    const SECTION_HEADER_SIZE: usize = 40;
+    const IMAGE_SCN_CNT_INITIALIZED_DATA: u32 = 0x40;
+    const IMAGE_SCN_MEM_READ: u32 = 0x4000_0000;
+    const IMAGE_SYM_CLASS_EXTERNAL: u8 = 2;
+    /// Most alignment a COFF section can ask for
+    const MAX_ALIGNMENT: usize = 8192;
+
+    let Ok(contents_len) = u32::try_from(contents.len()) else {
+        return Err("COFF object files cannot hold more than 4 GiB".to_string());
+    };
+    if alignment > MAX_ALIGNMENT {
+        return Err(format!(
+            "COFF sections cannot be aligned to more than {} bytes",
+            MAX_ALIGNMENT
+        ));
+    }
+
+    let data_offset = HEADER_SIZE + SECTION_HEADER_SIZE;
+    let symbol_table_offset = data_offset + contents.len();
+    let mut object = Vec::with_capacity(symbol_table_offset + 0x100);
+    object.put_u16(match arch {
+        ObjectArch::X86_64 => 0x8664,
+        ObjectArch::Aarch64 => 0xaa64,
+    });
+    object.put_u16(1);
+    // No timestamp, so that objects are reproducible
+    object.put_u32(0);
+    object.put_u32(symbol_table_offset as u32);
+    object.put_u32(symbols.len() as u32);
+    object.put_u16(0);
+    object.put_u16(0);
+
+    object.put_name(".rdata", 8);
+    object.put_u32(0);
+    object.put_u32(0);
+    object.put_u32(contents_len);
+    object.put_u32(data_offset as u32);
+    object.put_u32(0);
+    object.put_u32(0);
+    object.put_u16(0);
+    object.put_u16(0);
+    object.put_u32(
+        IMAGE_SCN_CNT_INITIALIZED_DATA
+            | IMAGE_SCN_MEM_READ
+            | ((alignment.trailing_zeros() + 1) << 20),
+    );
+    object.extend_from_slice(contents);
+
+    // Names longer than 8 bytes are kept in the string table, which starts with its own size
+    let mut strings = vec![0; 4];
+    for (name, offset, _) in symbols {
+        match name.len() {
+            0..=8 => object.put_name(name, 8),
+            _ => {
+                object.put_u32(0);
+                object.put_u32(push_string(&mut strings, name) as u32);
+            }
+        }
+        object.put_u32(*offset as u32);
+        object.put_u16(1);
+        object.put_u16(0);
+        object.put_u8(IMAGE_SYM_CLASS_EXTERNAL);
+        object.put_u8(0);
+    }
+    let strings_len = strings.len() as u32;
+    strings[..4].copy_from_slice(&strings_len.to_le_bytes());
+    object.extend_from_slice(&strings);
+
+    return Ok(object);
+}
+
+/// Mach-O 64-bit object with the data in `__TEXT,__const`, whose C symbols are prefixed with an
+/// underscore
+fn macho(
+    contents: &[u8],
+    symbols: &[Symbol],
+    alignment: usize,
+    arch: ObjectArch,
+) -> Result<Vec<u8>, String> {
+    const HEADER_SIZE: usize = 32;
+    const SEGMENT_COMMAND_SIZE: usize = 72 + 80;
+    const SYMTAB_COMMAND_SIZE: usize = 24;
+    const DYSYMTAB_COMMAND_SIZE: usize = 80;
+    const LC_SEGMENT_64: u32 = 0x19;
+    const LC_SYMTAB: u32 = 0x2;
+    const LC_DYSYMTAB: u32 = 0xb;
+    const N_SECT_EXT: u8 = 0x0f;
+    const MH_SUBSECTIONS_VIA_SYMBOLS: u32 = 0x2000;
+
+    let Ok(contents_len) = u32::try_from(contents.len()) else {
+        return Err("Mach-O object files cannot hold more than 4 GiB".to_string());
+    };
+
+    let commands_size = SEGMENT_COMMAND_SIZE + SYMTAB_COMMAND_SIZE + DYSYMTAB_COMMAND_SIZE;
+    let data_offset = (HEADER_SIZE + commands_size).next_multiple_of(alignment);
+    let symbol_table_offset = (data_offset + contents.len()).next_multiple_of(8);
+    let mut strings = vec![0];
+    let string_offsets: Vec<usize> = symbols
+        .iter()
+        .map(|(name, _, _)| push_string(&mut strings, &format!("_{}", name)))
+        .collect();
+    let strings_offset = symbol_table_offset + 16 * symbols.len();
+
+    let mut object = Vec::with_capacity(strings_offset + strings.len());
+    object.put_u32(0xfeed_facf);
+    match arch {
+        ObjectArch::X86_64 => {
+            object.put_u32(0x0100_0007);
+            object.put_u32(3);
+        }
+        ObjectArch::Aarch64 => {
+            object.put_u32(0x0100_000c);
+            object.put_u32(0);
+        }
+    }
+    // Object file
+    object.put_u32(1);
+    object.put_u32(3);
+    object.put_u32(commands_size as u32);
+    object.put_u32(MH_SUBSECTIONS_VIA_SYMBOLS);
+    object.put_u32(0);
+
+    // The one (unnamed) segment of an object file, and the section in it
+    object.put_u32(LC_SEGMENT_64);
+    object.put_u32(SEGMENT_COMMAND_SIZE as u32);
+    object.put_name("", 16);
+    object.put_u64(0);
+    object.put_u64(contents_len as u64);
+    object.put_u64(data_offset as u64);
+    object.put_u64(contents_len as u64);
+    object.put_u32(7);
+    object.put_u32(7);
+    object.put_u32(1);
+    object.put_u32(0);
+    object.put_name("__const", 16);
+    object.put_name("__TEXT", 16);
+    object.put_u64(0);
+    object.put_u64(contents_len as u64);
+    object.put_u32(data_offset as u32);
+    object.put_u32(alignment.trailing_zeros());
+    object.put_u32(0);
+    object.put_u32(0);
+    object.put_u32(0);
+    object.put_u32(0);
+    object.put_u32(0);
+    object.put_u32(0);
+
+    object.put_u32(LC_SYMTAB);
+    object.put_u32(SYMTAB_COMMAND_SIZE as u32);
+    object.put_u32(symbol_table_offset as u32);
+    object.put_u32(symbols.len() as u32);
+    object.put_u32(strings_offset as u32);
+    object.put_u32(strings.len() as u32);
+
+    // Every symbol is an external definition, already in name order
+    object.put_u32(LC_DYSYMTAB);
+    object.put_u32(DYSYMTAB_COMMAND_SIZE as u32);
+    for value in [0, 0, 0, symbols.len() as u32, symbols.len() as u32, 0] {
+        object.put_u32(value);
+    }
+    object.resize(HEADER_SIZE + commands_size, 0);
+
+    object.align_to(alignment);
+    object.extend_from_slice(contents);
+    object.align_to(8);
+    for ((_, offset, _), string_offset) in symbols.iter().zip(string_offsets) {
+        object.put_u32(string_offset as u32);
+        object.put_u8(N_SECT_EXT);
+        object.put_u8(1);
+        object.put_u16(0);
+        object.put_u64(*offset as u64);
+    }
+    object.extend_from_slice(&strings);
+
+    return Ok(object);
+}
@@ -0,0 +1,394 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+
+/// What to do when an output path is already taken by the output of another input of the same
+/// batch (as `foo.bin` and `foo.txt` both generate `foo.hpp`)
+///
+/// Files left by earlier runs are not collisions, they are simply replaced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CollisionPolicy {
+    /// Fail without writing the later output
+    Error,
+    /// Replace the earlier output with the later one
+    Overwrite,
+    /// Write the later output to the first free path of the form `<stem>_<n>.<extension>`
+    Rename,
+    /// Leave the earlier output alone and carry on without the later one
+    Skip,
+}
+
+/// How a generated header is written out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteMode {
+    /// Always write the output
+    Always,
+    /// Leave the output alone if it already has exactly the generated contents (`--if-changed`)
+    IfChanged,
+    /// Never write, only compare the output against the generated contents (`--check`)
+    Check,
+    /// Never write, only work out which paths would be written (`--list-outputs`)
+    DryRun,
+}
+
+/// Convert the `\r\n` line endings of generated text into `\n`, so that text generated on
+/// Windows is the same as everywhere else (`--reproducible`)
+///
+/// Every representation escapes carriage returns in the data, so only the line endings of the
+/// text (including those of comments, banners and templates given) are converted.
+pub fn unix_line_endings(text: &[u8]) -> Vec<u8> {
+    let mut converted = Vec::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(position) = rest.windows(2).position(|pair| pair == b"\r\n") {
+        converted.extend_from_slice(&rest[..position]);
+        rest = &rest[position + 1..];
+    }
+    converted.extend_from_slice(rest);
+
+    return converted;
+}
+
+/// Writer converting line endings as `unix_line_endings` does, for text written piece by piece
+pub struct UnixLineEndings<'a> {
+    inner: &'a mut dyn Write,
+    /// Whether the last piece ended in a carriage return, which depends on what comes next
+    carriage_return: bool,
+}
+
+impl<'a> UnixLineEndings<'a> {
+    pub fn new(inner: &'a mut dyn Write) -> UnixLineEndings<'a> {
+        return UnixLineEndings {
+            inner,
+            carriage_return: false,
+        };
+    }
+
+    /// Write out a carriage return the text ended in, which was not a line ending after all
+    pub fn finish(self) -> io::Result<()> {
+        if self.carriage_return {
+            self.inner.write_all(b"\r")?;
+        }
+
+        return Ok(());
+    }
+}
+
+impl Write for UnixLineEndings<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if std::mem::take(&mut self.carriage_return) && buf[0] != b'\n' {
+            self.inner.write_all(b"\r")?;
+        }
+
+        let (text, carriage_return) = match buf.strip_suffix(b"\r") {
+            Some(text) => (text, true),
+            None => (buf, false),
+        };
+        self.inner.write_all(&unix_line_endings(text))?;
+        self.carriage_return = carriage_return;
+
+        return Ok(buf.len());
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        return self.inner.flush();
+    }
+}
+
+/// Summarise how a stale output differs from what it should contain: the number of lines which
+/// differ and the first of them
+pub fn diff_summary(existing: &str, generated: &str) -> String {
+    /// Longest excerpt of a line shown in the summary
+    const EXCERPT_LEN: usize = 72;
+    let excerpt = |line: Option<&str>| match line {
+        Some(line) if line.chars().count() > EXCERPT_LEN => {
+            format!("{}...", line.chars().take(EXCERPT_LEN).collect::<String>())
+        }
+        Some(line) => line.to_string(),
+        None => "<end of file>".to_string(),
+    };
+
+    let existing_lines: Vec<&str> = existing.lines().collect();
+    let generated_lines: Vec<&str> = generated.lines().collect();
+    let line_count = existing_lines.len().max(generated_lines.len());
+    let differing: Vec<usize> = (0..line_count)
+        .filter(|&i| existing_lines.get(i) != generated_lines.get(i))
+        .collect();
+
+    return match differing.first() {
+        Some(&first) => format!(
+            "{} of {} lines differ, first at line {}\n  - {}\n  + {}",
+            differing.len(),
+            line_count,
+            first + 1,
+            excerpt(existing_lines.get(first).copied()),
+            excerpt(generated_lines.get(first).copied())
+        ),
+        // Only line endings differ
+        None => "line endings differ".to_string(),
+    };
+}
+
+/// Outcome of applying a collision policy to an output path
+#[derive(Debug, PartialEq, Eq)]
+pub enum Resolution {
+    /// Write to this path
+    Write(PathBuf),
+    /// Do not write anything
+    Skip,
+    /// The path is taken and the policy says to fail
+    Collision,
+}
+
+/// Apply a collision policy to an output path, `is_taken` decides whether a path collides (is
+/// already taken by another output of the batch)
+pub fn resolve_collision(
+    path: &Path,
+    policy: CollisionPolicy,
+    is_taken: impl Fn(&Path) -> bool,
+) -> Resolution {
+    if !is_taken(path) {
+        return Resolution::Write(path.to_path_buf());
+    }
+
+    return match policy {
+        CollisionPolicy::Error => Resolution::Collision,
+        CollisionPolicy::Overwrite => Resolution::Write(path.to_path_buf()),
+        CollisionPolicy::Skip => Resolution::Skip,
+        CollisionPolicy::Rename => {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let extension = path.extension().map(|e| e.to_string_lossy());
+            let mut n: usize = 1;
+            loop {
+                let file_name = match extension {
+                    Some(ref extension) => format!("{}_{}.{}", stem, n, extension),
+                    None => format!("{}_{}", stem, n),
+                };
+                let candidate = path.with_file_name(file_name);
+                if !is_taken(&candidate) {
+                    return Resolution::Write(candidate);
+                }
+                n += 1;
+            }
+        }
+    };
+}
+
+/// Write a file by writing a temporary file next to it and renaming that over it, so that the
+/// file is either left as it was or completely written, never half-written
+pub fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    return write_atomically_with(path, |writer| writer.write_all(contents));
+}
+
+/// Write a file atomically as `write_atomically` does, with its contents written piece by piece
+/// by the given function
+pub fn write_atomically_with(
+    path: &Path,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> io::Result<()> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+    let result = File::create(&temp_path)
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            write(&mut writer)?;
+            return writer.flush();
+        })
+        .and_then(|_| std::fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+
+    return result;
+}
+
+/// Outcome of generating one entry of a batch
+pub struct JobOutcome {
+    pub input: PathBuf,
+    /// Files written, or `None` if the entry failed
+    pub written: Option<Vec<PathBuf>>,
+}
+
+fn push_json_string(out_string: &mut String, s: &str) {
+    out_string.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out_string.push_str("\\\""),
+            '\\' => out_string.push_str("\\\\"),
+            '\n' => out_string.push_str("\\n"),
+            '\r' => out_string.push_str("\\r"),
+            '\t' => out_string.push_str("\\t"),
+            c if (c as u32) < 0x20 => out_string.push_str(format!("\\u{:04x}", c as u32).as_str()),
+            c => out_string.push(c),
+        }
+    }
+    out_string.push('"');
+}
+
+/// Format the input of an entry and, if it succeeded, its outputs as a JSON object
+fn push_outcome(out_string: &mut String, outcome: &JobOutcome) {
+    out_string.push_str("{\"input\":");
+    push_json_string(out_string, &outcome.input.to_string_lossy());
+    if let Some(ref written) = outcome.written {
+        out_string.push_str(",\"outputs\":");
+        push_json_paths(out_string, written);
+    }
+    out_string.push('}');
+}
+
+fn push_json_paths(out_string: &mut String, paths: &[PathBuf]) {
+    out_string.push('[');
+    for (index, path) in paths.iter().enumerate() {
+        if index > 0 {
+            out_string.push(',');
+        }
+        push_json_string(out_string, &path.to_string_lossy());
+    }
+    out_string.push(']');
+}
+
+/// Format the outcome of every entry of a batch as a JSON document of the form
+/// `{"succeeded":[{"input":"...","outputs":["..."]}],"failed":[{"input":"..."}]}`
+pub fn batch_report(outcomes: &[JobOutcome]) -> String {
+    let mut succeeded = Vec::new();
+    let mut failed = Vec::new();
+    for outcome in outcomes {
+        let mut entry = String::new();
+        push_outcome(&mut entry, outcome);
+        match outcome.written {
+            Some(_) => succeeded.push(entry),
+            None => failed.push(entry),
+        }
+    }
+
+    return format!(
+        "{{\"succeeded\":[{}],\"failed\":[{}]}}\n",
+        succeeded.join(","),
+        failed.join(",")
+    );
+}
+
+/// Format the files a batch would write as a JSON document of the form
+/// `{"inputs":[{"input":"...","outputs":["..."]}],"outputs":["..."]}`, the last listing every
+/// output including those generated from all of the inputs (an amalgamation or index)
+pub fn outputs_listing(outcomes: &[JobOutcome], outputs: &[PathBuf]) -> String {
+    let mut out_string = String::from("{\"inputs\":[");
+    for (index, outcome) in outcomes.iter().enumerate() {
+        if index > 0 {
+            out_string.push(',');
+        }
+        push_outcome(&mut out_string, outcome);
+    }
+    out_string.push_str("],\"outputs\":");
+    push_json_paths(&mut out_string, outputs);
+    out_string.push_str("}\n");
+
+    return out_string;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn untaken_paths_are_written() {
+        let resolution = resolve_collision(Path::new("a.hpp"), CollisionPolicy::Error, |_| false);
+        assert_eq!(resolution, Resolution::Write(PathBuf::from("a.hpp")));
+    }
+
+    #[test]
+    fn taken_paths_follow_the_policy() {
+        let taken = |path: &Path| path == Path::new("a.hpp");
+        let path = Path::new("a.hpp");
+        assert_eq!(
+            resolve_collision(path, CollisionPolicy::Error, taken),
+            Resolution::Collision
+        );
+        assert_eq!(
+            resolve_collision(path, CollisionPolicy::Overwrite, taken),
+            Resolution::Write(PathBuf::from("a.hpp"))
+        );
+        assert_eq!(
+            resolve_collision(path, CollisionPolicy::Skip, taken),
+            Resolution::Skip
+        );
+    }
+
+    #[test]
+    fn renaming_picks_the_first_free_path() {
+        let taken =
+            |path: &Path| path == Path::new("dir/a.hpp") || path == Path::new("dir/a_1.hpp");
+        assert_eq!(
+            resolve_collision(Path::new("dir/a.hpp"), CollisionPolicy::Rename, taken),
+            Resolution::Write(PathBuf::from("dir/a_2.hpp"))
+        );
+        let taken = |path: &Path| path == Path::new("a");
+        assert_eq!(
+            resolve_collision(Path::new("a"), CollisionPolicy::Rename, taken),
+            Resolution::Write(PathBuf::from("a_1"))
+        );
+    }
+
+    #[test]
+    fn only_crlf_pairs_are_converted() {
+        assert_eq!(unix_line_endings(b"a\r\nb\r\n"), b"a\nb\n");
+        assert_eq!(unix_line_endings(b"a\rb\n\r"), b"a\rb\n\r");
+        assert_eq!(unix_line_endings(b"\r\r\n\r"), b"\r\n\r");
+        assert_eq!(unix_line_endings(b""), b"");
+    }
+
+    /// Text written through `UnixLineEndings` in the given pieces
+    fn write_in_pieces(pieces: &[&[u8]]) -> Vec<u8> {
+        let mut converted = Vec::new();
+        let mut writer = UnixLineEndings::new(&mut converted);
+        for piece in pieces {
+            writer.write_all(piece).unwrap();
+        }
+        writer.finish().unwrap();
+
+        return converted;
+    }
+
+    #[test]
+    fn writer_converts_like_the_function() {
+        assert_eq!(write_in_pieces(&[b"a\r\nb", b"\r\nc\r\n"]), b"a\nb\nc\n");
+        assert_eq!(write_in_pieces(&[b"", b"a\rb"]), b"a\rb");
+    }
+
+    #[test]
+    fn writer_joins_pairs_split_across_writes() {
+        assert_eq!(write_in_pieces(&[b"a\r", b"\nb"]), b"a\nb");
+        assert_eq!(write_in_pieces(&[b"a\r", b"", b"\nb"]), b"a\nb");
+        // A carriage return followed by anything else, or by nothing at all, is kept
+        assert_eq!(write_in_pieces(&[b"a\r", b"b\r"]), b"a\rb\r");
+        assert_eq!(write_in_pieces(&[b"\r", b"\r", b"\n"]), b"\r\n");
+    }
+
+    #[test]
+    fn summaries_show_the_first_differing_line() {
+        assert_eq!(
+            diff_summary("a\nb\nc\n", "a\nB\nC\n"),
+            "2 of 3 lines differ, first at line 2\n  - b\n  + B"
+        );
+        assert_eq!(
+            diff_summary("a\n", "a\nb\n"),
+            "1 of 2 lines differ, first at line 2\n  - <end of file>\n  + b"
+        );
+        assert_eq!(diff_summary("a\r\nb\r\n", "a\nb\n"), "line endings differ");
+    }
+
+    #[test]
+    fn summaries_cut_long_lines_short() {
+        let long = "x".repeat(100);
+        let summary = diff_summary(&long, "y");
+        assert!(summary.contains(&format!("  - {}...\n", "x".repeat(72))));
+        assert!(summary.ends_with("  + y"));
+    }
+}
@@ -0,0 +1,171 @@
+use crate::{
+    LINE_ENDING, Representation, SourceOptions, array_data,
+    backend::{self, Backend, Features},
+    checksum::{self, ChecksumAlgorithm},
+    format_as_byte_string,
+};
+
+/// Keywords of Python 3, leaving out the soft keywords which can still name a constant
+const KEYWORDS: [&str; 35] = [
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
+/// Backend generating Python modules, in which every representation of bytes is a byte string
+pub struct PythonBackend;
+
+impl Backend for PythonBackend {
+    fn extension(&self) -> &'static str {
+        return "py";
+    }
+
+    fn keywords(&self) -> &'static [&'static str] {
+        return &KEYWORDS;
+    }
+
+    fn reserves_underscores(&self) -> bool {
+        return false;
+    }
+
+    fn guarded(&self) -> bool {
+        return false;
+    }
+
+    fn derived_symbol(&self, symbol_name: String) -> String {
+        return symbol_name.to_ascii_uppercase();
+    }
+
+    fn line_comment(&self) -> &'static str {
+        return "#";
+    }
+
+    // A module has no namespace but its path, and nothing in it is private
+    fn unsupported(&self, features: &Features) -> Option<&'static str> {
+        if let Some(reason) = backend::unsupported_outside_c(features) {
+            return Some(reason);
+        }
+        let unsupported = features.encoding
+            || features.namespace
+            || features.internal_linkage
+            || features.span_accessor
+            || features.regions
+            || features.toc;
+
+        return unsupported.then_some(
+            "cannot be combined with --encode, --namespace, --internal-linkage, --span-accessor, --regions or --toc",
+        );
+    }
+
+    fn generate_src(
+        &self,
+        data: &[u8],
+        representation: Representation,
+        symbol_name: &str,
+        options: &SourceOptions,
+    ) -> String {
+        return match representation {
+            Representation::BraceInitialiser
+            | Representation::EscapedStringLiteral
+            | Representation::StringLiteral => {
+                generate_src(&array_data(data, options), symbol_name, options)
+            }
+            _ => unreachable!("representation without a Python equivalent"),
+        };
+    }
+}
+
+/// Generate a Python module declaring the data as a `<SYMBOL>: bytes` byte string along with a
+/// `<SYMBOL>_SIZE: int`, which does not count a NUL terminator
+///
+/// Long data is split into a byte string per line (of as many bytes as array elements would be),
+/// which Python joins into one as it does C string literals.
+fn generate_src(data: &[u8], symbol_name: &str, options: &SourceOptions) -> String {
+    let array_len = match options.null_terminate {
+        true => data.len().saturating_sub(1),
+        false => data.len(),
+    };
+
+    let mut out_string = String::with_capacity(data.len() * 4 + 0x200);
+    for line in options.comment.as_deref().unwrap_or_default().lines() {
+        out_string.push('#');
+        if !line.trim_end().is_empty() {
+            out_string.push(' ');
+            out_string.push_str(line.trim_end());
+        }
+        out_string.push_str(LINE_ENDING);
+    }
+    match options.byte_format.bytes_per_line {
+        line_len if line_len > 0 && data.len() > line_len => {
+            out_string.push_str(format!("{}: bytes = (", symbol_name).as_str());
+            out_string.push_str(LINE_ENDING);
+            for chunk in data.chunks(line_len) {
+                out_string.push_str(
+                    format!(
+                        "{}b\"{}\"",
+                        options.byte_format.indent,
+                        format_as_byte_string(chunk)
+                    )
+                    .as_str(),
+                );
+                out_string.push_str(LINE_ENDING);
+            }
+            out_string.push(')');
+        }
+        _ => out_string.push_str(
+            format!(
+                "{}: bytes = b\"{}\"",
+                symbol_name,
+                format_as_byte_string(data)
+            )
+            .as_str(),
+        ),
+    }
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str(format!("{}_SIZE: int = {}", symbol_name, array_len).as_str());
+    out_string.push_str(LINE_ENDING);
+
+    for checksum in &options.checksums {
+        let name = checksum.algorithm.name().to_ascii_uppercase();
+        match checksum.algorithm {
+            ChecksumAlgorithm::Crc32 => {
+                let mut crc = [0; 4];
+                crc.copy_from_slice(&checksum.digest);
+                out_string.push_str(
+                    format!(
+                        "{}_{}: int = {:#010x}",
+                        symbol_name,
+                        name,
+                        u32::from_be_bytes(crc)
+                    )
+                    .as_str(),
+                );
+            }
+            ChecksumAlgorithm::Sha256 => out_string.push_str(
+                format!(
+                    "{}_{}: bytes = b\"{}\"",
+                    symbol_name,
+                    name,
+                    format_as_byte_string(&checksum.digest)
+                )
+                .as_str(),
+            ),
+        }
+        out_string.push_str(LINE_ENDING);
+        if options.checksum_hex {
+            out_string.push_str(
+                format!(
+                    "{}_{}_HEX: str = \"{}\"",
+                    symbol_name,
+                    name,
+                    checksum::to_hex(&checksum.digest)
+                )
+                .as_str(),
+            );
+            out_string.push_str(LINE_ENDING);
+        }
+    }
+
+    return out_string;
+}
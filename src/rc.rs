@@ -0,0 +1,74 @@
+use crate::{
+    LINE_ENDING, SourceOptions, object, push_doc_comment, push_namespace_close, push_namespace_open,
+};
+
+/// Helper loading an `RCDATA` resource from the module (executable or DLL) the calling code is
+/// linked into, defined once however many headers use it
+const LOAD_RESOURCE: &str = r#"#ifndef BIN2HPP_DETAIL_RESOURCE
+#define BIN2HPP_DETAIL_RESOURCE
+namespace bin2hpp_detail{struct resource{const std::uint8_t* data;std::size_t size;};inline resource load_resource(const char* name) noexcept{HMODULE module=nullptr;if(!GetModuleHandleExA(GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS|GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT,reinterpret_cast<LPCSTR>(&load_resource),&module))return {nullptr,0};HRSRC info=FindResourceA(module,name,MAKEINTRESOURCEA(10));if(!info)return {nullptr,0};HGLOBAL handle=LoadResource(module,info);if(!handle)return {nullptr,0};return {static_cast<const std::uint8_t*>(LockResource(handle)),SizeofResource(module,info)};}}
+#endif"#;
+
+/// Name of the resource holding the data: the symbol (with the namespace folded into it) in
+/// upper case, as the resource compiler stores names
+pub fn resource_name(symbol_name: &str, ns_name: Option<&str>) -> String {
+    return object::object_symbol(symbol_name, ns_name).to_ascii_uppercase();
+}
+
+/// Generate a resource script compiling the input file into an `RCDATA` resource, leaving the
+/// resource compiler to read it
+pub fn generate_src(path: &str, symbol_name: &str, options: &SourceOptions) -> String {
+    // Resource script strings escape backslashes as C does, and double quotes by doubling them
+    let rc_path = path.replace('\\', "\\\\").replace('"', "\"\"");
+
+    let mut out_string = String::with_capacity(0x100);
+    out_string.push_str(
+        format!(
+            "{} RCDATA \"{}\"",
+            resource_name(symbol_name, options.ns_name.as_deref()),
+            rc_path
+        )
+        .as_str(),
+    );
+    out_string.push_str(LINE_ENDING);
+
+    return out_string;
+}
+
+/// Generate a header with a `<symbol>()` accessor returning the data and size of the resource
+/// compiled from the resource script, found and loaded with `FindResource`/`LoadResource` on
+/// first use (`{nullptr,0}` if the script was not linked in)
+pub fn generate_header_src(symbol_name: &str, options: &SourceOptions) -> String {
+    let mut out_string = String::with_capacity(LOAD_RESOURCE.len() + 0x200);
+    for header in ["<cstddef>", "<cstdint>", "<windows.h>"] {
+        out_string.push_str(format!("#include {}", header).as_str());
+        out_string.push_str(LINE_ENDING);
+    }
+    for line in LOAD_RESOURCE.lines() {
+        out_string.push_str(line);
+        out_string.push_str(LINE_ENDING);
+    }
+
+    push_namespace_open(
+        &mut out_string,
+        options.ns_name.as_deref(),
+        options.internal_linkage,
+    );
+    push_doc_comment(&mut out_string, options.comment.as_deref());
+    out_string.push_str(
+        format!(
+            "inline const bin2hpp_detail::resource& {}() noexcept{{static const bin2hpp_detail::resource resource=bin2hpp_detail::load_resource(\"{}\");return resource;}}",
+            symbol_name,
+            resource_name(symbol_name, options.ns_name.as_deref())
+        )
+        .as_str(),
+    );
+    push_namespace_close(
+        &mut out_string,
+        options.ns_name.as_deref(),
+        options.internal_linkage,
+    );
+    out_string.push_str(LINE_ENDING);
+
+    return out_string;
+}
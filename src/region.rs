@@ -0,0 +1,45 @@
+use clap::ValueEnum;
+
+use crate::LINE_ENDING;
+
+/// How sections of the generated source are marked so that editors can fold them (`--regions`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RegionStyle {
+    /// `#pragma region`/`#pragma endregion`, folded by Visual Studio, VS Code and CLion, and
+    /// ignored by MSVC, Clang and GCC 13 or later (older GCCs warn with -Wunknown-pragmas)
+    Pragma,
+    /// `// region`/`// endregion` comments, folded by VS Code and CLion and invisible to every
+    /// compiler
+    Comment,
+}
+
+/// Surround a section of generated source with region markers, leaving the `#include` lines it
+/// starts with outside so the folded region holds only what is declared
+pub fn wrap(src: &str, name: &str, style: RegionStyle) -> String {
+    let mut split = 0;
+    for line in src.split_inclusive('\n') {
+        if !line.starts_with("#include") {
+            break;
+        }
+        split += line.len();
+    }
+    let (includes, body) = src.split_at(split);
+
+    let (begin, end) = match style {
+        RegionStyle::Pragma => ("#pragma region", "#pragma endregion"),
+        RegionStyle::Comment => ("// region", "// endregion"),
+    };
+
+    let mut out_string = String::with_capacity(src.len() + name.len() * 2 + 0x40);
+    out_string.push_str(includes);
+    out_string.push_str(format!("{} {}", begin, name).as_str());
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str(body);
+    if !body.ends_with('\n') {
+        out_string.push_str(LINE_ENDING);
+    }
+    out_string.push_str(format!("{} {}", end, name).as_str());
+    out_string.push_str(LINE_ENDING);
+
+    return out_string;
+}
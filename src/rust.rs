@@ -0,0 +1,283 @@
+use crate::{
+    DATA_MARKER, LINE_ENDING, Representation, SourceOptions, array_data,
+    backend::{self, Backend, Features},
+    checksum::{self, ChecksumAlgorithm},
+    format_as_byte_string, format_as_elements, push_doc_comment,
+};
+
+/// Keywords of Rust 2024, including those reserved for future use
+const KEYWORDS: [&str; 53] = [
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "gen", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+    "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "union", "unsafe", "use", "where", "while", "abstract", "become", "box", "do", "final",
+    "macro", "override", "priv", "try", "typeof", "unsized", "virtual", "yield",
+];
+
+/// Backend generating Rust modules, in which only byte arrays, string literals and included
+/// files can be generated
+pub struct RustBackend;
+
+impl Backend for RustBackend {
+    fn extension(&self) -> &'static str {
+        return "rs";
+    }
+
+    fn keywords(&self) -> &'static [&'static str] {
+        return &KEYWORDS;
+    }
+
+    fn reserves_underscores(&self) -> bool {
+        return false;
+    }
+
+    fn guarded(&self) -> bool {
+        return false;
+    }
+
+    // rustc warns about constants which are not upper case
+    fn derived_symbol(&self, symbol_name: String) -> String {
+        return symbol_name.to_ascii_uppercase();
+    }
+
+    fn unsupported(&self, features: &Features) -> Option<&'static str> {
+        if let Some(reason) = backend::unsupported_outside_c(features) {
+            return Some(reason);
+        }
+        let unsupported = features.span_accessor
+            || (features.encoding && (!features.included_encoding || features.partial_input));
+
+        return unsupported.then_some(
+            "has no --span-accessor, and can only --encode incbin or embed whole files",
+        );
+    }
+
+    fn generate_src(
+        &self,
+        data: &[u8],
+        representation: Representation,
+        symbol_name: &str,
+        options: &SourceOptions,
+    ) -> String {
+        return match representation {
+            Representation::BraceInitialiser => {
+                let data = array_data(data, options);
+                generate_src_for_array(
+                    &format_as_elements(&data, &options.byte_format, options.element_type),
+                    data.len(),
+                    symbol_name,
+                    options,
+                )
+            }
+            Representation::EscapedStringLiteral | Representation::StringLiteral => {
+                generate_src_for_string(
+                    data,
+                    representation == Representation::StringLiteral,
+                    symbol_name,
+                    options,
+                )
+            }
+            Representation::Incbin | Representation::Embed => generate_src_for_include(
+                options
+                    .embed_path
+                    .as_deref()
+                    .or(options.incbin_path.as_deref())
+                    .expect("included representation without a path"),
+                data.len(),
+                symbol_name,
+                options,
+            ),
+            _ => unreachable!("representation without a Rust equivalent"),
+        };
+    }
+
+    fn template(
+        &self,
+        array_len: usize,
+        representation: Representation,
+        symbol_name: &str,
+        options: &SourceOptions,
+    ) -> Option<String> {
+        return match representation {
+            Representation::BraceInitialiser => Some(generate_src_for_array(
+                DATA_MARKER,
+                array_len,
+                symbol_name,
+                options,
+            )),
+            _ => None,
+        };
+    }
+}
+
+/// Generate a Rust module declaring the data as a `<SYMBOL>: &[u8]` constant over the array
+/// elements given
+///
+/// Unlike a C array the slice knows its length, so `<SYMBOL>_SIZE` (if one is asked for) is only
+/// there for symmetry with the C and C++ headers; it does not count a NUL terminator.
+fn generate_src_for_array(
+    array_contents: &str,
+    array_len: usize,
+    symbol_name: &str,
+    options: &SourceOptions,
+) -> String {
+    let data_len = match options.null_terminate {
+        true => array_len.saturating_sub(1),
+        false => array_len,
+    };
+
+    return generate_src(
+        &format!("&[u8] = &[{}]", array_contents),
+        data_len,
+        symbol_name,
+        options,
+    );
+}
+
+/// Generate a Rust module declaring the data as a string literal: a `&str` for text (if it is
+/// valid UTF-8), or a byte string for binary data
+fn generate_src_for_string(
+    data: &[u8],
+    text: bool,
+    symbol_name: &str,
+    options: &SourceOptions,
+) -> String {
+    let declaration = match std::str::from_utf8(data) {
+        Ok(text_data) if text => format!("&str = \"{}\"", escape_str(text_data)),
+        _ => format!("&[u8] = b\"{}\"", format_as_byte_string(data)),
+    };
+
+    return generate_src(&declaration, data.len(), symbol_name, options);
+}
+
+/// Generate a Rust module declaring the data as `include_bytes!` of the input, leaving the
+/// compiler to read it (resolving a relative path against the directory of the module)
+fn generate_src_for_include(
+    path: &str,
+    data_len: usize,
+    symbol_name: &str,
+    options: &SourceOptions,
+) -> String {
+    return generate_src(
+        &format!("&[u8] = include_bytes!(\"{}\")", escape_str(path)),
+        data_len,
+        symbol_name,
+        options,
+    );
+}
+
+fn generate_src(
+    declaration: &str,
+    data_len: usize,
+    symbol_name: &str,
+    options: &SourceOptions,
+) -> String {
+    // Anyone using a generated module only sees what it declares, so internal linkage keeps the
+    // constants to the crate
+    let visibility = match options.internal_linkage {
+        true => "pub(crate)",
+        false => "pub",
+    };
+    let modules: Vec<&str> = match options.ns_name {
+        Some(ref namespace) => namespace.split("::").collect(),
+        None => Vec::new(),
+    };
+
+    let mut out_string = String::with_capacity(declaration.len() + 0x100);
+    for module in &modules {
+        out_string.push_str(format!("{} mod {}{{", visibility, module).as_str());
+    }
+    push_doc_comment(&mut out_string, options.comment.as_deref());
+    out_string.push_str(format!("{} const {}: {};", visibility, symbol_name, declaration).as_str());
+    if options.size_constant {
+        out_string.push_str(
+            format!(
+                "{} const {}_SIZE: usize = {};",
+                visibility, symbol_name, data_len
+            )
+            .as_str(),
+        );
+    }
+    out_string.push_str(&checksum_declarations(symbol_name, visibility, options));
+    for _ in &modules {
+        out_string.push('}');
+    }
+    out_string.push_str(LINE_ENDING);
+
+    return out_string;
+}
+
+/// `<SYMBOL>_<ALGORITHM>` constants holding the checksums asked for: a `u32` for CRC32 and a
+/// `[u8; 32]` for SHA-256, with a `<SYMBOL>_<ALGORITHM>_HEX` string of each if asked for
+fn checksum_declarations(symbol_name: &str, visibility: &str, options: &SourceOptions) -> String {
+    let mut declarations = String::new();
+    for checksum in &options.checksums {
+        let name = checksum.algorithm.name().to_ascii_uppercase();
+        match checksum.algorithm {
+            ChecksumAlgorithm::Crc32 => {
+                let mut crc = [0; 4];
+                crc.copy_from_slice(&checksum.digest);
+                declarations.push_str(
+                    format!(
+                        "{} const {}_{}: u32 = {:#010x};",
+                        visibility,
+                        symbol_name,
+                        name,
+                        u32::from_be_bytes(crc)
+                    )
+                    .as_str(),
+                );
+            }
+            ChecksumAlgorithm::Sha256 => {
+                let elements: Vec<String> = checksum
+                    .digest
+                    .iter()
+                    .map(|byte| format!("{:#04x}", byte))
+                    .collect();
+                declarations.push_str(
+                    format!(
+                        "{} const {}_{}: [u8; 32] = [{}];",
+                        visibility,
+                        symbol_name,
+                        name,
+                        elements.join(",")
+                    )
+                    .as_str(),
+                );
+            }
+        }
+        if options.checksum_hex {
+            declarations.push_str(
+                format!(
+                    "{} const {}_{}_HEX: &str = \"{}\";",
+                    visibility,
+                    symbol_name,
+                    name,
+                    checksum::to_hex(&checksum.digest)
+                )
+                .as_str(),
+            );
+        }
+    }
+
+    return declarations;
+}
+
+/// Escape text for a Rust string literal, leaving everything but quotes, backslashes and control
+/// characters as it is
+fn escape_str(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + text.len() / 8);
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(format!("\\u{{{:x}}}", c as u32).as_str()),
+            c => escaped.push(c),
+        }
+    }
+
+    return escaped;
+}
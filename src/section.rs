@@ -0,0 +1,31 @@
+use crate::LINE_ENDING;
+
+/// Macro placing a definition in a named section: with an attribute on GCC and Clang, and with
+/// `__declspec(allocate)` on MSVC, which has to be told about the section first
+///
+/// Defined once however many headers use it, like the other helpers.
+const SECTION_MACRO: &str = r#"#ifndef BIN2HPP_SECTION
+#ifdef _MSC_VER
+#define BIN2HPP_SECTION(name) __pragma(section(name,read)) __declspec(allocate(name))
+#else
+#define BIN2HPP_SECTION(name) __attribute__((section(name)))
+#endif
+#endif"#;
+
+/// Push the definition of the `BIN2HPP_SECTION` macro
+pub(crate) fn push_helper(out_string: &mut String) {
+    for line in SECTION_MACRO.lines() {
+        out_string.push_str(line);
+        out_string.push_str(LINE_ENDING);
+    }
+}
+
+/// Specifier (followed by a space) placing a definition in the section, if it is to go in one
+///
+/// The name is written into a string literal as is, so it cannot contain a `"` or `\`.
+pub(crate) fn specifier(section: Option<&str>) -> String {
+    return match section {
+        Some(name) => format!("BIN2HPP_SECTION(\"{}\") ", name),
+        None => String::new(),
+    };
+}
@@ -0,0 +1,91 @@
+use std::io;
+
+use crate::{
+    DATA_MARKER, LINE_ENDING, Representation, SourceOptions, array_data, array_specifiers,
+    format_as_raw, format_as_text_literals, push_namespace_close, push_namespace_open,
+    string_literal, write_template,
+};
+
+/// Write the source file defining data which a header generated with `extern_data` only
+/// declares, so that the initialiser is compiled once rather than in every file including it
+///
+/// The file includes the header by name (relative to the directory containing both) so that
+/// the definitions are checked against their declarations. Arrays and escaped strings are
+/// streamed as with `write_src`.
+pub fn write_definition_src<W: io::Write + ?Sized>(
+    out: &mut W,
+    data: &[u8],
+    representation: Representation,
+    symbol_name: &str,
+    options: &SourceOptions,
+    header_name: &str,
+) -> io::Result<()> {
+    let data = &array_data(data, options)[..];
+
+    // Include
+    let mut out_string = String::with_capacity(0x100);
+    out_string.push_str(format!("#include \"{}\"", header_name).as_str());
+    out_string.push_str(LINE_ENDING);
+
+    // Namespace
+    push_namespace_open(&mut out_string, options.ns_name.as_deref(), false);
+
+    // Definition
+    let char_type = options.char_type;
+    let definition = match representation {
+        Representation::BraceInitialiser if options.span_accessor => format!(
+            "namespace bin2hpp_detail{{{}extern const std::array<{},{}> {}_storage{{{}}};}}",
+            array_specifiers(options),
+            options.element_type.name(),
+            data.len(),
+            symbol_name,
+            DATA_MARKER
+        ),
+        Representation::BraceInitialiser => format!(
+            "{}extern const std::array<{},{}> {}{{{}}};",
+            array_specifiers(options),
+            options.element_type.name(),
+            data.len(),
+            symbol_name,
+            DATA_MARKER
+        ),
+        Representation::EscapedStringLiteral => format!(
+            "extern const {} {}{{{},{}}};",
+            char_type.view_type(),
+            symbol_name,
+            string_literal(DATA_MARKER, char_type),
+            data.len()
+        ),
+        Representation::StringLiteral => {
+            let literal = match options.raw_text {
+                true => format_as_raw(data, char_type, options.max_literal_len),
+                false => format_as_text_literals(data, char_type, options.max_literal_len),
+            };
+            match options.text_view {
+                true => format!(
+                    "extern const {} {}{{{},{}}};",
+                    char_type.view_type(),
+                    symbol_name,
+                    literal,
+                    data.len()
+                ),
+                false => format!(
+                    "extern const {}* const {} = {};",
+                    char_type.name(),
+                    symbol_name,
+                    literal
+                ),
+            }
+        }
+        _ => unreachable!("representation without a separate definition"),
+    };
+    out_string.push_str(&definition);
+
+    // Close namespace (if need be)
+    push_namespace_close(&mut out_string, options.ns_name.as_deref(), false);
+    if !out_string.ends_with(LINE_ENDING) {
+        out_string.push_str(LINE_ENDING);
+    }
+
+    return write_template(out, &out_string, data, representation, options);
+}
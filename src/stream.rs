@@ -0,0 +1,61 @@
+use crate::LINE_ENDING;
+
+/// Input stream over a block of memory, seekable so that parsers which rewind or skip work too
+///
+/// `std::ispanstream` would do the same job but needs C++23.
+const IMEMSTREAM: &str = r#"class membuf:public std::streambuf{
+public:
+membuf(const char* data,std::size_t size){char* begin=const_cast<char*>(data);setg(begin,begin,begin+size);}
+protected:
+pos_type seekoff(off_type off,std::ios_base::seekdir dir,std::ios_base::openmode which) override{
+if(!(which&std::ios_base::in))return pos_type(off_type(-1));
+const off_type base=dir==std::ios_base::beg?0:dir==std::ios_base::cur?gptr()-eback():egptr()-eback();
+const off_type target=base+off;if(target<0||target>egptr()-eback())return pos_type(off_type(-1));
+setg(eback(),eback()+target,egptr());return pos_type(target);
+}
+pos_type seekpos(pos_type pos,std::ios_base::openmode which) override{return seekoff(off_type(pos),std::ios_base::beg,which);}
+};
+class imemstream:private membuf,public std::istream{
+public:
+imemstream(const void* data,std::size_t size):membuf(static_cast<const char*>(data),size),std::istream(static_cast<membuf*>(this)){}
+};
+"#;
+
+/// Push the headers and the `imemstream` class used by `<symbol>_stream()`, guarded so that
+/// several generated headers can be included in the same translation unit
+pub fn push_helper(out_string: &mut String) {
+    for header in ["cstddef", "istream", "streambuf"] {
+        out_string.push_str(format!("#include <{}>", header).as_str());
+        out_string.push_str(LINE_ENDING);
+    }
+    out_string.push_str("#ifndef BIN2HPP_DETAIL_IMEMSTREAM");
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str("#define BIN2HPP_DETAIL_IMEMSTREAM");
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str("namespace bin2hpp_detail{");
+    out_string.push_str(LINE_ENDING);
+    for line in IMEMSTREAM.lines() {
+        out_string.push_str(line);
+        out_string.push_str(LINE_ENDING);
+    }
+    out_string.push('}');
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str("#endif");
+    out_string.push_str(LINE_ENDING);
+}
+
+/// Push a `<symbol>_stream()` function returning a `std::istream` over the embedded data
+///
+/// The stream is returned by value, which relies on C++17's guaranteed copy elision as streams
+/// which point into themselves cannot be moved.
+pub fn push_accessor(out_string: &mut String, symbol_name: &str, view_expr: &str) {
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str(
+        format!(
+            "inline ::bin2hpp_detail::imemstream {}_stream(){{const auto& view={};return ::bin2hpp_detail::imemstream(view.data(),view.size());}}",
+            symbol_name, view_expr
+        )
+        .as_str(),
+    );
+    out_string.push_str(LINE_ENDING);
+}
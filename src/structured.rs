@@ -0,0 +1,290 @@
+use std::fmt;
+
+use clap::ValueEnum;
+
+use crate::{
+    LINE_ENDING, format_as_escaped, json, push_doc_comment, push_namespace_close,
+    push_namespace_open, sanitise_identifier, toml,
+};
+
+/// Format of structured data accepted by `--decode-struct`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StructuredFormat {
+    Json,
+    Toml,
+}
+
+/// A value parsed from a JSON or TOML document, with table entries kept in document order
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Value>),
+    Table(Vec<(String, Value)>),
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Debug)]
+pub enum StructuredError {
+    /// The input is not valid UTF-8
+    NotUtf8,
+    /// The input could not be parsed
+    Parse(StructuredFormat, ParseError),
+    /// A value cannot be represented as constexpr C++ data
+    Unsupported { key: String, reason: &'static str },
+}
+
+impl fmt::Display for StructuredError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StructuredError::NotUtf8 => write!(f, "structured input is not valid UTF-8"),
+            StructuredError::Parse(format, error) => {
+                let name = match format {
+                    StructuredFormat::Json => "JSON",
+                    StructuredFormat::Toml => "TOML",
+                };
+                write!(f, "failed to parse {} input, {}", name, error)
+            }
+            StructuredError::Unsupported { key, reason } => {
+                write!(f, "cannot represent \"{}\": {}", key, reason)
+            }
+        }
+    }
+}
+
+/// C++ type which a value maps onto
+#[derive(Debug, Clone, PartialEq)]
+enum CppType {
+    Bool,
+    Integer,
+    Float,
+    String,
+    Array(Box<CppType>, usize),
+    /// Struct with the given name and fields (name, type)
+    Struct(String, Vec<(String, CppType)>),
+}
+
+impl CppType {
+    fn name(&self) -> String {
+        return match self {
+            CppType::Bool => "bool".to_string(),
+            CppType::Integer => "std::int64_t".to_string(),
+            CppType::Float => "double".to_string(),
+            CppType::String => "std::string_view".to_string(),
+            CppType::Array(element, len) => format!("std::array<{},{}>", element.name(), len),
+            CppType::Struct(name, _) => name.clone(),
+        };
+    }
+
+    /// Whether two types have the same layout, ignoring struct names
+    fn same_shape(&self, other: &CppType) -> bool {
+        return match (self, other) {
+            (CppType::Array(a, a_len), CppType::Array(b, b_len)) => {
+                a_len == b_len && a.same_shape(b)
+            }
+            (CppType::Struct(_, a), CppType::Struct(_, b)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .zip(b)
+                        .all(|((a_name, a), (b_name, b))| a_name == b_name && a.same_shape(b))
+            }
+            _ => self == other,
+        };
+    }
+}
+
+/// Work out the C++ type of a value, `key` is the value's dotted path for error messages and
+/// `name` is the identifier it will be stored under
+fn type_of(value: &Value, key: &str, name: &str) -> Result<CppType, StructuredError> {
+    let unsupported = |reason| StructuredError::Unsupported {
+        key: key.to_string(),
+        reason,
+    };
+
+    return match value {
+        Value::Null => Err(unsupported("null values have no type")),
+        Value::Bool(_) => Ok(CppType::Bool),
+        Value::Integer(_) => Ok(CppType::Integer),
+        Value::Float(f) if !f.is_finite() => Err(unsupported("infinity and NaN are not supported")),
+        Value::Float(_) => Ok(CppType::Float),
+        Value::String(_) => Ok(CppType::String),
+        Value::Array(elements) => {
+            let first = match elements.first() {
+                Some(first) => type_of(first, &format!("{}[0]", key), name)?,
+                None => return Err(unsupported("empty arrays have no element type")),
+            };
+            let mut array_type = first;
+            for (index, element) in elements.iter().enumerate().skip(1) {
+                let element_type = type_of(element, &format!("{}[{}]", key, index), name)?;
+                // Arrays mixing integers and floats are arrays of floats
+                match (&array_type, &element_type) {
+                    (CppType::Integer, CppType::Float) => array_type = CppType::Float,
+                    (CppType::Float, CppType::Integer) => (),
+                    _ if element_type.same_shape(&array_type) => (),
+                    _ => return Err(unsupported("array elements must all have the same type")),
+                }
+            }
+            Ok(CppType::Array(Box::new(array_type), elements.len()))
+        }
+        Value::Table(entries) => {
+            let mut fields = Vec::with_capacity(entries.len());
+            for (entry_key, entry) in entries {
+                let field_name = sanitise_identifier(entry_key);
+                if fields.iter().any(|(name, _)| *name == field_name) {
+                    return Err(unsupported("two keys map to the same C++ identifier"));
+                }
+                let entry_type = type_of(entry, &format!("{}.{}", key, entry_key), &field_name)?;
+                fields.push((field_name, entry_type));
+            }
+            Ok(CppType::Struct(format!("{}_t", name), fields))
+        }
+    };
+}
+
+/// Push the definitions of every struct used by a type, nested inside one another so that the
+/// names of structs for different keys cannot clash
+fn push_struct_definition(out_string: &mut String, cpp_type: &CppType) {
+    match cpp_type {
+        CppType::Array(element, _) => push_struct_definition(out_string, element),
+        CppType::Struct(name, fields) => {
+            out_string.push_str(format!("struct {}{{", name).as_str());
+            for (_, field_type) in fields {
+                push_struct_definition(out_string, field_type);
+            }
+            for (field_name, field_type) in fields {
+                out_string.push_str(format!("{} {};", field_type.name(), field_name).as_str());
+            }
+            out_string.push_str("};");
+        }
+        _ => (),
+    }
+}
+
+/// Push the aggregate initialiser for a value (whose type has already been checked)
+fn push_initialiser(out_string: &mut String, value: &Value, cpp_type: &CppType) {
+    match value {
+        Value::Null => (),
+        Value::Bool(b) => out_string.push_str(if *b { "true" } else { "false" }),
+        Value::Integer(i) if *cpp_type == CppType::Float => {
+            out_string.push_str(format!("{:?}", *i as f64).as_str())
+        }
+        // INT64_MIN cannot be written as a negated literal without overflowing first
+        Value::Integer(i64::MIN) => out_string.push_str("(-9223372036854775807LL-1)"),
+        Value::Integer(i) => out_string.push_str(format!("{}LL", i).as_str()),
+        Value::Float(f) => out_string.push_str(format!("{:?}", f).as_str()),
+        Value::String(s) => {
+            out_string.push('"');
+            out_string.push_str(&format_as_escaped(s.as_bytes()));
+            out_string.push('"');
+        }
+        Value::Array(elements) => {
+            // The outer braces belong to std::array, the inner ones to its C array member
+            let element_type = match cpp_type {
+                CppType::Array(element_type, _) => element_type,
+                _ => cpp_type,
+            };
+            out_string.push_str("{{");
+            for (index, element) in elements.iter().enumerate() {
+                if index > 0 {
+                    out_string.push(',');
+                }
+                push_initialiser(out_string, element, element_type);
+            }
+            out_string.push_str("}}");
+        }
+        Value::Table(entries) => {
+            let fields: &[(String, CppType)] = match cpp_type {
+                CppType::Struct(_, fields) => fields,
+                _ => &[],
+            };
+            out_string.push('{');
+            for (index, ((_, entry), (_, field_type))) in entries.iter().zip(fields).enumerate() {
+                if index > 0 {
+                    out_string.push(',');
+                }
+                push_initialiser(out_string, entry, field_type);
+            }
+            out_string.push('}');
+        }
+    }
+}
+
+/// Generate constexpr C++ data from a JSON or TOML document, whose top level must be an object
+/// (JSON) or table (TOML)
+///
+/// Tables become structs, arrays become `std::array`s, strings become `std::string_view`s,
+/// integers become `std::int64_t`s and floats become `double`s.
+pub fn generate_src(
+    data: &[u8],
+    format: StructuredFormat,
+    symbol_name: &str,
+    ns_name: Option<&str>,
+    comment: Option<&str>,
+    internal_linkage: bool,
+) -> Result<String, StructuredError> {
+    let text = match std::str::from_utf8(data) {
+        Ok(text) => text.strip_prefix('\u{feff}').unwrap_or(text), // byte order mark
+        Err(_) => return Err(StructuredError::NotUtf8),
+    };
+
+    let parsed = match format {
+        StructuredFormat::Json => json::parse(text),
+        StructuredFormat::Toml => toml::parse(text),
+    };
+    let value = match parsed {
+        Ok(value) => value,
+        Err(error) => return Err(StructuredError::Parse(format, error)),
+    };
+
+    if !matches!(value, Value::Table(_)) {
+        return Err(StructuredError::Unsupported {
+            key: symbol_name.to_string(),
+            reason: "the top level must be an object or table",
+        });
+    }
+    let cpp_type = type_of(&value, symbol_name, symbol_name)?;
+
+    // Includes
+    let mut out_string = String::with_capacity(data.len() * 2 + 0x100);
+    out_string.push_str("#include <array>");
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str("#include <cstdint>");
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str("#include <string_view>");
+    out_string.push_str(LINE_ENDING);
+
+    // Namespace
+    push_namespace_open(&mut out_string, ns_name, internal_linkage);
+
+    // Struct definitions
+    push_struct_definition(&mut out_string, &cpp_type);
+    out_string.push_str(LINE_ENDING);
+
+    // Data
+    push_doc_comment(&mut out_string, comment);
+    out_string.push_str(format!("constexpr {} {}", cpp_type.name(), symbol_name).as_str());
+    push_initialiser(&mut out_string, &value, &cpp_type);
+    out_string.push(';');
+
+    // Close namespace (if need be)
+    push_namespace_close(&mut out_string, ns_name, internal_linkage);
+
+    // Trailing newline
+    out_string.push_str(LINE_ENDING);
+
+    return Ok(out_string);
+}
@@ -0,0 +1,387 @@
+use std::fmt;
+
+use crate::{
+    SourceOptions, array_data,
+    checksum::{self, ChecksumAlgorithm},
+    format_as_elements,
+};
+
+/// Error in a template given to `--template`, with the line of the template it was found on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    /// A `{{`, `{%` or `{#` is never closed
+    Unterminated { line: usize },
+    /// A variable which is not defined is used
+    UnknownVariable { line: usize, name: String },
+    /// A filter which is not known is applied
+    UnknownFilter { line: usize, name: String },
+    /// A tag which is not known (or which is malformed) is used
+    UnknownTag { line: usize, tag: String },
+    /// An `else` or `endif` has no `if`, or an `if` no `endif`
+    Unbalanced { line: usize },
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::Unterminated { line } => {
+                write!(f, "line {}: tag is never closed", line)
+            }
+            TemplateError::UnknownVariable { line, name } => {
+                write!(f, "line {}: unknown variable \"{}\"", line, name)
+            }
+            TemplateError::UnknownFilter { line, name } => write!(
+                f,
+                "line {}: unknown filter \"{}\" (expected upper, lower or trim)",
+                line, name
+            ),
+            TemplateError::UnknownTag { line, tag } => write!(
+                f,
+                "line {}: unknown tag \"{}\" (expected if, else or endif)",
+                line, tag
+            ),
+            TemplateError::Unbalanced { line } => {
+                write!(f, "line {}: if, else and endif do not match up", line)
+            }
+        }
+    }
+}
+
+/// Variables a template can use, by name
+pub struct Variables {
+    variables: Vec<(String, String)>,
+}
+
+impl Variables {
+    /// Variables describing the data embedded under the symbol given
+    ///
+    /// - `payload`: the data formatted as initialiser list elements, as the byte array of a header
+    ///   would hold it (so obfuscated and NUL-terminated if that was asked for)
+    /// - `symbol` and `namespace` (empty if there is none)
+    /// - `size`: the size of the data in bytes, not counting a NUL terminator
+    /// - `checksums.crc32` and `checksums.sha256`: the checksums of the data in hex
+    /// - `source`: the input path, and `version`: the tool's version
+    pub fn new(data: &[u8], symbol_name: &str, source: &str, options: &SourceOptions) -> Variables {
+        let payload = format_as_elements(
+            &array_data(data, options),
+            &options.byte_format,
+            options.element_type,
+        );
+        let variables = vec![
+            ("payload".to_string(), payload),
+            ("symbol".to_string(), symbol_name.to_string()),
+            (
+                "namespace".to_string(),
+                options.ns_name.clone().unwrap_or_default(),
+            ),
+            ("size".to_string(), data.len().to_string()),
+            (
+                "checksums.crc32".to_string(),
+                checksum::to_hex(&ChecksumAlgorithm::Crc32.digest(data)),
+            ),
+            (
+                "checksums.sha256".to_string(),
+                checksum::to_hex(&ChecksumAlgorithm::Sha256.digest(data)),
+            ),
+            ("source".to_string(), source.to_string()),
+            ("version".to_string(), env!("CARGO_PKG_VERSION").to_string()),
+        ];
+
+        return Variables { variables };
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        return self
+            .variables
+            .iter()
+            .find(|(variable, _)| variable == name)
+            .map(|(_, value)| value.as_str());
+    }
+}
+
+enum Token<'a> {
+    Text(&'a str),
+    Expression { line: usize, contents: &'a str },
+    Tag { line: usize, contents: &'a str },
+}
+
+/// Render a template, made of text and a subset of Tera's syntax: `{{ variable }}` (optionally
+/// through `| upper`, `| lower` or `| trim` filters), `{% if variable %}` (or `if not`) with an
+/// optional `{% else %}` up to `{% endif %}`, a variable being true if it is not empty, and
+/// `{# comments #}`
+///
+/// A `-` just inside a delimiter (`{{-`, `-%}`) trims whitespace from the text next to it, as in
+/// Tera. Everything else of Tera's (loops, `elif`, expressions, macros, includes and so on) is
+/// left out, and a tag using it is an error.
+pub fn render(template: &str, variables: &Variables) -> Result<String, TemplateError> {
+    let tokens = tokenise(template)?;
+
+    let mut out_string = String::with_capacity(template.len() * 2);
+    // Whether each `if` entered (innermost last) is outputting, and whether it has had its else
+    let mut conditions: Vec<(bool, bool)> = Vec::new();
+    let outputting = |conditions: &[(bool, bool)]| {
+        return conditions.iter().all(|&(active, _)| active);
+    };
+    for token in tokens {
+        match token {
+            Token::Text(text) => {
+                if outputting(&conditions) {
+                    out_string.push_str(text);
+                }
+            }
+            Token::Expression { line, contents } => {
+                let value = evaluate(contents, line, variables)?;
+                if outputting(&conditions) {
+                    out_string.push_str(&value);
+                }
+            }
+            Token::Tag { line, contents } => {
+                let words: Vec<&str> = contents.split_whitespace().collect();
+                match words[..] {
+                    ["if", name] | ["if", "not", name] => {
+                        let value = variables.get(name).ok_or(TemplateError::UnknownVariable {
+                            line,
+                            name: name.to_string(),
+                        })?;
+                        let negated = words.len() == 3;
+                        conditions.push((value.is_empty() == negated, false));
+                    }
+                    ["else"] => match conditions.last_mut() {
+                        Some((active, had_else)) if !*had_else => {
+                            *active = !*active;
+                            *had_else = true;
+                        }
+                        _ => return Err(TemplateError::Unbalanced { line }),
+                    },
+                    ["endif"] => {
+                        if conditions.pop().is_none() {
+                            return Err(TemplateError::Unbalanced { line });
+                        }
+                    }
+                    _ => {
+                        return Err(TemplateError::UnknownTag {
+                            line,
+                            tag: contents.trim().to_string(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+    if !conditions.is_empty() {
+        return Err(TemplateError::Unbalanced {
+            line: template.lines().count(),
+        });
+    }
+
+    return Ok(out_string);
+}
+
+/// Split a template into text, expressions and tags, dropping comments and trimming whitespace
+/// where a delimiter asks for it
+fn tokenise(template: &str) -> Result<Vec<Token<'_>>, TemplateError> {
+    let mut tokens = Vec::new();
+    let mut rest = template;
+    let mut trim_next = false;
+    loop {
+        let Some(start) = find_delimiter(rest) else {
+            push_text(&mut tokens, rest, trim_next, false);
+            break;
+        };
+        let offset = template.len() - rest.len() + start;
+        let line = template[..offset].matches('\n').count() + 1;
+        let close = match &rest[start..start + 2] {
+            "{{" => "}}",
+            "{%" => "%}",
+            _ => "#}",
+        };
+        let Some(len) = rest[start + 2..].find(close) else {
+            return Err(TemplateError::Unterminated { line });
+        };
+        let mut contents = &rest[start + 2..start + 2 + len];
+        let trim_before = contents.starts_with('-');
+        if trim_before {
+            contents = &contents[1..];
+        }
+        let trim_after = contents.ends_with('-');
+        if trim_after {
+            contents = &contents[..contents.len() - 1];
+        }
+        push_text(&mut tokens, &rest[..start], trim_next, trim_before);
+        match close {
+            "}}" => tokens.push(Token::Expression { line, contents }),
+            "%}" => tokens.push(Token::Tag { line, contents }),
+            _ => (),
+        }
+        trim_next = trim_after;
+        rest = &rest[start + 2 + len + 2..];
+    }
+
+    return Ok(tokens);
+}
+
+/// Position of the first opening delimiter in some text
+fn find_delimiter(text: &str) -> Option<usize> {
+    return ["{{", "{%", "{#"]
+        .iter()
+        .filter_map(|delimiter| text.find(delimiter))
+        .min();
+}
+
+fn push_text<'a>(tokens: &mut Vec<Token<'a>>, text: &'a str, trim_start: bool, trim_end: bool) {
+    let text = match trim_start {
+        true => text.trim_start(),
+        false => text,
+    };
+    let text = match trim_end {
+        true => text.trim_end(),
+        false => text,
+    };
+    if !text.is_empty() {
+        tokens.push(Token::Text(text));
+    }
+}
+
+/// Value of an expression: a variable, through any filters applied to it
+fn evaluate(expression: &str, line: usize, variables: &Variables) -> Result<String, TemplateError> {
+    let mut parts = expression.split('|').map(str::trim);
+    let name = parts.next().unwrap_or_default();
+    let mut value = match variables.get(name) {
+        Some(value) => value.to_string(),
+        None => {
+            return Err(TemplateError::UnknownVariable {
+                line,
+                name: name.to_string(),
+            });
+        }
+    };
+    for filter in parts {
+        value = match filter {
+            "upper" => value.to_uppercase(),
+            "lower" => value.to_lowercase(),
+            "trim" => value.trim().to_string(),
+            _ => {
+                return Err(TemplateError::UnknownFilter {
+                    line,
+                    name: filter.to_string(),
+                });
+            }
+        };
+    }
+
+    return Ok(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rendered(template: &str) -> Result<String, TemplateError> {
+        let variables = Variables {
+            variables: vec![
+                ("symbol".to_string(), "logo_png".to_string()),
+                ("namespace".to_string(), String::new()),
+                ("padded".to_string(), "  Mixed Case  ".to_string()),
+            ],
+        };
+        return render(template, &variables);
+    }
+
+    #[test]
+    fn variables_are_substituted_through_filters() {
+        assert_eq!(rendered("a {{ symbol }} b").unwrap(), "a logo_png b");
+        assert_eq!(rendered("{{symbol|upper}}").unwrap(), "LOGO_PNG");
+        assert_eq!(rendered("[{{ padded | trim }}]").unwrap(), "[Mixed Case]");
+        assert_eq!(
+            rendered("[{{ padded | trim | lower }}]").unwrap(),
+            "[mixed case]"
+        );
+        assert_eq!(rendered("no tags at all").unwrap(), "no tags at all");
+    }
+
+    #[test]
+    fn conditions_test_whether_variables_are_empty() {
+        let template = "{% if namespace %}ns{% else %}global{% endif %}";
+        assert_eq!(rendered(template).unwrap(), "global");
+        let template = "{% if not namespace %}global{% endif %}{% if symbol %}!{% endif %}";
+        assert_eq!(rendered(template).unwrap(), "global!");
+        let template = "{% if symbol %}a{% if namespace %}b{% else %}c{% endif %}d{% endif %}";
+        assert_eq!(rendered(template).unwrap(), "acd");
+    }
+
+    #[test]
+    fn comments_are_dropped() {
+        assert_eq!(rendered("a{# {{ symbol }} #}b").unwrap(), "ab");
+    }
+
+    #[test]
+    fn dashes_trim_the_whitespace_next_to_a_delimiter() {
+        assert_eq!(rendered("a  {{- symbol -}}  b").unwrap(), "alogo_pngb");
+        let template = "x\n{%- if symbol -%}\n  y\n{%- endif %}\nz";
+        assert_eq!(rendered(template).unwrap(), "xy\nz");
+        assert_eq!(rendered("a \n{#- comment #}b").unwrap(), "ab");
+    }
+
+    #[test]
+    fn unclosed_delimiters_are_errors() {
+        for template in ["{{ symbol", "a\n{% if symbol", "a\n\nb {# comment"] {
+            let line = template.lines().count();
+            assert_eq!(
+                rendered(template),
+                Err(TemplateError::Unterminated { line })
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_names_are_errors() {
+        assert_eq!(
+            rendered("a\n{{ size }}"),
+            Err(TemplateError::UnknownVariable {
+                line: 2,
+                name: "size".to_string()
+            })
+        );
+        assert_eq!(
+            rendered("{% if size %}{% endif %}"),
+            Err(TemplateError::UnknownVariable {
+                line: 1,
+                name: "size".to_string()
+            })
+        );
+        assert_eq!(
+            rendered("{{ symbol | title }}"),
+            Err(TemplateError::UnknownFilter {
+                line: 1,
+                name: "title".to_string()
+            })
+        );
+        assert_eq!(
+            rendered("{% for byte in payload %}"),
+            Err(TemplateError::UnknownTag {
+                line: 1,
+                tag: "for byte in payload".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn unbalanced_conditions_are_errors() {
+        assert_eq!(
+            rendered("{% else %}"),
+            Err(TemplateError::Unbalanced { line: 1 })
+        );
+        assert_eq!(
+            rendered("a\n{% endif %}"),
+            Err(TemplateError::Unbalanced { line: 2 })
+        );
+        assert_eq!(
+            rendered("{% if symbol %}{% else %}\n{% else %}{% endif %}"),
+            Err(TemplateError::Unbalanced { line: 2 })
+        );
+        assert_eq!(
+            rendered("{% if symbol %}\n\n"),
+            Err(TemplateError::Unbalanced { line: 2 })
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use clap::ValueEnum;
+
+use crate::{LINE_ENDING, checksum};
+
+/// Unit test framework targeted by `--emit-test`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TestFramework {
+    Gtest,
+    Catch2,
+    Doctest,
+}
+
+/// What the test needs to know about the embedded symbol
+pub struct TestSubject<'a> {
+    /// File name of the generated header, which must be on the include path
+    pub header_name: &'a str,
+    /// Expression evaluating to something with `data()` and `size()` over the embedded bytes
+    pub view_expr: String,
+    /// Name of the test case
+    pub test_name: &'a str,
+    /// Macro which must be defined for the real data to be embedded
+    pub guard_macro: Option<&'a str>,
+    pub size: usize,
+    pub sha256: [u8; 32],
+}
+
+/// Generate a test source asserting that the embedded data has the size and SHA-256 digest of the
+/// original input
+pub fn generate_test_src(framework: TestFramework, subject: &TestSubject) -> String {
+    let mut out_string = String::with_capacity(0x1000);
+    let mut push_line = |line: &str| {
+        out_string.push_str(line);
+        out_string.push_str(LINE_ENDING);
+    };
+
+    push_line(match framework {
+        TestFramework::Gtest => "#include <gtest/gtest.h>",
+        TestFramework::Catch2 => "#include <catch2/catch_test_macros.hpp>",
+        TestFramework::Doctest => "#include <doctest/doctest.h>",
+    });
+    push_line("#include <array>");
+    push_line("#include <cstddef>");
+    push_line("#include <cstdint>");
+    push_line("#include <string_view>");
+    if let Some(guard) = subject.guard_macro {
+        push_line(format!("#ifndef {}", guard).as_str());
+        push_line(format!("#define {}", guard).as_str());
+        push_line("#endif");
+    }
+    push_line(format!("#include \"{}\"", subject.header_name).as_str());
+
+    push_line("namespace{");
+    push_line(checksum::cpp_sha256_function("bin2hpp_sha256").trim_end());
+    push_line("}");
+
+    let (open, check) = match framework {
+        TestFramework::Gtest => (
+            format!("TEST(bin2hpp,{}){{", subject.test_name),
+            "EXPECT_TRUE",
+        ),
+        TestFramework::Catch2 | TestFramework::Doctest => (
+            format!(
+                "TEST_CASE(\"{} matches the data it was generated from\"){{",
+                subject.test_name
+            ),
+            "CHECK",
+        ),
+    };
+    push_line(&open);
+    push_line(format!("const auto view={};", subject.view_expr).as_str());
+    push_line(format!("{}(view.size()=={}u);", check, subject.size).as_str());
+    push_line(
+        format!(
+            "const std::array<std::uint8_t,32> expected{};",
+            checksum::to_initialiser(&subject.sha256)
+        )
+        .as_str(),
+    );
+    push_line(
+        format!(
+            "{}(bin2hpp_sha256(reinterpret_cast<const unsigned char*>(view.data()),view.size())==expected);",
+            check
+        )
+        .as_str(),
+    );
+    push_line("}");
+
+    return out_string;
+}
@@ -0,0 +1,82 @@
+use crate::LINE_ENDING;
+
+/// A symbol listed in the table of contents of a generated header
+#[derive(Debug, Clone)]
+pub struct TocEntry {
+    pub symbol: String,
+    pub source: String,
+    pub size: usize,
+}
+
+/// Prepend a comment block to the generated source listing every symbol, the file it was generated
+/// from, the line of the header it is declared on and its size in bytes
+///
+/// `preceding_lines` is the number of lines which will end up above the table itself.
+pub fn prepend_toc(src: &str, entries: &[TocEntry], preceding_lines: usize) -> String {
+    const HEADINGS: [&str; 4] = ["symbol", "source", "line", "size"];
+
+    // Header comment, a line for the headings, a line per entry and a trailing blank line
+    let toc_line_count = entries.len() + 3;
+    let line_offset = toc_line_count + preceding_lines;
+
+    let rows: Vec<[String; 4]> = entries
+        .iter()
+        .map(|entry| {
+            let line = match find_declaration_line(src, &entry.symbol) {
+                Some(line) => (line + line_offset).to_string(),
+                None => "?".to_string(),
+            };
+            [
+                entry.symbol.clone(),
+                entry.source.clone(),
+                line,
+                entry.size.to_string(),
+            ]
+        })
+        .collect();
+
+    let mut widths = HEADINGS.map(|h| h.len());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut out_string = String::with_capacity(src.len() + 0x40 * toc_line_count);
+    out_string.push_str("// Table of contents");
+    out_string.push_str(LINE_ENDING);
+    push_row(&mut out_string, &HEADINGS, &widths);
+    for row in &rows {
+        push_row(&mut out_string, row, &widths);
+    }
+    out_string.push_str(LINE_ENDING);
+    out_string.push_str(src);
+
+    return out_string;
+}
+
+fn push_row<S: AsRef<str>>(out_string: &mut String, cells: &[S], widths: &[usize]) {
+    let mut line = String::from("//");
+    for (cell, width) in cells.iter().zip(widths) {
+        line.push_str(format!("  {:<width$}", cell.as_ref(), width = width).as_str());
+    }
+    out_string.push_str(line.trim_end());
+    out_string.push_str(LINE_ENDING);
+}
+
+/// Find the (1-based) line on which a symbol first appears as a whole identifier
+fn find_declaration_line(src: &str, symbol: &str) -> Option<usize> {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+
+    for (index, line) in src.lines().enumerate() {
+        for (start, _) in line.match_indices(symbol) {
+            let before = line[..start].chars().next_back();
+            let after = line[start + symbol.len()..].chars().next();
+            if !before.is_some_and(is_ident) && !after.is_some_and(is_ident) {
+                return Some(index + 1);
+            }
+        }
+    }
+
+    return None;
+}
@@ -0,0 +1,592 @@
+use crate::structured::{ParseError, Value};
+
+/// Parse a TOML document
+///
+/// Everything except dates and times is supported: tables, arrays of tables, dotted and quoted
+/// keys, inline tables and arrays, and all four kinds of string.
+pub fn parse(text: &str) -> Result<Value, ParseError> {
+    let mut parser = Parser { text, pos: 0 };
+    let mut root: Vec<(String, Value)> = Vec::new();
+    let mut current_path: Vec<String> = Vec::new();
+
+    loop {
+        parser.skip_whitespace_and_comments(true);
+        let c = match parser.peek() {
+            Some(c) => c,
+            None => break,
+        };
+
+        if c == '[' {
+            let is_array = parser.rest().starts_with("[[");
+            parser.pos += if is_array { 2 } else { 1 };
+            parser.skip_whitespace_and_comments(false);
+            let path = parser.parse_key()?;
+            parser.skip_whitespace_and_comments(false);
+            parser.expect(if is_array { "]]" } else { "]" })?;
+            parser.expect_line_end()?;
+
+            if is_array {
+                let (last, parent) = path.split_last().unwrap();
+                let table = table_at(&mut root, parent, &parser)?;
+                match table.iter_mut().find(|(key, _)| key == last) {
+                    Some((_, Value::Array(elements))) => elements.push(Value::Table(Vec::new())),
+                    Some(_) => {
+                        return Err(parser.error(
+                            format!("key \"{}\" is already defined", path.join(".")).as_str(),
+                        ));
+                    }
+                    None => {
+                        table.push((last.clone(), Value::Array(vec![Value::Table(Vec::new())])))
+                    }
+                }
+            } else {
+                table_at(&mut root, &path, &parser)?;
+            }
+            current_path = path;
+            continue;
+        }
+
+        let key = parser.parse_key()?;
+        parser.skip_whitespace_and_comments(false);
+        parser.expect("=")?;
+        parser.skip_whitespace_and_comments(false);
+        let value = parser.parse_value()?;
+        parser.expect_line_end()?;
+
+        let (last, parent) = key.split_last().unwrap();
+        let full_parent: Vec<String> = current_path.iter().chain(parent).cloned().collect();
+        let table = table_at(&mut root, &full_parent, &parser)?;
+        if table.iter().any(|(k, _)| k == last) {
+            return Err(parser.error(format!("key \"{}\" is already defined", last).as_str()));
+        }
+        table.push((last.clone(), value));
+    }
+
+    return Ok(Value::Table(root));
+}
+
+/// Get the table at the given path, creating (empty) tables along the way as needed and
+/// descending into the last element of arrays of tables
+fn table_at<'a>(
+    root: &'a mut Vec<(String, Value)>,
+    path: &[String],
+    parser: &Parser,
+) -> Result<&'a mut Vec<(String, Value)>, ParseError> {
+    let mut table = root;
+
+    for key in path {
+        let index = match table.iter().position(|(k, _)| k == key) {
+            Some(index) => index,
+            None => {
+                table.push((key.clone(), Value::Table(Vec::new())));
+                table.len() - 1
+            }
+        };
+
+        table = match &mut table[index].1 {
+            Value::Table(entries) => entries,
+            Value::Array(elements) => match elements.last_mut() {
+                Some(Value::Table(entries)) => entries,
+                _ => {
+                    return Err(parser.error(format!("key \"{}\" is not a table", key).as_str()));
+                }
+            },
+            _ => return Err(parser.error(format!("key \"{}\" is not a table", key).as_str())),
+        };
+    }
+
+    return Ok(table);
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> ParseError {
+        let line = self.text[..self.pos].matches('\n').count() + 1;
+        return ParseError {
+            line,
+            message: message.to_string(),
+        };
+    }
+
+    fn rest(&self) -> &str {
+        return &self.text[self.pos..];
+    }
+
+    fn peek(&self) -> Option<char> {
+        return self.rest().chars().next();
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<(), ParseError> {
+        if self.rest().starts_with(literal) {
+            self.pos += literal.len();
+            return Ok(());
+        }
+
+        return Err(self.error(format!("expected \"{}\"", literal).as_str()));
+    }
+
+    /// Skip spaces, tabs and comments, and newlines too if `newlines` is set
+    fn skip_whitespace_and_comments(&mut self, newlines: bool) {
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' => self.pos += 1,
+                '\r' | '\n' if newlines => self.pos += 1,
+                '#' => {
+                    while let Some(c) = self.peek() {
+                        if c == '\n' {
+                            break;
+                        }
+                        self.pos += c.len_utf8();
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Expect nothing but whitespace and an optional comment before the end of the line
+    fn expect_line_end(&mut self) -> Result<(), ParseError> {
+        self.skip_whitespace_and_comments(false);
+        return match self.peek() {
+            None | Some('\n') => Ok(()),
+            Some('\r') if self.rest().starts_with("\r\n") => Ok(()),
+            Some(_) => Err(self.error("expected the end of the line")),
+        };
+    }
+
+    /// Parse a (possibly dotted) key into its parts
+    fn parse_key(&mut self) -> Result<Vec<String>, ParseError> {
+        let mut parts = Vec::new();
+
+        loop {
+            let part = match self.peek() {
+                Some('"') => self.parse_basic_string()?,
+                Some('\'') => self.parse_literal_string()?,
+                _ => {
+                    let start = self.pos;
+                    while let Some(c) = self.peek() {
+                        if !(c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                            break;
+                        }
+                        self.pos += 1;
+                    }
+                    if start == self.pos {
+                        return Err(self.error("expected a key"));
+                    }
+                    self.text[start..self.pos].to_string()
+                }
+            };
+            parts.push(part);
+
+            self.skip_whitespace_and_comments(false);
+            if self.peek() != Some('.') {
+                return Ok(parts);
+            }
+            self.pos += 1;
+            self.skip_whitespace_and_comments(false);
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, ParseError> {
+        let rest = self.rest();
+        return match self.peek() {
+            Some('"') if rest.starts_with("\"\"\"") => {
+                Ok(Value::String(self.parse_multiline_basic_string()?))
+            }
+            Some('"') => Ok(Value::String(self.parse_basic_string()?)),
+            Some('\'') if rest.starts_with("'''") => {
+                Ok(Value::String(self.parse_multiline_literal_string()?))
+            }
+            Some('\'') => Ok(Value::String(self.parse_literal_string()?)),
+            Some('[') => self.parse_array(),
+            Some('{') => self.parse_inline_table(),
+            Some(_) if rest.starts_with("true") => {
+                self.pos += 4;
+                Ok(Value::Bool(true))
+            }
+            Some(_) if rest.starts_with("false") => {
+                self.pos += 5;
+                Ok(Value::Bool(false))
+            }
+            Some(_) => self.parse_number(),
+            None => Err(self.error("expected a value")),
+        };
+    }
+
+    fn parse_array(&mut self) -> Result<Value, ParseError> {
+        self.pos += 1; // '['
+        let mut elements = Vec::new();
+
+        loop {
+            self.skip_whitespace_and_comments(true);
+            if self.peek() == Some(']') {
+                self.pos += 1;
+                return Ok(Value::Array(elements));
+            }
+
+            elements.push(self.parse_value()?);
+
+            self.skip_whitespace_and_comments(true);
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => (),
+                _ => return Err(self.error("expected \",\" or \"]\"")),
+            }
+        }
+    }
+
+    fn parse_inline_table(&mut self) -> Result<Value, ParseError> {
+        self.pos += 1; // '{'
+        let mut entries: Vec<(String, Value)> = Vec::new();
+
+        self.skip_whitespace_and_comments(false);
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Value::Table(entries));
+        }
+
+        loop {
+            self.skip_whitespace_and_comments(false);
+            let key = self.parse_key()?;
+            self.skip_whitespace_and_comments(false);
+            self.expect("=")?;
+            self.skip_whitespace_and_comments(false);
+            let value = self.parse_value()?;
+
+            let (last, parent) = key.split_last().unwrap();
+            let table = table_at(&mut entries, parent, self)?;
+            if table.iter().any(|(k, _)| k == last) {
+                return Err(self.error(format!("key \"{}\" is already defined", last).as_str()));
+            }
+            table.push((last.clone(), value));
+
+            self.skip_whitespace_and_comments(false);
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(Value::Table(entries));
+                }
+                _ => return Err(self.error("expected \",\" or \"}\"")),
+            }
+        }
+    }
+
+    fn parse_basic_string(&mut self) -> Result<String, ParseError> {
+        self.pos += 1; // opening quote
+        let mut string = String::new();
+
+        loop {
+            let c = match self.peek() {
+                Some('\n') | None => return Err(self.error("unterminated string")),
+                Some(c) => c,
+            };
+            self.pos += c.len_utf8();
+
+            match c {
+                '"' => return Ok(string),
+                '\\' => string.push(self.parse_escape()?),
+                _ => string.push(c),
+            }
+        }
+    }
+
+    fn parse_multiline_basic_string(&mut self) -> Result<String, ParseError> {
+        self.pos += 3; // opening quotes
+        self.skip_leading_newline();
+        let mut string = String::new();
+
+        loop {
+            if self.rest().starts_with("\"\"\"") {
+                // Up to two quotes may immediately precede the closing delimiter
+                while self.rest().starts_with("\"\"\"\"") {
+                    string.push('"');
+                    self.pos += 1;
+                }
+                self.pos += 3;
+                return Ok(string);
+            }
+
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return Err(self.error("unterminated string")),
+            };
+            self.pos += c.len_utf8();
+
+            if c != '\\' {
+                string.push(c);
+                continue;
+            }
+
+            // A backslash at the end of a line trims all whitespace up to the next non-whitespace
+            let trimmed = self.rest().trim_start_matches([' ', '\t']);
+            if trimmed.starts_with('\n') || trimmed.starts_with("\r\n") {
+                let rest_len = self.rest().len();
+                let skip = rest_len
+                    - self
+                        .rest()
+                        .trim_start_matches([' ', '\t', '\r', '\n'])
+                        .len();
+                self.pos += skip;
+                continue;
+            }
+            string.push(self.parse_escape()?);
+        }
+    }
+
+    fn parse_literal_string(&mut self) -> Result<String, ParseError> {
+        self.pos += 1; // opening quote
+        let end = match self.rest().find(['\'', '\n']) {
+            Some(end) if self.rest()[end..].starts_with('\'') => end,
+            _ => return Err(self.error("unterminated string")),
+        };
+        let string = self.rest()[..end].to_string();
+        self.pos += end + 1;
+
+        return Ok(string);
+    }
+
+    fn parse_multiline_literal_string(&mut self) -> Result<String, ParseError> {
+        self.pos += 3; // opening quotes
+        self.skip_leading_newline();
+        let mut end = match self.rest().find("'''") {
+            Some(end) => end,
+            None => return Err(self.error("unterminated string")),
+        };
+        // Up to two quotes may immediately precede the closing delimiter
+        while self.rest()[end + 3..].starts_with('\'') && end + 3 < self.rest().len() {
+            end += 1;
+        }
+        let string = self.rest()[..end].to_string();
+        self.pos += end + 3;
+
+        return Ok(string);
+    }
+
+    /// A newline immediately after the opening delimiter of a multi-line string is trimmed
+    fn skip_leading_newline(&mut self) {
+        if self.rest().starts_with("\r\n") {
+            self.pos += 2;
+        } else if self.rest().starts_with('\n') {
+            self.pos += 1;
+        }
+    }
+
+    fn parse_escape(&mut self) -> Result<char, ParseError> {
+        let escape = match self.peek() {
+            Some(c) => c,
+            None => return Err(self.error("unterminated string")),
+        };
+        self.pos += escape.len_utf8();
+
+        let digits = match escape {
+            'b' => return Ok('\u{8}'),
+            't' => return Ok('\t'),
+            'n' => return Ok('\n'),
+            'f' => return Ok('\u{c}'),
+            'r' => return Ok('\r'),
+            'e' => return Ok('\u{1b}'),
+            '"' => return Ok('"'),
+            '\\' => return Ok('\\'),
+            'u' => 4,
+            'U' => 8,
+            _ => return Err(self.error("invalid escape sequence")),
+        };
+
+        let code_point = self
+            .rest()
+            .get(..digits)
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .and_then(char::from_u32);
+        return match code_point {
+            Some(c) => {
+                self.pos += digits;
+                Ok(c)
+            }
+            None => Err(self.error("invalid unicode escape")),
+        };
+    }
+
+    fn parse_number(&mut self) -> Result<Value, ParseError> {
+        let end = self
+            .rest()
+            .find([' ', '\t', '\r', '\n', ',', ']', '}', '#'])
+            .unwrap_or(self.rest().len());
+        let token = &self.rest()[..end];
+        let error = || self.error(format!("invalid value \"{}\"", token).as_str());
+
+        let value = match token.trim_start_matches(['+', '-']) {
+            "inf" | "nan" => {
+                let magnitude = if token.ends_with("inf") {
+                    f64::INFINITY
+                } else {
+                    f64::NAN
+                };
+                Value::Float(if token.starts_with('-') {
+                    -magnitude
+                } else {
+                    magnitude
+                })
+            }
+            _ if token.contains(':') || token.matches('-').count() >= 2 => {
+                return Err(self.error("dates and times are not supported"));
+            }
+            _ => {
+                let digits = token.replace('_', "");
+                let radix = match digits.get(..2) {
+                    Some("0x") => Some(16),
+                    Some("0o") => Some(8),
+                    Some("0b") => Some(2),
+                    _ => None,
+                };
+
+                if let Some(radix) = radix {
+                    match i64::from_str_radix(&digits[2..], radix) {
+                        Ok(integer) => Value::Integer(integer),
+                        Err(_) => return Err(error()),
+                    }
+                } else if digits.contains(['.', 'e', 'E']) {
+                    match digits.parse::<f64>() {
+                        Ok(float) => Value::Float(float),
+                        Err(_) => return Err(error()),
+                    }
+                } else {
+                    match digits.parse::<i64>() {
+                        Ok(integer) => Value::Integer(integer),
+                        Err(_) => return Err(error()),
+                    }
+                }
+            }
+        };
+        self.pos += end;
+
+        return Ok(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Value {
+        return Value::String(s.to_string());
+    }
+
+    fn table(entries: &[(&str, Value)]) -> Value {
+        return Value::Table(
+            entries
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect(),
+        );
+    }
+
+    #[test]
+    fn tables_and_dotted_keys() {
+        let text =
+            "title = \"x\" # comment\n[a.b]\nc = 1\nd.e = true\n[f]\n\"quoted key\" = 'literal'\n";
+        assert_eq!(
+            parse(text).unwrap(),
+            table(&[
+                ("title", string("x")),
+                (
+                    "a",
+                    table(&[(
+                        "b",
+                        table(&[
+                            ("c", Value::Integer(1)),
+                            ("d", table(&[("e", Value::Bool(true))]))
+                        ])
+                    )])
+                ),
+                ("f", table(&[("quoted key", string("literal"))])),
+            ])
+        );
+    }
+
+    #[test]
+    fn arrays_of_tables_and_inline_values() {
+        let text = "[[job]]\nname = \"a\"\n[[job]]\nname = \"b\"\nargs = [1, [2, 3], ]\npoint = { x = 1, y.z = 2 }\n";
+        assert_eq!(
+            parse(text).unwrap(),
+            table(&[(
+                "job",
+                Value::Array(vec![
+                    table(&[("name", string("a"))]),
+                    table(&[
+                        ("name", string("b")),
+                        (
+                            "args",
+                            Value::Array(vec![
+                                Value::Integer(1),
+                                Value::Array(vec![Value::Integer(2), Value::Integer(3)]),
+                            ])
+                        ),
+                        (
+                            "point",
+                            table(&[
+                                ("x", Value::Integer(1)),
+                                ("y", table(&[("z", Value::Integer(2))]))
+                            ])
+                        ),
+                    ]),
+                ])
+            )])
+        );
+    }
+
+    #[test]
+    fn strings_of_every_kind() {
+        let value = |text: &str| {
+            let Value::Table(mut entries) = parse(text).unwrap() else {
+                panic!("expected a table");
+            };
+            return entries.remove(0).1;
+        };
+        assert_eq!(value("s = \"a\\tb\\u00e9\\U0001F600\""), string("a\tbé😀"));
+        assert_eq!(value("s = 'C:\\path'"), string("C:\\path"));
+        assert_eq!(
+            value("s = \"\"\"\nline\\\n   joined\"\"\"\""),
+            string("linejoined\"")
+        );
+        assert_eq!(value("s = '''\nraw\\n'''''"), string("raw\\n''"));
+    }
+
+    #[test]
+    fn numbers() {
+        let value = |text: &str| {
+            let Value::Table(mut entries) = parse(&format!("n = {}", text)).unwrap() else {
+                panic!("expected a table");
+            };
+            return entries.remove(0).1;
+        };
+        assert_eq!(value("1_000"), Value::Integer(1000));
+        assert_eq!(value("0xdead_beef"), Value::Integer(0xdead_beef));
+        assert_eq!(value("0o17"), Value::Integer(15));
+        assert_eq!(value("0b101"), Value::Integer(5));
+        assert_eq!(value("+1.5e2"), Value::Float(150.0));
+        assert_eq!(value("-inf"), Value::Float(f64::NEG_INFINITY));
+        assert!(matches!(value("nan"), Value::Float(f) if f.is_nan()));
+    }
+
+    #[test]
+    fn malformed_documents_report_their_line() {
+        for (text, line) in [
+            ("a = 1\na = 2\n", 2),
+            ("a = 1\n\nb = \n", 3),
+            ("[t]\nx = 1\n[t.x]\n", 3),
+            ("d = 1979-05-27\n", 1),
+            ("s = \"unterminated\n", 1),
+            ("a = 1 b = 2\n", 1),
+        ] {
+            match parse(text) {
+                Ok(value) => panic!("{:?} parsed as {:?}", text, value),
+                Err(error) => assert_eq!(error.line, line, "{:?}: {}", text, error),
+            }
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use std::{
+    io::{self, BufRead, IsTerminal, Write},
+    path::Path,
+};
+
+use bin2hpp::{
+    ByteFormat, CharType, DEFAULT_MAX_LITERAL_LEN, ElementType, LINE_ENDING, Language,
+    Representation, SourceOptions, generate_src, sanitise_os_identifier,
+    words::{Endian, WordLayout},
+};
+
+use crate::Encoding;
+
+/// Only the start of large inputs is previewed, so that every keystroke redraws instantly
+const PREVIEW_LIMIT: usize = 0x1_0000;
+/// Bytes shown on each row of the hex view
+const HEX_ROW_LEN: usize = 8;
+/// Width of the hex view: offset, bytes and their ASCII rendering
+const HEX_PANE_WIDTH: usize = 9 + HEX_ROW_LEN * 3 + HEX_ROW_LEN;
+
+const HELP: &str = "b: binary/text  e: next encoding  s NAME: symbol  n [NAME]: namespace  g [MACRO]: guard macro  j/k: scroll  q: quit";
+
+/// Options toggled in the preview
+struct PreviewState {
+    binary: bool,
+    encode: Encoding,
+    symbol_name: String,
+    namespace: Option<String>,
+    guard_macro: Option<String>,
+    /// First row shown in both panes
+    scroll: usize,
+}
+
+/// Run the interactive preview of an input, reading one command per line from stdin
+///
+/// Each frame shows a hex view of the input beside the header generated with the current options,
+/// along with the command line which would generate it.
+pub fn run(input_path: &Path) -> io::Result<()> {
+    let data = std::fs::read(input_path)?;
+    let input_name = input_path.to_string_lossy().to_string();
+    let mut state = PreviewState {
+        binary: std::str::from_utf8(&data).is_err(),
+        encode: Encoding::Bytes,
+        symbol_name: sanitise_os_identifier(input_path.file_name().unwrap_or_default()),
+        namespace: None,
+        guard_macro: None,
+        scroll: 0,
+    };
+
+    let (width, height) = terminal_size();
+    let body_rows = height.saturating_sub(5).max(1);
+    let clear = io::stdout().is_terminal();
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        let mut out = io::stdout().lock();
+        if clear {
+            out.write_all(b"\x1b[2J\x1b[H")?;
+        }
+        out.write_all(render(&data, &input_name, &state, width, body_rows).as_bytes())?;
+        out.write_all(b"> ")?;
+        out.flush()?;
+
+        let Some(line) = lines.next() else {
+            return Ok(());
+        };
+        let line = line?;
+        let (command, argument) = match line.trim().split_once(' ') {
+            Some((command, argument)) => (command, Some(argument.trim().to_string())),
+            None => (line.trim(), None),
+        };
+
+        match command {
+            "b" => state.binary = !state.binary,
+            "e" => {
+                state.encode = match state.encode {
+                    Encoding::Bytes => Encoding::Bits,
+                    Encoding::Bits => Encoding::Incbin,
+                    Encoding::Incbin => Encoding::Embed,
+                    Encoding::Embed => Encoding::U16,
+                    Encoding::U16 => Encoding::U32,
+                    Encoding::U32 => Encoding::U64,
+                    Encoding::U64 => Encoding::Bytes,
+                }
+            }
+            "s" => {
+                if let Some(symbol_name) = argument {
+                    state.symbol_name = symbol_name;
+                }
+            }
+            "n" => state.namespace = argument,
+            "g" => state.guard_macro = argument,
+            "j" => state.scroll += body_rows,
+            "k" => state.scroll = state.scroll.saturating_sub(body_rows),
+            "q" => return Ok(()),
+            _ => (),
+        }
+    }
+}
+
+/// Terminal size from the COLUMNS and LINES variables most shells export, or a typical default
+fn terminal_size() -> (usize, usize) {
+    let from_env = |name: &str, default: usize| -> usize {
+        return std::env::var(name)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default);
+    };
+
+    return (from_env("COLUMNS", 120), from_env("LINES", 30));
+}
+
+fn render(
+    data: &[u8],
+    input_name: &str,
+    state: &PreviewState,
+    width: usize,
+    body_rows: usize,
+) -> String {
+    let preview_data = &data[..data.len().min(PREVIEW_LIMIT)];
+    // Don't let the cut-off at the preview limit split a character in text mode
+    let preview_data = match std::str::from_utf8(preview_data) {
+        Err(error) if error.error_len().is_none() => &preview_data[..error.valid_up_to()],
+        _ => preview_data,
+    };
+    let preview_width = width.saturating_sub(HEX_PANE_WIDTH + 3).max(16);
+
+    let is_text = !state.binary && state.encode == Encoding::Bytes;
+    let generated = match !is_text || std::str::from_utf8(preview_data).is_ok() {
+        true => generate_preview(preview_data, input_name, state),
+        false => "input is not valid UTF-8 text, press b to switch to binary mode".to_string(),
+    };
+    let preview_rows: Vec<String> = generated
+        .lines()
+        .flat_map(|line| wrap(line, preview_width))
+        .collect();
+
+    let mut frame = String::new();
+    frame.push_str(&command_line(input_name, state));
+    frame.push_str(LINE_ENDING);
+    if data.len() > PREVIEW_LIMIT {
+        frame.push_str(
+            format!(
+                "(previewing the first {} of {} bytes)",
+                PREVIEW_LIMIT,
+                data.len()
+            )
+            .as_str(),
+        );
+    }
+    frame.push_str(LINE_ENDING);
+
+    for row in state.scroll..state.scroll + body_rows {
+        let hex = hex_row(data, row * HEX_ROW_LEN);
+        let generated = preview_rows.get(row).map(String::as_str).unwrap_or("");
+        frame.push_str(format!("{:<w$} | {}", hex, generated, w = HEX_PANE_WIDTH).trim_end());
+        frame.push_str(LINE_ENDING);
+    }
+
+    frame.push_str(HELP);
+    frame.push_str(LINE_ENDING);
+
+    return frame;
+}
+
+fn generate_preview(data: &[u8], input_name: &str, state: &PreviewState) -> String {
+    let representation = match state.encode {
+        Encoding::Bits => Representation::PackedBits,
+        Encoding::Incbin => Representation::Incbin,
+        Encoding::Embed => Representation::Embed,
+        Encoding::U16 | Encoding::U32 | Encoding::U64 => Representation::Words(WordLayout {
+            size: state.encode.word_size().expect("word encoding"),
+            endian: Endian::Little,
+        }),
+        Encoding::Bytes if state.binary => Representation::BraceInitialiser,
+        Encoding::Bytes => Representation::StringLiteral,
+    };
+    let options = SourceOptions {
+        language: Language::Cpp,
+        ns_name: state.namespace.clone(),
+        guard_macro: state.guard_macro.clone(),
+        loader_path: None,
+        verifier: None,
+        incbin_path: match representation {
+            Representation::Incbin => Some(input_name.to_string()),
+            _ => None,
+        },
+        embed_path: match representation {
+            Representation::Embed => Some(input_name.to_string()),
+            _ => None,
+        },
+        input_offset: None,
+        delta: None,
+        stream_accessor: false,
+        span_accessor: false,
+        comment: None,
+        char_type: CharType::Char,
+        text_view: false,
+        raw_text: false,
+        max_literal_len: DEFAULT_MAX_LITERAL_LEN,
+        internal_linkage: false,
+        extern_data: false,
+        align: None,
+        section: None,
+        null_terminate: false,
+        size_constant: false,
+        checksums: Vec::new(),
+        checksum_hex: false,
+        assert_size: None,
+        assert_size_max: None,
+        element_type: ElementType::Uint8,
+        xor_key: None,
+        byte_format: ByteFormat::default(),
+    };
+
+    return generate_src(data, representation, &state.symbol_name, &options);
+}
+
+/// Format one row of the hex view, or nothing past the end of the data
+fn hex_row(data: &[u8], offset: usize) -> String {
+    if offset >= data.len() {
+        return String::new();
+    }
+
+    let bytes = &data[offset..data.len().min(offset + HEX_ROW_LEN)];
+    let hex: String = bytes.iter().map(|b| format!("{:02x} ", b)).collect();
+    let ascii: String = bytes
+        .iter()
+        .map(|&b| match b {
+            0x20..=0x7e => b as char,
+            _ => '.',
+        })
+        .collect();
+
+    return format!("{:08x} {:<w$}{}", offset, hex, ascii, w = HEX_ROW_LEN * 3);
+}
+
+/// Split a line into rows of at most `width` characters
+fn wrap(line: &str, width: usize) -> Vec<String> {
+    let chars: Vec<char> = line.chars().collect();
+    if chars.is_empty() {
+        return vec![String::new()];
+    }
+
+    return chars
+        .chunks(width)
+        .map(|chunk| chunk.iter().collect())
+        .collect();
+}
+
+/// The command line which generates the header being previewed
+fn command_line(input_name: &str, state: &PreviewState) -> String {
+    let mut args = vec![format!("bin2hpp -i {}", input_name)];
+    if state.binary {
+        args.push("-b".to_string());
+    }
+    match state.encode {
+        Encoding::Bytes => (),
+        Encoding::Bits => args.push("--encode bits".to_string()),
+        Encoding::Incbin => args.push("--encode incbin".to_string()),
+        Encoding::Embed => args.push("--encode embed".to_string()),
+        Encoding::U16 => args.push("--encode u16".to_string()),
+        Encoding::U32 => args.push("--encode u32".to_string()),
+        Encoding::U64 => args.push("--encode u64".to_string()),
+    }
+    args.push(format!("-s {}", state.symbol_name));
+    if let Some(ref namespace) = state.namespace {
+        args.push(format!("-n {}", namespace));
+    }
+    if let Some(ref guard_macro) = state.guard_macro {
+        args.push(format!("--guard-macro {}", guard_macro));
+    }
+
+    return args.join(" ");
+}
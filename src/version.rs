@@ -0,0 +1,116 @@
+use std::{fmt, io, path::Path};
+
+use bin2hpp::{
+    structured::{ParseError, Value},
+    toml,
+};
+
+#[derive(Debug)]
+pub enum VersionError {
+    Io(io::Error),
+    Parse(ParseError),
+    /// The file was read but does not declare a version
+    NotFound(&'static str),
+}
+
+impl fmt::Display for VersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VersionError::Io(error) => write!(f, "failed to read project version: {}", error),
+            VersionError::Parse(error) => {
+                write!(f, "failed to parse project version file, {}", error)
+            }
+            VersionError::NotFound(reason) => write!(f, "no project version found: {}", reason),
+        }
+    }
+}
+
+/// Find the project version given to `--project-version-from`: read from a `Cargo.toml` or
+/// `CMakeLists.txt` if the argument names one, or taken literally otherwise
+pub fn resolve(spec: &str) -> Result<String, VersionError> {
+    let path = Path::new(spec);
+    return match path.file_name().and_then(|name| name.to_str()) {
+        Some("Cargo.toml") => {
+            from_cargo_manifest(&std::fs::read_to_string(path).map_err(VersionError::Io)?)
+        }
+        Some("CMakeLists.txt") => {
+            from_cmake_lists(&std::fs::read_to_string(path).map_err(VersionError::Io)?)
+        }
+        _ => Ok(spec.to_string()),
+    };
+}
+
+fn get<'a>(table: &'a Value, key: &str) -> Option<&'a Value> {
+    let Value::Table(entries) = table else {
+        return None;
+    };
+
+    return entries.iter().find(|(k, _)| k == key).map(|(_, v)| v);
+}
+
+/// `package.version`, or `workspace.package.version` in a workspace root
+fn from_cargo_manifest(text: &str) -> Result<String, VersionError> {
+    let manifest = toml::parse(text).map_err(VersionError::Parse)?;
+    let package_version = get(&manifest, "package").and_then(|p| get(p, "version"));
+    let workspace_version = get(&manifest, "workspace")
+        .and_then(|w| get(w, "package"))
+        .and_then(|p| get(p, "version"));
+
+    return match (package_version, workspace_version) {
+        (Some(Value::String(version)), _) | (None, Some(Value::String(version))) => {
+            Ok(version.clone())
+        }
+        (Some(Value::Table(_)), _) => Err(VersionError::NotFound(
+            "the package inherits its version from the workspace, pass the workspace's Cargo.toml",
+        )),
+        _ => Err(VersionError::NotFound(
+            "Cargo.toml has no package.version or workspace.package.version",
+        )),
+    };
+}
+
+/// The `VERSION` argument of the `project()` command
+fn from_cmake_lists(text: &str) -> Result<String, VersionError> {
+    let uncommented: String = text
+        .lines()
+        .map(|line| match line.find('#') {
+            Some(index) => &line[..index],
+            None => line,
+        })
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    let lowercase = uncommented.to_ascii_lowercase();
+    let mut search_from = 0;
+    while let Some(found) = lowercase[search_from..].find("project") {
+        let start = search_from + found;
+        search_from = start + "project".len();
+
+        // Must be the command itself rather than part of another identifier
+        let preceded_by_identifier = lowercase[..start]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_');
+        let rest = uncommented[search_from..].trim_start();
+        if preceded_by_identifier || !rest.starts_with('(') {
+            continue;
+        }
+
+        let arguments = match rest[1..].find(')') {
+            Some(end) => &rest[1..end + 1],
+            None => continue,
+        };
+        let mut tokens = arguments.split_whitespace();
+        while let Some(token) = tokens.next() {
+            if token == "VERSION"
+                && let Some(version) = tokens.next()
+            {
+                return Ok(version.trim_matches('"').to_string());
+            }
+        }
+    }
+
+    return Err(VersionError::NotFound(
+        "CMakeLists.txt has no project() command with a VERSION",
+    ));
+}
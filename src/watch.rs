@@ -0,0 +1,68 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::input::{self, SymlinkPolicy};
+
+/// What a watched file looked like when last checked, or nothing if it did not exist
+type FileState = Option<(SystemTime, u64)>;
+
+/// Modification times and sizes of every watched file, compared between polls to notice changes
+///
+/// Polling keeps the tool free of platform notification APIs, at the cost of noticing changes a
+/// poll interval late.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    files: Vec<(PathBuf, FileState)>,
+}
+
+impl Snapshot {
+    /// Take the state of the given paths, each file below those which are directories included
+    pub fn take(paths: &[PathBuf], policy: SymlinkPolicy) -> Snapshot {
+        let mut files = Vec::with_capacity(paths.len());
+        for path in paths {
+            if path.is_dir() {
+                let relative_paths = input::walk_dir(path, policy, false).unwrap_or_default();
+                files.extend(relative_paths.into_iter().map(|relative_path| {
+                    let file_path = path.join(relative_path);
+                    let state = file_state(&file_path);
+                    return (file_path, state);
+                }));
+            } else {
+                files.push((path.clone(), file_state(path)));
+            }
+        }
+
+        return Snapshot { files };
+    }
+
+    /// Number of files watched, those which do not exist included
+    pub fn file_count(&self) -> usize {
+        return self.files.len();
+    }
+
+    /// Paths whose state differs from that in an earlier snapshot, including those which have
+    /// appeared or disappeared since
+    pub fn changed_since(&self, earlier: &Snapshot) -> Vec<PathBuf> {
+        let mut changed: Vec<PathBuf> = self
+            .files
+            .iter()
+            .filter(|file| !earlier.files.contains(file))
+            .map(|(path, _)| path.clone())
+            .collect();
+        for (path, _) in &earlier.files {
+            if !self.files.iter().any(|(p, _)| p == path) {
+                changed.push(path.clone());
+            }
+        }
+
+        return changed;
+    }
+}
+
+fn file_state(path: &Path) -> FileState {
+    let metadata = fs::metadata(path).ok()?;
+    return Some((metadata.modified().ok()?, metadata.len()));
+}
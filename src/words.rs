@@ -0,0 +1,152 @@
+use clap::ValueEnum;
+
+use crate::{
+    LINE_ENDING, SourceOptions, array_specifiers, declares_size, push_checksum_constants,
+    push_declaration, push_doc_comment, push_namespace_close, push_namespace_open,
+    push_size_constant, section,
+};
+
+/// Width of the words the data is packed into (`--encode u16`, `u32` or `u64`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordSize {
+    U16,
+    U32,
+    U64,
+}
+
+impl WordSize {
+    /// Number of bytes in each word
+    pub fn bytes(self) -> usize {
+        return match self {
+            WordSize::U16 => 2,
+            WordSize::U32 => 4,
+            WordSize::U64 => 8,
+        };
+    }
+
+    pub fn type_name(self) -> &'static str {
+        return match self {
+            WordSize::U16 => "std::uint16_t",
+            WordSize::U32 => "std::uint32_t",
+            WordSize::U64 => "std::uint64_t",
+        };
+    }
+}
+
+/// Order of the bytes of the input within each word (`--endian`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Endian {
+    /// The first byte is the least significant, as on x86 and most ARM targets
+    Little,
+    /// The first byte is the most significant, as in network byte order
+    Big,
+}
+
+/// How the data is packed into words
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WordLayout {
+    pub size: WordSize,
+    pub endian: Endian,
+}
+
+/// Pack bytes into words of the given layout, the last of which is padded with zero bytes if the
+/// data does not fill it
+fn pack_words(data: &[u8], layout: WordLayout) -> Vec<u64> {
+    return data
+        .chunks(layout.size.bytes())
+        .map(|chunk| {
+            let mut bytes = [0u8; 8];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            let word = &mut bytes[..layout.size.bytes()];
+            if layout.endian == Endian::Little {
+                word.reverse();
+            }
+            return word.iter().fold(0u64, |w, &b| (w << 8) | u64::from(b));
+        })
+        .collect();
+}
+
+fn words_declaration(
+    words: &[u64],
+    layout: WordLayout,
+    symbol_name: &str,
+    options: &SourceOptions,
+) -> String {
+    let digit_count = layout.size.bytes() * 2;
+    let contents: Vec<String> = words
+        .iter()
+        .map(|w| format!("0x{:0w$x}u", w, w = digit_count))
+        .collect();
+    return format!(
+        "{}constexpr std::array<{},{}> {}{{{}}};",
+        array_specifiers(options),
+        layout.size.type_name(),
+        words.len(),
+        symbol_name,
+        contents.join(",")
+    );
+}
+
+/// Generate the input as an array of words of the given width and byte order, for tables which
+/// are consumed a word at a time such as register initialisation sequences
+///
+/// `<symbol>_size` (if asked for) holds the size of the input in bytes, which tells whether the
+/// last word was padded.
+pub fn generate_src(
+    data: &[u8],
+    layout: WordLayout,
+    symbol_name: &str,
+    options: &SourceOptions,
+) -> String {
+    let words = pack_words(data, layout);
+
+    // Includes
+    let mut out_string = String::with_capacity(words.len() * (layout.size.bytes() * 2 + 4) + 0x100);
+    out_string.push_str("#include <array>");
+    out_string.push_str(LINE_ENDING);
+    if declares_size(options) {
+        out_string.push_str("#include <cstddef>");
+        out_string.push_str(LINE_ENDING);
+    }
+    out_string.push_str("#include <cstdint>");
+    out_string.push_str(LINE_ENDING);
+    if options.section.is_some() {
+        section::push_helper(&mut out_string);
+    }
+
+    // Namespace
+    push_namespace_open(
+        &mut out_string,
+        options.ns_name.as_deref(),
+        options.internal_linkage,
+    );
+
+    // Words
+    push_doc_comment(&mut out_string, options.comment.as_deref());
+    push_declaration(
+        &mut out_string,
+        &words_declaration(&words, layout, symbol_name, options),
+        &words_declaration(&[], layout, symbol_name, options),
+        options.guard_macro.as_deref(),
+    );
+
+    // Size of the input in bytes
+    push_size_constant(&mut out_string, symbol_name, data.len(), 0, options);
+
+    // Checksums
+    push_checksum_constants(&mut out_string, symbol_name, &[], options);
+
+    // Close namespace (if need be)
+    push_namespace_close(
+        &mut out_string,
+        options.ns_name.as_deref(),
+        options.internal_linkage,
+    );
+
+    // Trailing newline
+    if !out_string.ends_with(LINE_ENDING) {
+        out_string.push_str(LINE_ENDING);
+    }
+
+    return out_string;
+}
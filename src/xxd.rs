@@ -0,0 +1,41 @@
+/// Number of bytes on each line of `xxd -i` output
+const BYTES_PER_LINE: usize = 12;
+
+/// Symbol `xxd -i` derives from an input path as given on its command line: every character
+/// other than an ASCII letter or digit replaced by `_`, prefixed by `__` if it starts with a digit
+pub fn symbol_name(path: &str) -> String {
+    let mut name = String::with_capacity(path.len() + 2);
+    if path.starts_with(|c: char| c.is_ascii_digit()) {
+        name.push_str("__");
+    }
+    name.extend(path.chars().map(|c| match c.is_ascii_alphanumeric() {
+        true => c,
+        false => '_',
+    }));
+
+    return name;
+}
+
+/// Generate exactly what `xxd -i` writes for the data under the symbol name given
+///
+/// Lines always end in `\n` as xxd's do, whatever the platform, so that the output can be
+/// compared against xxd's byte for byte.
+pub fn generate_src(data: &[u8], symbol_name: &str) -> String {
+    let mut out_string = String::with_capacity(data.len() * 6 + 0x80);
+    out_string.push_str(format!("unsigned char {}[] = {{\n", symbol_name).as_str());
+    for (index, byte) in data.iter().enumerate() {
+        match index {
+            0 => out_string.push_str("  "),
+            _ if index % BYTES_PER_LINE == 0 => out_string.push_str(",\n  "),
+            _ => out_string.push_str(", "),
+        }
+        out_string.push_str(format!("0x{:02x}", byte).as_str());
+    }
+    if !data.is_empty() {
+        out_string.push('\n');
+    }
+    out_string.push_str("};\n");
+    out_string.push_str(format!("unsigned int {}_len = {};\n", symbol_name, data.len()).as_str());
+
+    return out_string;
+}
@@ -0,0 +1,663 @@
+#![allow(clippy::needless_return)]
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Empty directory for a test to work in, named after the test
+fn work_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("bin2hpp-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("failed to create the test directory");
+
+    return dir;
+}
+
+/// Run the tool in a directory with the given arguments
+fn bin2hpp(dir: &Path, arguments: &[&str]) -> Output {
+    return Command::new(env!("CARGO_BIN_EXE_bin2hpp"))
+        .current_dir(dir)
+        .args(arguments)
+        .output()
+        .expect("failed to run bin2hpp");
+}
+
+fn read(path: impl AsRef<Path>) -> String {
+    return fs::read_to_string(path).expect("failed to read an output");
+}
+
+/// Directory holding `foo.bin` and `foo.txt`, which both generate `foo.hpp`
+fn colliding_inputs(name: &str) -> PathBuf {
+    let dir = work_dir(name);
+    fs::write(dir.join("foo.bin"), "bin").unwrap();
+    fs::write(dir.join("foo.txt"), "txt").unwrap();
+
+    return dir;
+}
+
+const COLLIDING: [&str; 6] = ["-i", "foo.bin", "-i", "foo.txt", "-d", "."];
+
+#[test]
+fn collision_error_fails_on_the_later_input() {
+    let dir = colliding_inputs("collision-error");
+    let output = bin2hpp(
+        &dir,
+        &[&COLLIDING[..], &["--on-collision", "error"]].concat(),
+    );
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("also the output of an earlier input")
+    );
+    assert!(read(dir.join("foo.hpp")).contains("foo_bin"));
+}
+
+#[test]
+fn collision_error_allows_rerunning() {
+    let dir = work_dir("collision-rerun");
+    fs::write(dir.join("foo.bin"), "bin").unwrap();
+    let arguments = ["-i", "foo.bin", "-d", ".", "--on-collision", "error"];
+    assert!(bin2hpp(&dir, &arguments).status.success());
+    assert!(bin2hpp(&dir, &arguments).status.success());
+}
+
+#[test]
+fn collision_overwrite_keeps_the_later_input() {
+    let dir = colliding_inputs("collision-overwrite");
+    let output = bin2hpp(
+        &dir,
+        &[&COLLIDING[..], &["--on-collision", "overwrite"]].concat(),
+    );
+    assert!(output.status.success());
+    let src = read(dir.join("foo.hpp"));
+    assert!(src.contains("foo_txt") && !src.contains("foo_bin"));
+}
+
+#[test]
+fn collision_skip_keeps_the_earlier_input() {
+    let dir = colliding_inputs("collision-skip");
+    let output = bin2hpp(
+        &dir,
+        &[&COLLIDING[..], &["--on-collision", "skip"]].concat(),
+    );
+    assert!(output.status.success());
+    let src = read(dir.join("foo.hpp"));
+    assert!(src.contains("foo_bin") && !src.contains("foo_txt"));
+}
+
+#[test]
+fn collision_rename_is_idempotent() {
+    let dir = colliding_inputs("collision-rename");
+    let arguments = [&COLLIDING[..], &["--on-collision", "rename"]].concat();
+    for _ in 0..2 {
+        assert!(bin2hpp(&dir, &arguments).status.success());
+    }
+    assert!(read(dir.join("foo.hpp")).contains("foo_bin"));
+    assert!(read(dir.join("foo_1.hpp")).contains("foo_txt"));
+    assert!(!dir.join("foo_2.hpp").exists());
+}
+
+#[test]
+fn collision_rename_warns() {
+    let dir = colliding_inputs("collision-rename-warning");
+    let arguments = [&COLLIDING[..], &["--on-collision", "rename"]].concat();
+    let output = bin2hpp(&dir, &arguments);
+    assert!(output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("declared in \"./foo_1.hpp\" instead")
+    );
+
+    fs::remove_file(dir.join("foo_1.hpp")).unwrap();
+    let output = bin2hpp(&dir, &[&arguments[..], &["--fail-on-warning"]].concat());
+    assert!(!output.status.success());
+    assert!(!dir.join("foo_1.hpp").exists());
+}
+
+#[test]
+fn collisions_fail_unless_forced() {
+    let dir = colliding_inputs("collision-default");
+    assert!(!bin2hpp(&dir, &COLLIDING).status.success());
+    assert!(
+        bin2hpp(&dir, &[&COLLIDING[..], &["--force"]].concat())
+            .status
+            .success()
+    );
+    assert!(read(dir.join("foo.hpp")).contains("foo_txt"));
+}
+
+/// Compile a translation unit with the C++ compiler (`$CXX`, or `c++`), returning whether it
+/// compiled, or nothing if there is no compiler to run
+fn compiles_as_cpp(dir: &Path, src: &str) -> Option<bool> {
+    let path = dir.join("test.cpp");
+    fs::write(&path, src).unwrap();
+    let compiler = std::env::var("CXX").unwrap_or_else(|_| "c++".to_string());
+    let output = Command::new(compiler)
+        .current_dir(dir)
+        .args(["-std=c++20", "-fsyntax-only", "test.cpp"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        eprintln!("{}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    return Some(output.status.success());
+}
+
+#[test]
+fn embed_with_span_accessor_compiles() {
+    let dir = work_dir("embed-span");
+    fs::write(dir.join("data.bin"), [1, 2, 3, 4]).unwrap();
+    let arguments = ["-i", "data.bin", "-o", "data.hpp", "-b"];
+    let output = bin2hpp(
+        &dir,
+        &[&arguments[..], &["--encode", "embed", "--span-accessor"]].concat(),
+    );
+    assert!(output.status.success());
+    let src = "#include \"data.hpp\"\nstatic_assert(data_bin().size() == 4);\n";
+    assert_ne!(compiles_as_cpp(&dir, src), Some(false));
+}
+
+#[test]
+fn header_budget_packs_inputs_into_numbered_headers() {
+    let dir = work_dir("header-budget");
+    for (name, size) in [("a.bin", 3000), ("b.bin", 3000), ("c.bin", 500)] {
+        fs::write(dir.join(name), vec![0xA5; size]).unwrap();
+    }
+    let arguments = ["-b", "-i", "a.bin", "-i", "b.bin", "-i", "c.bin"];
+    let output = bin2hpp(
+        &dir,
+        &[
+            &arguments[..],
+            &["-o", "data.hpp", "--header-budget", "24K"],
+        ]
+        .concat(),
+    );
+    assert!(output.status.success());
+
+    // Each input generates around 16 KiB, so the two large ones cannot share a header
+    let first = read(dir.join("data_1.hpp"));
+    let second = read(dir.join("data_2.hpp"));
+    assert!(first.contains("a_bin") && first.contains("c_bin") && !first.contains("b_bin"));
+    assert!(second.contains("b_bin") && !second.contains("a_bin"));
+    assert!(!dir.join("data.hpp").exists() && !dir.join("data_3.hpp").exists());
+
+    let src = "#include \"data_1.hpp\"\n#include \"data_2.hpp\"\nstatic_assert(c_bin.size() == 500);\nconst auto* b = data_2_find(\"b.bin\");\n";
+    assert_ne!(compiles_as_cpp(&dir, src), Some(false));
+}
+
+#[test]
+fn header_budget_counts_everything_written_to_a_header() {
+    let dir = work_dir("header-budget-overhead");
+    let mut arguments = vec!["-b", "-o", "data.hpp", "--toc", "--header-budget", "3000"];
+    let names: Vec<String> = (0..20).map(|index| format!("input{}.bin", index)).collect();
+    for (index, name) in names.iter().enumerate() {
+        fs::write(dir.join(name), vec![0x5A; 40 + index * 7]).unwrap();
+        arguments.extend(["-i", name]);
+    }
+    assert!(bin2hpp(&dir, &arguments).status.success());
+
+    // The guard, includes, metadata, table of contents and index are counted too
+    let mut embedded = Vec::new();
+    for number in 1.. {
+        let Ok(src) = fs::read_to_string(dir.join(format!("data_{}.hpp", number))) else {
+            break;
+        };
+        assert!(
+            src.len() <= 3000,
+            "data_{}.hpp is {} bytes",
+            number,
+            src.len()
+        );
+        embedded.extend(
+            names
+                .iter()
+                .filter(|name| src.contains(&format!("\"{}\"", name))),
+        );
+    }
+    embedded.sort();
+    let mut expected: Vec<&String> = names.iter().collect();
+    expected.sort();
+    assert_eq!(embedded, expected);
+}
+
+#[test]
+fn respect_gitignore_skips_ignored_files() {
+    let dir = work_dir("gitignore");
+    for path in ["assets/build", "assets/sub", ".git/info"] {
+        fs::create_dir_all(dir.join(path)).unwrap();
+    }
+    for path in [
+        "assets/logo.bin",
+        "assets/build/logo.bin",
+        "assets/scratch.tmp",
+        "assets/keep.tmp",
+        "assets/sub/notes.txt",
+        "assets/sub/data.bin",
+        "assets/texture.raw",
+        "assets/secret.bin",
+    ] {
+        fs::write(dir.join(path), "x").unwrap();
+    }
+    fs::write(dir.join("assets/.gitignore"), "build/\n*.tmp\n!keep.tmp\n").unwrap();
+    fs::write(dir.join("assets/sub/.gitignore"), "notes.txt\n").unwrap();
+    // The directory walked is inside a repository whose own ignore files apply too
+    fs::write(dir.join(".gitignore"), "*.raw\n").unwrap();
+    fs::write(dir.join(".git/info/exclude"), "secret.bin\n").unwrap();
+
+    let arguments = [
+        "-r",
+        "-i",
+        "assets",
+        "-o",
+        "assets.hpp",
+        "--respect-gitignore",
+    ];
+    assert!(bin2hpp(&dir, &arguments).status.success());
+    let src = read(dir.join("assets.hpp"));
+    for name in ["logo.bin", "keep.tmp", "sub/data.bin"] {
+        assert!(
+            src.contains(&format!("\"assets/{}\"", name)),
+            "{} is missing",
+            name
+        );
+    }
+    for name in [
+        "build/logo.bin",
+        "scratch.tmp",
+        "sub/notes.txt",
+        ".gitignore",
+        "texture.raw",
+        "secret.bin",
+    ] {
+        assert!(
+            !src.contains(&format!("\"assets/{}\"", name)),
+            "{} is embedded",
+            name
+        );
+    }
+
+    // Nor is the repository itself embedded when walking its root
+    let arguments = ["-r", "-i", ".", "-o", "all.hpp", "--respect-gitignore"];
+    assert!(bin2hpp(&dir, &arguments).status.success());
+    let src = read(dir.join("all.hpp"));
+    assert!(src.contains("assets/logo.bin") && !src.contains(".git/info"));
+}
+
+#[test]
+fn checksum_constants_hold_the_digests_of_the_input() {
+    let dir = work_dir("checksum-constants");
+    fs::write(dir.join("check.bin"), "123456789").unwrap();
+    let output = bin2hpp(
+        &dir,
+        &[
+            "-i",
+            "check.bin",
+            "-o",
+            "check.hpp",
+            "-b",
+            "--checksum",
+            "crc32,sha256",
+            "--checksum-hex",
+        ],
+    );
+    assert!(output.status.success());
+
+    let src = read(dir.join("check.hpp"));
+    assert!(src.contains("check_bin_crc32=0xcbf43926u;"));
+    assert!(src.contains("check_bin_crc32_hex=\"cbf43926\";"));
+    assert!(src.contains(
+        "check_bin_sha256_hex=\"15e2b0d3c33891ebb0f1ef609ec419420c20e320ce94c65fbc8c3312448eb225\";"
+    ));
+    let test_src = "#include \"check.hpp\"\n\
+                    static_assert(check_bin_crc32==0xcbf43926u);\n\
+                    static_assert(check_bin_sha256[0]==0x15&&check_bin_sha256[31]==0x25);\n";
+    assert_ne!(compiles_as_cpp(&dir, test_src), Some(false));
+}
+
+/// Compile and run a program with the C++ compiler (`$CXX`, or `c++`) given some more flags (or
+/// libraries to link), returning what it wrote to stdout, or nothing if there is no compiler to
+/// run
+fn run_as_cpp(dir: &Path, src: &str, flags: &[&str]) -> Option<Vec<u8>> {
+    fs::write(dir.join("main.cpp"), src).unwrap();
+    let compiler = std::env::var("CXX").unwrap_or_else(|_| "c++".to_string());
+    let output = Command::new(compiler)
+        .current_dir(dir)
+        .args(["-std=c++17", "main.cpp", "-o", "main"])
+        .args(flags)
+        .output()
+        .ok()?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = Command::new(dir.join("main")).output().unwrap();
+    assert!(output.status.success());
+    return Some(output.stdout);
+}
+
+#[test]
+fn compressed_data_decompresses_at_runtime() {
+    let dir = work_dir("compress-runtime");
+    let mut state: u32 = 1;
+    let mut data: Vec<u8> = (0..0x2000)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            return (state >> 16) as u8;
+        })
+        .collect();
+    data.extend(b"the quick brown fox jumps over the lazy dog ".repeat(0x400));
+    fs::write(dir.join("data.bin"), &data).unwrap();
+
+    for (algorithm, library) in [
+        ("lz4", None),
+        ("gzip", Some(("zlib.h", "-lz"))),
+        ("zstd", Some(("zstd.h", "-lzstd"))),
+    ] {
+        // The libraries decompressing zstd and gzip data are not installed everywhere
+        let libraries = match library {
+            Some((header, flag)) => {
+                if compiles_as_cpp(&dir, &format!("#include <{}>\n", header)) != Some(true) {
+                    continue;
+                }
+                vec![flag]
+            }
+            None => Vec::new(),
+        };
+
+        let output = bin2hpp(
+            &dir,
+            &[
+                "-i",
+                "data.bin",
+                "-o",
+                "data.hpp",
+                "-b",
+                "--compress",
+                algorithm,
+            ],
+        );
+        assert!(output.status.success());
+        let src = "#include \"data.hpp\"\n#include <cstdio>\n\
+                   int main(){auto out=data_bin_decompress();\
+                   std::fwrite(out.data(),1,out.size(),stdout);return out.empty();}\n";
+        if let Some(decompressed) = run_as_cpp(&dir, src, &libraries) {
+            assert!(
+                decompressed == data,
+                "{} data decompressed wrongly",
+                algorithm
+            );
+        }
+    }
+}
+
+/// Symbols an object file defines, as listed by `llvm-nm` (or `nm`, which may not know the
+/// formats foreign to the host), or nothing if neither could read it
+fn defined_symbols(path: &Path) -> Option<Vec<String>> {
+    let output = ["llvm-nm", "nm"].iter().find_map(|tool| {
+        let output = Command::new(tool)
+            .args(["--defined-only".as_ref(), path.as_os_str()])
+            .output()
+            .ok()?;
+        return output.status.success().then_some(output);
+    })?;
+
+    return Some(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.split_whitespace().last().map(str::to_string))
+            .collect(),
+    );
+}
+
+#[test]
+fn object_files_define_the_data_and_its_size() {
+    let dir = work_dir("object-symbols");
+    fs::write(dir.join("data.bin"), "hello object").unwrap();
+
+    for (format, extension, prefix) in [("elf", "o", ""), ("coff", "obj", ""), ("mach-o", "o", "_")]
+    {
+        for arch in ["x86-64", "aarch64"] {
+            let output = bin2hpp(
+                &dir,
+                &[
+                    "-i",
+                    "data.bin",
+                    "-o",
+                    "data.hpp",
+                    "-b",
+                    "--object",
+                    format,
+                    "--object-arch",
+                    arch,
+                ],
+            );
+            assert!(output.status.success());
+            let object_path = dir.join(format!("data.{}", extension));
+            if let Some(symbols) = defined_symbols(&object_path) {
+                assert!(
+                    symbols.contains(&format!("{}data_bin", prefix))
+                        && symbols.contains(&format!("{}data_bin_size", prefix)),
+                    "{} {} object defines {:?}",
+                    format,
+                    arch,
+                    symbols
+                );
+            }
+            fs::remove_file(object_path).unwrap();
+        }
+    }
+
+    // Linked into a program for the host, the object holds the data itself
+    if cfg!(all(target_os = "linux", target_arch = "x86_64")) {
+        let output = bin2hpp(
+            &dir,
+            &["-i", "data.bin", "-o", "data.hpp", "-b", "--object", "elf"],
+        );
+        assert!(output.status.success());
+        let src = "#include \"data.hpp\"\n#include <cstdio>\n\
+                   int main(){std::fwrite(data_bin,1,data_bin_size,stdout);}\n";
+        if let Some(out) = run_as_cpp(&dir, src, &["data.o"]) {
+            assert_eq!(out, b"hello object");
+        }
+    }
+}
+
+#[test]
+fn nested_namespaces_of_keyword_and_digit_directories_compile() {
+    let dir = work_dir("nested-namespaces");
+    fs::create_dir_all(dir.join("assets/new/3d/__x")).unwrap();
+    fs::create_dir_all(dir.join("out")).unwrap();
+    fs::write(dir.join("assets/new/3d/x.bin"), [1, 2, 3]).unwrap();
+    fs::write(dir.join("assets/new/3d/__x/y.bin"), [4, 5]).unwrap();
+    let arguments = [
+        "-b",
+        "-r",
+        "--nested-namespaces",
+        "-i",
+        "assets",
+        "-d",
+        "out",
+    ];
+    assert!(bin2hpp(&dir, &arguments).status.success());
+
+    let src = "#include \"out/x.hpp\"\n#include \"out/y.hpp\"\nstatic_assert(new_::file_3d::x_bin.size() == 3);\nstatic_assert(new_::file_3d::x::y_bin.size() == 2);\n";
+    assert_ne!(compiles_as_cpp(&dir, src), Some(false));
+}
+
+#[test]
+fn reproducible_headers_record_absolute_paths_relative() {
+    // The same tree in two places, given by absolute paths, generates the same header
+    let headers: Vec<String> = ["reproducible-a", "reproducible-b"]
+        .iter()
+        .map(|name| {
+            let dir = work_dir(name);
+            fs::create_dir_all(dir.join("build")).unwrap();
+            fs::write(dir.join("data.bin"), "data").unwrap();
+            fs::write(dir.join("banner.txt"), "Generated, do not edit").unwrap();
+            let absolute = |path: &str| dir.join(path).to_string_lossy().to_string();
+            let output = bin2hpp(
+                &dir.join("build"),
+                &[
+                    "-i",
+                    &absolute("data.bin"),
+                    &format!("--output-path={}", absolute("build/data.hpp")),
+                    "--banner",
+                    &absolute("banner.txt"),
+                    "--reproducible",
+                ],
+            );
+            assert!(output.status.success());
+            return read(dir.join("build/data.hpp"));
+        })
+        .collect();
+
+    assert_eq!(headers[0], headers[1]);
+    assert!(headers[0].contains(
+        "// arguments=-i ../data.bin --output-path=data.hpp --banner ../banner.txt --reproducible"
+    ));
+    assert!(headers[0].contains("// source=../data.bin"));
+}
+
+/// Generate a header for a file with the given name and arguments, returning its source or the
+/// diagnostics explaining why it failed
+fn symbol_of(name: &str, arguments: &[&str]) -> Result<String, String> {
+    // Tests run in parallel, so every call gets a directory of its own
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    let dir = work_dir(&format!("symbol-{}", CALLS.fetch_add(1, Ordering::Relaxed)));
+    fs::write(dir.join(name), "x").unwrap();
+    let output = bin2hpp(&dir, &[&["-i", name, "-o", "out.hpp"], arguments].concat());
+    return match output.status.success() {
+        true => Ok(read(dir.join("out.hpp"))),
+        false => Err(String::from_utf8_lossy(&output.stderr).to_string()),
+    };
+}
+
+#[test]
+fn affixes_are_kept_as_given() {
+    let src = symbol_of("int", &["--symbol-suffix", "_t"]).unwrap();
+    assert!(src.contains(" int_t "), "{}", src);
+    let src = symbol_of("3d.bin", &["--symbol-prefix", "asset_"]).unwrap();
+    assert!(src.contains(" asset_3d_bin "), "{}", src);
+    let src = symbol_of(
+        "logo.png",
+        &["--symbol-prefix", "k", "--symbol-case", "pascal"],
+    )
+    .unwrap();
+    assert!(src.contains(" kLogoPng "), "{}", src);
+}
+
+#[test]
+fn only_the_derived_part_is_mangled() {
+    let src = symbol_of("3d.bin", &["--symbol-suffix", "_data"]).unwrap();
+    assert!(src.contains(" file_3d_bin_data "), "{}", src);
+}
+
+#[test]
+fn affixes_making_invalid_symbols_are_errors() {
+    let error = symbol_of("data.bin", &["--symbol-prefix", "_"]).unwrap_err();
+    assert!(
+        error.contains("\"_data_bin\"") && error.contains("reserved"),
+        "{}",
+        error
+    );
+    let error = symbol_of("int", &["--symbol-suffix", "__"]).unwrap_err();
+    assert!(
+        error.contains("\"int__\"") && error.contains("reserved"),
+        "{}",
+        error
+    );
+    let error = symbol_of("data.bin", &["--symbol-prefix", "my-"]).unwrap_err();
+    assert!(error.contains("--symbol-prefix \"my-\""), "{}", error);
+}
+
+#[test]
+fn patch_symbols_are_derived_as_for_inputs() {
+    let dir = work_dir("patch-symbol");
+    fs::write(dir.join("old.bin"), "aaaabbbb").unwrap();
+    fs::write(dir.join("3d-new.bin"), "aaaacccc").unwrap();
+    let patch = |arguments: &[&str]| {
+        let output = bin2hpp(
+            &dir,
+            &[
+                &["patch", "old.bin", "3d-new.bin", "-o", "out.hpp"],
+                arguments,
+            ]
+            .concat(),
+        );
+        assert!(output.status.success());
+        return read(dir.join("out.hpp"));
+    };
+
+    assert!(patch(&[]).contains("file_3d_new_bin_patch_apply("));
+    let src = patch(&["--symbol-case", "pascal", "--symbol-prefix", "k"]);
+    assert!(src.contains("k3dNewBinPatch_apply("));
+}
+
+#[test]
+fn text_survives_trigraph_translation() {
+    let dir = work_dir("trigraphs");
+    fs::write(dir.join("text.txt"), "a??=b??/c??!").unwrap();
+    let output = bin2hpp(&dir, &["-i", "text.txt", "-o", "text.hpp"]);
+    assert!(output.status.success());
+    let src = "#include \"text.hpp\"\n#include <cstdio>\n\
+               int main(){std::fputs(text_txt,stdout);}\n";
+    if let Some(out) = run_as_cpp(&dir, src, &["-trigraphs"]) {
+        assert_eq!(out, b"a??=b??/c??!");
+    }
+}
+
+#[test]
+fn options_conflicting_with_a_group_conflict_with_each_member() {
+    let dir = work_dir("groups");
+    fs::write(dir.join("data.bin"), "data").unwrap();
+    let conflicts: [(&[&str], &[&str]); 6] = [
+        (&["--dedupe"], &["--stream-accessor"]),
+        (&["--dedupe"], &["--verify-fn", "crc32"]),
+        (&["--xxd-compat"], &["--compress", "lz4"]),
+        (&["--xxd-compat"], &["--split"]),
+        (&["--index", "index.hpp"], &["--manifest"]),
+        (&["--decode-csv", "u8"], &["--decode-struct", "json"]),
+    ];
+    for (option, member) in conflicts {
+        let mut arguments = vec!["-i", "data.bin", "-o", "data.hpp"];
+        arguments.extend(option);
+        arguments.extend(member);
+        let output = bin2hpp(&dir, &arguments);
+        assert_eq!(output.status.code(), Some(2), "{:?}", arguments);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be used with"));
+    }
+
+    // Only some groups are of options which exclude one another
+    let output = bin2hpp(
+        &dir,
+        &[
+            "-i",
+            "data.bin",
+            "-o",
+            "data.hpp",
+            "-b",
+            "--stub",
+            "--guard-macro",
+            "REAL",
+        ],
+    );
+    assert!(output.status.success());
+    let output = bin2hpp(
+        &dir,
+        &[
+            "-i",
+            "data.bin",
+            "-o",
+            "data.hpp",
+            "-b",
+            "--verify-fn",
+            "crc32",
+            "--span-accessor",
+        ],
+    );
+    assert!(output.status.success());
+}